@@ -0,0 +1,44 @@
+/* C interface to the fountain reassembly engine. Build with `cargo rustc --release --lib
+ * --crate-type cdylib --no-default-features --features ffi` and link against the resulting
+ * libfountain. */
+
+#ifndef FOUNTAIN_H
+#define FOUNTAIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct FountainDecoder FountainDecoder;
+
+typedef enum FountainStatus {
+    FOUNTAIN_STATUS_SCANNING = 0,
+    FOUNTAIN_STATUS_CHUNK_FOUND = 1,
+    FOUNTAIN_STATUS_COMPLETE = 2,
+    FOUNTAIN_STATUS_ERROR = 3,
+} FountainStatus;
+
+typedef struct FountainFile {
+    char *filename;
+    uint8_t *data;
+    size_t len;
+} FountainFile;
+
+FountainDecoder *fountain_decoder_new(void);
+FountainStatus fountain_decoder_feed_payload(FountainDecoder *decoder, const uint8_t *payload,
+                                             size_t len);
+void fountain_decoder_progress(const FountainDecoder *decoder, uint32_t *current,
+                               uint32_t *total);
+FountainFile *fountain_decoder_poll_result(FountainDecoder *decoder);
+const char *fountain_decoder_last_error(const FountainDecoder *decoder);
+void fountain_decoder_free(FountainDecoder *decoder);
+void fountain_file_free(FountainFile *file);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FOUNTAIN_H */
@@ -6,5 +6,6 @@ cd "${current_dir}/../.."
 
 cargo build --bin fountain-encode --release --features encode
 cargo build --bin fountain-decode --release --features decode
+cargo build --bin fountain --release --features encode,decode
 
 bash "${current_dir}/compile.wasm.sh"
@@ -3,5 +3,5 @@ set -e
 
 cd "$(dirname "${BASH_SOURCE[0]}")/../.."
 
-cargo build --target wasm32-unknown-unknown --release --no-default-features --features wasm
+cargo rustc --lib --crate-type cdylib --target wasm32-unknown-unknown --release --no-default-features --features wasm
 wasm-bindgen target/wasm32-unknown-unknown/release/fountain.wasm --out-dir www/pkg --target web
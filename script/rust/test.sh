@@ -4,3 +4,6 @@ set -e
 cd "$(dirname "${BASH_SOURCE[0]}")/../.."
 
 cargo test -- --nocapture
+
+# The core must build where there is no standard library at all
+cargo build --lib --no-default-features --target thumbv7em-none-eabihf
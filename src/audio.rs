@@ -0,0 +1,552 @@
+//! Experimental audio modem: chunks sent as FSK tones, for air-gapped transfers where no
+//! camera is at hand. The sender plays a WAV file; the receiver records it, or decodes a
+//! microphone stream such as `arecord -f S16_LE -r 48000 -t wav -` piped in.
+//!
+//! Each chunk of [`raptorq_packets`] becomes one frame: a preamble alternating the lowest and
+//! highest tone, the 16-bit big-endian length of the chunk bytes, the bytes and the start of
+//! their SHA-256 for checking. Every byte is sent as two symbols of 4 bits, each one of 16
+//! tones held for the symbol duration. Frames that do not check out are dropped and the
+//! fountain code makes up for them, like for QR codes that do not scan.
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::chunk::{sha256_digest, Chunk, FileMetadata, MAX_PAYLOAD_SIZE};
+use crate::packets::{raptorq_packets, PacketAssembler, PacketOptions};
+
+/// Sample rate of the WAV files written.
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+/// How long each tone is held. The tones are spaced by its inverse, 100 Hz.
+pub const DEFAULT_SYMBOL_MS: u32 = 10;
+/// Chunk size for audio: short frames are less likely to be hit by a noise.
+pub const DEFAULT_AUDIO_PACKET_SIZE: usize = 64;
+
+const TONES: usize = 16;
+const PREAMBLE: [usize; 8] = [0, TONES - 1, 0, TONES - 1, 0, TONES - 1, 0, TONES - 1];
+/// Silent symbols before each frame, so the receiver's search starts clean.
+const GAP_SYMBOLS: usize = 4;
+/// Bytes of the SHA-256 of a frame sent to check it.
+const CHECK_SIZE: usize = 4;
+/// Share of a window's energy the expected tone must have to start a frame.
+const MIN_TONE_SHARE: f32 = 0.4;
+const AMPLITUDE: f32 = 0.5;
+
+/// Frequency range of the tones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioBand {
+    /// From 1.2 kHz, heard by anyone in the room but carried by any speaker and microphone.
+    #[default]
+    Audible,
+    /// From 18 kHz, inaudible to most adults. Needs a sample rate of 44.1 kHz or more, and
+    /// laptop speakers and microphones often roll off there.
+    Ultrasonic,
+}
+
+impl AudioBand {
+    fn base_frequency(self) -> f32 {
+        match self {
+            AudioBand::Audible => 1_200.0,
+            AudioBand::Ultrasonic => 18_000.0,
+        }
+    }
+}
+
+impl FromStr for AudioBand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "audible" => Ok(AudioBand::Audible),
+            "ultrasonic" => Ok(AudioBand::Ultrasonic),
+            _ => Err(anyhow!(
+                "Unknown audio band '{}': expected audible or ultrasonic",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AudioBand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AudioBand::Audible => "audible",
+            AudioBand::Ultrasonic => "ultrasonic",
+        })
+    }
+}
+
+/// Modulation settings. The receiver must use the same band and symbol duration as the
+/// sender; it takes the sample rate from the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioOptions {
+    band: AudioBand,
+    symbol_ms: u32,
+    sample_rate: u32,
+}
+
+impl Default for AudioOptions {
+    fn default() -> Self {
+        Self {
+            band: AudioBand::default(),
+            symbol_ms: DEFAULT_SYMBOL_MS,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+        }
+    }
+}
+
+impl AudioOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn band(mut self, band: AudioBand) -> Self {
+        self.band = band;
+        self
+    }
+
+    /// How long each tone is held. Longer symbols are slower but survive echoey rooms.
+    pub fn symbol_ms(mut self, symbol_ms: u32) -> Self {
+        self.symbol_ms = symbol_ms;
+        self
+    }
+
+    /// Sample rate of the audio written; ignored when decoding.
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Frequencies of the tones at `sample_rate` and the samples each symbol lasts.
+    fn modem(&self, sample_rate: u32) -> Result<([f32; TONES], usize)> {
+        if !(1..=1_000).contains(&self.symbol_ms) {
+            return Err(anyhow!(
+                "Invalid symbol duration: {} ms. It must be between 1 and 1000 ms.",
+                self.symbol_ms
+            ));
+        }
+        let spacing = 1_000.0 / self.symbol_ms as f32;
+        let base = self.band.base_frequency();
+        let tones = std::array::from_fn(|i| base + i as f32 * spacing);
+        let highest = tones[TONES - 1] + spacing;
+        if highest > sample_rate as f32 / 2.0 {
+            return Err(anyhow!(
+                "A sample rate of {} Hz cannot carry {} tones up to {} Hz",
+                sample_rate,
+                self.band,
+                highest
+            ));
+        }
+        let symbol_len = (sample_rate as u64 * self.symbol_ms as u64 / 1_000) as usize;
+        Ok((tones, symbol_len))
+    }
+}
+
+/// Outcome of [`encode_bytes_to_wav`].
+#[derive(Debug, Clone, Copy)]
+pub struct AudioEncodeResult {
+    /// Frames sent, one per chunk.
+    pub num_chunks: usize,
+    /// Playing time of the audio.
+    pub duration: Duration,
+}
+
+/// Turns chunks into frames of tones, as samples at the configured sample rate.
+pub fn modulate(
+    chunks: impl IntoIterator<Item = Chunk>,
+    options: &AudioOptions,
+) -> Result<Vec<i16>> {
+    let (tones, symbol_len) = options.modem(options.sample_rate)?;
+    let step = std::f32::consts::TAU / options.sample_rate as f32;
+    let mut samples = Vec::new();
+    let mut phase = 0.0f32;
+    for chunk in chunks {
+        let bytes = chunk.to_bytes()?;
+        samples.resize(samples.len() + GAP_SYMBOLS * symbol_len, 0);
+        for tone in frame_symbols(&bytes) {
+            for _ in 0..symbol_len {
+                samples.push((phase.sin() * AMPLITUDE * i16::MAX as f32) as i16);
+                // Continuous phase keeps the spectrum of each symbol narrow
+                phase = (phase + step * tones[tone]) % std::f32::consts::TAU;
+            }
+        }
+    }
+    samples.resize(samples.len() + GAP_SYMBOLS * symbol_len, 0);
+    Ok(samples)
+}
+
+/// Tones of the frame carrying `bytes`.
+fn frame_symbols(bytes: &[u8]) -> Vec<usize> {
+    let mut frame = (bytes.len() as u16).to_be_bytes().to_vec();
+    frame.extend_from_slice(bytes);
+    frame.extend_from_slice(&sha256_digest(&frame)[..CHECK_SIZE]);
+    PREAMBLE
+        .iter()
+        .copied()
+        .chain(
+            frame
+                .iter()
+                .flat_map(|&byte| [(byte >> 4) as usize, (byte & 0x0F) as usize]),
+        )
+        .collect()
+}
+
+/// Encodes `data` under `filename` into chunks as [`raptorq_packets`] does and writes them
+/// as a 16-bit mono WAV file.
+pub fn encode_bytes_to_wav<W: Write>(
+    data: &[u8],
+    filename: &str,
+    packet_options: &PacketOptions,
+    audio_options: &AudioOptions,
+    writer: W,
+) -> Result<AudioEncodeResult> {
+    let chunks: Vec<Chunk> = raptorq_packets(data, filename, packet_options)?.collect();
+    let num_chunks = chunks.len();
+    let samples = modulate(chunks, audio_options)?;
+    write_wav(writer, &samples, audio_options.sample_rate)?;
+    Ok(AudioEncodeResult {
+        num_chunks,
+        duration: Duration::from_secs_f64(samples.len() as f64 / audio_options.sample_rate as f64),
+    })
+}
+
+/// Writes mono 16-bit samples as a WAV file.
+pub fn write_wav<W: Write>(mut writer: W, samples: &[i16], sample_rate: u32) -> Result<()> {
+    let data_len = u32::try_from(samples.len() * 2)
+        .ok()
+        .filter(|len| *len <= u32::MAX - 36)
+        .ok_or_else(|| anyhow!("Audio of {} samples is too long for WAV", samples.len()))?;
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(36 + data_len).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    header.extend_from_slice(&1u16.to_le_bytes()); // mono
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes()); // bytes per frame
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    writer.write_all(&header)?;
+
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    writer.write_all(&bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Layout of the samples in a WAV stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WavFormat {
+    sample_rate: u32,
+    channels: u16,
+    /// Bytes of sample data, or `None` for a live stream whose header does not know it.
+    data_len: Option<u32>,
+}
+
+/// Reads a WAV header up to the start of the sample data. Only 16-bit PCM is supported.
+fn read_wav_header<R: Read>(reader: &mut R) -> Result<WavFormat> {
+    let mut riff = [0u8; 12];
+    reader.read_exact(&mut riff)?;
+    if &riff[..4] != b"RIFF" || &riff[8..] != b"WAVE" {
+        return Err(anyhow!("Not a WAV file"));
+    }
+    let mut format = None;
+    loop {
+        let mut chunk_header = [0u8; 8];
+        reader
+            .read_exact(&mut chunk_header)
+            .map_err(|e| match e.kind() {
+                ErrorKind::UnexpectedEof => anyhow!("WAV file has no sample data"),
+                _ => e.into(),
+            })?;
+        let len = u32::from_le_bytes(chunk_header[4..].try_into().expect("4 bytes"));
+        match &chunk_header[..4] {
+            b"fmt " => {
+                let mut fmt = vec![0u8; len as usize];
+                reader.read_exact(&mut fmt)?;
+                if fmt.len() < 16 {
+                    return Err(anyhow!("Invalid WAV format chunk"));
+                }
+                let field = |at: usize| u16::from_le_bytes([fmt[at], fmt[at + 1]]);
+                // PCM, or WAVE_FORMAT_EXTENSIBLE as some recorders write it
+                if !matches!(field(0), 1 | 0xFFFE) || field(14) != 16 {
+                    return Err(anyhow!("Only 16-bit PCM WAV audio is supported"));
+                }
+                format = Some((
+                    u32::from_le_bytes(fmt[4..8].try_into().expect("4 bytes")),
+                    field(2).max(1),
+                ));
+            }
+            b"data" => {
+                let (sample_rate, channels) =
+                    format.ok_or_else(|| anyhow!("WAV sample data before its format"))?;
+                return Ok(WavFormat {
+                    sample_rate,
+                    channels,
+                    // Recorders writing to a pipe leave the length at 0 or the maximum
+                    data_len: Some(len).filter(|&len| len != 0 && len < u32::MAX - 36),
+                });
+            }
+            _ => {
+                // Chunks are padded to an even length
+                let skip = u64::from(len) + u64::from(len % 2);
+                std::io::copy(&mut reader.by_ref().take(skip), &mut std::io::sink())?;
+            }
+        }
+    }
+}
+
+/// Decodes a file from a WAV recording or a live WAV stream, returning as soon as enough
+/// frames arrived. Like [`PacketAssembler::add`], returns the filename, contents and
+/// metadata.
+pub fn decode_from_wav<R: Read>(
+    mut reader: R,
+    options: &AudioOptions,
+) -> Result<(String, Vec<u8>, FileMetadata)> {
+    let format = read_wav_header(&mut reader)?;
+    let mut reader: Box<dyn Read> = match format.data_len {
+        Some(len) => Box::new(reader.take(u64::from(len))),
+        None => Box::new(reader),
+    };
+    let mut demodulator = Demodulator::new(format.sample_rate, options)?;
+    let mut assembler = PacketAssembler::new();
+    let frame_len = format.channels as usize * 2;
+    let mut block = vec![0u8; frame_len * 4_096];
+    let mut pending = 0;
+    loop {
+        let read = reader.read(&mut block[pending..])?;
+        if read == 0 {
+            break;
+        }
+        pending += read;
+        let whole = pending - pending % frame_len;
+        // Only the first channel is listened to
+        let samples: Vec<i16> = block[..whole]
+            .chunks_exact(frame_len)
+            .map(|frame| i16::from_le_bytes([frame[0], frame[1]]))
+            .collect();
+        block.copy_within(whole..pending, 0);
+        pending -= whole;
+
+        for chunk in demodulator.push(&samples) {
+            log::debug!("Received chunk {}", chunk.header.index);
+            if let Some(file) = assembler.add(chunk)? {
+                return Ok(file);
+            }
+        }
+    }
+    let (received, needed) = assembler.progress();
+    Err(anyhow!(
+        "The audio ended before the file was complete: {} of at least {} chunks received",
+        received,
+        needed
+    ))
+}
+
+/// Finds frames in audio fed to it piece by piece and returns the chunks they carry.
+pub struct Demodulator {
+    /// Goertzel coefficients of the tones.
+    coefficients: [f32; TONES],
+    symbol_len: usize,
+    /// Samples not yet searched, from `pos` on.
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+/// Outcome of reading a frame after its preamble.
+enum FrameRead {
+    Chunk(Chunk, usize),
+    Invalid,
+    Incomplete,
+}
+
+impl Demodulator {
+    /// A demodulator for audio at `sample_rate` sent with the band and symbol duration of
+    /// `options`.
+    pub fn new(sample_rate: u32, options: &AudioOptions) -> Result<Self> {
+        let (tones, symbol_len) = options.modem(sample_rate)?;
+        Ok(Self {
+            coefficients: tones
+                .map(|tone| 2.0 * (std::f32::consts::TAU * tone / sample_rate as f32).cos()),
+            symbol_len,
+            buffer: Vec::new(),
+            pos: 0,
+        })
+    }
+
+    /// Adds mono samples and returns the chunks of the frames completed by them.
+    pub fn push(&mut self, samples: &[i16]) -> Vec<Chunk> {
+        self.buffer
+            .extend(samples.iter().map(|&s| s as f32 / i16::MAX as f32));
+        let n = self.symbol_len;
+        let step = (n / 4).max(1);
+        let mut chunks = Vec::new();
+        // Room to align the preamble and read the frame length after it
+        while self.pos + (PREAMBLE.len() + 5) * n <= self.buffer.len() {
+            let (tone, share) = self.strongest_tone(self.pos);
+            if tone != PREAMBLE[0] || share < MIN_TONE_SHARE {
+                self.pos += step;
+                continue;
+            }
+            // The first window where the tone dominates may start up to a symbol early
+            let start = (0..=16)
+                .map(|k| self.pos.saturating_sub(step) + k * n / 16)
+                .max_by(|&a, &b| self.preamble_score(a).total_cmp(&self.preamble_score(b)))
+                .expect("candidates are not empty");
+            let preamble_found = PREAMBLE
+                .iter()
+                .enumerate()
+                .all(|(i, &tone)| self.strongest_tone(start + i * n).0 == tone);
+            if !preamble_found {
+                self.pos += step;
+                continue;
+            }
+            match self.read_frame(start + PREAMBLE.len() * n) {
+                FrameRead::Chunk(chunk, end) => {
+                    chunks.push(chunk);
+                    self.pos = end;
+                }
+                FrameRead::Invalid => self.pos += step,
+                FrameRead::Incomplete => break,
+            }
+        }
+        // Keep a symbol before the search position for the alignment
+        let consumed = self.pos.saturating_sub(n);
+        if consumed > 0 {
+            self.buffer.drain(..consumed);
+            self.pos -= consumed;
+        }
+        chunks
+    }
+
+    fn read_frame(&self, start: usize) -> FrameRead {
+        let n = self.symbol_len;
+        let byte_at = |index: usize| {
+            let at = start + index * 2 * n;
+            (self.strongest_tone(at).0 << 4 | self.strongest_tone(at + n).0) as u8
+        };
+        let len = u16::from_be_bytes([byte_at(0), byte_at(1)]) as usize;
+        if len > MAX_PAYLOAD_SIZE {
+            return FrameRead::Invalid;
+        }
+        let frame_len = 2 + len + CHECK_SIZE;
+        let end = start + frame_len * 2 * n;
+        if end > self.buffer.len() {
+            return FrameRead::Incomplete;
+        }
+        let frame: Vec<u8> = (0..frame_len).map(byte_at).collect();
+        let (body, check) = frame.split_at(2 + len);
+        if sha256_digest(body)[..CHECK_SIZE] != *check {
+            return FrameRead::Invalid;
+        }
+        match Chunk::from_bytes(&body[2..]) {
+            Ok(chunk) => FrameRead::Chunk(chunk, end),
+            Err(_) => FrameRead::Invalid,
+        }
+    }
+
+    /// Sum of the shares of the preamble tones, highest when `start` is the frame start.
+    fn preamble_score(&self, start: usize) -> f32 {
+        PREAMBLE
+            .iter()
+            .enumerate()
+            .map(|(i, &tone)| self.tone_shares(start + i * self.symbol_len)[tone])
+            .sum()
+    }
+
+    fn strongest_tone(&self, start: usize) -> (usize, f32) {
+        self.tone_shares(start)
+            .into_iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("there are tones")
+    }
+
+    /// Share of the energy of the symbol window at `start` in each tone, 1.0 for a pure tone.
+    fn tone_shares(&self, start: usize) -> [f32; TONES] {
+        let window = &self.buffer[start..start + self.symbol_len];
+        let energy: f32 = window.iter().map(|s| s * s).sum();
+        if energy <= f32::EPSILON {
+            return [0.0; TONES];
+        }
+        let scale = energy * self.symbol_len as f32 / 2.0;
+        self.coefficients.map(|coefficient| {
+            // Goertzel filter
+            let (mut s1, mut s2) = (0.0f32, 0.0f32);
+            for &sample in window {
+                let s0 = sample + coefficient * s1 - s2;
+                s2 = s1;
+                s1 = s0;
+            }
+            (s1 * s1 + s2 * s2 - coefficient * s1 * s2) / scale
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noisy(samples: &[i16], level: f32) -> Vec<i16> {
+        let mut state = 1u32;
+        samples
+            .iter()
+            .map(|&s| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = ((state >> 16) as f32 / 32_768.0 - 1.0) * level * i16::MAX as f32;
+                (s as f32 * 0.6 + noise) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_wav_roundtrip() {
+        let data: Vec<u8> = (0..400u32).map(|i| (i * 13 % 256) as u8).collect();
+        let packets = PacketOptions::new().packet_size(48).redundancy(1.5);
+        let mut wav = Vec::new();
+        let result =
+            encode_bytes_to_wav(&data, "x.bin", &packets, &AudioOptions::new(), &mut wav).unwrap();
+        assert!(result.num_chunks > 1);
+
+        let (filename, decoded, _) = decode_from_wav(&wav[..], &AudioOptions::new()).unwrap();
+        assert_eq!(filename, "x.bin");
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_demodulator_with_noise_and_fragments() {
+        let options = AudioOptions::new()
+            .band(AudioBand::Ultrasonic)
+            .sample_rate(44_100);
+        let chunks: Vec<Chunk> =
+            raptorq_packets(b"hello over the air", "hi.txt", &PacketOptions::new())
+                .unwrap()
+                .collect();
+        let samples = noisy(&modulate(chunks.clone(), &options).unwrap(), 0.15);
+
+        let mut demodulator = Demodulator::new(44_100, &options).unwrap();
+        let received: Vec<Chunk> = samples
+            .chunks(1_000)
+            .flat_map(|piece| demodulator.push(piece))
+            .collect();
+        assert_eq!(received.len(), chunks.len());
+        for (got, sent) in received.iter().zip(&chunks) {
+            assert_eq!(got.to_bytes().unwrap(), sent.to_bytes().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_audio_options_are_validated() {
+        let options = AudioOptions::new()
+            .band(AudioBand::Ultrasonic)
+            .sample_rate(22_050);
+        assert!(modulate(Vec::new(), &options).is_err());
+        assert!(Demodulator::new(48_000, &AudioOptions::new().symbol_ms(0)).is_err());
+        assert!(decode_from_wav(&b"RIFF\0\0\0\0AVI "[..], &AudioOptions::new()).is_err());
+    }
+}
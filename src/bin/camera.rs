@@ -0,0 +1,22 @@
+use anyhow::Result;
+use clap::Parser;
+
+#[path = "cli/camera.rs"]
+mod camera;
+#[path = "cli/save.rs"]
+mod save;
+#[cfg(all(feature = "camera-v4l", target_os = "linux"))]
+#[path = "cli/v4l.rs"]
+mod v4l;
+
+#[derive(Parser)]
+#[command(name = "fountain-camera")]
+#[command(author, version, about = "Receive a file live from a camera pointed at a screen of QR codes", long_about = None)]
+struct Cli {
+    #[command(flatten)]
+    args: camera::CameraArgs,
+}
+
+fn main() -> Result<()> {
+    camera::run(Cli::parse().args)
+}
@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Result};
+use clap::Args;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::encode::parse_size;
+use fountain::{
+    encode_bytes_to_gif_with_options,
+    qr::{parse_ec_level, Version},
+    CompressionSetting, EcLevel, EncodeOptions, FileMetadata, MAX_PAYLOAD_SIZE,
+};
+
+/// Options of `fountain bench`.
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Amount of data to encode, e.g. 500KB, 10MB or 1GB (binary units; a plain number is bytes)
+    #[arg(long, value_parser = parse_size)]
+    size: u64,
+
+    /// Maximum payload size (bytes) per QR code (default: ~1400)
+    #[arg(short = 's', long, alias = "chunk-size")]
+    payload: Option<usize>,
+
+    /// Frame interval in milliseconds used for the transfer time estimate (default: 2000)
+    #[arg(short, long, default_value = "2000")]
+    interval: u64,
+
+    /// QR error correction level: L, M, Q or H (default: M)
+    #[arg(short = 'e', long, default_value = "M", value_parser = parse_ec_level)]
+    ec_level: EcLevel,
+
+    /// Ratio of generated RaptorQ packets to source packets (default: 1.5)
+    #[arg(short = 'r', long)]
+    redundancy: Option<f64>,
+
+    /// Compression applied before encoding. The generated data is random, so it stays incompressible.
+    #[arg(short = 'c', long, default_value = "zlib")]
+    compression: CompressionSetting,
+
+    /// Pixel scale for QR code modules (default: 4)
+    #[arg(long, default_value = "4")]
+    pixel_scale: u32,
+
+    /// Print the measurements as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Incompressible pseudo-random bytes, so the measurement reflects the worst case.
+fn random_data(len: usize) -> Vec<u8> {
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 33) as u8
+        })
+        .collect()
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{:.1}s", duration.as_secs_f64()),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m {:02}s", secs / 3600, secs / 60 % 60, secs % 60),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Encodes `--size` bytes of random data to a temporary GIF and reports what a real transfer
+/// with the same options would take.
+pub fn run(args: BenchArgs) -> Result<()> {
+    let len = usize::try_from(args.size).map_err(|_| anyhow!("Size is too large"))?;
+    let data = random_data(len);
+
+    let mut options = EncodeOptions::new()
+        .chunk_size(args.payload.unwrap_or(MAX_PAYLOAD_SIZE))
+        .pixel_scale(args.pixel_scale)
+        .ec_level(args.ec_level)
+        .compression(args.compression)
+        .interval_ms(args.interval);
+    if let Some(redundancy) = args.redundancy {
+        options = options.redundancy(redundancy);
+    }
+
+    let output_gif =
+        std::env::temp_dir().join(format!("fountain-bench-{}.gif", std::process::id()));
+    let started = Instant::now();
+    let encoded = encode_bytes_to_gif_with_options(
+        &data,
+        "bench.bin",
+        &FileMetadata::default(),
+        &output_gif,
+        &options,
+    );
+    let encode_time = started.elapsed();
+    let measured = encoded.and_then(|result| Ok((fs::metadata(&output_gif)?.len(), result)));
+    let _ = fs::remove_file(&output_gif);
+    let (gif_bytes, result) = measured?;
+    let qr = result
+        .qr
+        .as_ref()
+        .ok_or_else(|| anyhow!("The GIF has no QR codes"))?;
+    let (Version::Normal(qr_version) | Version::Micro(qr_version)) = qr.version;
+    let (modules, width) = (qr.modules(), qr.image_px.0);
+    let transfer_time = Duration::from_millis(args.interval * result.num_chunks as u64);
+
+    if args.json {
+        let summary = serde_json::json!({
+            "size": args.size,
+            "payload_size": result.effective_size,
+            "num_chunks": result.num_chunks,
+            "qr_version": qr_version,
+            "image_px": width,
+            "gif_bytes": gif_bytes,
+            "encode_ms": encode_time.as_millis() as u64,
+            "interval_ms": args.interval,
+            "transfer_ms": transfer_time.as_millis() as u64,
+        });
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    println!(
+        "Data size: {} (random, incompressible)",
+        format_bytes(args.size)
+    );
+    println!("Payload size: {} bytes per QR code", result.effective_size);
+    println!("Chunks: {}", result.num_chunks);
+    println!(
+        "QR version: {} ({}x{} modules, {}x{} px frames)",
+        qr_version, modules, modules, width, width
+    );
+    println!("GIF size: {}", format_bytes(gif_bytes));
+    println!("Encode time: {}", format_duration(encode_time));
+    println!(
+        "Transfer time at {}ms per frame: {}",
+        args.interval,
+        format_duration(transfer_time)
+    );
+    Ok(())
+}
@@ -0,0 +1,738 @@
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use image::GrayImage;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph};
+use ratatui::{Frame, Terminal};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStderr, Command, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fountain::qr::decode_all_qr_from_gray;
+use fountain::receive::{PayloadReceiver, ReceiveStatus};
+use fountain::{sha256_hex, Chunk};
+
+use crate::save::{output_path, save_received};
+#[cfg(all(feature = "camera-v4l", target_os = "linux"))]
+use crate::v4l::V4lCamera;
+
+/// Throughput and frame rate are averaged over this much of the recent past.
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+/// Shortest time between two redraws of the dashboard.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const KEY_POLL: Duration = Duration::from_millis(20);
+/// Lines of saved files and errors kept on the dashboard.
+const MAX_MESSAGES: usize = 4;
+
+/// Size of the frames captured and scanned: `WIDTHxHEIGHT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for FrameSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid frame size '{}': expected WIDTHxHEIGHT", s);
+        let (width, height) = s.split_once('x').ok_or_else(invalid)?;
+        let size = Self {
+            width: width.trim().parse().map_err(|_| invalid())?,
+            height: height.trim().parse().map_err(|_| invalid())?,
+        };
+        if size.width == 0 || size.height == 0 {
+            return Err(invalid());
+        }
+        Ok(size)
+    }
+}
+
+/// Options of `fountain-camera`.
+#[derive(Args)]
+pub struct CameraArgs {
+    /// Camera to capture: /dev/video0 on Linux and 0 on macOS by default; on Windows e.g.
+    /// "video=Integrated Camera"
+    #[arg(long)]
+    device: Option<String>,
+
+    /// Capture through Video4Linux2 directly instead of ffmpeg; also used when ffmpeg is not
+    /// installed
+    #[cfg(all(feature = "camera-v4l", target_os = "linux"))]
+    #[arg(long, conflicts_with = "input_format")]
+    v4l: bool,
+
+    /// ffmpeg input format of the camera: v4l2 on Linux, avfoundation on macOS and dshow on
+    /// Windows by default
+    #[arg(long, value_name = "FORMAT")]
+    input_format: Option<String>,
+
+    /// Size of the frames to capture and scan, as WIDTHxHEIGHT
+    #[arg(long, value_name = "WIDTHxHEIGHT", default_value = "1280x720")]
+    size: FrameSize,
+
+    /// Frames per second to capture
+    #[arg(long, default_value_t = 15, value_parser = clap::value_parser!(u32).range(1..))]
+    fps: u32,
+
+    /// Read raw 8-bit grayscale frames of --size from stdin instead of running ffmpeg, e.g.
+    /// from `ffmpeg ... -f rawvideo -pix_fmt gray -` or a capture program of your own
+    #[cfg_attr(
+        all(feature = "camera-v4l", target_os = "linux"),
+        arg(conflicts_with = "v4l")
+    )]
+    #[arg(long, conflicts_with_all = ["device", "input_format"])]
+    stdin: bool,
+
+    /// Directory to save received files in
+    #[arg(short = 'o', long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Overwrite a different file of the same name in the output directory, instead of
+    /// saving the received one under a numbered name such as "notes (1).txt"
+    #[arg(short = 'f', long)]
+    force: bool,
+
+    /// Write the chunks received to this file after each new one, for a sender's
+    /// `fountain encode --terminal --feedback FILE`
+    #[arg(long, value_name = "FILE")]
+    feedback_file: Option<PathBuf>,
+
+    /// Print progress instead of drawing the dashboard, and exit once a file is saved, for
+    /// scripts and sessions without a terminal
+    #[arg(long)]
+    headless: bool,
+}
+
+/// What the capture thread reports.
+enum CameraEvent {
+    /// Payloads of the QR codes in a frame, or `None` when it is the same as the one before.
+    Frame(Option<Vec<Vec<u8>>>),
+    /// No more frames will come, with the reason if it was not the end of the input.
+    Ended(Result<()>),
+}
+
+/// Receives files from the camera with a live dashboard until `q` is pressed, or with
+/// `--headless`, receives one file printing progress on stderr.
+pub fn run(args: CameraArgs) -> Result<()> {
+    if !args.headless && !io::stdout().is_terminal() {
+        return Err(anyhow!(
+            "The camera receiver draws its dashboard on a terminal; pass --headless to receive without one"
+        ));
+    }
+    fs::create_dir_all(&args.output_dir)?;
+
+    let (source, mut child, frames) = open_source(&args)?;
+    let FrameSize { width, height } = frames.size();
+    let source = format!("{} | {}x{} at {} fps", source, width, height, args.fps);
+    let (sender, events) = mpsc::channel();
+    thread::spawn(move || capture(frames, sender));
+
+    let result = if args.headless {
+        receive_headless(&source, &events, &args)
+    } else {
+        let mut dashboard = Dashboard::new(source);
+        let result = show_dashboard(&mut dashboard, &events, &args);
+        for message in &dashboard.messages {
+            println!("{}", message);
+        }
+        result
+    };
+    if let Some(child) = child.as_mut() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    result
+}
+
+/// Where the frames come from.
+enum Frames {
+    /// Raw 8-bit grayscale frames of `size` from ffmpeg or stdin, with ffmpeg's error output.
+    Raw {
+        input: Box<dyn Read + Send>,
+        stderr: Option<ChildStderr>,
+        size: FrameSize,
+    },
+    #[cfg(all(feature = "camera-v4l", target_os = "linux"))]
+    V4l(V4lCamera),
+}
+
+impl Frames {
+    fn size(&self) -> FrameSize {
+        match self {
+            Frames::Raw { size, .. } => *size,
+            #[cfg(all(feature = "camera-v4l", target_os = "linux"))]
+            Frames::V4l(camera) => camera.size(),
+        }
+    }
+
+    /// Waits for the next frame, or returns `None` at the end of the input.
+    fn next_frame(&mut self) -> Result<Option<GrayImage>> {
+        match self {
+            Frames::Raw {
+                input,
+                stderr,
+                size,
+            } => {
+                let mut frame = vec![0u8; size.width as usize * size.height as usize];
+                if let Err(e) = input.read_exact(&mut frame) {
+                    let mut message = String::new();
+                    if let Some(mut stderr) = stderr.take() {
+                        let _ = stderr.read_to_string(&mut message);
+                    }
+                    return if !message.trim().is_empty() {
+                        Err(anyhow!("ffmpeg: {}", message.trim()))
+                    } else if e.kind() == io::ErrorKind::UnexpectedEof {
+                        Ok(None)
+                    } else {
+                        Err(e.into())
+                    };
+                }
+                Ok(Some(
+                    GrayImage::from_raw(size.width, size.height, frame)
+                        .expect("frame holds width x height pixels"),
+                ))
+            }
+            #[cfg(all(feature = "camera-v4l", target_os = "linux"))]
+            Frames::V4l(camera) => camera.next_frame().map(Some),
+        }
+    }
+}
+
+/// Opens stdin or the camera, and returns a description of it with its frames and the
+/// ffmpeg process capturing them, if any.
+fn open_source(args: &CameraArgs) -> Result<(String, Option<Child>, Frames)> {
+    if args.stdin {
+        let frames = Frames::Raw {
+            input: Box::new(io::stdin()),
+            stderr: None,
+            size: args.size,
+        };
+        return Ok(("stdin".to_string(), None, frames));
+    }
+    #[cfg(all(feature = "camera-v4l", target_os = "linux"))]
+    if args.v4l {
+        return open_v4l(args);
+    }
+
+    match spawn_ffmpeg(args) {
+        Ok((device, mut child)) => {
+            let frames = Frames::Raw {
+                input: Box::new(child.stdout.take().expect("ffmpeg stdout is piped")),
+                stderr: child.stderr.take(),
+                size: args.size,
+            };
+            Ok((format!("{} via ffmpeg", device), Some(child), frames))
+        }
+        #[cfg(all(feature = "camera-v4l", target_os = "linux"))]
+        Err(e)
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::NotFound) =>
+        {
+            open_v4l(args)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(all(feature = "camera-v4l", target_os = "linux"))]
+fn open_v4l(args: &CameraArgs) -> Result<(String, Option<Child>, Frames)> {
+    let device = args.device.as_deref().unwrap_or("/dev/video0");
+    let camera = V4lCamera::open(device, args.size, args.fps)?;
+    Ok((format!("{} via V4L2", device), None, Frames::V4l(camera)))
+}
+
+/// Starts ffmpeg capturing the camera as raw grayscale frames on its stdout, and returns a
+/// description of the camera with the process.
+fn spawn_ffmpeg(args: &CameraArgs) -> Result<(String, Child)> {
+    let (default_format, default_device) = if cfg!(target_os = "linux") {
+        (Some("v4l2"), Some("/dev/video0"))
+    } else if cfg!(target_os = "macos") {
+        (Some("avfoundation"), Some("0"))
+    } else if cfg!(windows) {
+        (Some("dshow"), None)
+    } else {
+        (None, None)
+    };
+    let format = args
+        .input_format
+        .as_deref()
+        .or(default_format)
+        .ok_or_else(|| anyhow!("No default camera input format here; pass --input-format"))?;
+    let device = args
+        .device
+        .as_deref()
+        .or(default_device)
+        .ok_or_else(|| anyhow!("No default camera here; pass --device"))?;
+
+    let FrameSize { width, height } = args.size;
+    let child = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-f", format])
+        .args(["-framerate", &args.fps.to_string()])
+        .args(["-video_size", &format!("{}x{}", width, height)])
+        .args(["-i", device])
+        // Cameras that do not offer the size still deliver frames of it
+        .args(["-vf", &format!("scale={}:{}", width, height)])
+        .args(["-f", "rawvideo", "-pix_fmt", "gray", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run ffmpeg; install it, or pass --stdin with frames from elsewhere")?;
+    Ok((device.to_string(), child))
+}
+
+/// Reads frames until the input ends, scanning each one that differs from the one before
+/// for QR codes.
+fn capture(mut frames: Frames, events: mpsc::Sender<CameraEvent>) {
+    let mut previous: Option<GrayImage> = None;
+    loop {
+        let frame = match frames.next_frame() {
+            Ok(Some(frame)) => frame,
+            ended => {
+                let _ = events.send(CameraEvent::Ended(ended.map(|_| ())));
+                return;
+            }
+        };
+
+        // A still picture holds nothing new
+        let codes = if previous.as_ref() == Some(&frame) {
+            None
+        } else {
+            let codes = decode_all_qr_from_gray(&frame);
+            previous = Some(frame);
+            Some(codes)
+        };
+        if events.send(CameraEvent::Frame(codes)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Feeds the codes the camera sees to the receiver until a file is complete and saved,
+/// printing progress on stderr.
+fn receive_headless(
+    source: &str,
+    events: &mpsc::Receiver<CameraEvent>,
+    args: &CameraArgs,
+) -> Result<()> {
+    eprintln!("Scanning {}; press Ctrl-C to stop", source);
+    let mut receiver = PayloadReceiver::new();
+    loop {
+        let codes = match events.recv() {
+            Ok(CameraEvent::Frame(codes)) => codes.unwrap_or_default(),
+            Ok(CameraEvent::Ended(result)) => {
+                result?;
+                return Err(anyhow!("The frames ended before a file was complete"));
+            }
+            Err(_) => return Err(anyhow!("The camera stopped")),
+        };
+        for payload in codes {
+            // Anything else in view may be a QR code too
+            let status = match receiver.feed(&payload) {
+                Ok(status) => status,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    continue;
+                }
+            };
+            if status != ReceiveStatus::Scanning {
+                write_feedback(args, &receiver)?;
+            }
+            match status {
+                ReceiveStatus::Scanning => {}
+                ReceiveStatus::ChunkFound => {
+                    let (received, needed) = receiver.progress();
+                    eprintln!("Chunks received: {}/{}", received, needed);
+                }
+                ReceiveStatus::Complete => {
+                    let (filename, data) = receiver
+                        .take_result()
+                        .ok_or_else(|| anyhow!("Decoder reported no file"))?;
+                    let path = save_received(&args.output_dir, &filename, &data, args.force)?
+                        .unwrap_or_else(|| output_path(&args.output_dir, &filename));
+                    println!("{}", saved_message(&path, &data));
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Writes the chunks received to `--feedback-file`, if given.
+fn write_feedback(args: &CameraArgs, receiver: &PayloadReceiver) -> Result<()> {
+    if let Some(path) = &args.feedback_file {
+        fs::write(path, receiver.feedback().to_string())
+            .with_context(|| format!("Failed to write feedback to {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn saved_message(path: &Path, data: &[u8]) -> String {
+    format!(
+        "Saved {} ({} bytes) | SHA-256 {} | checksum verified",
+        path.display(),
+        data.len(),
+        sha256_hex(data)
+    )
+}
+
+/// Terminal state of the dashboard: raw mode on the alternate screen, restored on drop and
+/// on panic.
+struct TuiGuard;
+
+impl TuiGuard {
+    fn enter() -> Result<Self> {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            previous(info);
+        }));
+        terminal::enable_raw_mode()?;
+        let guard = TuiGuard;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TuiGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+fn restore_terminal() {
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, crossterm::cursor::Show);
+    let _ = terminal::disable_raw_mode();
+}
+
+/// Feeds the codes the camera sees to the receiver and redraws the dashboard until `q`,
+/// Esc or Ctrl+C.
+fn show_dashboard(
+    dashboard: &mut Dashboard,
+    events: &mpsc::Receiver<CameraEvent>,
+    args: &CameraArgs,
+) -> Result<()> {
+    let _guard = TuiGuard::enter()?;
+    let mut tui = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut receiver = PayloadReceiver::new();
+    let mut drawn_at: Option<Instant> = None;
+
+    loop {
+        loop {
+            match events.try_recv() {
+                Ok(CameraEvent::Frame(codes)) => {
+                    dashboard.count_frame(codes.as_ref().map(Vec::len));
+                    for payload in codes.unwrap_or_default() {
+                        dashboard.receive(&mut receiver, &payload, args)?;
+                    }
+                }
+                Ok(CameraEvent::Ended(result)) => {
+                    dashboard.ended = Some(match result {
+                        Ok(()) => "The frames ended".to_string(),
+                        Err(e) => format!("The camera stopped: {}", e),
+                    });
+                }
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+            }
+        }
+
+        if drawn_at.is_none_or(|at| at.elapsed() >= REDRAW_INTERVAL) {
+            tui.draw(|frame| dashboard.draw(frame, &receiver))?;
+            drawn_at = Some(Instant::now());
+        }
+
+        if event::poll(KEY_POLL)? {
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c =
+                    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+                let quit = matches!(key.code, KeyCode::Char('q') | KeyCode::Esc);
+                if key.kind == KeyEventKind::Press && (quit || ctrl_c) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// What the dashboard shows besides the receiver's own progress.
+struct Dashboard {
+    source: String,
+    started_at: Instant,
+    frames: u64,
+    /// When the recent frames arrived, for the frame rate.
+    frame_times: VecDeque<Instant>,
+    /// QR codes in the latest frame that differed from the one before.
+    codes_in_view: usize,
+    last_code_at: Option<Instant>,
+    /// Times each chunk was scanned, by transfer length, packet size and index.
+    heat: HashMap<(u32, u16, u32), u32>,
+    /// Stream of the latest chunk scanned.
+    stream: Option<(u32, u16)>,
+    /// When the recent new chunks arrived, with their size in bytes, for the throughput.
+    arrivals: VecDeque<(Instant, usize)>,
+    /// Files saved, with their checksums, and errors.
+    messages: Vec<String>,
+    ended: Option<String>,
+}
+
+impl Dashboard {
+    fn new(source: String) -> Self {
+        Self {
+            source,
+            started_at: Instant::now(),
+            frames: 0,
+            frame_times: VecDeque::new(),
+            codes_in_view: 0,
+            last_code_at: None,
+            heat: HashMap::new(),
+            stream: None,
+            arrivals: VecDeque::new(),
+            messages: Vec::new(),
+            ended: None,
+        }
+    }
+
+    /// Counts a frame with `codes` QR codes, or `None` when it equals the one before.
+    fn count_frame(&mut self, codes: Option<usize>) {
+        let now = Instant::now();
+        self.frames += 1;
+        self.frame_times.push_back(now);
+        while self
+            .frame_times
+            .front()
+            .is_some_and(|&at| now - at > RATE_WINDOW)
+        {
+            self.frame_times.pop_front();
+        }
+        if let Some(codes) = codes {
+            self.codes_in_view = codes;
+        }
+        if self.codes_in_view > 0 {
+            self.last_code_at = Some(now);
+        }
+    }
+
+    /// Feeds one payload to the receiver, saving the file once it is complete.
+    fn receive(
+        &mut self,
+        receiver: &mut PayloadReceiver,
+        payload: &[u8],
+        args: &CameraArgs,
+    ) -> Result<()> {
+        let chunk = Chunk::from_qr_payload(payload).ok();
+        if let Some(chunk) = &chunk {
+            let header = &chunk.header;
+            self.stream = Some((header.total, header.packet_size));
+            *self
+                .heat
+                .entry((header.total, header.packet_size, header.index))
+                .or_default() += 1;
+        }
+
+        let status = match receiver.feed(payload) {
+            Ok(status) => status,
+            Err(e) => {
+                self.message(format!("Error: {}", e));
+                return Ok(());
+            }
+        };
+        if status == ReceiveStatus::Scanning {
+            return Ok(());
+        }
+        write_feedback(args, receiver)?;
+        let bytes = chunk.map_or(payload.len(), |chunk| chunk.data.len());
+        self.arrivals.push_back((Instant::now(), bytes));
+
+        if let Some((filename, data)) = receiver.take_result() {
+            if let Some(path) = save_received(&args.output_dir, &filename, &data, args.force)? {
+                self.message(saved_message(&path, &data));
+                self.arrivals.clear();
+            }
+        }
+        Ok(())
+    }
+
+    fn message(&mut self, message: String) {
+        self.messages.push(message);
+    }
+
+    /// New chunks per second and bytes per second over the recent past.
+    fn throughput(&mut self) -> (f64, f64) {
+        let now = Instant::now();
+        while self
+            .arrivals
+            .front()
+            .is_some_and(|&(at, _)| now - at > RATE_WINDOW)
+        {
+            self.arrivals.pop_front();
+        }
+        let window = RATE_WINDOW
+            .min(self.started_at.elapsed())
+            .as_secs_f64()
+            .max(1.0);
+        let bytes: usize = self.arrivals.iter().map(|(_, bytes)| bytes).sum();
+        (self.arrivals.len() as f64 / window, bytes as f64 / window)
+    }
+
+    fn draw(&mut self, frame: &mut Frame, receiver: &PayloadReceiver) {
+        let message_lines = self.messages.len().min(MAX_MESSAGES) as u16;
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(4),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(3),
+                Constraint::Length(message_lines.max(1) + 2),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+
+        let fps = self.frame_times.len() as f64
+            / RATE_WINDOW
+                .min(self.started_at.elapsed())
+                .as_secs_f64()
+                .max(1.0);
+        let in_view = match (self.codes_in_view, self.last_code_at) {
+            (0, Some(at)) => format!("No code in view (last one {}s ago)", at.elapsed().as_secs()),
+            (0, None) => "No code in view yet".to_string(),
+            (1, _) => "1 code in view".to_string(),
+            (n, _) => format!("{} codes in view", n),
+        };
+        let mut camera = vec![
+            Line::from(self.source.clone()),
+            Line::from(format!(
+                "{} frames | {:.1} fps | {}",
+                self.frames, fps, in_view
+            )),
+        ];
+        if let Some(ended) = &self.ended {
+            camera[1] = Line::styled(ended.clone(), Style::default().fg(Color::Red));
+        }
+        frame.render_widget(
+            Paragraph::new(camera).block(Block::default().borders(Borders::ALL).title("Camera")),
+            rows[0],
+        );
+
+        let (received, needed) = receiver.progress();
+        let size = receiver
+            .header()
+            .and_then(|header| header.original_size)
+            .map_or(String::new(), |size| {
+                format!(" | {:.1} KiB file", size as f64 / 1024.0)
+            });
+        let ratio = if needed == 0 {
+            0.0
+        } else {
+            (received as f64 / needed as f64).min(1.0)
+        };
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::default().borders(Borders::ALL).title("Progress"))
+                .gauge_style(Style::default().fg(Color::Green))
+                .ratio(ratio)
+                .label(format!("{}/{} chunks{}", received, needed, size)),
+            rows[1],
+        );
+
+        let (chunks_per_sec, bytes_per_sec) = self.throughput();
+        let eta = match needed.saturating_sub(received) {
+            _ if needed == 0 || chunks_per_sec == 0.0 => "--:--".to_string(),
+            left => {
+                let secs = (left as f64 / chunks_per_sec).ceil() as u64;
+                format!("{:02}:{:02}", secs / 60, secs % 60)
+            }
+        };
+        let elapsed = self.started_at.elapsed().as_secs();
+        frame.render_widget(
+            Paragraph::new(format!(
+                "{:.1} chunks/s | {:.1} KB/s | ETA {} | Elapsed {:02}:{:02}",
+                chunks_per_sec,
+                bytes_per_sec / 1000.0,
+                eta,
+                elapsed / 60,
+                elapsed % 60
+            ))
+            .block(Block::default().borders(Borders::ALL).title("Throughput")),
+            rows[2],
+        );
+
+        let grid = Block::default()
+            .borders(Borders::ALL)
+            .title("Chunks (grey: missing, green to red: scanned once to many times)");
+        let inner = grid.inner(rows[3]);
+        frame.render_widget(
+            Paragraph::new(self.grid_lines(needed, inner.width, inner.height)).block(grid),
+            rows[3],
+        );
+
+        let skip = self.messages.len().saturating_sub(MAX_MESSAGES);
+        let messages: Vec<Line> = self.messages[skip..]
+            .iter()
+            .map(|message| Line::from(message.clone()))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(messages).block(Block::default().borders(Borders::ALL).title("Files")),
+            rows[4],
+        );
+        frame.render_widget(Paragraph::new("q: quit"), rows[5]);
+    }
+
+    /// One cell per chunk of the current stream, up to the chunks needed or the highest
+    /// index scanned, coloured by how often it was scanned.
+    fn grid_lines(&self, needed: u32, width: u16, height: u16) -> Vec<Line<'static>> {
+        let Some((transfer_length, packet_size)) = self.stream else {
+            return Vec::new();
+        };
+        let highest = self
+            .heat
+            .keys()
+            .filter(|&&(t, p, _)| (t, p) == (transfer_length, packet_size))
+            .map(|&(_, _, index)| index + 1)
+            .max()
+            .unwrap_or(0);
+        let cells = needed.max(highest) as usize;
+        let width = (width as usize).max(1);
+        let shown = cells.min(width * height as usize);
+
+        (0..shown)
+            .step_by(width)
+            .map(|row_start| {
+                let spans: Vec<Span> = (row_start..(row_start + width).min(shown))
+                    .map(|index| {
+                        let count = self
+                            .heat
+                            .get(&(transfer_length, packet_size, index as u32))
+                            .copied()
+                            .unwrap_or(0);
+                        Span::styled("■", Style::default().fg(heat_color(count)))
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect()
+    }
+}
+
+fn heat_color(count: u32) -> Color {
+    match count {
+        0 => Color::DarkGray,
+        1 => Color::Green,
+        2 => Color::LightGreen,
+        3..=5 => Color::Yellow,
+        _ => Color::Red,
+    }
+}
@@ -0,0 +1,638 @@
+use anyhow::Result;
+use clap::Args;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use fountain::{
+    decode_from_chunk_files_to_vec, decode_from_chunk_files_with_options, decode_from_gif_to_vec,
+    decode_from_gif_with_options, decode_from_images_to_vec, decode_from_images_with_options,
+    decode_from_paths_to_vec, decode_from_paths_with_options, inspect_gif, inspect_image,
+    inspect_images, read_raw_gif, read_raw_image, read_raw_images, sha256_hex, verify_gif,
+    verify_images, DecodeOptions, DecodeResult, FramePosition, GlobPattern, ScanEffort, StreamInfo,
+    IMAGE_EXTENSIONS,
+};
+
+/// Largest decoded file --to-clipboard accepts; bigger ones belong in a file.
+#[cfg(feature = "clipboard")]
+const MAX_CLIPBOARD_BYTES: usize = 1024 * 1024;
+
+/// Options of `fountain-decode` and `fountain decode`.
+#[derive(Args)]
+pub struct DecodeArgs {
+    /// Input directory (containing images) or GIF file. With several inputs, any mix of images,
+    /// GIFs and directories (searched recursively) is decoded together as one stream.
+    #[arg(required_unless_present = "chunks_in")]
+    #[cfg_attr(feature = "screen", arg(required_unless_present = "screen"))]
+    #[cfg_attr(feature = "watch", arg(required_unless_present = "watch"))]
+    #[cfg_attr(feature = "audio", arg(required_unless_present = "audio"))]
+    input: Vec<PathBuf>,
+
+    /// Decode a directory of raw chunk files, one chunk per file, as written by
+    /// `fountain encode --chunks-out` or received over another transport
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["input", "info", "verify", "raw", "enhance"])]
+    #[cfg_attr(feature = "screen", arg(conflicts_with = "screen"))]
+    #[cfg_attr(feature = "watch", arg(conflicts_with = "watch"))]
+    #[cfg_attr(feature = "audio", arg(conflicts_with = "audio"))]
+    chunks_in: Option<PathBuf>,
+
+    /// Decode the images in this directory, then keep watching it and read each new image as
+    /// it appears (e.g. photos synced from a phone) until the file is complete
+    #[cfg(feature = "watch")]
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["input", "to_stdout", "info", "verify", "raw"])]
+    #[cfg_attr(feature = "screen", arg(conflicts_with = "screen"))]
+    #[cfg_attr(feature = "clipboard", arg(conflicts_with = "to_clipboard"))]
+    watch: Option<PathBuf>,
+
+    /// Capture the local display and decode the codes shown on it live, e.g. a sender
+    /// running in a VM or remote desktop window, until the file is complete
+    #[cfg(feature = "screen")]
+    #[arg(long, conflicts_with_all = ["input", "info", "verify", "raw", "recursive", "glob"])]
+    screen: bool,
+
+    /// Only capture this part of the display with --screen, as X,Y,WIDTHxHEIGHT in pixels
+    #[cfg(feature = "screen")]
+    #[arg(long, requires = "screen")]
+    region: Option<crate::screen::ScreenRegion>,
+
+    /// With --screen, write the chunks received to this file after each new one, for a
+    /// sender's `fountain encode --terminal --feedback FILE` to show only the missing codes
+    #[cfg(feature = "screen")]
+    #[arg(long, value_name = "FILE", requires = "screen")]
+    feedback_file: Option<PathBuf>,
+
+    /// Listen for a file sent with `fountain encode --audio-output-file` (experimental): a WAV
+    /// recording, or `-` for a live WAV stream on stdin such as `arecord -f S16_LE -t wav -`
+    #[cfg(feature = "audio")]
+    #[arg(long, value_name = "WAV", conflicts_with_all = ["input", "info", "verify", "raw", "recursive", "glob", "enhance"])]
+    #[cfg_attr(feature = "screen", arg(conflicts_with = "screen"))]
+    #[cfg_attr(feature = "watch", arg(conflicts_with = "watch"))]
+    audio: Option<PathBuf>,
+
+    /// Listen for tones at 18 kHz and above, as sent with `fountain encode --ultrasonic`
+    #[cfg(feature = "audio")]
+    #[arg(long, requires = "audio")]
+    ultrasonic: bool,
+
+    /// Output file path (defaults to original filename in current directory)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Write the decoded bytes to stdout instead of a file (progress goes to stderr)
+    #[arg(long, conflicts_with = "output")]
+    to_stdout: bool,
+
+    /// Place the decoded file on the clipboard instead of writing it, for short texts such
+    /// as passwords. It must be UTF-8 and at most 1 MiB.
+    #[cfg(feature = "clipboard")]
+    #[arg(long, conflicts_with_all = [
+        "output", "to_stdout", "info", "verify", "raw", "no_metadata", "no_skip_duplicates",
+        "recursive", "glob", "enhance", "effort", "json",
+    ])]
+    to_clipboard: bool,
+
+    /// Earlier version of the file, for a transfer sent with `fountain encode --base`; the
+    /// decoded delta is applied to it
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["to_stdout", "info", "verify", "raw"])]
+    #[cfg_attr(feature = "screen", arg(conflicts_with = "screen"))]
+    #[cfg_attr(feature = "clipboard", arg(conflicts_with = "to_clipboard"))]
+    base: Option<PathBuf>,
+
+    /// Only report what the chunk headers say (chunk counts, sizes, missing indices); writes nothing.
+    /// Also accepts a single image file.
+    #[arg(long, conflicts_with_all = ["output", "to_stdout"])]
+    info: bool,
+
+    /// Decode and validate the checksum without writing the result, to confirm the input is recoverable
+    #[arg(long, conflicts_with_all = ["output", "to_stdout", "info"])]
+    verify: bool,
+
+    /// Do not restore the permissions and modification time recorded in the stream
+    #[arg(long, conflicts_with_all = ["to_stdout", "info", "verify"])]
+    no_metadata: bool,
+
+    /// Run QR detection on every frame, even ones nearly identical to the previous frame
+    #[arg(long, conflicts_with_all = ["to_stdout", "info", "verify"])]
+    no_skip_duplicates: bool,
+
+    /// Also read images from subdirectories of the input directory
+    #[arg(long, conflicts_with_all = ["to_stdout", "info", "verify", "raw"])]
+    recursive: bool,
+
+    /// Only read images in the input directory whose path matches this pattern, e.g.
+    /// "page-*/img_*.jpg" (with --recursive). Without a '/', it matches file names.
+    #[arg(long, conflicts_with_all = ["to_stdout", "info", "verify", "raw"])]
+    glob: Option<GlobPattern>,
+
+    /// Retry images where no QR code is found after stretching contrast and thresholding
+    /// them, which helps with photos of paper or screens
+    #[arg(long, conflicts_with_all = ["to_stdout", "info", "verify", "raw"])]
+    enhance: bool,
+
+    /// How hard to try on images where no code is read at first: "normal", or "thorough" to
+    /// also try several threshold levels and rotations (slower, for tough scans)
+    #[arg(long, default_value = "normal", conflicts_with_all = ["to_stdout", "info", "verify", "raw"])]
+    effort: ScanEffort,
+
+    /// Start scanning a GIF here, e.g. where the transfer begins in a long screen recording:
+    /// a time such as 1:30, 00:01:30.5 or 90s, or a frame number counting from 0
+    #[arg(long, value_name = "TIME|FRAME", conflicts_with_all = ["chunks_in", "to_stdout", "info", "verify", "raw"])]
+    #[cfg_attr(feature = "screen", arg(conflicts_with = "screen"))]
+    #[cfg_attr(feature = "watch", arg(conflicts_with = "watch"))]
+    #[cfg_attr(feature = "audio", arg(conflicts_with = "audio"))]
+    #[cfg_attr(feature = "clipboard", arg(conflicts_with = "to_clipboard"))]
+    start: Option<FramePosition>,
+
+    /// Stop scanning a GIF here, leaving out the frame at this time or number
+    #[arg(long, value_name = "TIME|FRAME", conflicts_with_all = ["chunks_in", "to_stdout", "info", "verify", "raw"])]
+    #[cfg_attr(feature = "screen", arg(conflicts_with = "screen"))]
+    #[cfg_attr(feature = "watch", arg(conflicts_with = "watch"))]
+    #[cfg_attr(feature = "audio", arg(conflicts_with = "audio"))]
+    #[cfg_attr(feature = "clipboard", arg(conflicts_with = "to_clipboard"))]
+    end: Option<FramePosition>,
+
+    /// Print whatever the QR codes contain, one per line, without treating them as a fountain
+    /// stream. Works with any QR code and also accepts a single image file.
+    #[arg(long, conflicts_with_all = ["output", "to_stdout", "info", "verify"])]
+    raw: bool,
+
+    /// Only show warnings and errors from the decoder
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Show more detail: -v lists each chunk read, -vv also skipped frames
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Print only a JSON summary (chunk count, filename, output path, SHA-256, time taken) on
+    /// stdout, for scripts. Progress messages still go to stderr.
+    #[arg(long, conflicts_with_all = ["to_stdout", "raw"])]
+    json: bool,
+}
+
+pub fn run(args: DecodeArgs) -> Result<()> {
+    crate::logging::init_logging(args.quiet, args.verbose);
+    let started = Instant::now();
+
+    #[cfg(feature = "screen")]
+    if args.screen {
+        return decode_screen(&args, started);
+    }
+
+    if let Some(dir) = &args.chunks_in {
+        if in_memory(&args) {
+            let (original_filename, data) = decode_from_chunk_files_to_vec(dir)?;
+            return write_in_memory(&args, &original_filename, &data);
+        }
+        let result = decode_from_chunk_files_with_options(
+            dir,
+            args.output.as_deref(),
+            &stream_options(&args),
+        )?;
+        return print_result(&args, &result, started);
+    }
+
+    #[cfg(feature = "audio")]
+    if let Some(audio) = &args.audio {
+        return decode_audio(&args, audio, started);
+    }
+
+    #[cfg(feature = "watch")]
+    if let Some(dir) = &args.watch {
+        let result = fountain::decode_from_watched_dir_with_options(
+            dir,
+            args.output.as_deref(),
+            &stream_options(&args),
+        )?;
+        return print_result(&args, &result, started);
+    }
+
+    let frame_range = args.start.is_some() || args.end.is_some();
+    if args.input.len() > 1 {
+        if args.info || args.verify || args.raw {
+            anyhow::bail!("--info, --verify and --raw take a single input");
+        }
+        if frame_range {
+            anyhow::bail!("--start and --end take a single GIF input");
+        }
+        return decode_paths(&args, started);
+    }
+
+    let input = &args.input[0];
+    let (is_dir, is_gif) = input_kind(input)?;
+    if frame_range && !is_gif {
+        anyhow::bail!("--start and --end take a GIF input");
+    }
+
+    if args.info {
+        return show_info(input, is_dir, is_gif, args.json);
+    }
+
+    if args.raw {
+        let contents = if is_dir {
+            read_raw_images(input)?
+        } else if is_gif {
+            read_raw_gif(input)?
+        } else {
+            read_raw_image(input)?
+        };
+
+        let mut stdout = io::stdout().lock();
+        for content in &contents {
+            stdout.write_all(content)?;
+            stdout.write_all(b"\n")?;
+        }
+        stdout.flush()?;
+        return Ok(());
+    }
+
+    ensure_stream_input(input, is_dir, is_gif)?;
+
+    if args.verify {
+        return show_verification(input, is_dir, args.json, started);
+    }
+
+    if in_memory(&args) {
+        let (original_filename, data) = if is_dir {
+            decode_from_images_to_vec(input)?
+        } else {
+            decode_from_gif_to_vec(input)?
+        };
+        return write_in_memory(&args, &original_filename, &data);
+    }
+
+    let options = stream_options(&args);
+    let result = if is_dir {
+        decode_from_images_with_options(input, args.output.as_deref(), &options)?
+    } else {
+        decode_from_gif_with_options(input, args.output.as_deref(), &options)?
+    };
+    print_result(&args, &result, started)
+}
+
+/// Decode options for a single directory or GIF input.
+fn stream_options(args: &DecodeArgs) -> DecodeOptions {
+    let mut options = DecodeOptions::new()
+        .restore_metadata(!args.no_metadata)
+        .skip_duplicate_frames(!args.no_skip_duplicates)
+        .recursive(args.recursive)
+        .enhance(args.enhance)
+        .effort(args.effort);
+    if let Some(glob) = &args.glob {
+        options = options.glob(glob.clone());
+    }
+    if let Some(base) = &args.base {
+        options = options.base(base);
+    }
+    if let Some(start) = args.start {
+        options = options.start(start);
+    }
+    if let Some(end) = args.end {
+        options = options.end(end);
+    }
+    options
+}
+
+/// Whether `input` is a directory and whether it is a GIF, failing if it does not exist.
+pub fn input_kind(input: &Path) -> Result<(bool, bool)> {
+    if !input.exists() {
+        anyhow::bail!("Input path does not exist: {}", input.display());
+    }
+    let is_gif = input
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("gif"))
+        .unwrap_or(false);
+    Ok((input.is_dir(), is_gif))
+}
+
+pub fn ensure_stream_input(input: &Path, is_dir: bool, is_gif: bool) -> Result<()> {
+    if !is_dir && !is_gif {
+        anyhow::bail!(
+            "Unsupported input file type: {}. Only directories (containing {} files) or GIF files are supported.",
+            input.display(),
+            IMAGE_EXTENSIONS.join(", ")
+        );
+    }
+    if is_dir {
+        eprintln!("Decoding QR codes from directory: {}", input.display());
+    }
+    Ok(())
+}
+
+pub fn show_info(input: &Path, is_dir: bool, is_gif: bool, json: bool) -> Result<()> {
+    let info = if is_dir {
+        inspect_images(input)?
+    } else if is_gif {
+        inspect_gif(input)?
+    } else {
+        inspect_image(input)?
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info_json(&info))?);
+    } else {
+        print_info(&info);
+    }
+    Ok(())
+}
+
+pub fn show_verification(input: &Path, is_dir: bool, json: bool, started: Instant) -> Result<()> {
+    let report = if is_dir {
+        verify_images(input)?
+    } else {
+        verify_gif(input)?
+    };
+
+    if json {
+        let summary = serde_json::json!({
+            "original_filename": report.original_filename,
+            "original_size": report.original_size,
+            "num_chunks": report.num_chunks,
+            "sha256": report.sha256,
+            "elapsed_ms": started.elapsed().as_millis() as u64,
+        });
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    println!();
+    println!("Verification passed using {} QR code(s)", report.num_chunks);
+    println!("Original filename: {}", report.original_filename);
+    println!("Original size: {} bytes", report.original_size);
+    println!("SHA-256: {}", report.sha256);
+    Ok(())
+}
+
+/// Whether the decoded file goes to stdout or the clipboard instead of a file.
+fn in_memory(args: &DecodeArgs) -> bool {
+    #[cfg(feature = "clipboard")]
+    if args.to_clipboard {
+        return true;
+    }
+    args.to_stdout
+}
+
+/// Writes a file decoded in memory to stdout, or else places it on the clipboard.
+fn write_in_memory(args: &DecodeArgs, original_filename: &str, data: &[u8]) -> Result<()> {
+    if args.to_stdout {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(data)?;
+        stdout.flush()?;
+
+        eprintln!();
+        eprintln!("Original filename: {}", original_filename);
+        eprintln!("Wrote {} bytes to stdout", data.len());
+        eprintln!("SHA-256: {}", sha256_hex(data));
+        return Ok(());
+    }
+
+    #[cfg(feature = "clipboard")]
+    {
+        copy_to_clipboard(data)?;
+        eprintln!();
+        eprintln!("Original filename: {}", original_filename);
+        eprintln!("Copied {} bytes to the clipboard", data.len());
+        eprintln!("SHA-256: {}", sha256_hex(data));
+    }
+    Ok(())
+}
+
+/// Places a decoded text file on the clipboard, refusing binary data and large files.
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(data: &[u8]) -> Result<()> {
+    if data.len() > MAX_CLIPBOARD_BYTES {
+        anyhow::bail!(
+            "Decoded file is {} bytes; --to-clipboard takes at most {}, use --output instead",
+            data.len(),
+            MAX_CLIPBOARD_BYTES
+        );
+    }
+    let text = std::str::from_utf8(data)
+        .map_err(|_| anyhow::anyhow!("Decoded file is not text; use --output instead"))?;
+    let mut clipboard = arboard::Clipboard::new()
+        .map_err(|e| anyhow::anyhow!("Failed to open the clipboard: {}", e))?;
+    // On X11 the text outlives this process only if a clipboard manager takes it over,
+    // which arboard asks for when the clipboard is dropped
+    clipboard
+        .set_text(text)
+        .map_err(|e| anyhow::anyhow!("Failed to set the clipboard: {}", e))
+}
+
+fn decode_paths(args: &DecodeArgs, started: Instant) -> Result<()> {
+    if in_memory(args) {
+        let (original_filename, data) = decode_from_paths_to_vec(&args.input)?;
+        return write_in_memory(args, &original_filename, &data);
+    }
+
+    let mut options = DecodeOptions::new()
+        .restore_metadata(!args.no_metadata)
+        .skip_duplicate_frames(!args.no_skip_duplicates)
+        .enhance(args.enhance)
+        .effort(args.effort);
+    if let Some(base) = &args.base {
+        options = options.base(base);
+    }
+    let result = decode_from_paths_with_options(&args.input, args.output.as_deref(), &options)?;
+    print_result(args, &result, started)
+}
+
+/// Decodes the codes shown on the display and writes the file like a decoded stream.
+#[cfg(feature = "screen")]
+fn decode_screen(args: &DecodeArgs, started: Instant) -> Result<()> {
+    let (original_filename, data) =
+        crate::screen::receive(args.region, args.feedback_file.as_deref())?;
+    write_received(args, &original_filename, &data, started)
+}
+
+/// Listens to a WAV recording or stream and writes the file like a decoded stream.
+#[cfg(feature = "audio")]
+fn decode_audio(args: &DecodeArgs, audio: &Path, started: Instant) -> Result<()> {
+    use fountain::audio::{decode_from_wav, AudioBand, AudioOptions};
+
+    let band = if args.ultrasonic {
+        AudioBand::Ultrasonic
+    } else {
+        AudioBand::Audible
+    };
+    let options = AudioOptions::new().band(band);
+    let (original_filename, data, metadata) = if audio.as_os_str() == "-" {
+        eprintln!(
+            "Listening on stdin for {} tones; press Ctrl-C to stop",
+            band
+        );
+        decode_from_wav(io::stdin().lock(), &options)?
+    } else {
+        decode_from_wav(io::BufReader::new(std::fs::File::open(audio)?), &options)?
+    };
+    let data = match (metadata.base_sha256, &args.base) {
+        (None, _) => data,
+        (Some(_), Some(base)) => fountain::delta::apply(&std::fs::read(base)?, &data)?,
+        (Some(_), None) => anyhow::bail!(
+            "{} was sent as a delta; decode it with --base and the earlier version",
+            original_filename
+        ),
+    };
+    write_received(args, &original_filename, &data, started)
+}
+
+/// Writes a file received live, from the screen or by sound, and prints a summary.
+#[cfg(any(feature = "screen", feature = "audio"))]
+fn write_received(
+    args: &DecodeArgs,
+    original_filename: &str,
+    data: &[u8],
+    started: Instant,
+) -> Result<()> {
+    if in_memory(args) {
+        return write_in_memory(args, original_filename, data);
+    }
+    let sha256 = sha256_hex(data);
+
+    // The name comes from whatever was received; never let it leave the current directory
+    let output_path = match &args.output {
+        Some(path) => path.clone(),
+        None => Path::new(original_filename)
+            .file_name()
+            .map_or_else(|| PathBuf::from("received.bin"), PathBuf::from),
+    };
+    std::fs::write(&output_path, data)?;
+    if args.json {
+        let summary = serde_json::json!({
+            "original_filename": original_filename,
+            "output_path": output_path,
+            "sha256": sha256,
+            "elapsed_ms": started.elapsed().as_millis() as u64,
+        });
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    println!();
+    println!("Original filename: {}", original_filename);
+    println!("Output file: {}", output_path.display());
+    println!("SHA-256: {}", sha256);
+    Ok(())
+}
+
+fn print_result(args: &DecodeArgs, result: &DecodeResult, started: Instant) -> Result<()> {
+    if args.json {
+        let summary = serde_json::json!({
+            "original_filename": result.original_filename,
+            "output_path": result.output_path,
+            "num_chunks": result.num_chunks,
+            "sha256": result.sha256,
+            "seed": result.metadata.seed,
+            "elapsed_ms": started.elapsed().as_millis() as u64,
+        });
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    println!();
+    println!("Successfully decoded {} QR code(s)", result.num_chunks);
+    println!("Original filename: {}", result.original_filename);
+    println!("Output file: {}", result.output_path);
+    println!("SHA-256: {}", result.sha256);
+    if let Some(seed) = result.metadata.seed {
+        println!("Seed: {}", seed);
+    }
+    Ok(())
+}
+
+fn info_json(info: &StreamInfo) -> serde_json::Value {
+    let (original_filename, original_size) = match &info.original {
+        Some((filename, size)) => (Some(filename.clone()), Some(*size as u64)),
+        None => (None, info.original_size.map(u64::from)),
+    };
+    serde_json::json!({
+        "format_version": info.format_version,
+        "transfer_length": info.transfer_length,
+        "packet_size": info.packet_size,
+        "source_packets": info.source_packets,
+        "present_indices": info.present_indices,
+        "missing_indices": info.missing_indices,
+        "foreign_chunks": info.foreign_chunks,
+        "original_filename": original_filename,
+        "original_size": original_size,
+        "volume": info.volume.map(|volume| volume.index + 1),
+        "volume_count": info.volume.map(|volume| volume.count),
+        "seed": info.seed,
+        "sync_frames": info.sync.map(|sync| sync.frames),
+        "sync_interval_ms": info.sync.map(|sync| sync.interval_ms),
+    })
+}
+
+fn print_info(info: &StreamInfo) {
+    println!("Format version: {}", info.format_version);
+    println!("Mode: RaptorQ");
+    println!("Transfer length: {} bytes", info.transfer_length);
+    println!("Packet size: {} bytes", info.packet_size);
+    if let Some(volume) = info.volume {
+        println!("Volume: {} of {}", volume.index + 1, volume.count);
+    }
+    if let Some(sync) = info.sync {
+        println!(
+            "Start marker: {} frames every {} ms",
+            sync.frames, sync.interval_ms
+        );
+    }
+    println!(
+        "Source packets: {} (at least this many chunks are needed)",
+        info.source_packets
+    );
+    println!(
+        "Chunks found: {} [{}]",
+        info.present_indices.len(),
+        format_ranges(&info.present_indices)
+    );
+    if !info.missing_indices.is_empty() {
+        println!(
+            "Missing below highest index: {} [{}]",
+            info.missing_indices.len(),
+            format_ranges(&info.missing_indices)
+        );
+    }
+    if info.foreign_chunks > 0 {
+        println!(
+            "Ignored {} chunk(s) from a different stream",
+            info.foreign_chunks
+        );
+    }
+    match &info.original {
+        Some((filename, size)) => {
+            println!("Original filename: {}", filename);
+            println!("Original size: {} bytes", size);
+            if let Some(seed) = info.seed {
+                println!("Seed: {}", seed);
+            }
+        }
+        None => {
+            println!("Original filename: unknown (not enough chunks to decode)");
+            match info.original_size {
+                Some(size) => println!("Original size: {} bytes", size),
+                None => println!(
+                    "Original size: unknown (compressed size is {} bytes)",
+                    info.transfer_length
+                ),
+            }
+        }
+    }
+}
+
+/// Formats sorted indices as compact ranges, e.g. "0-3, 5, 7-9".
+fn format_ranges(indices: &[u32]) -> String {
+    let mut ranges: Vec<String> = Vec::new();
+    let mut iter = indices.iter().copied().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while iter.peek() == Some(&(end + 1)) {
+            end += 1;
+            iter.next();
+        }
+        if start == end {
+            ranges.push(start.to_string());
+        } else {
+            ranges.push(format!("{}-{}", start, end));
+        }
+    }
+    ranges.join(", ")
+}
@@ -0,0 +1,1127 @@
+use anyhow::{anyhow, Result};
+use clap::Args;
+use image::Rgb;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use fountain::{
+    display_qr_carousel_with, display_qr_once, display_qr_stream,
+    encode_bytes_for_terminal_single_with_options, encode_bytes_for_terminal_stream_with_options,
+    encode_bytes_for_terminal_with_options, encode_bytes_to_apng_with_options,
+    encode_bytes_to_chunk_files_with_options, encode_bytes_to_gif_with_options,
+    encode_bytes_to_images_with_options, encode_bytes_to_selected_images_with_options,
+    encode_bytes_to_single_image_with_options, encode_bytes_to_tiled_images_with_options,
+    encode_files_for_terminal_with_options, encode_text_for_terminal_with_options,
+    encode_text_to_image_with_options, print_sizing,
+    qr::{parse_color, parse_ec_level, parse_qr_version, Version},
+    read_file_metadata, sha256_hex, CarouselOptions, CompressionSetting, EcLevel, EncodeOptions,
+    EncodeResult, FileMetadata, FrameOrder, GridLayout, LoopCount, NameTemplate, PayloadEncoding,
+    QrStyle, TerminalFit, TerminalQrData, TerminalRender, TerminalSize, WireFormat,
+    MAX_PAYLOAD_SIZE,
+};
+
+/// Options of `fountain-encode` and `fountain encode`.
+#[derive(Args)]
+pub struct EncodeArgs {
+    /// Input file to encode, or `-` to read from stdin. Several files can be given with
+    /// --terminal, to show them in one carousel that cycles between them.
+    #[cfg_attr(feature = "clipboard", arg(required_unless_present = "from_clipboard"))]
+    #[cfg_attr(not(feature = "clipboard"), arg(required = true))]
+    input: Vec<PathBuf>,
+
+    /// Encode what is on the clipboard instead of an input file: a copied file, or text
+    /// (embedded as "clipboard.txt" unless --name is given)
+    #[cfg(feature = "clipboard")]
+    #[arg(long, conflicts_with = "input")]
+    from_clipboard: bool,
+
+    /// Filename to embed in the stream (defaults to the input file name; required when reading stdin)
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Output directory for QR code images
+    #[arg(short = 'm', long = "image-output-dir", required_unless_present_any = ["terminal", "gif_output_file", "chunks_out"])]
+    #[cfg_attr(feature = "audio", arg(required_unless_present = "audio_output_file"))]
+    image_output_dir: Option<PathBuf>,
+
+    /// Output animation containing all QR codes: a GIF, or a full-colour APNG when the
+    /// file name ends in .png or .apng
+    #[arg(short = 'g', long)]
+    gif_output_file: Option<PathBuf>,
+
+    /// Output WAV file playing the chunks as FSK tones, for sending a file by sound when no
+    /// camera is at hand (experimental; `fountain decode --audio` listens)
+    #[cfg(feature = "audio")]
+    #[arg(long, value_name = "WAV", conflicts_with_all = ["image_output_dir", "gif_output_file", "terminal", "single", "raw_text", "volume_size"])]
+    audio_output_file: Option<PathBuf>,
+
+    /// Send the audio at 18 kHz and above, inaudible to most adults
+    #[cfg(feature = "audio")]
+    #[arg(long, requires = "audio_output_file")]
+    ultrasonic: bool,
+
+    /// Output directory for raw chunk files, one per chunk with the bytes its QR code would
+    /// hold, for other transports such as NFC tags or Bluetooth; `fountain decode --chunks-in`
+    /// reads them
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["image_output_dir", "gif_output_file", "terminal", "single", "raw_text", "caption", "sync_frames"])]
+    #[cfg_attr(feature = "audio", arg(conflicts_with = "audio_output_file"))]
+    chunks_out: Option<PathBuf>,
+
+    /// Display QR codes in terminal instead of saving to files
+    #[arg(short, long)]
+    terminal: bool,
+
+    /// Interval in milliseconds for auto-switching QR codes in terminal mode or GIF frame duration (default: 2000)
+    #[arg(short, long, default_value = "2000")]
+    interval: u64,
+
+    /// Show the first GIF frame, the start marker with --sync-frames, for this many
+    /// milliseconds, so the receiver can find the code before the frames start changing
+    #[arg(long, value_name = "MS", requires = "gif_output_file")]
+    first_frame_ms: Option<u64>,
+
+    /// Frame duration in milliseconds for the cycles after the first
+    #[arg(long, value_name = "MS", requires = "cycles")]
+    repeat_interval: Option<u64>,
+
+    /// Order of GIF frames and terminal carousel codes: sequential (default), interleaved
+    /// (round-robin over RaptorQ source blocks, so missed stretches hurt no block much) or
+    /// shuffled (a fixed permutation, from --seed when given)
+    #[arg(long, default_value = "sequential", conflicts_with_all = ["stream", "single", "raw_text"])]
+    frame_order: FrameOrder,
+
+    /// Write the chunk sequence this many times into the GIF, so a single recording of it has
+    /// retransmissions built in (only with --gif-output-file)
+    #[arg(long, requires = "gif_output_file", value_parser = clap::value_parser!(u32).range(1..))]
+    cycles: Option<u32>,
+
+    /// Fill the cycles after the first with new repair packets instead of repeating it
+    #[arg(long, requires = "cycles")]
+    fresh_cycles: bool,
+
+    /// Show a grid of codes in each GIF frame as COLUMNSxROWS (e.g. 2x2), each with its own
+    /// chunk, for several times the throughput to a camera that resolves the smaller codes
+    #[arg(long, value_name = "COLSxROWS", requires = "gif_output_file")]
+    frame_grid: Option<GridLayout>,
+
+    /// Overlay three codes in each GIF frame, one per colour plane, for three times the data
+    /// per frame (experimental; only where colours arrive exactly, such as decoding the file)
+    #[arg(long, requires = "gif_output_file", conflicts_with = "caption")]
+    color_planes: bool,
+
+    /// Store only the part of each GIF frame that changed since the one before
+    #[arg(long, requires = "gif_output_file")]
+    gif_delta_frames: bool,
+
+    /// How many times the GIF or APNG plays: infinite (default), once, or a number; a single
+    /// pass suits animations embedded in documents
+    #[arg(
+        long = "loop",
+        value_name = "infinite|N|once",
+        requires = "gif_output_file"
+    )]
+    loop_count: Option<LoopCount>,
+
+    /// Start the GIF with a black and a white frame for the camera's exposure and a start
+    /// marker code with the frame count and interval; the terminal carousel shows the marker
+    #[arg(long, conflicts_with_all = ["stream", "single", "raw_text", "no_carousel", "columns"])]
+    sync_frames: bool,
+
+    /// Print the chunk number, a session ID (start of the SHA-256) and the filename under
+    /// each code of image and GIF output, to sort and check printouts by hand
+    #[arg(long, conflicts_with_all = ["terminal", "grid", "single", "raw_text"])]
+    caption: bool,
+
+    /// Framing of the codes: native (this tool's chunks) or ur (BC-UR `ur:bytes` parts, for UR
+    /// scanners and hardware wallets). UR sends the raw contents without filename or
+    /// compression, for GIF, APNG and terminal carousel output.
+    #[arg(long, default_value = "native", conflicts_with_all = ["image_output_dir", "chunks_out", "stream", "single", "raw_text", "sync_frames", "caption", "volume_size", "cycles", "frame_order"])]
+    wire_format: WireFormat,
+
+    /// Show all QR codes at once without carousel (only with --terminal)
+    #[arg(long)]
+    no_carousel: bool,
+
+    /// How QR codes are drawn in the terminal: half-block (default) or braille characters,
+    /// or an inline image via kitty, iterm2 or sixel. `image` detects a supported protocol
+    /// and falls back to half-block. Braille packs 2x4 modules per character, so much larger codes fit.
+    #[arg(long, default_value = "half-block", requires = "terminal")]
+    render: TerminalRender,
+
+    /// Show all QR codes at once, this many side by side (only with --terminal).
+    /// Without it, --no-carousel fits as many columns as the terminal width allows.
+    #[arg(long, requires = "terminal", value_parser = clap::value_parser!(u32).range(1..))]
+    columns: Option<u32>,
+
+    /// Fit codes to a terminal this many characters wide instead of the detected width, e.g.
+    /// when piping the output. Without --term-width/--term-height and no terminal to measure,
+    /// 120x60 is assumed.
+    #[arg(long, value_name = "COLS", requires_all = ["terminal", "term_height"], value_parser = clap::value_parser!(u16).range(1..))]
+    term_width: Option<u16>,
+
+    /// Fit codes to a terminal this many lines high instead of the detected height
+    #[arg(long, value_name = "ROWS", requires_all = ["terminal", "term_width"], value_parser = clap::value_parser!(u16).range(1..))]
+    term_height: Option<u16>,
+
+    /// Fail instead of reducing the payload size when codes of the requested size do not fit
+    /// the terminal (only with --terminal)
+    #[arg(long, requires = "terminal")]
+    no_shrink: bool,
+
+    /// Read which chunks the receiver has from this file or http:// URL every second and show
+    /// only the codes it lacks, ending once it has every file: the file of `fountain-decode
+    /// --screen --feedback-file`, the /api/feedback of `fountain serve`, or a PNG/JPEG
+    /// snapshot a webcam keeps overwriting, scanned for the ACK code the scanner page shows
+    #[arg(long, value_name = "FILE|URL", requires = "terminal", conflicts_with_all = ["stream", "no_carousel", "columns"])]
+    feedback: Option<String>,
+
+    /// Endlessly display freshly generated fountain packets instead of cycling a fixed set (only with --terminal)
+    #[arg(long, requires = "terminal", conflicts_with_all = ["no_carousel", "columns", "redundancy"])]
+    stream: bool,
+
+    /// Maximum payload size (bytes) per QR code. Smaller values make QR codes less dense and easier to scan.
+    /// Default is ~1400 for file output (high density) and 100 for terminal.
+    #[arg(short = 's', long, alias = "payload-size")]
+    chunk_size: Option<usize>,
+
+    /// Pixel scale for QR code modules (default: 4).
+    #[arg(long, default_value = "4")]
+    pixel_scale: u32,
+
+    /// Print resolution in dots per inch. With --module-mm, picks the pixel scale and caps the
+    /// payload size so codes print with modules of that size and fit --print-width-mm.
+    #[arg(long, value_name = "DPI", requires = "module_mm", conflicts_with_all = ["pixel_scale", "terminal", "chunks_out"])]
+    print_dpi: Option<u32>,
+
+    /// Smallest printed module size in millimetres (e.g. 0.5), for --print-dpi
+    #[arg(long, value_name = "MM", requires = "print_dpi")]
+    module_mm: Option<f64>,
+
+    /// Printable width in millimetres each code must fit, for --print-dpi (default: 180, for
+    /// A4 and US Letter)
+    #[arg(long, value_name = "MM", default_value = "180", requires = "print_dpi")]
+    print_width_mm: f64,
+
+    /// QR error correction level: L, M, Q or H (default: M).
+    /// Higher levels survive blurry captures better but hold less data per code.
+    #[arg(short = 'e', long, default_value = "M", value_parser = parse_ec_level)]
+    ec_level: EcLevel,
+
+    /// Use this QR version (1-40) for every image or GIF frame, so all outputs have the same
+    /// dimensions. The payload size is derived from the version's capacity.
+    #[arg(long, value_parser = parse_qr_version, conflicts_with = "terminal")]
+    qr_version: Option<Version>,
+
+    /// How chunk bytes are stored in each QR code: base45 (alphanumeric mode) or binary (byte mode).
+    #[arg(long, default_value = "base45")]
+    payload_encoding: PayloadEncoding,
+
+    /// Compression applied before encoding: none, zlib, zstd or brotli, optionally with a
+    /// level such as zstd:19 (default: zlib at level 9).
+    #[arg(short = 'c', long, default_value = "zlib")]
+    compression: CompressionSetting,
+
+    /// Send regions repeated anywhere in the file once, e.g. in disk images or long logs.
+    /// Decoders expand them automatically.
+    #[arg(long)]
+    dedup: bool,
+
+    /// Ratio of generated RaptorQ packets to source packets. Raise it for lossy capture conditions.
+    /// Default is 1.5 for file output and 2.0 for terminal.
+    #[arg(short = 'r', long)]
+    redundancy: Option<f64>,
+
+    /// Tile several QR codes into each image as COLUMNSxROWS (e.g. 3x3), each labelled with its number.
+    /// Only with --image-output-dir.
+    #[arg(long, requires = "image_output_dir", conflicts_with_all = ["terminal", "gif_output_file"])]
+    grid: Option<GridLayout>,
+
+    /// Only write the QR codes with these chunk indices (e.g. 3,7,19), as reported missing by the decoder.
+    /// All other options must match the original encoding. Only with --image-output-dir.
+    #[arg(long, value_delimiter = ',', requires = "image_output_dir", conflicts_with_all = ["terminal", "gif_output_file", "grid"])]
+    only_chunks: Option<Vec<u32>>,
+
+    /// Send a small file (a key, a TOTP seed) as one minimal QR code with a compact header,
+    /// skipping RaptorQ. Fails if it does not fit. With --image-output-dir or --terminal.
+    #[arg(long, conflicts_with_all = ["gif_output_file", "grid", "only_chunks", "stream"])]
+    single: bool,
+
+    /// Encode a short text as one plain QR code with no fountain header, readable by any phone
+    /// camera app. The input must be UTF-8. With --image-output-dir or --terminal.
+    #[arg(long, conflicts_with_all = ["single", "gif_output_file", "grid", "only_chunks", "stream"])]
+    raw_text: bool,
+
+    /// File name pattern for images, e.g. "{stem}-{index:05}-of-{total}.png".
+    /// Placeholders: {name}, {stem}, {ext}, {index}, {total}. Only with --image-output-dir.
+    #[arg(long, requires = "image_output_dir", conflicts_with_all = ["terminal", "gif_output_file"])]
+    name_template: Option<NameTemplate>,
+
+    /// Overwrite files in --image-output-dir that have the names of the images to write,
+    /// instead of failing before writing any
+    #[arg(short = 'f', long, requires = "image_output_dir")]
+    force: bool,
+
+    /// Color of the QR modules as hex, e.g. "#1a237e". Must contrast well with the background.
+    #[arg(long, default_value = "#000000", value_parser = parse_color, conflicts_with = "terminal")]
+    fg_color: Rgb<u8>,
+
+    /// Background color as hex, e.g. "#fff8e1". Must be lighter than the foreground.
+    #[arg(long, default_value = "#ffffff", value_parser = parse_color, conflicts_with = "terminal")]
+    bg_color: Rgb<u8>,
+
+    /// Draw data modules as dots instead of squares (finder patterns stay square).
+    #[arg(long, conflicts_with = "terminal")]
+    rounded: bool,
+
+    /// Quiet zone around each QR code, in modules (default: 4, minimum: 2).
+    #[arg(long, default_value = "4", conflicts_with = "terminal")]
+    margin: u32,
+
+    /// Image (e.g. PNG) drawn over the centre of each QR code. Requires --ec-level H.
+    #[arg(long, conflicts_with = "terminal")]
+    logo: Option<PathBuf>,
+
+    /// Split files larger than this (e.g. 2MB) into volumes that each decode on their own, so a
+    /// partial transfer still yields whole parts. Decoding all volumes together reassembles the file.
+    #[arg(long, value_parser = parse_size, conflicts_with_all = ["terminal", "grid", "only_chunks", "single", "raw_text"])]
+    volume_size: Option<u64>,
+
+    /// Pick the repair packets with this seed. The output is reproducible either way; the seed
+    /// is recorded in the stream, so a published backup can be regenerated bit for bit.
+    #[arg(long, conflicts_with_all = ["single", "raw_text"])]
+    seed: Option<u64>,
+
+    /// Do not record the input file's permissions and modification time in the stream.
+    #[arg(long)]
+    no_metadata: bool,
+
+    /// Send only what changed since this earlier version of the input, which the receiver
+    /// must already have; `fountain decode --base` applies it
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["single", "raw_text"])]
+    base: Option<PathBuf>,
+
+    /// Only show warnings and errors from the encoder and decoder
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Show more detail: -v lists each chunk read, -vv also skipped frames
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Print only a JSON summary (chunk count, output files, payload size, SHA-256, time taken)
+    /// instead of progress messages, for scripts. Not available in terminal mode.
+    #[arg(long, conflicts_with = "terminal")]
+    json: bool,
+}
+
+/// Parses a size such as `10MB`, `512k` or `1GiB` into bytes, using powers of 1024.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size '{}': expected e.g. 500KB or 10MB", s))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        other => {
+            return Err(format!(
+                "Unknown size unit '{}': use B, KB, MB or GB",
+                other
+            ))
+        }
+    };
+    let bytes = (number * multiplier as f64).round() as u64;
+    if bytes == 0 {
+        return Err("Size must be at least one byte".to_string());
+    }
+    Ok(bytes)
+}
+
+/// Prints a progress line, unless --json asked for the JSON summary alone.
+macro_rules! say {
+    ($args:expr) => {
+        if !$args.json {
+            println!();
+        }
+    };
+    ($args:expr, $($arg:tt)*) => {
+        if !$args.json {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub fn run(args: EncodeArgs) -> Result<()> {
+    crate::logging::init_logging(args.quiet, args.verbose);
+    if args.input.len() > 1 {
+        return run_terminal_files(&args);
+    }
+    let (contents, filename, metadata) = read_input(&args)?;
+    let (contents, metadata) = match &args.base {
+        Some(base) => delta_from_base(&args, base, &contents, metadata)?,
+        None => (contents, metadata),
+    };
+
+    say!(args, "Encoding file: {}", filename);
+    #[cfg(feature = "audio")]
+    if let Some(audio_output) = &args.audio_output_file {
+        return run_audio(&args, &contents, &filename, &metadata, audio_output);
+    }
+    if let Some(size) = args.chunk_size {
+        say!(args, "Max payload size: {} bytes", size);
+    }
+    say!(args, "Error correction level: {:?}", args.ec_level);
+
+    if args.raw_text {
+        run_raw_text(&args, &contents, &filename)?;
+    } else if args.single {
+        run_single(&args, &contents, &filename)?;
+    } else if args.terminal && args.stream {
+        run_terminal_stream(&args, &contents, &filename, &metadata)?;
+    } else if args.terminal {
+        run_terminal(&args, &contents, &filename, &metadata)?;
+    } else if let Some(gif_output) = &args.gif_output_file {
+        run_gif(&args, &contents, &filename, &metadata, gif_output)?;
+    } else if let Some(chunks_output) = &args.chunks_out {
+        run_chunks(&args, &contents, &filename, &metadata, chunks_output)?;
+    } else if let Some(images_output) = &args.image_output_dir {
+        run_images(&args, &contents, &filename, &metadata, images_output)?;
+    } else {
+        anyhow::bail!(
+            "No output method specified. Use --terminal, --image-output-dir, --gif-output-file or --chunks-out."
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the data to encode with the filename and attributes to embed,
+/// reading stdin when the input is `-`.
+fn read_input(args: &EncodeArgs) -> Result<(Vec<u8>, String, FileMetadata)> {
+    #[cfg(feature = "clipboard")]
+    if args.from_clipboard {
+        return read_clipboard(args);
+    }
+    let input = args
+        .input
+        .first()
+        .ok_or_else(|| anyhow!("No input file given"))?;
+    if input.as_os_str() == "-" {
+        let filename = args
+            .name
+            .clone()
+            .or_else(|| args.raw_text.then(|| "text".to_string()))
+            .ok_or_else(|| anyhow!("--name is required when reading from stdin"))?;
+        let mut contents = Vec::new();
+        io::stdin().read_to_end(&mut contents)?;
+        return Ok((contents, filename, FileMetadata::default()));
+    }
+
+    read_file(args, input)
+}
+
+fn read_file(args: &EncodeArgs, input: &Path) -> Result<(Vec<u8>, String, FileMetadata)> {
+    let contents = fs::read(input)?;
+    let filename = match &args.name {
+        Some(name) => name.clone(),
+        None => input
+            .file_name()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| anyhow!("Invalid filename"))?
+            .to_string(),
+    };
+    let metadata = if args.no_metadata {
+        FileMetadata::default()
+    } else {
+        read_file_metadata(input)?
+    };
+    Ok((contents, filename, metadata))
+}
+
+/// Replaces the contents by their delta against `base`, recording the base's SHA-256 so
+/// the receiver can check it has the same file.
+fn delta_from_base(
+    args: &EncodeArgs,
+    base: &Path,
+    contents: &[u8],
+    metadata: FileMetadata,
+) -> Result<(Vec<u8>, FileMetadata)> {
+    if args.wire_format == WireFormat::Ur {
+        anyhow::bail!("UR streams carry no metadata, so they cannot send a delta");
+    }
+    let base_contents = fs::read(base)?;
+    let delta = fountain::delta::diff(&base_contents, contents);
+    say!(
+        args,
+        "Delta against {}: {} bytes instead of {}",
+        base.display(),
+        delta.len(),
+        contents.len()
+    );
+    let metadata = FileMetadata {
+        base_sha256: Some(fountain::chunk::sha256_digest(&base_contents)),
+        ..metadata
+    };
+    Ok((delta, metadata))
+}
+
+/// Reads a file copied to the clipboard, or else the text on it.
+#[cfg(feature = "clipboard")]
+fn read_clipboard(args: &EncodeArgs) -> Result<(Vec<u8>, String, FileMetadata)> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|e| anyhow!("Failed to open the clipboard: {}", e))?;
+    let files = clipboard.get().file_list().unwrap_or_default();
+    match files.as_slice() {
+        [] => {}
+        [file] => return read_file(args, file),
+        _ => anyhow::bail!(
+            "The clipboard holds {} files; copy a single file or archive them first",
+            files.len()
+        ),
+    }
+    let text = clipboard
+        .get_text()
+        .map_err(|e| anyhow!("The clipboard holds neither a file nor text: {}", e))?;
+    let filename = args.name.clone().unwrap_or_else(|| "clipboard.txt".into());
+    Ok((text.into_bytes(), filename, FileMetadata::default()))
+}
+
+/// Library options for the settings shared by all outputs; QR styling is added per output.
+fn encode_options(args: &EncodeArgs) -> EncodeOptions {
+    let mut options = EncodeOptions::new()
+        .pixel_scale(args.pixel_scale)
+        .ec_level(args.ec_level)
+        .payload_encoding(args.payload_encoding)
+        .compression(args.compression)
+        .dedup(args.dedup)
+        .interval_ms(args.interval)
+        .terminal_render(args.render)
+        .shrink_to_terminal(!args.no_shrink)
+        .frame_order(args.frame_order)
+        .fresh_cycles(args.fresh_cycles)
+        .color_planes(args.color_planes)
+        .gif_delta_frames(args.gif_delta_frames)
+        .sync_frames(args.sync_frames)
+        .captions(args.caption)
+        .wire_format(args.wire_format);
+    if let Some(chunk_size) = args.chunk_size {
+        options = options.chunk_size(chunk_size);
+    }
+    if let Some(redundancy) = args.redundancy {
+        options = options.redundancy(redundancy);
+    }
+    if let Some(version) = args.qr_version {
+        options = options.qr_version(version);
+    }
+    if let (Some(width), Some(height)) = (args.term_width, args.term_height) {
+        options = options.terminal_size(width as usize, height as usize);
+    }
+    if let Some(template) = &args.name_template {
+        options = options.name_template(template.clone());
+    }
+    options = options.overwrite(args.force);
+    if let Some(size) = args.volume_size {
+        options = options.volume_size(usize::try_from(size).unwrap_or(usize::MAX));
+    }
+    if let Some(seed) = args.seed {
+        options = options.seed(seed);
+    }
+    if let Some(cycles) = args.cycles {
+        options = options.cycles(cycles);
+    }
+    if let Some(loop_count) = args.loop_count {
+        options = options.loop_count(loop_count);
+    }
+    if let Some(ms) = args.first_frame_ms {
+        options = options.first_frame_ms(ms);
+    }
+    if let Some(ms) = args.repeat_interval {
+        options = options.repeat_interval_ms(ms);
+    }
+    if let Some(layout) = args.frame_grid {
+        options = options.frame_grid(layout);
+    }
+    options
+}
+
+/// Library options for outputs that draw QR codes: the shared settings with the QR style,
+/// and the pixel scale and payload size for --print-dpi.
+fn image_options(args: &EncodeArgs) -> Result<EncodeOptions> {
+    let options = encode_options(args).style(qr_style(args)?);
+    let (Some(dpi), Some(module_mm)) = (args.print_dpi, args.module_mm) else {
+        return Ok(options);
+    };
+    let sizing = print_sizing(dpi, module_mm, args.print_width_mm, &options)?;
+    let (Version::Normal(max_version) | Version::Micro(max_version)) = sizing.max_version;
+    if let Some(version @ (Version::Normal(number) | Version::Micro(number))) = args.qr_version {
+        if version.width() > sizing.max_version.width() {
+            anyhow::bail!(
+                "--qr-version {} does not fit {} mm; at most version {} does",
+                number,
+                args.print_width_mm,
+                max_version
+            );
+        }
+    }
+    say!(
+        args,
+        "Print size: {} px per module ({:.2} mm at {} dpi), up to QR version {} ({} bytes per code)",
+        sizing.pixel_scale,
+        sizing.module_mm,
+        dpi,
+        max_version,
+        sizing.max_payload
+    );
+    let chunk_size = args
+        .chunk_size
+        .map_or(sizing.max_payload, |size| size.min(sizing.max_payload));
+    Ok(options
+        .pixel_scale(sizing.pixel_scale)
+        .chunk_size(chunk_size))
+}
+
+fn qr_style(args: &EncodeArgs) -> Result<QrStyle> {
+    let logo = match &args.logo {
+        Some(path) => {
+            if args.ec_level != EcLevel::H {
+                anyhow::bail!("--logo requires --ec-level H to recover the covered modules");
+            }
+            let logo = image::open(path)
+                .map_err(|e| anyhow!("Failed to load logo {}: {}", path.display(), e))?;
+            Some(logo.to_rgba8())
+        }
+        None => None,
+    };
+
+    let style = QrStyle {
+        foreground: args.fg_color,
+        background: args.bg_color,
+        rounded: args.rounded,
+        margin: args.margin,
+        logo,
+    };
+    style.validate()?;
+    Ok(style)
+}
+
+fn run_terminal(
+    args: &EncodeArgs,
+    contents: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+) -> Result<()> {
+    let data = encode_bytes_for_terminal_with_options(
+        contents,
+        filename,
+        metadata,
+        &encode_options(args),
+    )?;
+
+    println!("Generated {} QR code(s)", data.total);
+    println!("SHA-256: {}", sha256_hex(contents));
+    report_terminal_fit(&data.fit, data.effective_size);
+    println!();
+    show_terminal(args, &data, |size, shown| {
+        encode_bytes_for_terminal_with_options(
+            contents,
+            filename,
+            metadata,
+            &refit_options(args, size, shown),
+        )
+    })
+}
+
+/// Shows several files in one carousel, each as its own stream.
+fn run_terminal_files(args: &EncodeArgs) -> Result<()> {
+    if !args.terminal || args.stream || args.single || args.raw_text {
+        anyhow::bail!("Several input files can only be shown in one --terminal carousel");
+    }
+    if args.name.is_some()
+        || args.base.is_some()
+        || args.input.iter().any(|input| input.as_os_str() == "-")
+    {
+        anyhow::bail!("--name, --base and stdin take a single input file");
+    }
+    let files = args
+        .input
+        .iter()
+        .map(|input| read_file(args, input))
+        .collect::<Result<Vec<_>>>()?;
+    let data = encode_files_for_terminal_with_options(&files, &encode_options(args))?;
+
+    println!(
+        "Generated {} QR code(s) for {} files",
+        data.total,
+        files.len()
+    );
+    for (contents, filename, _) in &files {
+        println!("{}  SHA-256: {}", filename, sha256_hex(contents));
+    }
+    report_terminal_fit(&data.fit, data.effective_size);
+    println!();
+    show_terminal(args, &data, |size, shown| {
+        encode_files_for_terminal_with_options(&files, &refit_options(args, size, shown))
+    })
+}
+
+/// Options to encode the codes `shown` again for a terminal resized to `size`, keeping
+/// their payload size when it still fits, so receivers need not start over.
+fn refit_options(args: &EncodeArgs, size: TerminalSize, shown: &TerminalQrData) -> EncodeOptions {
+    let (width, height) = size.dimensions();
+    encode_options(args)
+        .terminal_size(width, height)
+        .chunk_size(shown.effective_size)
+}
+
+/// Prints the terminal size codes were fitted to when it was not read from the terminal,
+/// and a warning when the payload size was reduced to fit it.
+fn report_terminal_fit(fit: &TerminalFit, effective_size: usize) {
+    match fit.size {
+        TerminalSize::Detected { .. } => {}
+        TerminalSize::Given { width, height } => {
+            println!("Fitted to a {}x{} terminal", width, height)
+        }
+        TerminalSize::Assumed { width, height } => println!(
+            "Terminal size unknown or too small; assumed {}x{} (set --term-width and --term-height)",
+            width, height
+        ),
+    }
+    if fit.shrunk {
+        println!(
+            "WARNING! Automatically reduced payload size to {} bytes to fit terminal.",
+            effective_size
+        );
+    }
+}
+
+/// Shows the codes once or in a carousel; a carousel calls `refit` to encode them again
+/// when the terminal is resized, unless --term-width and --term-height fix the size.
+fn show_terminal<F>(args: &EncodeArgs, data: &TerminalQrData, refit: F) -> Result<()>
+where
+    F: FnMut(TerminalSize, &TerminalQrData) -> Result<TerminalQrData>,
+{
+    if args.no_carousel || args.columns.is_some() || data.total == 1 {
+        display_qr_once(data, args.columns.map(|c| c as usize));
+    } else {
+        println!("Starting carousel mode ({}ms interval)...", args.interval);
+        println!("Space: pause  Left/Right: step  +/-: speed  g: jump  q: quit");
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let mut options = CarouselOptions::new();
+        if args.term_width.is_none() {
+            options = options.refit(refit);
+        }
+        if let Some(source) = &args.feedback {
+            options = options.feedback(source);
+        }
+        display_qr_carousel_with(data, args.interval, options)?;
+    }
+
+    Ok(())
+}
+
+fn run_terminal_stream(
+    args: &EncodeArgs,
+    contents: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+) -> Result<()> {
+    let mut stream = encode_bytes_for_terminal_stream_with_options(
+        contents,
+        filename,
+        metadata,
+        &encode_options(args),
+    )?;
+
+    println!(
+        "Prepared {} source packet(s); repair packets are generated on the fly",
+        stream.source_packets
+    );
+    println!("SHA-256: {}", sha256_hex(contents));
+    report_terminal_fit(&stream.fit, stream.effective_size);
+    println!();
+
+    println!("Starting stream mode ({}ms interval)...", args.interval);
+    println!("Press Ctrl+C to exit");
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    display_qr_stream(&mut stream, args.interval)
+}
+
+fn run_images(
+    args: &EncodeArgs,
+    contents: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
+) -> Result<()> {
+    let options = image_options(args)?;
+    say!(args, "Output directory: {}", output_dir.display());
+
+    let started = Instant::now();
+    let result = match (&args.only_chunks, args.grid) {
+        (Some(indices), _) => {
+            say!(args, "Regenerating {} chunk(s)", indices.len());
+            encode_bytes_to_selected_images_with_options(
+                contents, filename, metadata, output_dir, indices, &options,
+            )?
+        }
+        (None, Some(layout)) => {
+            say!(args, "Grid layout: {}x{}", layout.columns, layout.rows);
+            encode_bytes_to_tiled_images_with_options(
+                contents, filename, metadata, output_dir, layout, &options,
+            )?
+        }
+        (None, None) => {
+            encode_bytes_to_images_with_options(contents, filename, metadata, output_dir, &options)?
+        }
+    };
+    if args.json {
+        return print_json(filename, &result, started);
+    }
+
+    // --print-dpi caps the payload size on purpose and says so upfront
+    let requested_size = args.chunk_size.unwrap_or(MAX_PAYLOAD_SIZE);
+    if result.effective_size < requested_size
+        && result.effective_size > 0
+        && args.print_dpi.is_none()
+    {
+        say!(args);
+        say!(
+            args,
+            "WARNING! Automatically reduced payload size to {} bytes to fit QR code capacity.",
+            result.effective_size
+        );
+    }
+
+    say!(args);
+    say!(
+        args,
+        "Successfully created {} QR code(s)",
+        result.num_chunks
+    );
+    say_qr_sizing(args, &result);
+    say!(args, "SHA-256: {}", result.sha256);
+    Ok(())
+}
+
+fn run_single(args: &EncodeArgs, contents: &[u8], filename: &str) -> Result<()> {
+    if args.terminal {
+        let data = encode_bytes_for_terminal_single_with_options(
+            contents,
+            filename,
+            &encode_options(args),
+        )?;
+        println!("Payload: {} bytes in a single QR code", data.effective_size);
+        println!("SHA-256: {}", sha256_hex(contents));
+        println!();
+        display_qr_once(&data, None);
+        return Ok(());
+    }
+
+    let output_dir = args
+        .image_output_dir
+        .as_deref()
+        .ok_or_else(|| anyhow!("--single needs --image-output-dir or --terminal"))?;
+    let options = image_options(args)?;
+    say!(args, "Output directory: {}", output_dir.display());
+
+    let started = Instant::now();
+    let result =
+        encode_bytes_to_single_image_with_options(contents, filename, output_dir, &options)?;
+    if args.json {
+        return print_json(filename, &result, started);
+    }
+
+    say!(args);
+    say!(
+        args,
+        "Successfully created a single QR code ({} byte payload)",
+        result.effective_size
+    );
+    say_qr_sizing(args, &result);
+    say!(args, "SHA-256: {}", result.sha256);
+    Ok(())
+}
+
+fn run_raw_text(args: &EncodeArgs, contents: &[u8], name: &str) -> Result<()> {
+    let text = std::str::from_utf8(contents)
+        .map_err(|_| anyhow!("--raw-text needs UTF-8 input; use --single for binary files"))?;
+
+    if args.terminal {
+        let data = encode_text_for_terminal_with_options(text, name, &encode_options(args))?;
+        println!("Text: {} bytes in a plain QR code", data.effective_size);
+        println!();
+        display_qr_once(&data, None);
+        return Ok(());
+    }
+
+    let output_dir = args
+        .image_output_dir
+        .as_deref()
+        .ok_or_else(|| anyhow!("--raw-text needs --image-output-dir or --terminal"))?;
+    let options = image_options(args)?;
+    say!(args, "Output directory: {}", output_dir.display());
+
+    let started = Instant::now();
+    let result = encode_text_to_image_with_options(text, name, output_dir, &options)?;
+    if args.json {
+        return print_json(name, &result, started);
+    }
+
+    say!(args);
+    say!(
+        args,
+        "Successfully created a plain QR code ({} bytes of text)",
+        result.effective_size
+    );
+    say_qr_sizing(args, &result);
+    Ok(())
+}
+
+fn run_gif(
+    args: &EncodeArgs,
+    contents: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_file: &Path,
+) -> Result<()> {
+    let options = image_options(args)?;
+    let extension = output_file
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    let is_apng = matches!(extension.as_deref(), Some("png" | "apng"));
+    if extension.as_deref() == Some("webp") {
+        anyhow::bail!("Animated WebP output is not supported; use a .gif or .png (APNG) file");
+    }
+
+    let kind = if is_apng { "APNG" } else { "GIF" };
+    say!(args, "Output {}: {}", kind, output_file.display());
+    say!(args, "{} frame interval: {}ms", kind, args.interval);
+    if let Some(layout) = args.frame_grid {
+        say!(args, "Codes per frame: {}x{}", layout.columns, layout.rows);
+    }
+    if args.color_planes {
+        say!(args, "Colour planes: 3 codes per tile");
+    }
+
+    let started = Instant::now();
+    let result = if is_apng {
+        encode_bytes_to_apng_with_options(contents, filename, metadata, output_file, &options)?
+    } else {
+        encode_bytes_to_gif_with_options(contents, filename, metadata, output_file, &options)?
+    };
+    if args.json {
+        return print_json(filename, &result, started);
+    }
+
+    // --print-dpi caps the payload size on purpose and says so upfront
+    let requested_size = args.chunk_size.unwrap_or(MAX_PAYLOAD_SIZE);
+    if result.effective_size < requested_size
+        && result.effective_size > 0
+        && args.print_dpi.is_none()
+    {
+        say!(args);
+        say!(
+            args,
+            "WARNING! Automatically reduced payload size to {} bytes to fit QR code capacity.",
+            result.effective_size
+        );
+    }
+
+    say!(args);
+    say!(
+        args,
+        "Successfully created {} QR code(s)",
+        result.num_chunks
+    );
+    if let Some(timing) = &result.timing {
+        say!(
+            args,
+            "{} frames, {:.1}s per loop",
+            timing.frame_ms.len(),
+            timing.total().as_secs_f64()
+        );
+        if timing.interval_ms != args.interval {
+            say!(
+                args,
+                "Frame interval stored as {}ms ({} counts in {})",
+                timing.interval_ms,
+                kind,
+                if is_apng {
+                    "ms up to 65535"
+                } else {
+                    "hundredths of a second"
+                }
+            );
+        }
+    }
+    if result.output_files.len() > 1 {
+        say!(args, "Split into {} volumes:", result.output_files.len());
+        for file in &result.output_files {
+            say!(args, "  {}", file);
+        }
+    }
+    say_qr_sizing(args, &result);
+    say!(args, "SHA-256: {}", result.sha256);
+    Ok(())
+}
+
+/// Writes the chunks as a WAV file of FSK tones.
+#[cfg(feature = "audio")]
+fn run_audio(
+    args: &EncodeArgs,
+    contents: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_file: &Path,
+) -> Result<()> {
+    use fountain::audio::{AudioBand, AudioOptions, DEFAULT_AUDIO_PACKET_SIZE};
+    use fountain::packets::DEFAULT_REDUNDANCY_FACTOR;
+
+    if args.wire_format == WireFormat::Ur {
+        anyhow::bail!("Audio output sends native chunks; --wire-format ur is for QR codes");
+    }
+    let packet_size = args.chunk_size.unwrap_or(DEFAULT_AUDIO_PACKET_SIZE);
+    let mut packet_options = fountain::PacketOptions::new()
+        .packet_size(packet_size)
+        .compression(CompressionSetting {
+            dedup: args.dedup,
+            ..args.compression
+        })
+        .redundancy(args.redundancy.unwrap_or(DEFAULT_REDUNDANCY_FACTOR))
+        .metadata(*metadata);
+    if let Some(seed) = args.seed {
+        packet_options = packet_options.seed(seed);
+    }
+    let band = if args.ultrasonic {
+        AudioBand::Ultrasonic
+    } else {
+        AudioBand::Audible
+    };
+    say!(
+        args,
+        "Output WAV: {} ({} tones)",
+        output_file.display(),
+        band
+    );
+    say!(args, "Packet size: {} bytes", packet_size);
+
+    let started = Instant::now();
+    let writer = std::io::BufWriter::new(fs::File::create(output_file)?);
+    let result = fountain::audio::encode_bytes_to_wav(
+        contents,
+        filename,
+        &packet_options,
+        &AudioOptions::new().band(band),
+        writer,
+    )?;
+    let sha256 = sha256_hex(contents);
+    if args.json {
+        let summary = serde_json::json!({
+            "filename": filename,
+            "num_chunks": result.num_chunks,
+            "output_files": [output_file],
+            "effective_size": packet_size,
+            "sha256": sha256,
+            "duration_ms": result.duration.as_millis() as u64,
+            "elapsed_ms": started.elapsed().as_millis() as u64,
+        });
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    say!(args);
+    say!(
+        args,
+        "Successfully created {} audio frame(s), {:.1}s of sound",
+        result.num_chunks,
+        result.duration.as_secs_f64()
+    );
+    say!(args, "SHA-256: {}", sha256);
+    Ok(())
+}
+
+fn run_chunks(
+    args: &EncodeArgs,
+    contents: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
+) -> Result<()> {
+    say!(args, "Output directory: {}", output_dir.display());
+    let started = Instant::now();
+    let result = encode_bytes_to_chunk_files_with_options(
+        contents,
+        filename,
+        metadata,
+        output_dir,
+        &encode_options(args),
+    )?;
+    if args.json {
+        return print_json(filename, &result, started);
+    }
+
+    say!(args);
+    say!(
+        args,
+        "Successfully wrote {} chunk file(s) of up to {} bytes",
+        result.num_chunks,
+        result.effective_size
+    );
+    say!(args, "SHA-256: {}", result.sha256);
+    Ok(())
+}
+
+/// Prints the QR version and image size of `result`, for checking how large modules print.
+fn say_qr_sizing(args: &EncodeArgs, result: &EncodeResult) {
+    let Some(qr) = &result.qr else {
+        return;
+    };
+    let (Version::Normal(number) | Version::Micro(number)) = qr.version;
+    say!(
+        args,
+        "QR version: {} ({}x{} modules, {}x{} px images at {} px per module)",
+        number,
+        qr.modules(),
+        qr.modules(),
+        qr.image_px.0,
+        qr.image_px.1,
+        qr.pixel_scale
+    );
+}
+
+fn print_json(filename: &str, result: &EncodeResult, started: Instant) -> Result<()> {
+    let summary = serde_json::json!({
+        "filename": filename,
+        "num_chunks": result.num_chunks,
+        "output_files": result.output_files,
+        "effective_size": result.effective_size,
+        "sha256": result.sha256,
+        "timing": result.timing.as_ref().map(|timing| serde_json::json!({
+            "frame_ms": timing.frame_ms,
+            "interval_ms": timing.interval_ms,
+            "total_ms": timing.total().as_millis() as u64,
+        })),
+        "qr": result.qr.as_ref().map(|qr| {
+            let (Version::Normal(number) | Version::Micro(number)) = qr.version;
+            serde_json::json!({
+                "version": number,
+                "modules": qr.modules(),
+                "pixel_scale": qr.pixel_scale,
+                "image_px": [qr.image_px.0, qr.image_px.1],
+                "chunk_bytes": qr.chunk_bytes,
+            })
+        }),
+        "elapsed_ms": started.elapsed().as_millis() as u64,
+    });
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Saving received files in an output directory, shared by the camera receiver and
+//! `fountain serve`.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where a received file is saved. The name comes from whatever was scanned, so only its
+/// last component is kept, never to leave the output directory.
+pub fn output_path(output_dir: &Path, filename: &str) -> PathBuf {
+    let name = Path::new(filename)
+        .file_name()
+        .map_or_else(|| "received.bin".into(), |name| name.to_os_string());
+    output_dir.join(name)
+}
+
+/// Saves a received file in `output_dir` and returns where, or `None` when the same file is
+/// there already. Unless `force` is set, a different file of the same name is kept and the
+/// received one gets the first free numbered name, e.g. `notes (1).txt`.
+pub fn save_received(
+    output_dir: &Path,
+    filename: &str,
+    data: &[u8],
+    force: bool,
+) -> Result<Option<PathBuf>> {
+    let path = output_path(output_dir, filename);
+    let mut candidate = path.clone();
+    for n in 1.. {
+        match fs::read(&candidate) {
+            // A single-code file completes again every time it is scanned
+            Ok(saved) if saved == data => return Ok(None),
+            Ok(_) if !force => candidate = numbered_path(&path, n),
+            _ => break,
+        }
+    }
+    fs::write(&candidate, data)
+        .with_context(|| format!("Failed to save {}", candidate.display()))?;
+    Ok(Some(candidate))
+}
+
+/// `path` with ` (n)` after its stem, e.g. `notes (2).txt`.
+fn numbered_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{} ({}).{}", stem, n, ext.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_received_keeps_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("notes.txt"), "mine").unwrap();
+
+        let saved = save_received(dir.path(), "../notes.txt", b"theirs", false).unwrap();
+        assert_eq!(saved, Some(dir.path().join("notes (1).txt")));
+        assert_eq!(fs::read(dir.path().join("notes.txt")).unwrap(), b"mine");
+        // The same file again is not saved twice
+        assert_eq!(
+            save_received(dir.path(), "notes.txt", b"theirs", false).unwrap(),
+            None
+        );
+        let saved = save_received(dir.path(), "notes.txt", b"more", false).unwrap();
+        assert_eq!(saved, Some(dir.path().join("notes (2).txt")));
+
+        let saved = save_received(dir.path(), "notes.txt", b"forced", true).unwrap();
+        assert_eq!(saved, Some(dir.path().join("notes.txt")));
+        assert_eq!(fs::read(dir.path().join("notes.txt")).unwrap(), b"forced");
+    }
+}
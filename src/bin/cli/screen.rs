@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Context, Result};
+use image::imageops;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use xcap::Monitor;
+
+use fountain::qr::decode_all_qr_from_gray;
+use fountain::receive::{PayloadReceiver, ReceiveStatus};
+
+/// Shortest time between two captures, so a still screen does not keep a core busy.
+const CAPTURE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Part of the display to capture, in the pixels of the screenshot: `X,Y,WIDTHxHEIGHT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenRegion {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for ScreenRegion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid region '{}': expected X,Y,WIDTHxHEIGHT", s);
+        let (x, rest) = s.split_once(',').ok_or_else(invalid)?;
+        let (y, size) = rest.split_once(',').ok_or_else(invalid)?;
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        let region = Self {
+            x: x.trim().parse().map_err(|_| invalid())?,
+            y: y.trim().parse().map_err(|_| invalid())?,
+            width: width.trim().parse().map_err(|_| invalid())?,
+            height: height.trim().parse().map_err(|_| invalid())?,
+        };
+        if region.width == 0 || region.height == 0 {
+            return Err(invalid());
+        }
+        Ok(region)
+    }
+}
+
+/// Captures the display over and over and scans it for QR codes until a file is complete,
+/// then returns its name and contents. Without a region, the whole primary monitor is
+/// captured; with one, the monitor that contains its top-left corner. With `feedback_file`,
+/// the chunks received are written there after every new one, for the sender's carousel.
+pub fn receive(
+    region: Option<ScreenRegion>,
+    feedback_file: Option<&Path>,
+) -> Result<(String, Vec<u8>)> {
+    let monitor = match region {
+        Some(region) => Monitor::from_point(region.x, region.y)
+            .with_context(|| format!("No monitor contains {},{}", region.x, region.y))?,
+        None => Monitor::all()
+            .context("Failed to list monitors")?
+            .into_iter()
+            .find(|monitor| monitor.is_primary())
+            .ok_or_else(|| anyhow!("No primary monitor found"))?,
+    };
+    eprintln!(
+        "Scanning monitor {} ({}x{}); press Ctrl-C to stop",
+        monitor.name(),
+        monitor.width(),
+        monitor.height()
+    );
+
+    let mut receiver = PayloadReceiver::new();
+    let mut previous = None;
+    loop {
+        let started = Instant::now();
+        let screenshot = monitor
+            .capture_image()
+            .context("Failed to capture the screen")?;
+        let mut gray = imageops::grayscale(&screenshot);
+        if let Some(region) = region {
+            // Region coordinates are on the whole desktop; the screenshot starts at the monitor
+            let x = region.x.saturating_sub(monitor.x()).max(0) as u32;
+            let y = region.y.saturating_sub(monitor.y()).max(0) as u32;
+            gray = imageops::crop_imm(&gray, x, y, region.width, region.height).to_image();
+        }
+
+        // A sender holds each code for several captures; scan only when the picture changes
+        if previous.as_ref() != Some(&gray) {
+            for payload in decode_all_qr_from_gray(&gray) {
+                let status = receiver.feed(&payload)?;
+                if status != ReceiveStatus::Scanning {
+                    if let Some(path) = feedback_file {
+                        fs::write(path, receiver.feedback().to_string()).with_context(|| {
+                            format!("Failed to write feedback to {}", path.display())
+                        })?;
+                    }
+                }
+                match status {
+                    ReceiveStatus::Scanning => {}
+                    ReceiveStatus::ChunkFound => {
+                        let (received, needed) = receiver.progress();
+                        eprintln!("Chunks received: {}/{}", received, needed);
+                    }
+                    ReceiveStatus::Complete => {
+                        return receiver
+                            .take_result()
+                            .ok_or_else(|| anyhow!("Decoder reported no file"));
+                    }
+                }
+            }
+            previous = Some(gray);
+        }
+        thread::sleep(CAPTURE_INTERVAL.saturating_sub(started.elapsed()));
+    }
+}
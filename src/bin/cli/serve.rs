@@ -0,0 +1,285 @@
+use anyhow::{anyhow, Result};
+use clap::Args;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tiny_http::{Header, Method, ReadWrite, Request, Response, Server};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use fountain::receive::{PayloadReceiver, ReceiveStatus};
+use fountain::sha256_hex;
+
+use crate::save::{output_path, save_received};
+
+const INDEX_HTML: &str = include_str!("../../../www/index.html");
+const MAIN_JS: &str = include_str!("../../../www/main.js");
+/// Path the page posts payloads to; the page learns it from its `<body>` tag.
+const PAYLOAD_PATH: &str = "/api/payload";
+/// WebSocket that pushes progress after every payload that adds to the transfer.
+const PROGRESS_PATH: &str = "/api/progress";
+/// The same progress as one JSON document, for clients that poll.
+const STATUS_PATH: &str = "/api/status";
+/// Chunks received, for `fountain encode --feedback` to pass over in its carousel.
+const FEEDBACK_PATH: &str = "/api/feedback";
+/// Largest request body read as a payload; no QR code holds more than 2953 bytes.
+const MAX_PAYLOAD_BYTES: u64 = 4096;
+
+type Reply = Response<Cursor<Vec<u8>>>;
+
+/// Options of `fountain serve`.
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to listen on. Use 0.0.0.0:PORT to reach the page from a phone on the network.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Directory to save received files in
+    #[arg(short = 'o', long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Overwrite a different file of the same name in the output directory, instead of
+    /// saving the received one under a numbered name such as "notes (1).txt"
+    #[arg(short = 'f', long)]
+    force: bool,
+
+    /// Directory with the wasm-bindgen output of the scanner (`fountain.js` and
+    /// `fountain_bg.wasm`), as built by script/rust/compile.wasm.sh
+    #[arg(long, default_value = "www/pkg")]
+    pkg_dir: PathBuf,
+}
+
+/// Serves the web scanner and assembles the payloads it posts, until interrupted.
+pub fn run(args: ServeArgs) -> Result<()> {
+    if !args.pkg_dir.join("fountain_bg.wasm").is_file() {
+        return Err(anyhow!(
+            "No scanner build in {}; run script/rust/compile.wasm.sh or pass --pkg-dir",
+            args.pkg_dir.display()
+        ));
+    }
+    fs::create_dir_all(&args.output_dir)?;
+    let server = Server::http(&args.listen)
+        .map_err(|e| anyhow!("Failed to listen on {}: {}", args.listen, e))?;
+    println!("Scanner page: http://{}/scanner/", args.listen);
+    println!("Saving received files to {}", args.output_dir.display());
+
+    let mut session = Session::new(&args.output_dir, args.force);
+    for mut request in server.incoming_requests() {
+        if request.url() == PROGRESS_PATH {
+            session.watch(request);
+            continue;
+        }
+        let response = handle(&mut request, &mut session, &args);
+        if let Err(e) = request.respond(response) {
+            log::warn!("Failed to answer a request: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle(request: &mut Request, session: &mut Session, args: &ServeArgs) -> Reply {
+    let url = request.url().to_string();
+    match (request.method(), url.as_str()) {
+        (Method::Get, "/") => Response::from_data(Vec::new())
+            .with_status_code(302)
+            .with_header(header("Location", "/scanner/")),
+        (Method::Get, "/scanner/" | "/scanner/index.html") => {
+            let page = INDEX_HTML.replacen(
+                "<body>",
+                &format!(
+                    "<body data-payload-url=\"{}\" data-progress-url=\"{}\">",
+                    PAYLOAD_PATH, PROGRESS_PATH
+                ),
+                1,
+            );
+            content(page.into_bytes(), "text/html; charset=utf-8")
+        }
+        (Method::Get, "/scanner/main.js") => content(MAIN_JS.into(), "text/javascript"),
+        (Method::Get, path) if path.starts_with("/scanner/pkg/") => {
+            serve_pkg_file(&args.pkg_dir, &path["/scanner/pkg/".len()..])
+        }
+        (Method::Get, STATUS_PATH) => json(&session.progress()),
+        (Method::Get, FEEDBACK_PATH) => content(
+            session.receiver.feedback().to_string().into_bytes(),
+            "text/plain; charset=utf-8",
+        ),
+        (Method::Post, PAYLOAD_PATH) => match session.receive_payload(request) {
+            Ok(reply) => json(&reply),
+            Err(e) => json(&serde_json::json!({ "error": e.to_string() })).with_status_code(400),
+        },
+        _ => not_found(),
+    }
+}
+
+/// The transfer in progress and the WebSocket clients following it.
+struct Session<'a> {
+    receiver: PayloadReceiver,
+    output_dir: &'a Path,
+    /// Whether a received file replaces a different one of the same name.
+    force: bool,
+    /// When the first chunk of the current transfer arrived, for the ETA.
+    first_chunk_at: Option<Instant>,
+    /// Name of the last file saved, until the next transfer starts.
+    saved: Option<String>,
+    watchers: Vec<WebSocket<Box<dyn ReadWrite + Send>>>,
+}
+
+impl<'a> Session<'a> {
+    fn new(output_dir: &'a Path, force: bool) -> Self {
+        Self {
+            receiver: PayloadReceiver::new(),
+            output_dir,
+            force,
+            first_chunk_at: None,
+            saved: None,
+            watchers: Vec::new(),
+        }
+    }
+
+    /// Feeds the request body to the receiver, saving each file once it is complete.
+    fn receive_payload(&mut self, request: &mut Request) -> Result<serde_json::Value> {
+        let mut payload = Vec::new();
+        request
+            .as_reader()
+            .take(MAX_PAYLOAD_BYTES)
+            .read_to_end(&mut payload)?;
+        let status = self.receiver.feed(&payload)?;
+        if status == ReceiveStatus::Scanning {
+            return Ok(self.progress());
+        }
+        if self.first_chunk_at.is_none() {
+            self.first_chunk_at = Some(Instant::now());
+            self.saved = None;
+        }
+
+        let mut reply = self.progress();
+        if let Some((filename, data)) = self.receiver.take_result() {
+            let name = self.save(&filename, &data)?;
+            // The receiver keeps the chunks of other files in the same carousel
+            self.first_chunk_at = None;
+            self.saved = Some(name);
+            reply = self.progress();
+        }
+        self.broadcast(&reply);
+        Ok(reply)
+    }
+
+    /// Writes a received file to the output directory and returns the name it was saved as.
+    fn save(&self, filename: &str, data: &[u8]) -> Result<String> {
+        let path = match save_received(self.output_dir, filename, data, self.force)? {
+            Some(path) => {
+                println!(
+                    "Saved {} ({} bytes, SHA-256: {})",
+                    path.display(),
+                    data.len(),
+                    sha256_hex(data)
+                );
+                path
+            }
+            // A single-code file completes again every time the scanner posts its code
+            None => output_path(self.output_dir, filename),
+        };
+        Ok(path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned())
+    }
+
+    /// Chunks received and needed, the gaps below the highest chunk seen, an estimate of
+    /// the seconds left at the rate so far, and the name of the last file saved.
+    fn progress(&self) -> serde_json::Value {
+        let (received, needed) = self.receiver.progress();
+        let seen = self.receiver.received();
+        let highest = seen.last().copied().unwrap_or(0);
+        let missing: Vec<u32> = (0..highest)
+            .filter(|i| seen.binary_search(i).is_err())
+            .collect();
+        let eta_seconds = self.first_chunk_at.filter(|_| received >= 2).map(|first| {
+            let per_chunk = first.elapsed().as_secs_f64() / (received - 1) as f64;
+            (per_chunk * needed.saturating_sub(received) as f64).ceil() as u64
+        });
+        serde_json::json!({
+            "received": received,
+            "needed": needed,
+            "missing": missing,
+            "eta_seconds": eta_seconds,
+            "saved": self.saved,
+        })
+    }
+
+    /// Accepts a WebSocket client of [`PROGRESS_PATH`] and sends it the current progress.
+    fn watch(&mut self, request: Request) {
+        let key = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+            .map(|h| h.value.as_str().to_string());
+        let Some(key) = key else {
+            let reply =
+                Response::from_string("Expected a WebSocket handshake").with_status_code(400);
+            if let Err(e) = request.respond(reply) {
+                log::warn!("Failed to answer a request: {}", e);
+            }
+            return;
+        };
+        let reply = Response::empty(101).with_header(header(
+            "Sec-WebSocket-Accept",
+            &derive_accept_key(key.as_bytes()),
+        ));
+        let stream = request.upgrade("websocket", reply);
+        let mut watcher = WebSocket::from_raw_socket(stream, Role::Server, None);
+        if watcher
+            .send(Message::text(self.progress().to_string()))
+            .is_ok()
+        {
+            self.watchers.push(watcher);
+        }
+    }
+
+    /// Sends `progress` to every WebSocket client, dropping those that went away.
+    fn broadcast(&mut self, progress: &serde_json::Value) {
+        let text = progress.to_string();
+        self.watchers
+            .retain_mut(|watcher| watcher.send(Message::text(text.clone())).is_ok());
+    }
+}
+
+/// Serves a file of the wasm-bindgen output, which is built separately from this binary.
+fn serve_pkg_file(pkg_dir: &Path, name: &str) -> Reply {
+    let safe = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !safe {
+        return not_found();
+    }
+    let content_type = match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+        Some("js") => "text/javascript",
+        Some("wasm") => "application/wasm",
+        _ => "application/octet-stream",
+    };
+    match fs::read(pkg_dir.join(name)) {
+        Ok(data) => content(data, content_type),
+        Err(_) => not_found(),
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+fn content(data: Vec<u8>, content_type: &str) -> Reply {
+    Response::from_data(data).with_header(header("Content-Type", content_type))
+}
+
+fn json(value: &serde_json::Value) -> Reply {
+    content(value.to_string().into_bytes(), "application/json")
+}
+
+fn not_found() -> Reply {
+    Response::from_string("Not found").with_status_code(404)
+}
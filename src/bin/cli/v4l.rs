@@ -0,0 +1,130 @@
+use anyhow::{anyhow, Context, Result};
+use image::{GrayImage, ImageFormat};
+use v4l::buffer::Type;
+use v4l::io::mmap::Stream;
+use v4l::io::traits::CaptureStream;
+use v4l::video::capture::Parameters;
+use v4l::video::Capture;
+use v4l::{Device, Format, FourCC};
+
+use crate::camera::FrameSize;
+
+/// Pixel formats this reads, best first: brightness alone, brightness interleaved with
+/// colour, and JPEG, which nearly every webcam offers.
+const PIXEL_FORMATS: [&[u8; 4]; 3] = [b"GREY", b"YUYV", b"MJPG"];
+
+/// A camera streaming through Video4Linux2 directly, without ffmpeg.
+pub struct V4lCamera {
+    stream: Stream<'static>,
+    format: Format,
+}
+
+impl V4lCamera {
+    /// Opens `device` and asks it for frames of `size` at `fps`. The driver picks the
+    /// nearest size it supports; `size()` tells which.
+    pub fn open(device: &str, size: FrameSize, fps: u32) -> Result<Self> {
+        let camera =
+            Device::with_path(device).with_context(|| format!("Failed to open {}", device))?;
+        let offered: Vec<FourCC> = camera
+            .enum_formats()
+            .with_context(|| format!("Failed to list the pixel formats of {}", device))?
+            .into_iter()
+            .map(|description| description.fourcc)
+            .collect();
+        let fourcc = PIXEL_FORMATS
+            .iter()
+            .map(|repr| FourCC::new(repr))
+            .find(|fourcc| offered.contains(fourcc))
+            .ok_or_else(|| anyhow!("{} offers none of the formats GREY, YUYV or MJPG", device))?;
+        let format = camera
+            .set_format(&Format::new(size.width, size.height, fourcc))
+            .with_context(|| format!("Failed to set the format of {}", device))?;
+        // Not every driver lets the frame rate be chosen; its own is fine then
+        let _ = camera.set_params(&Parameters::with_fps(fps));
+        let stream = Stream::with_buffers(&camera, Type::VideoCapture, 4)
+            .with_context(|| format!("Failed to start streaming from {}", device))?;
+        Ok(Self { stream, format })
+    }
+
+    /// Size of the frames the driver settled on.
+    pub fn size(&self) -> FrameSize {
+        FrameSize {
+            width: self.format.width,
+            height: self.format.height,
+        }
+    }
+
+    /// Waits for the next frame and returns its brightness.
+    pub fn next_frame(&mut self) -> Result<GrayImage> {
+        let (width, height) = (self.format.width, self.format.height);
+        let fourcc = self.format.fourcc;
+        let (buffer, metadata) = self.stream.next()?;
+        let data = &buffer[..(metadata.bytesused as usize).min(buffer.len())];
+
+        if fourcc == FourCC::new(b"MJPG") {
+            let frame = image::load_from_memory_with_format(data, ImageFormat::Jpeg)
+                .context("Failed to decode a JPEG frame")?;
+            return Ok(frame.to_luma8());
+        }
+        // GREY holds one byte per pixel; YUYV two, brightness first
+        let step = if fourcc == FourCC::new(b"YUYV") { 2 } else { 1 };
+        luma_from_packed(data, width, height, self.format.stride as usize, step)
+    }
+}
+
+/// Brightness of a frame whose rows start every `stride` bytes and hold a brightness byte
+/// every `step` bytes, first of each pixel. A `stride` shorter than a row means rows are
+/// not padded.
+fn luma_from_packed(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    step: usize,
+) -> Result<GrayImage> {
+    let row = width as usize * step;
+    let stride = stride.max(row);
+    if data.len() < stride * (height as usize).saturating_sub(1) + row {
+        return Err(anyhow!("Short frame: {} bytes", data.len()));
+    }
+    let pixels = data
+        .chunks(stride)
+        .take(height as usize)
+        .flat_map(|line| line[..row].iter().step_by(step).copied())
+        .collect();
+    Ok(GrayImage::from_raw(width, height, pixels).expect("frame holds width x height pixels"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_luma_from_grey() {
+        // Rows of 3 pixels padded to 4 bytes, without padding after the last
+        let data = [10, 20, 30, 0, 40, 50, 60];
+        let frame = luma_from_packed(&data, 3, 2, 4, 1).unwrap();
+        assert_eq!(frame.into_raw(), vec![10, 20, 30, 40, 50, 60]);
+
+        let unpadded = luma_from_packed(&[1, 2, 3, 4], 2, 2, 0, 1).unwrap();
+        assert_eq!(unpadded.into_raw(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_luma_from_yuyv() {
+        // Y0 U Y1 V for each pair of pixels; only the Y bytes are kept
+        let data = [10, 128, 20, 129, 30, 130, 40, 131];
+        let frame = luma_from_packed(&data, 2, 2, 4, 2).unwrap();
+        assert_eq!(frame.into_raw(), vec![10, 20, 30, 40]);
+
+        let padded = [1, 0, 2, 0, 9, 9, 3, 0, 4, 0];
+        let frame = luma_from_packed(&padded, 2, 2, 6, 2).unwrap();
+        assert_eq!(frame.into_raw(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_luma_from_short_frame() {
+        assert!(luma_from_packed(&[10, 128, 20, 129, 30], 2, 2, 4, 2).is_err());
+        assert!(luma_from_packed(&[], 1, 1, 1, 1).is_err());
+    }
+}
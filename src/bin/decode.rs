@@ -1,53 +1,22 @@
 use anyhow::Result;
 use clap::Parser;
-use std::path::PathBuf;
 
-use fountain::{decode_from_gif, decode_from_images, qr::QR_FILE_EXTENSION};
+#[path = "cli/decode.rs"]
+mod decode;
+#[path = "cli/logging.rs"]
+mod logging;
+#[cfg(feature = "screen")]
+#[path = "cli/screen.rs"]
+mod screen;
 
 #[derive(Parser)]
 #[command(name = "fountain-decode")]
 #[command(author, version, about = "Decode QR code images back to original file", long_about = None)]
 struct Cli {
-    /// Input directory (containing images) or GIF file
-    input: PathBuf,
-
-    /// Output file path (defaults to original filename in current directory)
-    #[arg(short, long)]
-    output: Option<PathBuf>,
+    #[command(flatten)]
+    args: decode::DecodeArgs,
 }
 
 fn main() -> Result<()> {
-    let args = Cli::parse();
-
-    if !args.input.exists() {
-        anyhow::bail!("Input path does not exist: {}", args.input.display());
-    }
-
-    let result = if args.input.is_dir() {
-        println!("Decoding QR codes from directory: {}", args.input.display());
-        decode_from_images(&args.input, args.output.as_deref())?
-    } else {
-        let is_gif = args
-            .input
-            .extension()
-            .map(|ext| ext.to_ascii_lowercase() == "gif")
-            .unwrap_or(false);
-
-        if is_gif {
-            decode_from_gif(&args.input, args.output.as_deref())?
-        } else {
-            anyhow::bail!(
-                "Unsupported input file type: {}. Only directories (containing {} files) or GIF files are supported.",
-                args.input.display(),
-                QR_FILE_EXTENSION
-            );
-        }
-    };
-
-    println!();
-    println!("Successfully decoded {} QR code(s)", result.num_chunks);
-    println!("Original filename: {}", result.original_filename);
-    println!("Output file: {}", result.output_path);
-
-    Ok(())
+    decode::run(Cli::parse().args)
 }
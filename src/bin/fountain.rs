@@ -0,0 +1,100 @@
+//! One binary with the encoder and decoder as subcommands; `fountain-encode` and
+//! `fountain-decode` take the same options as `fountain encode` and `fountain decode`.
+
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[path = "cli/bench.rs"]
+mod bench;
+#[cfg(feature = "camera")]
+#[path = "cli/camera.rs"]
+mod camera;
+#[path = "cli/decode.rs"]
+mod decode;
+#[path = "cli/encode.rs"]
+mod encode;
+#[path = "cli/logging.rs"]
+mod logging;
+#[cfg(any(feature = "camera", feature = "serve"))]
+#[path = "cli/save.rs"]
+mod save;
+#[cfg(feature = "screen")]
+#[path = "cli/screen.rs"]
+mod screen;
+#[cfg(feature = "serve")]
+#[path = "cli/serve.rs"]
+mod serve;
+#[cfg(all(feature = "camera-v4l", target_os = "linux"))]
+#[path = "cli/v4l.rs"]
+mod v4l;
+
+#[derive(Parser)]
+#[command(name = "fountain")]
+#[command(author, version, about = "Convert any file to QR codes and back", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Encode a file to QR codes using RaptorQ (Fountain Codes)
+    Encode(Box<encode::EncodeArgs>),
+    /// Decode QR code images back to the original file
+    Decode(decode::DecodeArgs),
+    /// Report what the chunk headers say (chunk counts, sizes, missing indices); writes nothing
+    Info(CheckArgs),
+    /// Decode and validate the checksum without writing the result
+    Verify(CheckArgs),
+    /// Encode generated data to a temporary GIF and report chunks, QR version, GIF size,
+    /// encode time and transfer time, to choose payload size and interval before a transfer
+    Bench(bench::BenchArgs),
+    /// Host the web scanner for phones and assemble the codes they scan into a file here
+    #[cfg(feature = "serve")]
+    Serve(serve::ServeArgs),
+    /// Receive files live from a camera pointed at a screen of QR codes
+    #[cfg(feature = "camera")]
+    Camera(camera::CameraArgs),
+}
+
+#[derive(Args)]
+struct CheckArgs {
+    /// Input directory (containing images) or GIF file; `info` also accepts a single image file
+    input: PathBuf,
+
+    /// Print the result as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Encode(args) => encode::run(*args),
+        Command::Decode(args) => decode::run(args),
+        Command::Info(args) => {
+            logging::init_logging(false, 0);
+            let (is_dir, is_gif) = decode::input_kind(&args.input)?;
+            decode::show_info(&args.input, is_dir, is_gif, args.json)
+        }
+        Command::Verify(args) => {
+            logging::init_logging(false, 0);
+            let started = Instant::now();
+            let (is_dir, is_gif) = decode::input_kind(&args.input)?;
+            decode::ensure_stream_input(&args.input, is_dir, is_gif)?;
+            decode::show_verification(&args.input, is_dir, args.json, started)
+        }
+        Command::Bench(args) => {
+            logging::init_logging(true, 0);
+            bench::run(args)
+        }
+        #[cfg(feature = "serve")]
+        Command::Serve(args) => {
+            logging::init_logging(false, 0);
+            serve::run(args)
+        }
+        #[cfg(feature = "camera")]
+        Command::Camera(args) => camera::run(args),
+    }
+}
@@ -0,0 +1,28 @@
+use std::fmt;
+#[cfg(any(feature = "encode", feature = "decode"))]
+use {
+    anyhow::Result,
+    std::sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Error returned when an operation stops because its cancel flag was set.
+/// Callers can tell it apart from real failures with `error.is::<Cancelled>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Operation cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Fails with [`Cancelled`] once `flag` has been set.
+#[cfg(any(feature = "encode", feature = "decode"))]
+pub(crate) fn check_cancelled(flag: Option<&AtomicBool>) -> Result<()> {
+    match flag {
+        Some(flag) if flag.load(Ordering::Relaxed) => Err(Cancelled.into()),
+        _ => Ok(()),
+    }
+}
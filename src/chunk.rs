@@ -1,163 +1,418 @@
+use crate::core::{
+    qr_payload_bytes, verify_checksums, METADATA_FLAG_BASE, METADATA_FLAG_MODE,
+    METADATA_FLAG_MTIME, METADATA_FLAG_SEED, METADATA_FLAG_SHA256, METADATA_MARKER,
+};
+use crate::dedup::{deduplicate, expand};
 use anyhow::{anyhow, Result};
 use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
 use sha2::{Digest, Sha256};
-use std::io::{Read, Write};
-
-// Default chunk size for QR code generation
-// Smaller = smaller QR codes but more of them
-// Larger = larger QR codes but fewer of them
-//
-// QR code size reference (binary mode, M error correction):
-//   ~100 bytes -> ~29x29 modules (fits in small terminal)
-//   ~200 bytes -> ~37x37 modules
-//   ~500 bytes -> ~53x53 modules
-//   ~1400 bytes -> ~73x73 modules (original default)
-pub const DEFAULT_PAYLOAD_SIZE: usize = 100; // Small default for terminal display
-pub const MAX_PAYLOAD_SIZE: usize = 1400; // Max for file output
-pub const CHECKSUM_SIZE: usize = 8;
-pub const HEADER_SIZE: usize = 11; // 1 (version) + 4 (transfer len) + 4 (esi) + 2 (packet size)
-
-#[derive(Debug, Clone)]
-pub struct ChunkHeader {
-    pub version: u8,
-    pub total: u32,       // Transfer Length
-    pub index: u32,       // ESI
-    pub packet_size: u16, // Packet Size
-}
-
-#[derive(Debug, Clone)]
-pub struct Chunk {
-    pub header: ChunkHeader,
-    pub data: Vec<u8>,
-}
-
-impl ChunkHeader {
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![0u8; HEADER_SIZE];
-        bytes[0] = self.version;
-        bytes[1..5].copy_from_slice(&self.total.to_be_bytes());
-        bytes[5..9].copy_from_slice(&self.index.to_be_bytes());
-        bytes[9..11].copy_from_slice(&self.packet_size.to_be_bytes());
-        bytes
-    }
-
-    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize)> {
-        if bytes.is_empty() {
-            return Err(anyhow!("Invalid header: empty"));
-        }
-        let version = bytes[0];
-        if version != 1 {
-            return Err(anyhow!("Unsupported chunk version: {}. Only Version 1 (RaptorQ) is supported.", version));
-        }
-
-        if bytes.len() < HEADER_SIZE {
-            return Err(anyhow!("Invalid header: too short"));
-        }
-        let total = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
-        let index = u32::from_be_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
-        let packet_size = u16::from_be_bytes([bytes[9], bytes[10]]);
-        Ok((
-            ChunkHeader {
-                version,
-                total,
-                index,
-                packet_size,
-            },
-            HEADER_SIZE,
-        ))
+use std::io::{BufRead, Read, Write};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+pub use crate::core::{
+    calculate_checksum, pack_data, pack_data_with_metadata, sha256_digest, sha256_hex, unpack_data,
+    unpack_data_with_metadata, Chunk, ChunkHeader, FileMetadata, PayloadEncoding, Reassembler,
+    Reassembly, SyncInfo, Volume, WireFormat, CHECKSUM_SIZE, CHUNK_VERSION, DEFAULT_PAYLOAD_SIZE,
+    HEADER_SIZE, HEADER_SIZE_V1, HEADER_SIZE_V3, MAX_PAYLOAD_SIZE, SINGLE_CODE_MARKER, SYNC_MARKER,
+    VOLUME_CHUNK_VERSION,
+};
+
+// Leading byte of a compressed object identifying the algorithm (zlib streams are untagged)
+const COMPRESSION_TAG_NONE: u8 = 0x00;
+const COMPRESSION_TAG_ZSTD: u8 = 0x01;
+const COMPRESSION_TAG_BROTLI: u8 = 0x02;
+// Followed by a whole compressed object of deduplication records
+const COMPRESSION_TAG_DEDUP: u8 = 0x03;
+const ZLIB_LEVEL: u32 = 9;
+const ZSTD_LEVEL: u32 = 19;
+const BROTLI_LEVEL: u32 = 11;
+#[cfg(feature = "encode")]
+const BROTLI_WINDOW_BITS: u32 = 22;
+#[cfg(any(
+    feature = "encode",
+    feature = "decode",
+    feature = "wasm",
+    feature = "ffi"
+))]
+const BROTLI_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Compression applied to the packed data before it is split into fountain packets.
+/// The choice is recorded in the compressed object, so decoding needs no configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionAlgorithm {
+    None,
+    #[default]
+    Zlib,
+    Zstd,
+    /// Smallest output for text-heavy files, at the cost of slow encoding.
+    Brotli,
+}
+
+impl CompressionAlgorithm {
+    /// Levels the algorithm accepts, lowest (fastest) first, or `None` if it has no levels.
+    pub fn levels(self) -> Option<RangeInclusive<u32>> {
+        match self {
+            CompressionAlgorithm::None => None,
+            CompressionAlgorithm::Zlib => Some(0..=9),
+            CompressionAlgorithm::Zstd => Some(1..=22),
+            CompressionAlgorithm::Brotli => Some(0..=11),
+        }
+    }
+
+    /// Level used when none is given.
+    pub fn default_level(self) -> u32 {
+        match self {
+            CompressionAlgorithm::None => 0,
+            CompressionAlgorithm::Zlib => ZLIB_LEVEL,
+            CompressionAlgorithm::Zstd => ZSTD_LEVEL,
+            CompressionAlgorithm::Brotli => BROTLI_LEVEL,
+        }
     }
 }
 
-impl Chunk {
-    pub fn to_bytes(&self) -> Result<Vec<u8>> {
-        let header_bytes = self.header.to_bytes();
-        let mut result = Vec::with_capacity(header_bytes.len() + self.data.len());
-        result.extend_from_slice(&header_bytes);
-        result.extend_from_slice(&self.data);
-        Ok(result)
+impl FromStr for CompressionAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(CompressionAlgorithm::None),
+            "zlib" => Ok(CompressionAlgorithm::Zlib),
+            "zstd" => Ok(CompressionAlgorithm::Zstd),
+            "brotli" => Ok(CompressionAlgorithm::Brotli),
+            _ => Err(anyhow!(
+                "Invalid compression algorithm: {}. Expected none, zlib, zstd or brotli.",
+                s
+            )),
+        }
     }
+}
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        let (header, header_len) = ChunkHeader::from_bytes(bytes)?;
-        let data = bytes[header_len..].to_vec();
+/// A compression algorithm and optionally its level, parsed from `ALGORITHM[:LEVEL]` such as
+/// `zstd:19`. Only the algorithm is recorded in the compressed object; the level matters to
+/// the encoder alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompressionSetting {
+    pub algorithm: CompressionAlgorithm,
+    /// Defaults to [`CompressionAlgorithm::default_level`].
+    pub level: Option<u32>,
+    /// Replace repeated regions by references first, see [`crate::dedup`]. Recorded in the
+    /// compressed object, and skipped when nothing repeats.
+    pub dedup: bool,
+}
+
+impl CompressionSetting {
+    pub fn new(algorithm: CompressionAlgorithm, level: Option<u32>) -> Result<Self> {
+        if let Some(level) = level {
+            match algorithm.levels() {
+                Some(levels) if levels.contains(&level) => {}
+                Some(levels) => {
+                    return Err(anyhow!(
+                        "Invalid {:?} level {}: expected {} to {}",
+                        algorithm,
+                        level,
+                        levels.start(),
+                        levels.end()
+                    ))
+                }
+                None => return Err(anyhow!("Compression 'none' takes no level")),
+            }
+        }
+        Ok(Self {
+            algorithm,
+            level,
+            dedup: false,
+        })
+    }
 
-        Ok(Chunk { header, data })
+    /// The level to compress at.
+    pub fn level(&self) -> u32 {
+        self.level.unwrap_or_else(|| self.algorithm.default_level())
     }
 }
 
-pub fn compress(data: &[u8]) -> Result<Vec<u8>> {
-    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
-    encoder.write_all(data)?;
-    Ok(encoder.finish()?)
+impl From<CompressionAlgorithm> for CompressionSetting {
+    fn from(algorithm: CompressionAlgorithm) -> Self {
+        Self {
+            algorithm,
+            level: None,
+            dedup: false,
+        }
+    }
 }
 
-pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
-    let mut decoder = ZlibDecoder::new(data);
-    let mut result = Vec::new();
-    decoder.read_to_end(&mut result)?;
-    Ok(result)
+impl FromStr for CompressionSetting {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (algorithm, level) = match s.split_once(':') {
+            Some((algorithm, level)) => {
+                let level = level
+                    .trim()
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid compression level: {}", level))?;
+                (algorithm, Some(level))
+            }
+            None => (s, None),
+        };
+        Self::new(algorithm.trim().parse()?, level)
+    }
 }
 
-pub fn calculate_checksum(data: &[u8]) -> Vec<u8> {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    let result = hasher.finalize();
-    result[..CHECKSUM_SIZE].to_vec()
+/// Packs a small file into one self-contained QR payload: [`SINGLE_CODE_MARKER`] followed by
+/// the packed file, compressed only when that makes it smaller. Without a chunk header or
+/// RaptorQ overhead, short secrets fit the smallest code possible.
+pub fn to_single_code_payload(
+    data: &[u8],
+    filename: &str,
+    encoding: PayloadEncoding,
+) -> Result<Vec<u8>> {
+    let packed = pack_data(data, filename);
+    let mut bytes = vec![SINGLE_CODE_MARKER];
+    bytes.extend_from_slice(&compress_or_store(&packed, CompressionAlgorithm::Zlib)?);
+    Ok(match encoding {
+        PayloadEncoding::Base45 => base45::encode(&bytes).into_bytes(),
+        PayloadEncoding::Binary => bytes,
+    })
 }
 
-// Pack data: [Checksum 8B] [Filename] [\0] [Content]
-pub fn pack_data(data: &[u8], filename: &str) -> Vec<u8> {
-    let checksum = calculate_checksum(data);
-    // Sanitize filename: remove null bytes
-    let clean_filename = filename.replace('\0', "");
+/// Parses a payload made by [`to_single_code_payload`] into the filename and contents.
+/// Returns `None` for chunks and any other QR content.
+pub fn from_single_code_payload(payload: &[u8]) -> Option<Result<(String, Vec<u8>)>> {
+    let bytes = qr_payload_bytes(payload).ok()?;
+    let (&marker, compressed) = bytes.split_first()?;
+    if marker != SINGLE_CODE_MARKER {
+        return None;
+    }
+    Some(decompress(compressed).and_then(|packed| unpack_data(&packed)))
+}
 
-    let mut packed = Vec::with_capacity(CHECKSUM_SIZE + clean_filename.len() + 1 + data.len());
-    packed.extend_from_slice(&checksum);
-    packed.extend_from_slice(clean_filename.as_bytes());
-    packed.push(0); // Null terminator
-    packed.extend_from_slice(data);
-    packed
+/// Compresses `data` with an algorithm, e.g. [`CompressionAlgorithm::Zlib`], at its default
+/// level, or with a [`CompressionSetting`] that picks the level too.
+pub fn compress(data: &[u8], compression: impl Into<CompressionSetting>) -> Result<Vec<u8>> {
+    let compression = compression.into();
+    if compression.dedup {
+        if let Some(records) = deduplicate(data) {
+            let inner = CompressionSetting {
+                dedup: false,
+                ..compression
+            };
+            let mut result = vec![COMPRESSION_TAG_DEDUP];
+            result.extend_from_slice(&compress(&records, inner)?);
+            return Ok(result);
+        }
+    }
+    let level = compression.level();
+    match compression.algorithm {
+        CompressionAlgorithm::None => {
+            let mut result = Vec::with_capacity(data.len() + 1);
+            result.push(COMPRESSION_TAG_NONE);
+            result.extend_from_slice(data);
+            Ok(result)
+        }
+        // Zlib output is stored untagged, exactly as before algorithms were selectable
+        CompressionAlgorithm::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        CompressionAlgorithm::Zstd => compress_zstd(data, level),
+        CompressionAlgorithm::Brotli => compress_brotli(data, level),
+    }
 }
 
-// Unpack data: -> (Filename, Content)
-pub fn unpack_data(packed: &[u8]) -> Result<(String, Vec<u8>)> {
-    if packed.len() < CHECKSUM_SIZE + 2 {
-        // Min: Checksum + 1 char + \0
-        return Err(anyhow!("Invalid packed data: too short"));
+/// Like [`compress`], but stores `data` uncompressed when compressing would not make it
+/// smaller, as for JPEG or ZIP files. The tag records the choice, so decoding is unchanged.
+pub fn compress_or_store(
+    data: &[u8],
+    compression: impl Into<CompressionSetting>,
+) -> Result<Vec<u8>> {
+    let compressed = compress(data, compression)?;
+    if compressed.len() <= data.len() {
+        return Ok(compressed);
     }
+    compress(data, CompressionAlgorithm::None)
+}
 
-    let expected_checksum = &packed[..CHECKSUM_SIZE];
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    let tag = *data
+        .first()
+        .ok_or_else(|| anyhow!("Invalid compressed data: empty"))?;
 
-    let mut null_pos = None;
-    for i in CHECKSUM_SIZE..packed.len() {
-        if packed[i] == 0 {
-            null_pos = Some(i);
-            break;
+    match tag {
+        COMPRESSION_TAG_NONE => Ok(data[1..].to_vec()),
+        COMPRESSION_TAG_ZSTD => decompress_zstd(&data[1..]),
+        COMPRESSION_TAG_BROTLI => {
+            let mut result = Vec::new();
+            decompress_brotli_reader(&data[1..])?.read_to_end(&mut result)?;
+            Ok(result)
+        }
+        COMPRESSION_TAG_DEDUP => expand(&decompress(&data[1..])?),
+        // The first byte of a zlib stream (CMF) always carries method 8 in its low nibble
+        b if b & 0x0F == 8 => {
+            let mut decoder = ZlibDecoder::new(data);
+            let mut result = Vec::new();
+            decoder.read_to_end(&mut result)?;
+            Ok(result)
         }
+        _ => Err(anyhow!("Unknown compression tag: {:#04x}", tag)),
     }
+}
 
-    let null_idx =
-        null_pos.ok_or_else(|| anyhow!("Invalid packed data: missing filename terminator"))?;
+/// Streaming form of [`decompress`], for objects too large to hold in memory.
+pub fn decompress_reader<'a, R: Read + 'a>(mut reader: R) -> Result<Box<dyn Read + 'a>> {
+    let mut tag = [0u8; 1];
+    reader
+        .read_exact(&mut tag)
+        .map_err(|_| anyhow!("Invalid compressed data: empty"))?;
 
-    let filename_bytes = &packed[CHECKSUM_SIZE..null_idx];
-    let filename = std::str::from_utf8(filename_bytes)
-        .map_err(|_| anyhow!("Invalid filename: not valid UTF-8"))?
-        .to_string();
+    match tag[0] {
+        COMPRESSION_TAG_NONE => Ok(Box::new(reader)),
+        COMPRESSION_TAG_ZSTD => decompress_zstd_reader(reader),
+        COMPRESSION_TAG_BROTLI => decompress_brotli_reader(reader),
+        // References may point anywhere earlier, so the records are expanded in memory
+        COMPRESSION_TAG_DEDUP => {
+            let mut records = Vec::new();
+            decompress_reader(reader)?.read_to_end(&mut records)?;
+            Ok(Box::new(std::io::Cursor::new(expand(&records)?)))
+        }
+        // The zlib header byte was consumed to detect the algorithm, so hand it back
+        b if b & 0x0F == 8 => Ok(Box::new(ZlibDecoder::new(
+            std::io::Cursor::new(tag).chain(reader),
+        ))),
+        b => Err(anyhow!("Unknown compression tag: {:#04x}", b)),
+    }
+}
+
+#[cfg(feature = "encode")]
+fn compress_zstd(data: &[u8], level: u32) -> Result<Vec<u8>> {
+    let mut result = vec![COMPRESSION_TAG_ZSTD];
+    result.extend_from_slice(&zstd::encode_all(data, level as i32)?);
+    Ok(result)
+}
 
-    let content = packed[null_idx + 1..].to_vec();
+#[cfg(not(feature = "encode"))]
+fn compress_zstd(_data: &[u8], _level: u32) -> Result<Vec<u8>> {
+    Err(anyhow!("Zstd compression requires the encode feature"))
+}
+
+#[cfg(feature = "encode")]
+fn compress_brotli(data: &[u8], level: u32) -> Result<Vec<u8>> {
+    let mut encoder = brotli::CompressorWriter::new(
+        vec![COMPRESSION_TAG_BROTLI],
+        BROTLI_BUFFER_SIZE,
+        level,
+        BROTLI_WINDOW_BITS,
+    );
+    encoder.write_all(data)?;
+    encoder.flush()?;
+    Ok(encoder.into_inner())
+}
+
+#[cfg(not(feature = "encode"))]
+fn compress_brotli(_data: &[u8], _level: u32) -> Result<Vec<u8>> {
+    Err(anyhow!("Brotli compression requires the encode feature"))
+}
 
-    let actual_checksum = calculate_checksum(&content);
-    if actual_checksum != expected_checksum {
-        return Err(anyhow!(
-            "Checksum mismatch: expected {:?}, got {:?}",
-            expected_checksum,
-            actual_checksum
-        ));
+#[cfg(any(feature = "decode", feature = "wasm", feature = "ffi"))]
+fn decompress_zstd(data: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = ruzstd::decoding::StreamingDecoder::new(data)
+        .map_err(|e| anyhow!("Invalid zstd data: {}", e))?;
+    let mut result = Vec::new();
+    decoder.read_to_end(&mut result)?;
+    Ok(result)
+}
+
+#[cfg(not(any(feature = "decode", feature = "wasm", feature = "ffi")))]
+fn decompress_zstd(_data: &[u8]) -> Result<Vec<u8>> {
+    Err(anyhow!("Zstd decompression requires the decode feature"))
+}
+
+#[cfg(any(feature = "decode", feature = "wasm", feature = "ffi"))]
+fn decompress_zstd_reader<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+    let decoder = ruzstd::decoding::StreamingDecoder::new(reader)
+        .map_err(|e| anyhow!("Invalid zstd data: {}", e))?;
+    Ok(Box::new(decoder))
+}
+
+#[cfg(not(any(feature = "decode", feature = "wasm", feature = "ffi")))]
+fn decompress_zstd_reader<'a, R: Read + 'a>(_reader: R) -> Result<Box<dyn Read + 'a>> {
+    Err(anyhow!("Zstd decompression requires the decode feature"))
+}
+
+#[cfg(any(feature = "decode", feature = "wasm", feature = "ffi"))]
+fn decompress_brotli_reader<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>> {
+    Ok(Box::new(brotli_decompressor::Decompressor::new(
+        reader,
+        BROTLI_BUFFER_SIZE,
+    )))
+}
+
+#[cfg(not(any(feature = "decode", feature = "wasm", feature = "ffi")))]
+fn decompress_brotli_reader<'a, R: Read + 'a>(_reader: R) -> Result<Box<dyn Read + 'a>> {
+    Err(anyhow!("Brotli decompression requires the decode feature"))
+}
+
+/// Streaming form of [`unpack_data_with_metadata`]: copies the content to `writer` and returns
+/// the filename, the metadata and the content's SHA-256. The checksums can only be compared at
+/// the end, so on a mismatch the content has already been written.
+pub fn unpack_data_to<R: BufRead, W: Write>(
+    mut reader: R,
+    mut writer: W,
+) -> Result<(String, FileMetadata, [u8; 32])> {
+    let mut expected_checksum = [0u8; CHECKSUM_SIZE];
+    reader
+        .read_exact(&mut expected_checksum)
+        .map_err(|_| anyhow!("Invalid packed data: too short"))?;
+
+    let metadata = if reader.fill_buf()?.first() == Some(&METADATA_MARKER) {
+        let truncated = |_| anyhow!("Invalid packed data: truncated metadata");
+        let mut block = vec![0u8; 2];
+        reader.read_exact(&mut block).map_err(truncated)?;
+        let flags = block[1];
+        let fields_len = [
+            (METADATA_FLAG_MODE, 4),
+            (METADATA_FLAG_MTIME, 8),
+            (METADATA_FLAG_SHA256, 32),
+            (METADATA_FLAG_SEED, 8),
+            (METADATA_FLAG_BASE, 32),
+        ]
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, len)| len)
+        .sum::<usize>();
+        block.resize(2 + fields_len, 0);
+        reader.read_exact(&mut block[2..]).map_err(truncated)?;
+        FileMetadata::from_bytes(&block)?.0
+    } else {
+        FileMetadata::default()
+    };
+
+    let mut filename_bytes = Vec::new();
+    reader.read_until(0, &mut filename_bytes)?;
+    if filename_bytes.pop() != Some(0) {
+        return Err(anyhow!("Invalid packed data: missing filename terminator"));
+    }
+    let filename = String::from_utf8(filename_bytes)
+        .map_err(|_| anyhow!("Invalid filename: not valid UTF-8"))?;
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        writer.write_all(&buffer[..n])?;
     }
+    writer.flush()?;
+    let actual_sha256: [u8; 32] = hasher.finalize().into();
 
-    Ok((filename, content))
+    verify_checksums(&expected_checksum, &metadata, &actual_sha256)?;
+    Ok((filename, metadata, actual_sha256))
 }
 
 #[cfg(test)]
@@ -165,14 +420,179 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_pack_unpack() {
-        let data = b"Some random data";
-        let filename = "example.file";
+    fn test_single_code_payload() {
+        let secret = b"JBSWY3DPEHPK3PXP";
+        for encoding in [PayloadEncoding::Base45, PayloadEncoding::Binary] {
+            let payload = to_single_code_payload(secret, "totp.txt", encoding).unwrap();
+            // Marker, the stored-compression tag and the 8-byte checksum are all it adds
+            let raw_len = 1 + 1 + CHECKSUM_SIZE + "totp.txt".len() + 1 + secret.len();
+            if encoding == PayloadEncoding::Binary {
+                assert_eq!(payload.len(), raw_len);
+            }
+            let (filename, data) = from_single_code_payload(&payload).unwrap().unwrap();
+            assert_eq!(filename, "totp.txt");
+            assert_eq!(data, secret);
+        }
+
+        // Regular chunks are not mistaken for single-code payloads
+        let chunk = Chunk {
+            header: ChunkHeader {
+                version: CHUNK_VERSION,
+                total: 10,
+                index: 0,
+                packet_size: 10,
+                source_symbols: Some(1),
+                original_size: Some(5),
+                volume: None,
+            },
+            data: vec![0; 14],
+        };
+        let payload = chunk.to_qr_payload(PayloadEncoding::Base45).unwrap();
+        assert!(from_single_code_payload(&payload).is_none());
+        assert!(from_single_code_payload(b"https://example.com").is_none());
+    }
+
+    #[test]
+    fn test_sync_payload() {
+        let info = SyncInfo {
+            frames: 42,
+            interval_ms: 150,
+            transfer_length: 5000,
+            packet_size: 400,
+            source_symbols: 13,
+            original_size: 4800,
+            volume: None,
+        };
+        for encoding in [PayloadEncoding::Base45, PayloadEncoding::Binary] {
+            for volume in [None, Some(Volume { index: 0, count: 3 })] {
+                let info = SyncInfo { volume, ..info };
+                let payload = info.to_qr_payload(encoding);
+                assert_eq!(SyncInfo::from_qr_payload(&payload).unwrap().unwrap(), info);
+
+                // Neither kind of decoder mistakes the marker for data
+                assert!(Chunk::from_qr_payload(&payload).is_err());
+                assert!(from_single_code_payload(&payload).is_none());
+            }
+        }
+
+        let single = to_single_code_payload(b"secret", "a.txt", PayloadEncoding::Base45).unwrap();
+        assert!(SyncInfo::from_qr_payload(&single).is_none());
+        assert!(SyncInfo::from_qr_payload(&[SYNC_MARKER, 0, 1])
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    fn test_compression_roundtrip() {
+        let data = b"compressible compressible compressible compressible".repeat(20);
+
+        for algorithm in [
+            CompressionAlgorithm::None,
+            CompressionAlgorithm::Zlib,
+            CompressionAlgorithm::Zstd,
+            CompressionAlgorithm::Brotli,
+        ] {
+            let compressed = compress(&data, algorithm).unwrap();
+            assert_eq!(decompress(&compressed).unwrap(), data);
+            let mut streamed = Vec::new();
+            decompress_reader(compressed.as_slice())
+                .unwrap()
+                .read_to_end(&mut streamed)
+                .unwrap();
+            assert_eq!(streamed, data);
+
+            // Every level decodes the same way, since only the algorithm is recorded
+            if let Some(levels) = algorithm.levels() {
+                for level in [*levels.start(), *levels.end()] {
+                    let setting = CompressionSetting::new(algorithm, Some(level)).unwrap();
+                    let compressed = compress(&data, setting).unwrap();
+                    assert_eq!(decompress(&compressed).unwrap(), data);
+                }
+            }
+        }
+    }
 
-        let packed = pack_data(data, filename);
-        let (name, content) = unpack_data(&packed).unwrap();
+    #[test]
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    fn test_compress_or_store_skips_incompressible_data() {
+        let mut state = 7u32;
+        let noise: Vec<u8> = (0..5000)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect();
+        for algorithm in [
+            CompressionAlgorithm::Zlib,
+            CompressionAlgorithm::Zstd,
+            CompressionAlgorithm::Brotli,
+        ] {
+            let object = compress_or_store(&noise, algorithm).unwrap();
+            assert_eq!(object[0], COMPRESSION_TAG_NONE);
+            assert_eq!(object.len(), noise.len() + 1);
+            assert_eq!(decompress(&object).unwrap(), noise);
+
+            let text = b"compressible ".repeat(100);
+            let object = compress_or_store(&text, algorithm).unwrap();
+            assert!(object.len() < text.len());
+            assert_eq!(decompress(&object).unwrap(), text);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "encode", feature = "decode"))]
+    fn test_compression_with_dedup() {
+        // A block repeated further apart than the zlib window
+        let mut state = 3u32;
+        let mut noise = |len: usize| -> Vec<u8> {
+            (0..len)
+                .map(|_| {
+                    state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                    (state >> 24) as u8
+                })
+                .collect()
+        };
+        let block = noise(40_000);
+        let mut data = block.clone();
+        data.extend(noise(100_000));
+        data.extend_from_slice(&block);
+
+        for algorithm in [CompressionAlgorithm::None, CompressionAlgorithm::Zlib] {
+            let setting = CompressionSetting {
+                dedup: true,
+                ..algorithm.into()
+            };
+            let object = compress(&data, setting).unwrap();
+            assert_eq!(object[0], COMPRESSION_TAG_DEDUP);
+            assert_eq!(decompress(&object).unwrap(), data);
+            let mut streamed = Vec::new();
+            decompress_reader(object.as_slice())
+                .unwrap()
+                .read_to_end(&mut streamed)
+                .unwrap();
+            assert_eq!(streamed, data);
+        }
+
+        // Nothing repeats, so the records are not used
+        let setting = CompressionSetting {
+            dedup: true,
+            ..CompressionAlgorithm::None.into()
+        };
+        assert_eq!(compress(&block, setting).unwrap()[0], COMPRESSION_TAG_NONE);
+    }
+
+    #[test]
+    fn test_parse_compression_setting() {
+        let setting: CompressionSetting = "zstd:19".parse().unwrap();
+        assert_eq!(setting.algorithm, CompressionAlgorithm::Zstd);
+        assert_eq!(setting.level, Some(19));
+        let setting: CompressionSetting = "Brotli".parse().unwrap();
+        assert_eq!(setting.level(), BROTLI_LEVEL);
 
-        assert_eq!(name, filename);
-        assert_eq!(content, data);
+        assert!("zlib:10".parse::<CompressionSetting>().is_err());
+        assert!("zstd:0".parse::<CompressionSetting>().is_err());
+        assert!("none:1".parse::<CompressionSetting>().is_err());
+        assert!("zlib:best".parse::<CompressionSetting>().is_err());
     }
 }
@@ -0,0 +1,1072 @@
+//! The wire format and reassembly logic, free of `std` so that receivers without an operating
+//! system (e.g. a microcontroller with a camera) can reuse it with only an allocator.
+//!
+//! Compression, streaming and everything that touches files or images live in the modules
+//! behind the `std` feature; [`crate::chunk`] re-exports this module's items next to them.
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use anyhow::{anyhow, Result};
+use core::convert::Infallible;
+use core::mem;
+use core::str::FromStr;
+use raptorq::{Decoder, EncodingPacket, ObjectTransmissionInformation};
+use sha2::{Digest, Sha256};
+
+// Default chunk size for QR code generation
+// Smaller = smaller QR codes but more of them
+// Larger = larger QR codes but fewer of them
+//
+// QR code size reference (binary mode, M error correction):
+//   ~100 bytes -> ~29x29 modules (fits in small terminal)
+//   ~200 bytes -> ~37x37 modules
+//   ~500 bytes -> ~53x53 modules
+//   ~1400 bytes -> ~73x73 modules (original default)
+pub const DEFAULT_PAYLOAD_SIZE: usize = 100; // Small default for terminal display
+pub const MAX_PAYLOAD_SIZE: usize = 1400; // Max for file output
+pub const CHECKSUM_SIZE: usize = 8;
+pub const HEADER_SIZE_V1: usize = 11; // 1 (version) + 4 (transfer len) + 4 (esi) + 2 (packet size)
+pub const HEADER_SIZE: usize = 19; // v1 fields + 4 (source symbols) + 4 (original size)
+pub const HEADER_SIZE_V3: usize = 23; // v2 fields + 2 (volume index) + 2 (volume count)
+/// Header version written by encoders. Decoders also accept version 1.
+pub const CHUNK_VERSION: u8 = 2;
+/// Header version of chunks that belong to one volume of a file split into several.
+pub const VOLUME_CHUNK_VERSION: u8 = 3;
+/// First byte of a single-code payload. It is neither a chunk version nor a Base45
+/// character, so decoders can tell such payloads apart in either encoding.
+pub const SINGLE_CODE_MARKER: u8 = 0xFC;
+/// First byte of a start-marker payload, which announces an animated stream before its data
+/// frames. Like [`SINGLE_CODE_MARKER`], it is neither a chunk version nor a Base45 character.
+pub const SYNC_MARKER: u8 = 0xFD;
+const SYNC_PAYLOAD_SIZE: usize = 27;
+
+/// How serialized chunk bytes are placed into a QR code.
+///
+/// `Base45` uses the QR alphanumeric mode and is readable by any scanner that returns text.
+/// `Binary` stores the chunk bytes as-is in byte mode, saving the Base45 expansion.
+/// Decoding detects the encoding automatically: a raw chunk starts with its version byte,
+/// which is never a Base45 character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PayloadEncoding {
+    #[default]
+    Base45,
+    Binary,
+}
+
+impl FromStr for PayloadEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "base45" => Ok(PayloadEncoding::Base45),
+            "binary" | "raw" => Ok(PayloadEncoding::Binary),
+            _ => Err(anyhow!(
+                "Invalid payload encoding: {}. Expected base45 or binary.",
+                s
+            )),
+        }
+    }
+}
+
+/// Framing of the QR codes of an animated stream.
+///
+/// `Native` is this crate's chunk format. `Ur` writes Blockchain Commons `ur:bytes` parts
+/// (see [`crate::ur`]), readable by UR scanners and hardware wallets but without the
+/// filename, metadata and compression of native streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireFormat {
+    #[default]
+    Native,
+    Ur,
+}
+
+impl FromStr for WireFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "native" => Ok(WireFormat::Native),
+            "ur" | "bc-ur" => Ok(WireFormat::Ur),
+            _ => Err(anyhow!(
+                "Invalid wire format: {}. Expected native or ur.",
+                s
+            )),
+        }
+    }
+}
+
+impl WireFormat {
+    /// Wire format of one scanned QR payload, or `None` for content no decoder here reads,
+    /// such as a URL or a code from another app. Decoders route each payload by this before
+    /// parsing it, because UR text and plain text can also pass as Base45.
+    pub fn detect(payload: &[u8]) -> Option<Self> {
+        if crate::ur::is_ur(payload) {
+            return Some(WireFormat::Ur);
+        }
+        let bytes = qr_payload_bytes(payload).ok()?;
+        match *bytes.first()? {
+            SINGLE_CODE_MARKER | SYNC_MARKER => Some(WireFormat::Native),
+            _ => ChunkHeader::from_bytes(&bytes)
+                .is_ok()
+                .then_some(WireFormat::Native),
+        }
+    }
+}
+
+/// Short description of a payload that [`WireFormat::detect`] does not recognise, for logs
+/// and error messages.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+pub(crate) fn describe_unfamiliar(payload: &[u8]) -> String {
+    const MAX_CHARS: usize = 40;
+    match core::str::from_utf8(payload) {
+        Ok(text) if text.chars().count() > MAX_CHARS => {
+            let start: String = text.chars().take(MAX_CHARS).collect();
+            format!("text \"{}...\"", start.escape_debug())
+        }
+        Ok(text) => format!("text \"{}\"", text.escape_debug()),
+        Err(_) => format!("{} bytes of binary data", payload.len()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChunkHeader {
+    pub version: u8,
+    pub total: u32,       // Transfer Length
+    pub index: u32,       // ESI
+    pub packet_size: u16, // Packet Size
+    /// Number of source symbols (K) needed to decode; only carried by version 2.
+    pub source_symbols: Option<u32>,
+    /// Size of the original file before packing and compression; only carried by version 2.
+    pub original_size: Option<u32>,
+    /// Part of a split file this chunk's stream carries; only carried by version 3.
+    pub volume: Option<Volume>,
+}
+
+/// One of the self-contained streams a large file is split into. Each volume decodes on its
+/// own to a consecutive slice of the file, and the slices in order make up the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Volume {
+    /// 0-based position of this volume.
+    pub index: u16,
+    /// Number of volumes the file was split into.
+    pub count: u16,
+}
+
+impl Volume {
+    /// Name for this volume's slice of `filename` when it is saved on its own, e.g.
+    /// `backup.tar.002`, so that concatenating the parts in name order rebuilds the file.
+    pub fn part_name(&self, filename: &str) -> String {
+        format!("{}.{:03}", filename, self.index + 1)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub header: ChunkHeader,
+    pub data: Vec<u8>,
+}
+
+impl ChunkHeader {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; HEADER_SIZE_V1];
+        bytes[0] = self.version;
+        bytes[1..5].copy_from_slice(&self.total.to_be_bytes());
+        bytes[5..9].copy_from_slice(&self.index.to_be_bytes());
+        bytes[9..11].copy_from_slice(&self.packet_size.to_be_bytes());
+        if self.version >= 2 {
+            bytes.extend_from_slice(&self.source_symbols.unwrap_or(0).to_be_bytes());
+            bytes.extend_from_slice(&self.original_size.unwrap_or(0).to_be_bytes());
+        }
+        if self.version >= 3 {
+            let volume = self.volume.unwrap_or(Volume { index: 0, count: 1 });
+            bytes.extend_from_slice(&volume.index.to_be_bytes());
+            bytes.extend_from_slice(&volume.count.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Number of source symbols, from the header when it carries it or else derived from
+    /// the transfer length and packet size.
+    pub fn source_symbol_count(&self) -> u32 {
+        self.source_symbols.unwrap_or_else(|| {
+            (self.total as usize).div_ceil(self.packet_size.max(1) as usize) as u32
+        })
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize)> {
+        if bytes.is_empty() {
+            return Err(anyhow!("Invalid header: empty"));
+        }
+        let version = bytes[0];
+        let header_size = match version {
+            1 => HEADER_SIZE_V1,
+            2 => HEADER_SIZE,
+            3 => HEADER_SIZE_V3,
+            _ => {
+                return Err(anyhow!(
+                    "Unsupported chunk version: {}. Only Versions 1 to 3 (RaptorQ) are supported.",
+                    version
+                ))
+            }
+        };
+
+        if bytes.len() < header_size {
+            return Err(anyhow!("Invalid header: too short"));
+        }
+        let total = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+        let index = u32::from_be_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
+        let packet_size = u16::from_be_bytes([bytes[9], bytes[10]]);
+        let (source_symbols, original_size) = if version >= 2 {
+            (
+                Some(u32::from_be_bytes([
+                    bytes[11], bytes[12], bytes[13], bytes[14],
+                ])),
+                Some(u32::from_be_bytes([
+                    bytes[15], bytes[16], bytes[17], bytes[18],
+                ])),
+            )
+        } else {
+            (None, None)
+        };
+        let volume = (version >= 3).then(|| Volume {
+            index: u16::from_be_bytes([bytes[19], bytes[20]]),
+            count: u16::from_be_bytes([bytes[21], bytes[22]]),
+        });
+        Ok((
+            ChunkHeader {
+                version,
+                total,
+                index,
+                packet_size,
+                source_symbols,
+                original_size,
+                volume,
+            },
+            header_size,
+        ))
+    }
+}
+
+impl Chunk {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let header_bytes = self.header.to_bytes();
+        let mut result = Vec::with_capacity(header_bytes.len() + self.data.len());
+        result.extend_from_slice(&header_bytes);
+        result.extend_from_slice(&self.data);
+        Ok(result)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (header, header_len) = ChunkHeader::from_bytes(bytes)?;
+        let data = bytes[header_len..].to_vec();
+
+        Ok(Chunk { header, data })
+    }
+
+    /// Serializes the chunk into the bytes stored in a QR code.
+    pub fn to_qr_payload(&self, encoding: PayloadEncoding) -> Result<Vec<u8>> {
+        let bytes = self.to_bytes()?;
+        Ok(match encoding {
+            PayloadEncoding::Base45 => base45::encode(&bytes).into_bytes(),
+            PayloadEncoding::Binary => bytes,
+        })
+    }
+
+    /// Parses the content of a QR code, accepting both Base45 and raw binary payloads.
+    pub fn from_qr_payload(payload: &[u8]) -> Result<Self> {
+        if crate::ur::is_ur(payload) {
+            return Err(anyhow!("Not a chunk: the payload is a UR part"));
+        }
+        Chunk::from_bytes(&qr_payload_bytes(payload)?)
+    }
+}
+
+/// Bytes carried by a QR payload, decoding Base45 text when the payload is not raw binary.
+pub(crate) fn qr_payload_bytes(payload: &[u8]) -> Result<Cow<'_, [u8]>> {
+    match payload.first() {
+        Some(&b) if is_base45_char(b) => base45::decode(payload)
+            .map(Cow::Owned)
+            .map_err(|e| anyhow!("Invalid Base45 payload: {}", e)),
+        _ => Ok(Cow::Borrowed(payload)),
+    }
+}
+
+/// Session parameters carried by the start marker of a GIF, APNG or terminal carousel, so a
+/// receiver learns the frame rate and transfer parameters before the first data frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncInfo {
+    /// Number of data frames in one loop of the animation.
+    pub frames: u32,
+    /// Delay between frames in milliseconds.
+    pub interval_ms: u32,
+    /// Transfer length, packet size and source symbols as in every [`ChunkHeader`].
+    pub transfer_length: u32,
+    pub packet_size: u16,
+    pub source_symbols: u32,
+    pub original_size: u32,
+    pub volume: Option<Volume>,
+}
+
+impl SyncInfo {
+    /// Serializes the start marker into the bytes stored in a QR code.
+    pub fn to_qr_payload(&self, encoding: PayloadEncoding) -> Vec<u8> {
+        // A volume count of 0 stands for a file that was not split
+        let volume = self.volume.unwrap_or(Volume { index: 0, count: 0 });
+        let mut bytes = Vec::with_capacity(SYNC_PAYLOAD_SIZE);
+        bytes.push(SYNC_MARKER);
+        bytes.extend_from_slice(&self.frames.to_be_bytes());
+        bytes.extend_from_slice(&self.interval_ms.to_be_bytes());
+        bytes.extend_from_slice(&self.transfer_length.to_be_bytes());
+        bytes.extend_from_slice(&self.packet_size.to_be_bytes());
+        bytes.extend_from_slice(&self.source_symbols.to_be_bytes());
+        bytes.extend_from_slice(&self.original_size.to_be_bytes());
+        bytes.extend_from_slice(&volume.index.to_be_bytes());
+        bytes.extend_from_slice(&volume.count.to_be_bytes());
+        match encoding {
+            PayloadEncoding::Base45 => base45::encode(&bytes).into_bytes(),
+            PayloadEncoding::Binary => bytes,
+        }
+    }
+
+    /// Parses a start marker made by [`SyncInfo::to_qr_payload`].
+    /// Returns `None` for chunks and any other QR content.
+    pub fn from_qr_payload(payload: &[u8]) -> Option<Result<Self>> {
+        let bytes = qr_payload_bytes(payload).ok()?;
+        if bytes.first() != Some(&SYNC_MARKER) {
+            return None;
+        }
+        if bytes.len() < SYNC_PAYLOAD_SIZE {
+            return Some(Err(anyhow!("Invalid start marker: too short")));
+        }
+        let u32_at =
+            |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let u16_at = |i: usize| u16::from_be_bytes([bytes[i], bytes[i + 1]]);
+        let volume = Volume {
+            index: u16_at(23),
+            count: u16_at(25),
+        };
+        Some(Ok(SyncInfo {
+            frames: u32_at(1),
+            interval_ms: u32_at(5),
+            transfer_length: u32_at(9),
+            packet_size: u16_at(13),
+            source_symbols: u32_at(15),
+            original_size: u32_at(19),
+            volume: (volume.count > 0).then_some(volume),
+        }))
+    }
+}
+
+fn is_base45_char(b: u8) -> bool {
+    b.is_ascii_digit() || b.is_ascii_uppercase() || b" $%*+-./:".contains(&b)
+}
+
+pub fn calculate_checksum(data: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    let result = hasher.finalize();
+    result[..CHECKSUM_SIZE].to_vec()
+}
+
+/// Full SHA-256 digest of `data`.
+pub fn sha256_digest(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// Full SHA-256 of `data` as lowercase hex, as printed by `sha256sum`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(sha256_digest(data))
+}
+
+// Leading byte of the filename field announcing a metadata block (never part of a real name)
+pub(crate) const METADATA_MARKER: u8 = 0x01;
+pub(crate) const METADATA_FLAG_MODE: u8 = 0x01;
+pub(crate) const METADATA_FLAG_MTIME: u8 = 0x02;
+pub(crate) const METADATA_FLAG_SHA256: u8 = 0x04;
+pub(crate) const METADATA_FLAG_SEED: u8 = 0x08;
+pub(crate) const METADATA_FLAG_BASE: u8 = 0x10;
+
+/// File attributes carried alongside the contents so the decoder can restore them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// POSIX permission bits, e.g. `0o755`. Only the lowest nine are restored.
+    pub mode: Option<u32>,
+    /// Modification time in seconds since the Unix epoch.
+    pub mtime: Option<i64>,
+    /// SHA-256 of the original file, checked by the decoder after unpacking.
+    pub sha256: Option<[u8; 32]>,
+    /// Seed the encoder chose its repair packets with, so a run can be reproduced exactly.
+    pub seed: Option<u64>,
+    /// SHA-256 of the file the contents are a delta against; the receiver needs that file to
+    /// rebuild the original, see `fountain::delta`.
+    pub base_sha256: Option<[u8; 32]>,
+}
+
+impl FileMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.mode.is_none()
+            && self.mtime.is_none()
+            && self.sha256.is_none()
+            && self.seed.is_none()
+            && self.base_sha256.is_none()
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut flags = 0;
+        let mut fields = Vec::new();
+        if let Some(mode) = self.mode {
+            flags |= METADATA_FLAG_MODE;
+            fields.extend_from_slice(&mode.to_be_bytes());
+        }
+        if let Some(mtime) = self.mtime {
+            flags |= METADATA_FLAG_MTIME;
+            fields.extend_from_slice(&mtime.to_be_bytes());
+        }
+        if let Some(sha256) = self.sha256 {
+            flags |= METADATA_FLAG_SHA256;
+            fields.extend_from_slice(&sha256);
+        }
+        if let Some(seed) = self.seed {
+            flags |= METADATA_FLAG_SEED;
+            fields.extend_from_slice(&seed.to_be_bytes());
+        }
+        if let Some(base_sha256) = self.base_sha256 {
+            flags |= METADATA_FLAG_BASE;
+            fields.extend_from_slice(&base_sha256);
+        }
+
+        let mut bytes = vec![METADATA_MARKER, flags];
+        bytes.extend_from_slice(&fields);
+        bytes
+    }
+
+    /// Parses a metadata block starting at its marker and returns it with its length.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<(Self, usize)> {
+        let too_short = || anyhow!("Invalid packed data: truncated metadata");
+        let flags = *bytes.get(1).ok_or_else(too_short)?;
+        let mut pos = 2;
+        let mut take = |len: usize| -> Result<&[u8]> {
+            let field = bytes.get(pos..pos + len).ok_or_else(too_short)?;
+            pos += len;
+            Ok(field)
+        };
+
+        let mut metadata = FileMetadata::default();
+        if flags & METADATA_FLAG_MODE != 0 {
+            metadata.mode = Some(u32::from_be_bytes(take(4)?.try_into()?));
+        }
+        if flags & METADATA_FLAG_MTIME != 0 {
+            metadata.mtime = Some(i64::from_be_bytes(take(8)?.try_into()?));
+        }
+        if flags & METADATA_FLAG_SHA256 != 0 {
+            metadata.sha256 = Some(take(32)?.try_into()?);
+        }
+        if flags & METADATA_FLAG_SEED != 0 {
+            metadata.seed = Some(u64::from_be_bytes(take(8)?.try_into()?));
+        }
+        if flags & METADATA_FLAG_BASE != 0 {
+            metadata.base_sha256 = Some(take(32)?.try_into()?);
+        }
+        Ok((metadata, pos))
+    }
+}
+
+// Pack data: [Checksum 8B] [Filename] [\0] [Content]
+pub fn pack_data(data: &[u8], filename: &str) -> Vec<u8> {
+    pack_data_with_metadata(data, filename, &FileMetadata::default())
+}
+
+// Pack data: [Checksum 8B] [Metadata, if any] [Filename] [\0] [Content]
+pub fn pack_data_with_metadata(data: &[u8], filename: &str, metadata: &FileMetadata) -> Vec<u8> {
+    let checksum = calculate_checksum(data);
+    // Sanitize filename: remove null bytes and the metadata marker
+    let clean_filename = filename.replace(['\0', METADATA_MARKER as char], "");
+    let metadata_bytes = if metadata.is_empty() {
+        Vec::new()
+    } else {
+        metadata.to_bytes()
+    };
+
+    let mut packed = Vec::with_capacity(
+        CHECKSUM_SIZE + metadata_bytes.len() + clean_filename.len() + 1 + data.len(),
+    );
+    packed.extend_from_slice(&checksum);
+    packed.extend_from_slice(&metadata_bytes);
+    packed.extend_from_slice(clean_filename.as_bytes());
+    packed.push(0); // Null terminator
+    packed.extend_from_slice(data);
+    packed
+}
+// Unpack data: -> (Filename, Content)
+pub fn unpack_data(packed: &[u8]) -> Result<(String, Vec<u8>)> {
+    let (filename, content, _) = unpack_data_with_metadata(packed)?;
+    Ok((filename, content))
+}
+
+// Unpack data: -> (Filename, Content, Metadata)
+pub fn unpack_data_with_metadata(packed: &[u8]) -> Result<(String, Vec<u8>, FileMetadata)> {
+    if packed.len() < CHECKSUM_SIZE + 2 {
+        // Min: Checksum + 1 char + \0
+        return Err(anyhow!("Invalid packed data: too short"));
+    }
+
+    let (expected_checksum, rest) = packed.split_at(CHECKSUM_SIZE);
+    let (metadata, rest) = if rest.first() == Some(&METADATA_MARKER) {
+        let (metadata, len) = FileMetadata::from_bytes(rest)?;
+        (metadata, &rest[len..])
+    } else {
+        (FileMetadata::default(), rest)
+    };
+
+    let terminator = rest
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| anyhow!("Invalid packed data: missing filename terminator"))?;
+    let filename = String::from_utf8(rest[..terminator].to_vec())
+        .map_err(|_| anyhow!("Invalid filename: not valid UTF-8"))?;
+    let content = &rest[terminator + 1..];
+
+    verify_checksums(expected_checksum, &metadata, &sha256_digest(content))?;
+    Ok((filename, content.to_vec(), metadata))
+}
+
+/// Compares the content's SHA-256 with the checksum at the start of the packed data and with
+/// the full digest in its metadata, if any.
+pub(crate) fn verify_checksums(
+    expected_checksum: &[u8],
+    metadata: &FileMetadata,
+    actual_sha256: &[u8; 32],
+) -> Result<()> {
+    let actual_checksum = &actual_sha256[..CHECKSUM_SIZE];
+    if actual_checksum != expected_checksum {
+        return Err(anyhow!(
+            "Checksum mismatch: expected {:?}, got {:?}",
+            expected_checksum,
+            actual_checksum
+        ));
+    }
+
+    if let Some(expected_sha256) = metadata.sha256 {
+        if *actual_sha256 != expected_sha256 {
+            return Err(anyhow!(
+                "SHA-256 mismatch: expected {}, got {}",
+                hex::encode(expected_sha256),
+                hex::encode(actual_sha256)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Transfer length, packet size and volume index: the parameters chunks of one stream share.
+pub(crate) type StreamKey = (u32, u16, Option<u16>);
+
+pub(crate) fn stream_key(header: &ChunkHeader) -> StreamKey {
+    (
+        header.total,
+        header.packet_size,
+        header.volume.map(|volume| volume.index),
+    )
+}
+
+/// Rebuilds the object of one stream from its packets for a [`Reassembler`]. raptorq's
+/// [`Decoder`] keeps the object in memory; decoders that store it elsewhere, e.g. in a file as
+/// each source block is recovered, plug in with [`Reassembler::with_decoder`].
+pub trait ObjectDecoder {
+    /// What the decoder yields once it has enough packets.
+    type Object;
+    type Error;
+
+    /// Takes one packet, and returns the object once it is complete.
+    fn decode(&mut self, packet: EncodingPacket) -> Result<Option<Self::Object>, Self::Error>;
+}
+
+impl ObjectDecoder for Decoder {
+    type Object = Vec<u8>;
+    type Error = Infallible;
+
+    fn decode(&mut self, packet: EncodingPacket) -> Result<Option<Vec<u8>>, Infallible> {
+        // The object comes trimmed to the transfer length
+        Ok(Decoder::decode(self, packet))
+    }
+}
+
+/// Starts the decoder of a stream with the given parameters.
+type StartDecoder<D> =
+    Box<dyn FnMut(ObjectTransmissionInformation) -> Result<D, <D as ObjectDecoder>::Error> + Send>;
+
+/// Collects the chunks of a transfer and runs the RaptorQ decoder over them.
+///
+/// Chunks are grouped by their stream parameters, so that one misread header cannot configure
+/// the decoder for every packet that follows: it follows the parameters the most distinct
+/// chunks agree on, and starts a decoder for them with their chunks replayed whenever that
+/// changes. A stream keeps its decoder once it has one, and the packet data of its chunks
+/// then lives only in the decoder.
+///
+/// Once a stream is decoded its chunks are ignored, and the stream with the most chunks
+/// among the rest takes over. This way every file of a carousel that interleaves several
+/// files is assembled in turn.
+pub struct Reassembler<D: ObjectDecoder = Decoder> {
+    streams: BTreeMap<StreamKey, BTreeMap<u32, Chunk>>,
+    active: Option<StreamKey>,
+    /// Header of the chunk that made the active parameters win.
+    header: Option<ChunkHeader>,
+    /// Decoders of the streams that have been the active one.
+    decoders: BTreeMap<StreamKey, D>,
+    start: StartDecoder<D>,
+    /// Streams already decoded.
+    finished: BTreeSet<StreamKey>,
+    /// Indices of the chunks of the stream decoded last, reported with its header until
+    /// another stream takes over.
+    completed: Vec<u32>,
+}
+
+/// Outcome of [`Reassembler::add`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reassembly<O = Vec<u8>> {
+    /// The chunk was seen before, belongs to a stream already decoded, or fewer chunks agree
+    /// on its parameters than on the decoder's.
+    Ignored,
+    /// The chunk reached a decoder, which needs more.
+    Pending,
+    /// A decoder finished. Holds the compressed object, trimmed to the transfer length.
+    Complete(O),
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::with_decoder(|config| Ok(Decoder::new(config)))
+    }
+}
+
+impl Reassembler {
+    /// Reassembler decoding objects in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, chunk: Chunk) -> Reassembly {
+        match self.try_add(chunk) {
+            Ok(reassembly) => reassembly,
+            Err(never) => match never {},
+        }
+    }
+}
+
+impl<D: ObjectDecoder> Reassembler<D> {
+    /// Reassembler decoding each stream with the decoder `start` returns for its parameters.
+    pub fn with_decoder(
+        start: impl FnMut(ObjectTransmissionInformation) -> Result<D, D::Error> + Send + 'static,
+    ) -> Self {
+        Self {
+            streams: BTreeMap::new(),
+            active: None,
+            header: None,
+            decoders: BTreeMap::new(),
+            start: Box::new(start),
+            finished: BTreeSet::new(),
+            completed: Vec::new(),
+        }
+    }
+
+    /// Like [`Reassembler::add`], for decoders that can fail, e.g. writing to a file.
+    pub fn try_add(&mut self, chunk: Chunk) -> Result<Reassembly<D::Object>, D::Error> {
+        let key = stream_key(&chunk.header);
+        if self.finished.contains(&key) {
+            return Ok(Reassembly::Ignored);
+        }
+        let active_votes = self.active.map_or(0, |active| self.streams[&active].len());
+        let group = self.streams.entry(key).or_default();
+        let index = chunk.header.index;
+        if group.contains_key(&index) {
+            return Ok(Reassembly::Ignored);
+        }
+        let header = chunk.header.clone();
+        group.insert(index, chunk);
+        let votes = group.len();
+
+        if self.decoders.contains_key(&key) {
+            if self.active != Some(key) && votes > active_votes {
+                self.active = Some(key);
+                self.header = Some(header);
+            }
+            return self.feed(key, [index]);
+        }
+        if votes <= active_votes {
+            return Ok(Reassembly::Ignored);
+        }
+
+        // More chunks agree on these parameters than on the current ones (the first header
+        // may have been misread), so start decoding them and replay the chunks that carry them
+        let (transfer_length, packet_size, _) = key;
+        let config =
+            ObjectTransmissionInformation::with_defaults(transfer_length as u64, packet_size);
+        self.decoders.insert(key, (self.start)(config)?);
+        self.active = Some(key);
+        self.header = Some(header);
+        let indices: Vec<u32> = self.streams[&key].keys().copied().collect();
+        self.feed(key, indices)
+    }
+
+    /// Passes the packets of the given chunks of a stream to its decoder.
+    fn feed(
+        &mut self,
+        key: StreamKey,
+        indices: impl IntoIterator<Item = u32>,
+    ) -> Result<Reassembly<D::Object>, D::Error> {
+        let (Some(chunks), Some(decoder)) =
+            (self.streams.get_mut(&key), self.decoders.get_mut(&key))
+        else {
+            return Ok(Reassembly::Ignored);
+        };
+        for index in indices {
+            let Some(chunk) = chunks.get_mut(&index) else {
+                continue;
+            };
+            let packet = EncodingPacket::deserialize(&mem::take(&mut chunk.data));
+            if let Some(object) = decoder.decode(packet)? {
+                self.finish(key);
+                return Ok(Reassembly::Complete(object));
+            }
+        }
+        Ok(Reassembly::Pending)
+    }
+
+    /// Makes way for the next file; the header and count stay for naming this one.
+    fn finish(&mut self, key: StreamKey) {
+        let chunks = self.streams.remove(&key).unwrap_or_default();
+        if self.active != Some(key) {
+            self.header = chunks.values().next().map(|chunk| chunk.header.clone());
+        }
+        self.completed = chunks.into_keys().collect();
+        self.decoders.remove(&key);
+        self.finished.insert(key);
+        self.active = None;
+    }
+
+    /// Prepares for the transfer a start marker announces. A stream with its parameters that
+    /// was already decoded is decoded again, since a new transfer of the same length may
+    /// carry a different file.
+    pub fn start_transfer(&mut self, sync: &SyncInfo) {
+        self.finished.remove(&(
+            sync.transfer_length,
+            sync.packet_size,
+            sync.volume.map(|volume| volume.index),
+        ));
+    }
+
+    /// Header of a chunk with the parameters the decoder follows, once there is one.
+    pub fn header(&self) -> Option<&ChunkHeader> {
+        self.header.as_ref()
+    }
+
+    /// Indices of the chunks received for the parameters of [`header`](Self::header), in
+    /// ascending order.
+    pub fn received(&self) -> Vec<u32> {
+        match self.active {
+            Some(active) => self.streams[&active].keys().copied().collect(),
+            None => self.completed.clone(),
+        }
+    }
+
+    /// Number of chunks received for the parameters of [`header`](Self::header).
+    pub fn received_count(&self) -> usize {
+        self.active
+            .map_or(self.completed.len(), |active| self.streams[&active].len())
+    }
+
+    /// Transfer length and packet size of the streams already decoded.
+    pub fn finished_streams(&self) -> Vec<(u32, u16)> {
+        let mut finished: Vec<(u32, u16)> = self
+            .finished
+            .iter()
+            .map(|&(total, packet_size, _)| (total, packet_size))
+            .collect();
+        finished.dedup();
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let data = b"Some random data";
+        let filename = "example.file";
+
+        let packed = pack_data(data, filename);
+        let (name, content) = unpack_data(&packed).unwrap();
+
+        assert_eq!(name, filename);
+        assert_eq!(content, data);
+    }
+
+    #[test]
+    fn test_pack_unpack_metadata() {
+        let data = b"#!/bin/sh\necho hi\n";
+        let metadata = FileMetadata {
+            mode: Some(0o755),
+            mtime: Some(1_700_000_000),
+            sha256: Some(sha256_digest(data)),
+            seed: Some(42),
+            base_sha256: Some([7; 32]),
+        };
+
+        let packed = pack_data_with_metadata(data, "run.sh", &metadata);
+        let (name, content, unpacked) = unpack_data_with_metadata(&packed).unwrap();
+        assert_eq!(name, "run.sh");
+        assert_eq!(content, data);
+        assert_eq!(unpacked, metadata);
+
+        let tampered = FileMetadata {
+            sha256: Some([0; 32]),
+            ..metadata
+        };
+        let packed = pack_data_with_metadata(data, "run.sh", &tampered);
+        assert!(unpack_data_with_metadata(&packed).is_err());
+
+        // Streams without a metadata block read back with empty metadata
+        let packed = pack_data(data, "run.sh");
+        let (_, content, unpacked) = unpack_data_with_metadata(&packed).unwrap();
+        assert_eq!(content, data);
+        assert!(unpacked.is_empty());
+    }
+
+    #[test]
+    fn test_qr_payload_autodetect() {
+        let chunk = Chunk {
+            header: ChunkHeader {
+                version: 1,
+                total: 1234,
+                index: 7,
+                packet_size: 90,
+                source_symbols: None,
+                original_size: None,
+                volume: None,
+            },
+            data: (0..=255).collect(),
+        };
+
+        for encoding in [PayloadEncoding::Base45, PayloadEncoding::Binary] {
+            let payload = chunk.to_qr_payload(encoding).unwrap();
+            let parsed = Chunk::from_qr_payload(&payload).unwrap();
+            assert_eq!(parsed.header.index, 7);
+            assert_eq!(parsed.data, chunk.data);
+        }
+    }
+
+    #[test]
+    fn test_wire_format_detect() {
+        let packed = pack_data(b"detect", "detect.txt");
+        let chunk = Chunk {
+            header: ChunkHeader {
+                version: CHUNK_VERSION,
+                total: packed.len() as u32,
+                index: 0,
+                packet_size: packed.len() as u16,
+                source_symbols: Some(1),
+                original_size: Some(6),
+                volume: None,
+            },
+            data: packed,
+        };
+        for encoding in [PayloadEncoding::Base45, PayloadEncoding::Binary] {
+            let payload = chunk.to_qr_payload(encoding).unwrap();
+            assert_eq!(WireFormat::detect(&payload), Some(WireFormat::Native));
+        }
+
+        let ur = crate::ur::UrEncoder::new(&[7; 100], 30).unwrap().part(1);
+        assert_eq!(WireFormat::detect(ur.as_bytes()), Some(WireFormat::Ur));
+        assert!(Chunk::from_qr_payload(ur.as_bytes()).is_err());
+
+        for foreign in [
+            &b"https://example.com/"[..],
+            b"HELLO WORLD 123",
+            b"\x00\xff",
+        ] {
+            assert_eq!(WireFormat::detect(foreign), None);
+        }
+    }
+
+    #[test]
+    #[cfg(any(feature = "decode", feature = "wasm"))]
+    fn test_describe_unfamiliar() {
+        assert_eq!(
+            describe_unfamiliar(b"WIFI:S:home;;"),
+            "text \"WIFI:S:home;;\""
+        );
+    }
+
+    #[test]
+    fn test_header_versions() {
+        let v1 = ChunkHeader {
+            version: 1,
+            total: 1234,
+            index: 7,
+            packet_size: 90,
+            source_symbols: None,
+            original_size: None,
+            volume: None,
+        };
+        let bytes = v1.to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE_V1);
+        let (parsed, len) = ChunkHeader::from_bytes(&bytes).unwrap();
+        assert_eq!(len, HEADER_SIZE_V1);
+        assert_eq!(parsed.original_size, None);
+        assert_eq!(parsed.source_symbol_count(), 14);
+
+        let v2 = ChunkHeader {
+            version: CHUNK_VERSION,
+            source_symbols: Some(14),
+            original_size: Some(5000),
+            ..v1
+        };
+        let bytes = v2.to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE);
+        let (parsed, len) = ChunkHeader::from_bytes(&bytes).unwrap();
+        assert_eq!(len, HEADER_SIZE);
+        assert_eq!(parsed.source_symbols, Some(14));
+        assert_eq!(parsed.original_size, Some(5000));
+
+        assert!(ChunkHeader::from_bytes(&bytes[..HEADER_SIZE_V1]).is_err());
+        assert!(ChunkHeader::from_bytes(&[3; HEADER_SIZE]).is_err());
+
+        let volume = Volume { index: 2, count: 5 };
+        let v3 = ChunkHeader {
+            version: VOLUME_CHUNK_VERSION,
+            volume: Some(volume),
+            ..v2
+        };
+        let bytes = v3.to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE_V3);
+        let (parsed, len) = ChunkHeader::from_bytes(&bytes).unwrap();
+        assert_eq!(len, HEADER_SIZE_V3);
+        assert_eq!(parsed.original_size, Some(5000));
+        assert_eq!(parsed.volume, Some(volume));
+        assert_eq!(volume.part_name("backup.tar"), "backup.tar.003");
+        assert!(ChunkHeader::from_bytes(&[4; HEADER_SIZE_V3]).is_err());
+    }
+
+    #[test]
+    fn test_reassembler_follows_majority() {
+        let object: Vec<u8> = (0..2000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let header = |index: usize, total: u32| ChunkHeader {
+            version: CHUNK_VERSION,
+            total,
+            index: index as u32,
+            packet_size: 100,
+            source_symbols: Some(20),
+            original_size: Some(2000),
+            volume: None,
+        };
+        let chunks: Vec<Chunk> = raptorq::Encoder::with_defaults(&object, 100)
+            .get_encoded_packets(5)
+            .into_iter()
+            .enumerate()
+            .map(|(i, packet)| Chunk {
+                header: header(i, 2000),
+                data: packet.serialize(),
+            })
+            .collect();
+
+        let mut reassembler = Reassembler::new();
+        // A misread transfer length configures the decoder until more chunks disagree with it
+        let misread = Chunk {
+            header: header(0, 9999),
+            data: chunks[0].data.clone(),
+        };
+        assert_eq!(reassembler.add(misread), Reassembly::Pending);
+        assert_eq!(reassembler.add(chunks[0].clone()), Reassembly::Ignored);
+        assert_eq!(reassembler.add(chunks[3].clone()), Reassembly::Pending);
+        assert_eq!(reassembler.header().unwrap().total, 2000);
+        assert_eq!(reassembler.add(chunks[3].clone()), Reassembly::Ignored);
+        assert_eq!(reassembler.received(), vec![0, 3]);
+
+        let complete =
+            chunks
+                .iter()
+                .skip(4)
+                .find_map(|chunk| match reassembler.add(chunk.clone()) {
+                    Reassembly::Complete(data) => Some(data),
+                    _ => None,
+                });
+        assert_eq!(complete, Some(object));
+        let count = reassembler.received_count();
+        assert_eq!(reassembler.add(chunks[1].clone()), Reassembly::Ignored);
+        assert_eq!(reassembler.received_count(), count);
+    }
+
+    #[test]
+    fn test_reassembler_decodes_interleaved_streams() {
+        let stream = |len: u32, packet_size: u16| {
+            let object: Vec<u8> = (0..len).map(|i| (i * 13 % 251) as u8).collect();
+            let chunks: Vec<Chunk> = raptorq::Encoder::with_defaults(&object, packet_size)
+                .get_encoded_packets(5)
+                .into_iter()
+                .enumerate()
+                .map(|(i, packet)| Chunk {
+                    header: ChunkHeader {
+                        version: CHUNK_VERSION,
+                        total: len,
+                        index: i as u32,
+                        packet_size,
+                        source_symbols: Some(len.div_ceil(packet_size as u32)),
+                        original_size: Some(len),
+                        volume: None,
+                    },
+                    data: packet.serialize(),
+                })
+                .collect();
+            (object, chunks)
+        };
+        let (first, first_chunks) = stream(1500, 100);
+        let (second, second_chunks) = stream(900, 60);
+
+        // A carousel alternating between two files: the second file's chunks wait while the
+        // first is decoded, then its decoder catches up on them
+        let mut reassembler = Reassembler::new();
+        let mut decoded = Vec::new();
+        for (a, b) in first_chunks.iter().zip(&second_chunks) {
+            for chunk in [a, b] {
+                if let Reassembly::Complete(object) = reassembler.add(chunk.clone()) {
+                    decoded.push(object);
+                }
+            }
+        }
+        assert_eq!(decoded, vec![first, second]);
+
+        // Progress still describes the file just decoded
+        let header = reassembler.header().unwrap();
+        assert_eq!(header.total, 900);
+        assert!(reassembler.received_count() >= header.source_symbol_count() as usize);
+        assert_eq!(reassembler.received().len(), reassembler.received_count());
+
+        // Chunks of a decoded stream are ignored until a start marker announces it again
+        assert_eq!(
+            reassembler.add(second_chunks[0].clone()),
+            Reassembly::Ignored
+        );
+        reassembler.start_transfer(&SyncInfo {
+            frames: 0,
+            interval_ms: 0,
+            transfer_length: 1500,
+            packet_size: 100,
+            source_symbols: 15,
+            original_size: 1500,
+            volume: None,
+        });
+        assert_eq!(
+            reassembler.add(first_chunks[0].clone()),
+            Reassembly::Pending
+        );
+        assert_eq!(reassembler.header().unwrap().total, 1500);
+        assert_eq!(reassembler.received(), vec![0]);
+        assert_eq!(reassembler.received_count(), 1);
+    }
+}
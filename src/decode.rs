@@ -1,195 +1,1912 @@
 use anyhow::{anyhow, Result};
 use image::codecs::gif::GifDecoder;
 use image::{AnimationDecoder, DynamicImage};
-use raptorq::{Decoder, EncodingPacket, ObjectTransmissionInformation};
-use std::collections::HashMap;
+use raptorq::{partition, EncodingPacket, ObjectTransmissionInformation, SourceBlockDecoder};
+use sha2::{Digest, Sha256};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::BufReader;
-use std::path::Path;
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::chunk::{decompress, unpack_data, Chunk};
-use crate::qr::{decode_qr_from_dynamic_image, QR_FILE_EXTENSION};
+use crate::cancel::check_cancelled;
+use crate::chunk::{
+    decompress, decompress_reader, from_single_code_payload, sha256_digest, unpack_data_to,
+    unpack_data_with_metadata, Chunk, FileMetadata, SyncInfo, Volume, WireFormat,
+};
+use crate::core::{
+    describe_unfamiliar, stream_key, ObjectDecoder, Reassembler, Reassembly, StreamKey,
+};
+use crate::delta;
+use crate::qr::{
+    decode_all_qr_from_dynamic_image, decode_all_qr_thorough, decode_color_planes,
+    enhance_for_detection, is_blank_frame, FrameSignature, RoiTracker, ScanEffort,
+    QR_FILE_EXTENSION,
+};
+use crate::ur::{UrDecoder, UR_FILENAME};
 
 pub struct DecodeResult {
     pub original_filename: String,
     pub output_path: String,
     pub num_chunks: usize,
+    /// File attributes carried by the stream and applied to the output file.
+    pub metadata: FileMetadata,
+    /// SHA-256 of the decoded file as lowercase hex, comparable with `sha256sum` output.
+    pub sha256: String,
 }
 
-struct RaptorQStreamDecoder {
-    chunks: HashMap<u32, Chunk>,
-    decoder: Option<Decoder>,
+/// Settings for the decode entry points that write a file, built up from the defaults.
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    restore_metadata: bool,
+    skip_duplicate_frames: bool,
+    recursive: bool,
+    glob: Option<GlobPattern>,
+    enhance: bool,
+    effort: ScanEffort,
+    base: Option<PathBuf>,
+    frames: FrameRange,
+    pub(crate) cancel: Option<Arc<AtomicBool>>,
 }
 
-impl RaptorQStreamDecoder {
-    fn new() -> Self {
+impl Default for DecodeOptions {
+    fn default() -> Self {
         Self {
-            chunks: HashMap::new(),
-            decoder: None,
+            restore_metadata: true,
+            skip_duplicate_frames: true,
+            recursive: false,
+            glob: None,
+            enhance: false,
+            effort: ScanEffort::default(),
+            base: None,
+            frames: FrameRange::default(),
+            cancel: None,
         }
     }
+}
 
-    fn add_chunk(&mut self, chunk: Chunk) -> Result<Option<(String, Vec<u8>)>> {
-        if self.decoder.is_none() {
-            let config = ObjectTransmissionInformation::with_defaults(
-                chunk.header.total as u64,
-                chunk.header.packet_size,
-            );
-            self.decoder = Some(Decoder::new(config));
-        }
-
-        if !self.chunks.contains_key(&chunk.header.index) {
-            let index = chunk.header.index;
-            let total_len = chunk.header.total as usize;
-            let packet_data = chunk.data.clone();
-            self.chunks.insert(index, chunk);
-
-            if let Some(dec) = &mut self.decoder {
-                let packet = EncodingPacket::deserialize(&packet_data);
-                if let Some(result_data) = dec.decode(packet) {
-                    let mut final_data = result_data;
-                    final_data.truncate(total_len);
-                    let packed = decompress(&final_data)?;
-                    return Ok(Some(unpack_data(&packed)?));
+impl DecodeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to apply the permissions and modification time carried by the stream
+    /// to the output file (default: true).
+    pub fn restore_metadata(mut self, restore_metadata: bool) -> Self {
+        self.restore_metadata = restore_metadata;
+        self
+    }
+
+    /// Whether to skip QR detection on frames nearly identical to the previous one, as in
+    /// screen recordings that show each code for many frames (default: true).
+    pub fn skip_duplicate_frames(mut self, skip_duplicate_frames: bool) -> Self {
+        self.skip_duplicate_frames = skip_duplicate_frames;
+        self
+    }
+
+    /// Whether to also read images from subdirectories of an input directory (default: false).
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Only read the images of an input directory whose path matches `glob`.
+    pub fn glob(mut self, glob: GlobPattern) -> Self {
+        self.glob = Some(glob);
+        self
+    }
+
+    /// Whether to retry images where no code was found after cleaning them up with
+    /// [`enhance_for_detection`], for photos of paper or screens (default: false).
+    pub fn enhance(mut self, enhance: bool) -> Self {
+        self.enhance = enhance;
+        self
+    }
+
+    /// How hard to try on images where no code is read at first (default: normal).
+    pub fn effort(mut self, effort: ScanEffort) -> Self {
+        self.effort = effort;
+        self
+    }
+
+    /// Earlier version of the file, which a transfer sent as a delta is applied to. Such a
+    /// transfer fails to decode without it; others ignore it.
+    pub fn base(mut self, base: impl Into<PathBuf>) -> Self {
+        self.base = Some(base.into());
+        self
+    }
+
+    /// Only scan the frames of a GIF from `start` on, e.g. where the transfer begins in a long
+    /// screen recording.
+    pub fn start(mut self, start: FramePosition) -> Self {
+        self.frames.start = Some(start);
+        self
+    }
+
+    /// Stop scanning a GIF at `end`; the frame there is left out.
+    pub fn end(mut self, end: FramePosition) -> Self {
+        self.frames.end = Some(end);
+        self
+    }
+
+    /// Flag checked between images; once set, the decode stops with [`Cancelled`](crate::Cancelled).
+    pub fn cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+}
+
+/// Point in an animation: a frame, counting from 0, or a time from its start.
+///
+/// Parses from a time such as `1:30`, `00:01:30.5` or `90s`, or a plain frame number such
+/// as `120`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePosition {
+    Frame(usize),
+    Time(Duration),
+}
+
+impl FramePosition {
+    /// Whether frame `index`, shown from `at` on, comes before this position.
+    fn is_after(self, index: usize, at: Duration) -> bool {
+        match self {
+            FramePosition::Frame(frame) => index < frame,
+            FramePosition::Time(time) => at < time,
+        }
+    }
+}
+
+impl FromStr for FramePosition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "Invalid position '{}': expected a time such as 1:30 or 90s, or a frame number",
+                s
+            )
+        };
+        let s = s.trim();
+        let seconds = if let Some(seconds) = s.strip_suffix('s') {
+            seconds.parse::<f64>().map_err(|_| invalid())?
+        } else if s.contains(':') {
+            // [HOURS:]MINUTES:SECONDS, where only the seconds may have a fraction
+            let parts: Vec<&str> = s.split(':').collect();
+            let (seconds, larger) = parts.split_last().ok_or_else(invalid)?;
+            if larger.len() > 2 {
+                return Err(invalid());
+            }
+            let mut total = seconds.parse::<f64>().map_err(|_| invalid())?;
+            for (part, unit) in larger.iter().rev().zip([60.0, 3600.0]) {
+                total += part.parse::<u32>().map_err(|_| invalid())? as f64 * unit;
+            }
+            total
+        } else {
+            return s.parse().map(FramePosition::Frame).map_err(|_| invalid());
+        };
+        Duration::try_from_secs_f64(seconds)
+            .map(FramePosition::Time)
+            .map_err(|_| invalid())
+    }
+}
+
+/// Part of an animation a decode reads.
+#[derive(Debug, Clone, Copy, Default)]
+struct FrameRange {
+    start: Option<FramePosition>,
+    end: Option<FramePosition>,
+}
+
+impl FrameRange {
+    fn before_start(&self, index: usize, at: Duration) -> bool {
+        self.start.is_some_and(|start| start.is_after(index, at))
+    }
+
+    fn past_end(&self, index: usize, at: Duration) -> bool {
+        self.end.is_some_and(|end| !end.is_after(index, at))
+    }
+}
+
+/// Shell-style pattern selecting images inside an input directory, e.g. `page-*/img_*.jpg`.
+///
+/// `*` and `?` match within one path component and a `**` component matches any number of
+/// directories. Patterns with a `/` are matched against the path relative to the input
+/// directory; patterns without one are matched against the file name only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobPattern {
+    components: Vec<String>,
+}
+
+impl FromStr for GlobPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let components: Vec<String> = s
+            .split('/')
+            .filter(|part| !part.is_empty() && *part != ".")
+            .map(str::to_string)
+            .collect();
+        if components.is_empty() {
+            return Err(anyhow!("Invalid glob pattern: {:?}", s));
+        }
+        Ok(Self { components })
+    }
+}
+
+impl GlobPattern {
+    /// Whether `relative`, a path below the input directory, is selected by the pattern.
+    pub fn matches(&self, relative: &Path) -> bool {
+        let parts: Vec<String> = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        match self.components.as_slice() {
+            [name] => parts.last().is_some_and(|part| wildcard_match(name, part)),
+            components => components_match(components, &parts),
+        }
+    }
+}
+
+fn components_match(pattern: &[String], parts: &[String]) -> bool {
+    match pattern.split_first() {
+        None => parts.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=parts.len()).any(|skip| components_match(rest, &parts[skip..]))
+        }
+        Some((first, rest)) => parts.split_first().is_some_and(|(part, parts)| {
+            wildcard_match(first, part) && components_match(rest, parts)
+        }),
+    }
+}
+
+/// Matches `text` against a pattern where `*` is any run of characters and `?` any one.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// A file recovered from the stream, before it is written to its destination.
+struct DecodedFile {
+    filename: String,
+    data: FileData,
+    metadata: FileMetadata,
+    /// SHA-256 of the contents.
+    sha256: [u8; 32],
+}
+
+/// Bytes of a decoded object or file: in memory, or in a temporary file in the output
+/// directory when decoding to disk, so that transfers larger than memory can be received.
+enum FileData {
+    Memory(Vec<u8>),
+    Spooled(TempFile),
+}
+
+impl FileData {
+    /// Empty data, kept in a temporary file in `spool_dir` if one is given.
+    fn new(spool_dir: Option<&Path>) -> Result<Self> {
+        Ok(match spool_dir {
+            Some(dir) => FileData::Spooled(TempFile::create(dir)?),
+            None => FileData::Memory(Vec::new()),
+        })
+    }
+
+    fn len(&self) -> Result<u64> {
+        Ok(match self {
+            FileData::Memory(data) => data.len() as u64,
+            FileData::Spooled(temp) => temp.file.metadata()?.len(),
+        })
+    }
+
+    fn into_vec(self) -> Result<Vec<u8>> {
+        match self {
+            FileData::Memory(data) => Ok(data),
+            FileData::Spooled(temp) => Ok(fs::read(&temp.path)?),
+        }
+    }
+
+    /// Writes `bytes` at `offset`, growing the data as needed.
+    fn write_at(&mut self, offset: u64, bytes: &[u8]) -> Result<()> {
+        match self {
+            FileData::Memory(data) => {
+                let start = offset as usize;
+                let end = start + bytes.len();
+                if data.len() < end {
+                    data.resize(end, 0);
                 }
+                data[start..end].copy_from_slice(bytes);
+            }
+            FileData::Spooled(temp) => {
+                let mut file = &temp.file;
+                file.seek(SeekFrom::Start(offset))?;
+                file.write_all(bytes)?;
             }
         }
-        Ok(None)
+        Ok(())
+    }
+
+    /// Appends `other`, e.g. the next volume of a split file.
+    fn append(&mut self, other: FileData) -> Result<()> {
+        match self {
+            FileData::Memory(data) => data.extend(other.into_vec()?),
+            FileData::Spooled(temp) => {
+                let mut file = &temp.file;
+                file.seek(SeekFrom::End(0))?;
+                match other {
+                    FileData::Memory(data) => file.write_all(&data)?,
+                    FileData::Spooled(other) => {
+                        let mut source = &other.file;
+                        source.seek(SeekFrom::Start(0))?;
+                        io::copy(&mut source, &mut file)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn sha256(&self) -> Result<[u8; 32]> {
+        match self {
+            FileData::Memory(data) => Ok(sha256_digest(data)),
+            FileData::Spooled(temp) => {
+                let mut file = &temp.file;
+                file.seek(SeekFrom::Start(0))?;
+                let mut hasher = Sha256::new();
+                io::copy(&mut file, &mut hasher)?;
+                Ok(hasher.finalize().into())
+            }
+        }
+    }
+}
+
+/// A file created next to the output while decoding, removed again unless it is persisted.
+struct TempFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl TempFile {
+    fn create(dir: &Path) -> Result<Self> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!(".fountain-{}-{}.part", std::process::id(), id));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| anyhow!("Failed to create {}: {}", path.display(), e))?;
+        Ok(Self { path, file })
+    }
+
+    /// Moves the file to `path`, where it stays.
+    fn persist(mut self, path: &Path) -> Result<()> {
+        fs::rename(&self.path, path)?;
+        self.path = PathBuf::new();
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Outcome of a successful verification: the stream decodes and its checksum matches.
+pub struct VerifyReport {
+    pub original_filename: String,
+    pub original_size: usize,
+    pub num_chunks: usize,
+    /// SHA-256 of the decoded file as lowercase hex.
+    pub sha256: String,
+}
+
+/// What the chunk headers of an encoded artifact say about it.
+pub struct StreamInfo {
+    /// Chunk header version (1, 2 or 3, all RaptorQ; 3 marks one volume of a split file).
+    pub format_version: u8,
+    /// Length of the compressed, packed object carried by the stream.
+    pub transfer_length: u32,
+    pub packet_size: u16,
+    /// Number of source packets; slightly more than this many chunks are needed to decode.
+    pub source_packets: u32,
+    /// Sorted indices of the chunks found.
+    pub present_indices: Vec<u32>,
+    /// Indices below the highest one found that are absent.
+    pub missing_indices: Vec<u32>,
+    /// Chunks whose headers disagree with the majority, e.g. misreads or another stream.
+    pub foreign_chunks: usize,
+    /// Original file size from the chunk headers; streams written before header version 2
+    /// do not carry it.
+    pub original_size: Option<u32>,
+    /// Embedded filename and original size, known only when the chunks found are enough to decode.
+    pub original: Option<(String, usize)>,
+    /// Volume of a split file the stream carries; the embedded filename and size are the volume's.
+    pub volume: Option<Volume>,
+    /// Seed the repair packets were chosen with, known only when the chunks found are enough
+    /// to decode and the encoder was given one.
+    pub seed: Option<u64>,
+    /// Start marker shown before the data frames, when the encoder added sync frames.
+    pub sync: Option<SyncInfo>,
+}
+
+/// What the decoder made of one frame of an animation, passed to the callback of
+/// [`decode_from_gif_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameEvent {
+    /// Position of the frame among those read, counting from 0; with
+    /// [`DecodeOptions::start`], the frames skipped are not counted.
+    pub index: usize,
+    /// Name of the frame in log messages, e.g. `frame 12`.
+    pub label: String,
+    pub outcome: FrameOutcome,
+}
+
+/// Whether a frame was scanned, and what it held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameOutcome {
+    /// The frame could not be read.
+    Unreadable,
+    /// Not scanned, as it looks the same as the frame before.
+    Duplicate,
+    /// Not scanned, as it is a single flat colour.
+    Blank,
+    /// Scanned without finding a QR code.
+    NoCode,
+    /// `codes` QR codes were found, `new` of which no earlier frame held; a new code is
+    /// usually a chunk the transfer did not have yet.
+    Codes { codes: usize, new: usize },
+}
+
+/// Decodes a RaptorQ object one source block at a time. Each block is stored as soon as it is
+/// recovered and its packets are dropped, so only incomplete blocks are held in memory.
+struct BlockDecoder {
+    transfer_length: u64,
+    /// Decoders of the blocks still incomplete, by source block number.
+    blocks: Vec<Option<SourceBlockDecoder>>,
+    /// Offset of each block within the object.
+    offsets: Vec<u64>,
+    object: FileData,
+}
+
+impl BlockDecoder {
+    fn new(config: ObjectTransmissionInformation, object: FileData) -> Self {
+        // Blocks are laid out as the encoder partitions the object (RFC 6330, section 4.4.1.2)
+        let symbol_size = config.symbol_size() as u64;
+        let symbols = config.transfer_length().div_ceil(symbol_size) as u32;
+        let (long_symbols, short_symbols, long_blocks, short_blocks) =
+            partition(symbols, config.source_blocks());
+        let block_symbols = std::iter::repeat_n(long_symbols, long_blocks as usize)
+            .chain(std::iter::repeat_n(short_symbols, short_blocks as usize));
+
+        let mut blocks = Vec::new();
+        let mut offsets = Vec::new();
+        let mut offset = 0;
+        for (number, symbols) in block_symbols.enumerate() {
+            let length = symbols as u64 * symbol_size;
+            blocks.push(Some(SourceBlockDecoder::new2(
+                number as u8,
+                &config,
+                length,
+            )));
+            offsets.push(offset);
+            offset += length;
+        }
+
+        Self {
+            transfer_length: config.transfer_length(),
+            blocks,
+            offsets,
+            object,
+        }
+    }
+
+    fn stored_blocks(&self) -> usize {
+        self.blocks.iter().filter(|block| block.is_none()).count()
+    }
+}
+
+impl ObjectDecoder for BlockDecoder {
+    type Object = FileData;
+    type Error = anyhow::Error;
+
+    fn decode(&mut self, packet: EncodingPacket) -> Result<Option<FileData>> {
+        let number = packet.payload_id().source_block_number() as usize;
+        let Some(Some(block)) = self.blocks.get_mut(number) else {
+            return Ok(None);
+        };
+        let Some(data) = block.decode(std::iter::once(packet)) else {
+            return Ok(None);
+        };
+
+        self.blocks[number] = None;
+        // The last block is padded to a whole number of symbols
+        let offset = self.offsets[number];
+        let len = data
+            .len()
+            .min(self.transfer_length.saturating_sub(offset) as usize);
+        self.object.write_at(offset, &data[..len])?;
+        log::debug!(
+            "Source block {} recovered ({}/{} stored)",
+            number + 1,
+            self.stored_blocks(),
+            self.blocks.len()
+        );
+        if !self.blocks.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+        // The reassembler drops a decoder once it yields its object
+        Ok(Some(std::mem::replace(
+            &mut self.object,
+            FileData::Memory(Vec::new()),
+        )))
+    }
+}
+
+/// Reassembler writing recovered blocks to `spool_dir`, or keeping them in memory.
+fn stream_reassembler(spool_dir: Option<&Path>) -> Reassembler<BlockDecoder> {
+    let spool_dir = spool_dir.map(Path::to_path_buf);
+    Reassembler::with_decoder(move |config| {
+        Ok(BlockDecoder::new(
+            config,
+            FileData::new(spool_dir.as_deref())?,
+        ))
+    })
+}
+
+/// Describes how many chunks are still needed and which indices have not been seen,
+/// in the form `fountain-encode --only-chunks` accepts.
+fn missing_report(stream: &Reassembler<BlockDecoder>) -> String {
+    let Some(header) = stream.header() else {
+        return String::new();
+    };
+    let received = stream.received();
+    let highest = received.last().copied().unwrap_or(0);
+    let missing: Vec<String> = (0..highest)
+        .filter(|i| received.binary_search(i).is_err())
+        .map(|i| i.to_string())
+        .collect();
+
+    let mut report = format!(
+        "Found {} chunk(s), at least {} needed.",
+        received.len(),
+        header.source_symbol_count()
+    );
+    if !missing.is_empty() {
+        report.push_str(&format!(" Missing chunk indices: {}", missing.join(",")));
+    }
+    report
+}
+
+/// Decodes a file sent whole or split into volumes, with one stream decoder per volume.
+struct VolumeDecoder {
+    /// Stream decoders by volume index; chunks of a file sent whole use `None`.
+    streams: BTreeMap<Option<u16>, Reassembler<BlockDecoder>>,
+    /// Volumes recovered so far, by index.
+    decoded: BTreeMap<u16, DecodedFile>,
+    /// Number of volumes, as announced by the latest volume chunk.
+    count: Option<u16>,
+    /// Directory where decoded data is written, or `None` to keep it in memory.
+    spool_dir: Option<PathBuf>,
+}
+
+impl VolumeDecoder {
+    fn new(spool_dir: Option<&Path>) -> Self {
+        Self {
+            streams: BTreeMap::new(),
+            decoded: BTreeMap::new(),
+            count: None,
+            spool_dir: spool_dir.map(Path::to_path_buf),
+        }
+    }
+
+    /// Returns the file once it, or every one of its volumes, has been decoded.
+    fn add_chunk(&mut self, chunk: Chunk) -> Result<Option<DecodedFile>> {
+        let volume = chunk.header.volume;
+        if let Some(volume) = volume {
+            if self.decoded.contains_key(&volume.index) {
+                return Ok(None);
+            }
+            self.count = Some(volume.count);
+        }
+        let stream = self
+            .streams
+            .entry(volume.map(|volume| volume.index))
+            .or_insert_with(|| stream_reassembler(self.spool_dir.as_deref()));
+        let Reassembly::Complete(object) = stream.try_add(chunk)? else {
+            return Ok(None);
+        };
+        let decoded = finish_raptorq(object)?;
+        let Some(volume) = volume else {
+            return Ok(Some(decoded));
+        };
+
+        log::info!("Volume {}/{} decoded", volume.index + 1, volume.count);
+        self.decoded.insert(volume.index, decoded);
+        if !(0..volume.count).all(|index| self.decoded.contains_key(&index)) {
+            return Ok(None);
+        }
+        let mut parts = (0..volume.count).filter_map(|index| self.decoded.remove(&index));
+        let Some(mut file) = parts.next() else {
+            return Ok(None);
+        };
+        for part in parts {
+            file.data.append(part.data)?;
+        }
+        // Each volume was checked against its own digest; report the whole file's
+        file.sha256 = file.data.sha256()?;
+        file.metadata.sha256 = Some(file.sha256);
+        Ok(Some(file))
     }
 
     fn num_chunks(&self) -> usize {
-        self.chunks.len()
+        self.streams.values().map(Reassembler::received_count).sum()
+    }
+
+    /// Called when the input runs out before the whole file is decoded. A single complete
+    /// volume is returned on its own under its part name, e.g. when each volume was captured
+    /// in a separate session; otherwise the error lists what is missing.
+    fn finish(mut self, items: usize) -> Result<(DecodedFile, usize)> {
+        let num_chunks = self.num_chunks();
+        if num_chunks == 0 {
+            return Err(anyhow!("No valid QR chunks found"));
+        }
+        if self.decoded.len() == 1 {
+            if let (Some((index, mut part)), Some(count)) = (self.decoded.pop_first(), self.count) {
+                log::warn!(
+                    "Only volume {}/{} of {} decoded; decode all volumes at once to get the file",
+                    index + 1,
+                    count,
+                    part.filename
+                );
+                part.filename = Volume { index, count }.part_name(&part.filename);
+                return Ok((part, num_chunks));
+            }
+        }
+
+        Err(anyhow!(
+            "Could not decode with RaptorQ (insufficient packets after {} items). {}",
+            items,
+            self.missing_report()
+        ))
+    }
+
+    fn missing_report(&self) -> String {
+        let Some(count) = self.count else {
+            return self
+                .streams
+                .get(&None)
+                .map(missing_report)
+                .unwrap_or_default();
+        };
+        (0..count)
+            .filter(|index| !self.decoded.contains_key(index))
+            .map(|index| match self.streams.get(&Some(index)) {
+                Some(stream) => {
+                    format!("Volume {}/{}: {}", index + 1, count, missing_report(stream))
+                }
+                None => format!("Volume {}/{}: no chunks found.", index + 1, count),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Decompresses and unpacks a decoded object. A spooled object is unpacked into another
+/// temporary file next to it, streaming, so neither has to fit in memory.
+fn finish_raptorq(object: FileData) -> Result<DecodedFile> {
+    match object {
+        FileData::Memory(object) => {
+            let packed = decompress(&object)?;
+            let (filename, data, metadata) = unpack_data_with_metadata(&packed)?;
+            Ok(DecodedFile {
+                filename,
+                sha256: sha256_digest(&data),
+                data: FileData::Memory(data),
+                metadata,
+            })
+        }
+        FileData::Spooled(object) => {
+            let mut reader = &object.file;
+            reader.seek(SeekFrom::Start(0))?;
+            let dir = object.path.parent().unwrap_or(Path::new("."));
+            let output = TempFile::create(dir)?;
+            let packed = BufReader::new(decompress_reader(BufReader::new(reader))?);
+            let (filename, metadata, sha256) =
+                unpack_data_to(packed, BufWriter::new(&output.file))?;
+            Ok(DecodedFile {
+                filename,
+                data: FileData::Spooled(output),
+                metadata,
+                sha256,
+            })
+        }
     }
 }
 
+/// Rebuilds the file from `base` when the transfer is a delta; other files pass through.
+fn resolve_delta(decoded: DecodedFile, base: Option<&Path>) -> Result<DecodedFile> {
+    let Some(base_sha256) = decoded.metadata.base_sha256 else {
+        return Ok(decoded);
+    };
+    let base_path = base.ok_or_else(|| {
+        anyhow!(
+            "{} was sent as a delta against a file with SHA-256 {}; decode it with that file as the base",
+            decoded.filename,
+            hex::encode(base_sha256)
+        )
+    })?;
+    let base = fs::read(base_path)?;
+    if sha256_digest(&base) != base_sha256 {
+        return Err(anyhow!(
+            "Base {} is not the file the delta was made against (SHA-256 {})",
+            base_path.display(),
+            hex::encode(base_sha256)
+        ));
+    }
+    let data = delta::apply(&base, &decoded.data.into_vec()?)?;
+    Ok(DecodedFile {
+        filename: decoded.filename,
+        sha256: sha256_digest(&data),
+        data: FileData::Memory(data),
+        metadata: decoded.metadata,
+    })
+}
+
 fn decode_qr_bytes_to_chunk(qr_bytes: &[u8]) -> Option<Chunk> {
-    let qr_string = std::str::from_utf8(qr_bytes).ok()?;
-    let chunk_bytes = base45::decode(qr_string).ok()?;
-    Chunk::from_bytes(&chunk_bytes).ok()
+    Chunk::from_qr_payload(qr_bytes).ok()
+}
+
+/// QR codes seen during a scan that match no wire format, e.g. a URL in the background.
+#[derive(Default)]
+struct UnfamiliarCodes {
+    count: usize,
+    /// Description of the first one.
+    first: Option<String>,
+}
+
+impl UnfamiliarCodes {
+    fn note(&mut self, qr_bytes: &[u8], label: &str) {
+        let description = describe_unfamiliar(qr_bytes);
+        // The same foreign code tends to show in every frame of a recording; warn only once
+        if self.first.is_none() {
+            log::warn!(
+                "    Ignored QR code at {}, which is not a fountain stream: {}",
+                label,
+                description
+            );
+        } else {
+            log::debug!("Ignored QR code at {}: {}", label, description);
+        }
+        self.count += 1;
+        self.first.get_or_insert(description);
+    }
+
+    /// Error for a scan that found no chunks, naming what it found instead.
+    fn no_chunks_error(&self) -> anyhow::Error {
+        match &self.first {
+            Some(first) => anyhow!(
+                "No valid QR chunks found; {} QR code(s) held other content, such as {}",
+                self.count,
+                first
+            ),
+            None => anyhow!("No valid QR chunks found"),
+        }
+    }
 }
 
 fn save_decoded_file(
-    original_filename: String,
-    data: Vec<u8>,
+    decoded: DecodedFile,
     num_chunks: usize,
     output_path: Option<&Path>,
     default_dir: &Path,
+    options: &DecodeOptions,
 ) -> Result<DecodeResult> {
+    let decoded = resolve_delta(decoded, options.base.as_deref())?;
     let final_output_path = match output_path {
         Some(p) => p.to_path_buf(),
-        None => default_dir.join(&original_filename),
+        None => default_dir.join(&decoded.filename),
     };
 
-    fs::write(&final_output_path, &data)?;
+    match decoded.data {
+        FileData::Memory(data) => fs::write(&final_output_path, data)?,
+        FileData::Spooled(temp) => temp.persist(&final_output_path)?,
+    }
+    if options.restore_metadata {
+        apply_file_metadata(&final_output_path, &decoded.metadata)?;
+    }
 
     Ok(DecodeResult {
-        original_filename,
+        original_filename: decoded.filename,
         output_path: final_output_path.to_string_lossy().to_string(),
         num_chunks,
+        metadata: decoded.metadata,
+        sha256: hex::encode(decoded.sha256),
     })
 }
 
-fn decode_core<I>(
+/// Restores the permission bits (on Unix) and modification time recorded by the encoder.
+fn apply_file_metadata(path: &Path, metadata: &FileMetadata) -> Result<()> {
+    #[cfg(unix)]
+    if let Some(mode) = metadata.mode {
+        use std::os::unix::fs::PermissionsExt;
+        // Only read, write and execute bits; a received file never becomes setuid
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))?;
+    }
+
+    if let Some(mtime) = metadata.mtime {
+        let offset = std::time::Duration::from_secs(mtime.unsigned_abs());
+        // The time comes from the stream, so it may be beyond what the system can represent
+        let modified = if mtime >= 0 {
+            std::time::UNIX_EPOCH.checked_add(offset)
+        } else {
+            std::time::UNIX_EPOCH.checked_sub(offset)
+        };
+        match modified {
+            Some(modified) => File::options()
+                .write(true)
+                .open(path)?
+                .set_modified(modified)?,
+            None => log::warn!(
+                "Modification time {} of {} is out of range; not restored",
+                mtime,
+                path.display()
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the images through the RaptorQ decoder until the file is recovered, keeping it in
+/// memory. Returns the recovered file and the number of chunks collected.
+fn decode_in_memory<I, B>(images: I, options: &DecodeOptions) -> Result<(DecodedFile, usize)>
+where
+    I: Iterator<Item = (Result<B>, String)>,
+    B: Borrow<DynamicImage>,
+{
+    decode_stream(images, options, None, &mut |_| {})
+}
+
+/// Like [`decode_in_memory`], but with a `spool_dir`, each source block is written to a
+/// temporary file there as soon as it is recovered, and the file is unpacked next to it.
+/// `on_frame` learns what was found on every image.
+fn decode_stream<I, B>(
     images: I,
-    output_file: Option<&Path>,
-    default_dir: &Path,
-) -> Result<DecodeResult>
+    options: &DecodeOptions,
+    spool_dir: Option<&Path>,
+    on_frame: &mut dyn FnMut(FrameEvent),
+) -> Result<(DecodedFile, usize)>
 where
-    I: Iterator<Item = (Result<DynamicImage>, String)>,
+    I: Iterator<Item = (Result<B>, String)>,
+    B: Borrow<DynamicImage>,
 {
-    let mut rq_decoder = RaptorQStreamDecoder::new();
+    let mut payloads = PayloadDecoder::new(spool_dir);
     let mut count = 0;
+    let mut previous: Option<FrameSignature> = None;
+    let mut tracker = RoiTracker::default();
+    let mut seen: HashSet<Vec<u8>> = HashSet::new();
 
     for (img_result, label) in images {
+        check_cancelled(options.cancel.as_deref())?;
         count += 1;
+        let mut report = |label: String, outcome| {
+            on_frame(FrameEvent {
+                index: count - 1,
+                label,
+                outcome,
+            })
+        };
         let img = match img_result {
             Ok(img) => img,
             Err(e) => {
-                println!("    Failed to load {}: {}", label, e);
+                log::warn!("    Failed to load {}: {}", label, e);
+                report(label, FrameOutcome::Unreadable);
                 continue;
             }
         };
 
-        if let Ok(qr_bytes) = decode_qr_from_dynamic_image(&img) {
-            if let Some(chunk) = decode_qr_bytes_to_chunk(&qr_bytes) {
-                if let Some((original_filename, data)) = rq_decoder.add_chunk(chunk)? {
-                    println!("RaptorQ decoding successful at {}!", label);
-                    return save_decoded_file(
-                        original_filename,
-                        data,
-                        rq_decoder.num_chunks(),
-                        output_file,
-                        default_dir,
-                    );
+        let gray = img.borrow().to_luma8();
+        if options.skip_duplicate_frames {
+            // A repeated frame holds the same codes as the last one, which were already read
+            let signature = FrameSignature::of(&gray);
+            if previous
+                .as_ref()
+                .is_some_and(|prev| prev.matches(&signature))
+            {
+                log::trace!("Skipped {}: same as the previous frame", label);
+                report(label, FrameOutcome::Duplicate);
+                continue;
+            }
+            previous = Some(signature);
+        }
+        if is_blank_frame(&gray) {
+            // E.g. the black and white frames an animation starts with for the camera's exposure
+            log::trace!("Skipped {}: blank frame", label);
+            report(label, FrameOutcome::Blank);
+            continue;
+        }
+
+        let mut found = match decode_color_planes(img.borrow()) {
+            Some(found) => found,
+            None => tracker.decode(&gray),
+        };
+        if found.is_empty() && options.enhance {
+            found = tracker.decode(&enhance_for_detection(&gray));
+        }
+        if found.is_empty() && options.effort == ScanEffort::Thorough {
+            found = decode_all_qr_thorough(&gray);
+        }
+
+        // A single image may hold several QR codes, e.g. a printed page of tiles
+        let codes = found.len();
+        let mut new = 0;
+        let mut result = None;
+        for qr_bytes in found {
+            result = payloads.feed(&qr_bytes, &label)?;
+            if seen.insert(qr_bytes) {
+                new += 1;
+            }
+            if result.is_some() {
+                break;
+            }
+        }
+        let outcome = match codes {
+            0 => FrameOutcome::NoCode,
+            _ => FrameOutcome::Codes { codes, new },
+        };
+        report(label, outcome);
+        if let Some(decoded) = result {
+            return Ok(decoded);
+        }
+    }
+    payloads.finish(count)
+}
+
+/// Like [`decode_stream`], for QR payloads that arrived without images, e.g. as files.
+fn decode_payloads<I>(
+    payloads: I,
+    options: &DecodeOptions,
+    spool_dir: Option<&Path>,
+) -> Result<(DecodedFile, usize)>
+where
+    I: Iterator<Item = (Result<Vec<u8>>, String)>,
+{
+    let mut decoder = PayloadDecoder::new(spool_dir);
+    let mut count = 0;
+    for (payload, label) in payloads {
+        check_cancelled(options.cancel.as_deref())?;
+        count += 1;
+        match payload {
+            Ok(payload) => {
+                if let Some(decoded) = decoder.feed(&payload, &label)? {
+                    return Ok(decoded);
                 }
             }
+            Err(e) => log::warn!("    Failed to read {}: {}", label, e),
         }
     }
+    decoder.finish(count)
+}
+
+/// Routes QR payloads of every wire format to the matching decoder.
+struct PayloadDecoder {
+    rq_decoder: VolumeDecoder,
+    ur_decoder: UrDecoder,
+    unfamiliar: UnfamiliarCodes,
+}
 
-    if rq_decoder.num_chunks() == 0 {
-        return Err(anyhow!("No valid QR chunks found"));
+impl PayloadDecoder {
+    fn new(spool_dir: Option<&Path>) -> Self {
+        Self {
+            rq_decoder: VolumeDecoder::new(spool_dir),
+            ur_decoder: UrDecoder::new(),
+            unfamiliar: UnfamiliarCodes::default(),
+        }
     }
 
-    Err(anyhow!(
-        "Could not decode with RaptorQ (insufficient packets after {} items)",
-        count
-    ))
+    /// Adds the payload found at `label`. Returns the file and the number of chunks it took
+    /// once it is recovered.
+    fn feed(&mut self, qr_bytes: &[u8], label: &str) -> Result<Option<(DecodedFile, usize)>> {
+        match WireFormat::detect(qr_bytes) {
+            Some(WireFormat::Native) => {}
+            Some(WireFormat::Ur) => {
+                match self.ur_decoder.receive(qr_bytes) {
+                    Ok(Some(data)) => {
+                        log::info!("UR decoding successful at {}!", label);
+                        let decoded = DecodedFile {
+                            filename: UR_FILENAME.to_string(),
+                            sha256: sha256_digest(&data),
+                            data: FileData::Memory(data),
+                            metadata: FileMetadata::default(),
+                        };
+                        return Ok(Some((decoded, self.ur_decoder.parts_received().max(1))));
+                    }
+                    Ok(None) => log::debug!("Read UR part from {}", label),
+                    Err(e) => log::warn!("    Ignored UR part at {}: {}", label, e),
+                }
+                return Ok(None);
+            }
+            None => {
+                self.unfamiliar.note(qr_bytes, label);
+                return Ok(None);
+            }
+        }
+        if let Some(sync) = SyncInfo::from_qr_payload(qr_bytes) {
+            match sync {
+                Ok(sync) => log::info!(
+                    "Start marker at {}: {} frames every {} ms",
+                    label,
+                    sync.frames,
+                    sync.interval_ms
+                ),
+                Err(e) => log::warn!("    Ignored start marker at {}: {}", label, e),
+            }
+            return Ok(None);
+        }
+        // A small file sent as one self-contained code needs no fountain decoding
+        if let Some(single) = from_single_code_payload(qr_bytes) {
+            let (filename, data) = single?;
+            log::info!("Single-code payload found at {}!", label);
+            let metadata = FileMetadata::default();
+            return Ok(Some((
+                DecodedFile {
+                    filename,
+                    sha256: sha256_digest(&data),
+                    data: FileData::Memory(data),
+                    metadata,
+                },
+                1,
+            )));
+        }
+        if let Some(chunk) = decode_qr_bytes_to_chunk(qr_bytes) {
+            log::debug!("Read chunk {} from {}", chunk.header.index, label);
+            if let Some(decoded) = self.rq_decoder.add_chunk(chunk)? {
+                log::info!("RaptorQ decoding successful at {}!", label);
+                return Ok(Some((decoded, self.rq_decoder.num_chunks())));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Result once all `count` items were read: the file if it was recovered after all, or an
+    /// error saying what was missing.
+    fn finish(self, count: usize) -> Result<(DecodedFile, usize)> {
+        if self.rq_decoder.num_chunks() == 0 {
+            return Err(match self.ur_decoder.progress() {
+                Some((received, needed)) => anyhow!(
+                    "Could not decode the UR stream (got {} of {} fragments after {} items)",
+                    received,
+                    needed,
+                    count
+                ),
+                None => self.unfamiliar.no_chunks_error(),
+            });
+        }
+        self.rq_decoder.finish(count)
+    }
 }
 
-pub fn decode_from_gif(input_file: &Path, output_file: Option<&Path>) -> Result<DecodeResult> {
+fn inspect_core<I>(images: I) -> Result<StreamInfo>
+where
+    I: Iterator<Item = (Result<DynamicImage>, String)>,
+{
+    let mut streams: HashMap<StreamKey, BTreeMap<u32, Chunk>> = HashMap::new();
+    let mut sync = None;
+    let mut ur_parts = 0;
+    let mut unfamiliar = UnfamiliarCodes::default();
+
+    for (img_result, label) in images {
+        let img = match img_result {
+            Ok(img) => img,
+            Err(e) => {
+                log::warn!("    Failed to load {}: {}", label, e);
+                continue;
+            }
+        };
+
+        for qr_bytes in decode_all_qr_from_dynamic_image(&img) {
+            match WireFormat::detect(&qr_bytes) {
+                Some(WireFormat::Native) => {}
+                Some(WireFormat::Ur) => {
+                    ur_parts += 1;
+                    continue;
+                }
+                None => {
+                    unfamiliar.note(&qr_bytes, &label);
+                    continue;
+                }
+            }
+            if let Some(Ok(info)) = SyncInfo::from_qr_payload(&qr_bytes) {
+                sync.get_or_insert(info);
+                continue;
+            }
+            let Some(chunk) = decode_qr_bytes_to_chunk(&qr_bytes) else {
+                continue;
+            };
+            streams
+                .entry(stream_key(&chunk.header))
+                .or_default()
+                .entry(chunk.header.index)
+                .or_insert(chunk);
+        }
+    }
+
+    if streams.is_empty() {
+        return Err(if ur_parts > 0 {
+            anyhow!(
+                "Found {} UR part(s): UR streams carry no stream info, decode them instead",
+                ur_parts
+            )
+        } else {
+            unfamiliar.no_chunks_error()
+        });
+    }
+
+    // Report on the parameters most chunks agree on, as the decoder would
+    let total_chunks: usize = streams.values().map(BTreeMap::len).sum();
+    let chunks = streams
+        .into_values()
+        .max_by_key(BTreeMap::len)
+        .ok_or_else(|| anyhow!("No valid QR chunks found"))?;
+    let foreign_chunks = total_chunks - chunks.len();
+    let header = chunks
+        .values()
+        .next()
+        .map(|chunk| chunk.header.clone())
+        .ok_or_else(|| anyhow!("No valid QR chunks found"))?;
+
+    let present_indices: Vec<u32> = chunks.keys().copied().collect();
+    let highest = present_indices.last().copied().unwrap_or(0);
+    let missing_indices = (0..highest).filter(|i| !chunks.contains_key(i)).collect();
+
+    // Decoding in memory is the only way to learn the filename, which sits inside the payload
+    let mut reassembler = Reassembler::new();
+    let mut original = None;
+    let mut seed = None;
+    for chunk in chunks.into_values() {
+        let Reassembly::Complete(object) = reassembler.add(chunk) else {
+            continue;
+        };
+        if let Ok(decoded) = finish_raptorq(FileData::Memory(object)) {
+            seed = decoded.metadata.seed;
+            original = Some((decoded.filename, decoded.data.len()? as usize));
+        }
+        break;
+    }
+
+    Ok(StreamInfo {
+        format_version: header.version,
+        transfer_length: header.total,
+        packet_size: header.packet_size,
+        source_packets: header.source_symbol_count(),
+        original_size: header.original_size,
+        present_indices,
+        missing_indices,
+        foreign_chunks,
+        original,
+        volume: header.volume,
+        seed,
+        sync,
+    })
+}
+
+fn decode_core<I>(
+    images: I,
+    output_file: Option<&Path>,
+    default_dir: &Path,
+    options: &DecodeOptions,
+) -> Result<DecodeResult>
+where
+    I: Iterator<Item = (Result<DynamicImage>, String)>,
+{
+    decode_core_with(images, output_file, default_dir, options, &mut |_| {})
+}
+
+fn decode_core_with<I>(
+    images: I,
+    output_file: Option<&Path>,
+    default_dir: &Path,
+    options: &DecodeOptions,
+    on_frame: &mut dyn FnMut(FrameEvent),
+) -> Result<DecodeResult>
+where
+    I: Iterator<Item = (Result<DynamicImage>, String)>,
+{
+    let spool_dir = spool_dir(output_file, default_dir);
+    let (decoded, num_chunks) = decode_stream(images, options, Some(spool_dir), on_frame)?;
+    save_decoded_file(decoded, num_chunks, output_file, default_dir, options)
+}
+
+/// Directory where recovered blocks go: that of the output file, so the file never has to
+/// fit in memory.
+fn spool_dir<'a>(output_file: Option<&'a Path>, default_dir: &'a Path) -> &'a Path {
+    match output_file {
+        Some(path) => path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new(".")),
+        None => default_dir,
+    }
+}
+
+/// Decodes encoded image files (PNG, JPEG, GIF...) held in memory, without touching disk.
+/// Returns the original filename and file contents.
+pub fn decode_from_image_buffers(buffers: &[Vec<u8>]) -> Result<(String, Vec<u8>)> {
+    let images = buffers.iter().enumerate().map(|(i, buffer)| {
+        let res = image::load_from_memory(buffer).map_err(anyhow::Error::from);
+        (res, format!("buffer {}", i + 1))
+    });
+
+    let (decoded, _) = decode_in_memory(images, &DecodeOptions::default())?;
+    let decoded = resolve_delta(decoded, None)?;
+    Ok((decoded.filename, decoded.data.into_vec()?))
+}
+
+/// Decodes already loaded images, without touching disk.
+/// Returns the original filename and file contents.
+pub fn decode_from_dynamic_images(images: &[DynamicImage]) -> Result<(String, Vec<u8>)> {
+    let images = images
+        .iter()
+        .enumerate()
+        .map(|(i, img)| (Ok(img), format!("image {}", i + 1)));
+
+    let (decoded, _) = decode_in_memory(images, &DecodeOptions::default())?;
+    let decoded = resolve_delta(decoded, None)?;
+    Ok((decoded.filename, decoded.data.into_vec()?))
+}
+
+/// Frames of a GIF within `range`; those before it are decoded too, as each GIF frame is
+/// drawn over the one before, but not handed on.
+fn gif_frame_images(
+    input_file: &Path,
+    range: FrameRange,
+) -> Result<impl Iterator<Item = (Result<DynamicImage>, String)>> {
     let file = File::open(input_file)?;
     let reader = BufReader::new(file);
     let gif_decoder = GifDecoder::new(reader)?;
     let frames = gif_decoder.into_frames();
 
-    println!("Decoding QR codes from GIF: {}", input_file.display());
+    log::info!("Decoding QR codes from GIF: {}", input_file.display());
 
-    let images = frames.enumerate().map(|(i, frame_result)| {
-        let label = format!("frame {}", i + 1);
-        let res = frame_result
-            .map(|frame| DynamicImage::ImageRgba8(frame.buffer().clone()))
-            .map_err(anyhow::Error::from);
-        (res, label)
-    });
+    let mut elapsed = Duration::ZERO;
+    Ok(frames
+        .enumerate()
+        .map(move |(i, frame_result)| {
+            let at = elapsed;
+            if let Ok(frame) = &frame_result {
+                elapsed += Duration::from(frame.delay());
+            }
+            (i, at, frame_result)
+        })
+        .skip_while(move |&(i, at, _)| range.before_start(i, at))
+        .take_while(move |&(i, at, _)| !range.past_end(i, at))
+        .map(|(i, _, frame_result)| {
+            let label = format!("frame {}", i + 1);
+            let res = frame_result
+                .map(|frame| DynamicImage::ImageRgba8(frame.buffer().clone()))
+                .map_err(anyhow::Error::from);
+            (res, label)
+        }))
+}
+
+/// Extensions of the still images read from input directories. Besides the PNGs the
+/// encoder writes, this covers phone photos and scans of printed codes.
+pub const IMAGE_EXTENSIONS: &[&str] = &[QR_FILE_EXTENSION, "jpg", "jpeg", "bmp"];
 
-    decode_core(images, output_file, Path::new("."))
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_ascii_lowercase().as_str()))
 }
 
-pub fn decode_from_images(input_dir: &Path, output_file: Option<&Path>) -> Result<DecodeResult> {
-    let images_files: Vec<_> = fs::read_dir(input_dir)?
+/// Collects the files in `dir` with one of `extensions`, or all files for `None`, that the
+/// glob of `options` matches.
+fn collect_dir_files(
+    input_dir: &Path,
+    dir: &Path,
+    options: &DecodeOptions,
+    extensions: Option<&[&str]>,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for path in fs::read_dir(dir)?
         .filter_map(|entry| entry.ok())
-        .filter(|entry| {
-            entry
-                .path()
-                .extension()
-                .map(|ext| ext.to_ascii_lowercase() == QR_FILE_EXTENSION)
-                .unwrap_or(false)
-        })
         .map(|entry| entry.path())
-        .collect();
+    {
+        if path.is_dir() {
+            if options.recursive {
+                collect_dir_files(input_dir, &path, options, extensions, files)?;
+            }
+        } else if extensions.is_none_or(|extensions| has_extension(&path, extensions))
+            && options
+                .glob
+                .as_ref()
+                .is_none_or(|glob| glob.matches(path.strip_prefix(input_dir).unwrap_or(&path)))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn image_dir_images(
+    input_dir: &Path,
+    options: &DecodeOptions,
+) -> Result<impl Iterator<Item = (Result<DynamicImage>, String)>> {
+    let mut images_files = Vec::new();
+    collect_dir_files(
+        input_dir,
+        input_dir,
+        options,
+        Some(IMAGE_EXTENSIONS),
+        &mut images_files,
+    )?;
 
     if images_files.is_empty() {
         return Err(anyhow!(
             "No image ({}) files found in directory",
-            QR_FILE_EXTENSION
+            IMAGE_EXTENSIONS.join(", ")
         ));
     }
 
-    println!("Found {} QR code image(s)", images_files.len());
+    log::info!("Found {} QR code image(s)", images_files.len());
 
-    let images = images_files.into_iter().map(|path| {
+    let input_dir = input_dir.to_path_buf();
+    Ok(images_files.into_iter().map(move |path| {
         let label = path
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
+            .strip_prefix(&input_dir)
+            .unwrap_or(&path)
+            .display()
             .to_string();
         let res = image::open(path).map_err(anyhow::Error::from);
         (res, label)
-    });
+    }))
+}
+
+/// Payloads of the files in `input_dir`, one chunk each, as `fountain encode --chunks-out`
+/// writes them or another transport delivered them.
+fn chunk_dir_payloads(
+    input_dir: &Path,
+    options: &DecodeOptions,
+) -> Result<impl Iterator<Item = (Result<Vec<u8>>, String)>> {
+    let mut files = Vec::new();
+    collect_dir_files(input_dir, input_dir, options, None, &mut files)?;
+    if files.is_empty() {
+        return Err(anyhow!("No chunk files found in directory"));
+    }
+    files.sort();
+    log::info!("Found {} chunk file(s)", files.len());
+
+    let input_dir = input_dir.to_path_buf();
+    Ok(files.into_iter().map(move |path| {
+        let label = path
+            .strip_prefix(&input_dir)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        (fs::read(&path).map_err(anyhow::Error::from), label)
+    }))
+}
+
+type LabelledImages = Box<dyn Iterator<Item = (Result<DynamicImage>, String)>>;
+
+fn collect_input_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if !path.exists() {
+        return Err(anyhow!("Input path does not exist: {}", path.display()));
+    }
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_input_files(&entry, files)?;
+        } else if has_extension(&entry, IMAGE_EXTENSIONS) || has_extension(&entry, &["gif"]) {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn file_images(path: PathBuf) -> LabelledImages {
+    let name = path.display().to_string();
+    if !has_extension(&path, &["gif"]) {
+        let res = image::open(&path).map_err(anyhow::Error::from);
+        return Box::new(std::iter::once((res, name)));
+    }
+
+    match gif_frame_images(&path, FrameRange::default()) {
+        Ok(frames) => {
+            Box::new(frames.map(move |(res, label)| (res, format!("{} {}", name, label))))
+        }
+        Err(e) => Box::new(std::iter::once((Err(e), name))),
+    }
+}
+
+/// Images from any mix of image files, GIFs and directories (searched recursively).
+fn path_images(paths: &[PathBuf]) -> Result<impl Iterator<Item = (Result<DynamicImage>, String)>> {
+    let mut files = Vec::new();
+    for path in paths {
+        collect_input_files(path, &mut files)?;
+    }
+    if files.is_empty() {
+        return Err(anyhow!("No image or GIF files found in the given paths"));
+    }
+
+    log::info!("Found {} input file(s)", files.len());
+    Ok(files.into_iter().flat_map(file_images))
+}
+
+/// Images already in `input_dir`, then each new one as it is written there, blocking
+/// between them. Ends only when watching fails; a set cancel flag is passed on as an error
+/// for the decoder to stop at.
+#[cfg(feature = "watch")]
+fn watched_dir_images(
+    input_dir: &Path,
+    options: &DecodeOptions,
+) -> Result<impl Iterator<Item = (Result<DynamicImage>, String)>> {
+    use crate::cancel::Cancelled;
+    use notify::{EventKind, RecursiveMode, Watcher};
+    use std::collections::{HashSet, VecDeque};
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::time::Duration;
+
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let mode = if options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    // Watch before listing, so an image written in between is not missed
+    watcher.watch(input_dir, mode)?;
+    let mut existing = Vec::new();
+    collect_dir_files(
+        input_dir,
+        input_dir,
+        options,
+        Some(IMAGE_EXTENSIONS),
+        &mut existing,
+    )?;
+    existing.sort();
+    log::info!(
+        "Found {} QR code image(s); watching {} for more",
+        existing.len(),
+        input_dir.display()
+    );
+
+    let input_dir = input_dir.to_path_buf();
+    let options = options.clone();
+    let mut pending = VecDeque::from(existing);
+    let mut read: HashSet<PathBuf> = HashSet::new();
+    Ok(std::iter::from_fn(move || {
+        let _watching = &watcher;
+        loop {
+            if options
+                .cancel
+                .as_ref()
+                .is_some_and(|flag| flag.load(Ordering::Relaxed))
+            {
+                return Some((Err(Cancelled.into()), input_dir.display().to_string()));
+            }
+            while let Some(path) = pending.pop_front() {
+                if read.contains(&path) {
+                    continue;
+                }
+                let label = path
+                    .strip_prefix(&input_dir)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string();
+                match image::open(&path) {
+                    Ok(img) => {
+                        read.insert(path);
+                        return Some((Ok(img), label));
+                    }
+                    // Most likely still being written; its next change event retries it
+                    Err(e) => log::debug!("Could not load {} yet: {}", label, e),
+                }
+            }
+            match events.recv_timeout(Duration::from_millis(200)) {
+                Ok(Ok(event)) => {
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Access(_)
+                    ) {
+                        pending.extend(event.paths.into_iter().filter(|path| {
+                            path.is_file()
+                                && has_extension(path, IMAGE_EXTENSIONS)
+                                && options.glob.as_ref().is_none_or(|glob| {
+                                    glob.matches(path.strip_prefix(&input_dir).unwrap_or(path))
+                                })
+                        }));
+                    }
+                }
+                Ok(Err(e)) => log::warn!("Error watching {}: {}", input_dir.display(), e),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }))
+}
+
+/// Decodes chunks gathered from several inputs at once, e.g. photos of one printed backup
+/// spread across folders. Each path may be an image, a GIF or a directory, which is searched
+/// recursively for images and GIFs. All chunks go into a single reassembly.
+pub fn decode_from_paths(paths: &[PathBuf], output_file: Option<&Path>) -> Result<DecodeResult> {
+    decode_from_paths_with_options(paths, output_file, &DecodeOptions::default())
+}
+
+/// Like [`decode_from_paths`], with explicit [`DecodeOptions`].
+pub fn decode_from_paths_with_options(
+    paths: &[PathBuf],
+    output_file: Option<&Path>,
+    options: &DecodeOptions,
+) -> Result<DecodeResult> {
+    decode_core(path_images(paths)?, output_file, Path::new("."), options)
+}
 
+/// Like [`decode_from_paths`], but returns the original filename and file contents
+/// instead of writing them to disk.
+pub fn decode_from_paths_to_vec(paths: &[PathBuf]) -> Result<(String, Vec<u8>)> {
+    let (decoded, _) = decode_in_memory(path_images(paths)?, &DecodeOptions::default())?;
+    let decoded = resolve_delta(decoded, None)?;
+    Ok((decoded.filename, decoded.data.into_vec()?))
+}
+
+pub fn decode_from_gif(input_file: &Path, output_file: Option<&Path>) -> Result<DecodeResult> {
+    decode_from_gif_with_options(input_file, output_file, &DecodeOptions::default())
+}
+
+/// Like [`decode_from_gif`], with explicit [`DecodeOptions`].
+pub fn decode_from_gif_with_options(
+    input_file: &Path,
+    output_file: Option<&Path>,
+    options: &DecodeOptions,
+) -> Result<DecodeResult> {
     decode_core(
-        images,
+        gif_frame_images(input_file, options.frames)?,
+        output_file,
+        Path::new("."),
+        options,
+    )
+}
+
+/// Like [`decode_from_gif_with_options`], calling `on_frame` with what was found on every
+/// frame as the decode goes, for progress displays and statistics on a recording.
+pub fn decode_from_gif_with<F: FnMut(FrameEvent)>(
+    input_file: &Path,
+    output_file: Option<&Path>,
+    options: &DecodeOptions,
+    mut on_frame: F,
+) -> Result<DecodeResult> {
+    decode_core_with(
+        gif_frame_images(input_file, options.frames)?,
+        output_file,
+        Path::new("."),
+        options,
+        &mut on_frame,
+    )
+}
+
+/// Like [`decode_from_gif`], but returns the original filename and file contents
+/// instead of writing them to disk.
+pub fn decode_from_gif_to_vec(input_file: &Path) -> Result<(String, Vec<u8>)> {
+    let (decoded, _) = decode_in_memory(
+        gif_frame_images(input_file, FrameRange::default())?,
+        &DecodeOptions::default(),
+    )?;
+    let decoded = resolve_delta(decoded, None)?;
+    Ok((decoded.filename, decoded.data.into_vec()?))
+}
+
+pub fn decode_from_images(input_dir: &Path, output_file: Option<&Path>) -> Result<DecodeResult> {
+    decode_from_images_with_options(input_dir, output_file, &DecodeOptions::default())
+}
+
+/// Like [`decode_from_images`], with explicit [`DecodeOptions`].
+pub fn decode_from_images_with_options(
+    input_dir: &Path,
+    output_file: Option<&Path>,
+    options: &DecodeOptions,
+) -> Result<DecodeResult> {
+    decode_core(
+        image_dir_images(input_dir, options)?,
+        output_file,
+        input_dir.parent().unwrap_or(Path::new(".")),
+        options,
+    )
+}
+
+/// Like [`decode_from_images`], but returns the original filename and file contents
+/// instead of writing them to disk.
+pub fn decode_from_images_to_vec(input_dir: &Path) -> Result<(String, Vec<u8>)> {
+    let options = DecodeOptions::default();
+    let (decoded, _) = decode_in_memory(image_dir_images(input_dir, &options)?, &options)?;
+    let decoded = resolve_delta(decoded, None)?;
+    Ok((decoded.filename, decoded.data.into_vec()?))
+}
+
+/// Decodes the chunk payload files in `input_dir`, one chunk per file, as written by
+/// `encode_bytes_to_chunk_files_with_options` or carried over another transport such as NFC
+/// or Bluetooth. Files holding no chunk are skipped.
+pub fn decode_from_chunk_files(
+    input_dir: &Path,
+    output_file: Option<&Path>,
+) -> Result<DecodeResult> {
+    decode_from_chunk_files_with_options(input_dir, output_file, &DecodeOptions::default())
+}
+
+/// Like [`decode_from_chunk_files`], with explicit [`DecodeOptions`].
+pub fn decode_from_chunk_files_with_options(
+    input_dir: &Path,
+    output_file: Option<&Path>,
+    options: &DecodeOptions,
+) -> Result<DecodeResult> {
+    let default_dir = input_dir.parent().unwrap_or(Path::new("."));
+    let (decoded, num_chunks) = decode_payloads(
+        chunk_dir_payloads(input_dir, options)?,
+        options,
+        Some(spool_dir(output_file, default_dir)),
+    )?;
+    save_decoded_file(decoded, num_chunks, output_file, default_dir, options)
+}
+
+/// Like [`decode_from_chunk_files`], but returns the original filename and file contents
+/// instead of writing them to disk.
+pub fn decode_from_chunk_files_to_vec(input_dir: &Path) -> Result<(String, Vec<u8>)> {
+    let options = DecodeOptions::default();
+    let (decoded, _) = decode_payloads(chunk_dir_payloads(input_dir, &options)?, &options, None)?;
+    let decoded = resolve_delta(decoded, None)?;
+    Ok((decoded.filename, decoded.data.into_vec()?))
+}
+
+/// Decodes the images in `input_dir` and keeps watching it, reading each new image as it
+/// appears (e.g. photos synced from a phone), until the file is complete. Blocks until
+/// then, or until the cancel flag of [`DecodeOptions`] is set.
+#[cfg(feature = "watch")]
+pub fn decode_from_watched_dir(
+    input_dir: &Path,
+    output_file: Option<&Path>,
+) -> Result<DecodeResult> {
+    decode_from_watched_dir_with_options(input_dir, output_file, &DecodeOptions::default())
+}
+
+/// Like [`decode_from_watched_dir`], with explicit [`DecodeOptions`].
+#[cfg(feature = "watch")]
+pub fn decode_from_watched_dir_with_options(
+    input_dir: &Path,
+    output_file: Option<&Path>,
+    options: &DecodeOptions,
+) -> Result<DecodeResult> {
+    if !input_dir.is_dir() {
+        return Err(anyhow!("Not a directory: {}", input_dir.display()));
+    }
+    decode_core(
+        watched_dir_images(input_dir, options)?,
         output_file,
         input_dir.parent().unwrap_or(Path::new(".")),
+        options,
     )
 }
+
+fn verify_core<I>(images: I) -> Result<VerifyReport>
+where
+    I: Iterator<Item = (Result<DynamicImage>, String)>,
+{
+    // The checksum is validated while unpacking, so a successful decode is a full verification
+    let (decoded, num_chunks) = decode_in_memory(images, &DecodeOptions::default())?;
+    Ok(VerifyReport {
+        original_filename: decoded.filename,
+        original_size: decoded.data.len()? as usize,
+        num_chunks,
+        sha256: hex::encode(decoded.sha256),
+    })
+}
+
+/// Runs the full decode of a directory of images, including checksum validation,
+/// and discards the data.
+pub fn verify_images(input_dir: &Path) -> Result<VerifyReport> {
+    verify_core(image_dir_images(input_dir, &DecodeOptions::default())?)
+}
+
+/// Runs the full decode of a GIF, including checksum validation, and discards the data.
+pub fn verify_gif(input_file: &Path) -> Result<VerifyReport> {
+    verify_core(gif_frame_images(input_file, FrameRange::default())?)
+}
+
+/// Reads only chunk headers from a directory of images, without writing anything.
+pub fn inspect_images(input_dir: &Path) -> Result<StreamInfo> {
+    inspect_core(image_dir_images(input_dir, &DecodeOptions::default())?)
+}
+
+/// Reads only chunk headers from a GIF, without writing anything.
+pub fn inspect_gif(input_file: &Path) -> Result<StreamInfo> {
+    inspect_core(gif_frame_images(input_file, FrameRange::default())?)
+}
+
+/// Reads only chunk headers from a single image, e.g. one tiled page.
+pub fn inspect_image(input_file: &Path) -> Result<StreamInfo> {
+    let label = input_file.display().to_string();
+    let img = image::open(input_file).map_err(anyhow::Error::from);
+    inspect_core(std::iter::once((img, label)))
+}
+
+/// Contents of every distinct QR code in the images, in the order first seen, without
+/// interpreting them. Works for any QR code, not only fountain chunks.
+fn raw_contents_core<I>(images: I) -> Result<Vec<Vec<u8>>>
+where
+    I: Iterator<Item = (Result<DynamicImage>, String)>,
+{
+    let mut contents: Vec<Vec<u8>> = Vec::new();
+    for (img_result, label) in images {
+        let img = match img_result {
+            Ok(img) => img,
+            Err(e) => {
+                log::warn!("    Failed to load {}: {}", label, e);
+                continue;
+            }
+        };
+        for qr_bytes in decode_all_qr_from_dynamic_image(&img) {
+            if !contents.contains(&qr_bytes) {
+                contents.push(qr_bytes);
+            }
+        }
+    }
+
+    if contents.is_empty() {
+        return Err(anyhow!("No QR codes found"));
+    }
+    Ok(contents)
+}
+
+/// Raw contents of the QR codes in a directory of images.
+pub fn read_raw_images(input_dir: &Path) -> Result<Vec<Vec<u8>>> {
+    raw_contents_core(image_dir_images(input_dir, &DecodeOptions::default())?)
+}
+
+/// Raw contents of the QR codes in a GIF.
+pub fn read_raw_gif(input_file: &Path) -> Result<Vec<Vec<u8>>> {
+    raw_contents_core(gif_frame_images(input_file, FrameRange::default())?)
+}
+
+/// Raw contents of the QR codes in a single image.
+pub fn read_raw_image(input_file: &Path) -> Result<Vec<Vec<u8>>> {
+    let label = input_file.display().to_string();
+    let img = image::open(input_file).map_err(anyhow::Error::from);
+    raw_contents_core(std::iter::once((img, label)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{compress, pack_data_with_metadata, CompressionAlgorithm};
+    use raptorq::Encoder;
+
+    #[test]
+    fn test_extreme_mtime_does_not_panic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.txt");
+        fs::write(&path, b"old").unwrap();
+
+        // Either skipped or clamped by the file system, depending on the platform
+        for mtime in [i64::MIN, i64::MAX] {
+            let metadata = FileMetadata {
+                mtime: Some(mtime),
+                ..FileMetadata::default()
+            };
+            apply_file_metadata(&path, &metadata).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse_frame_position() {
+        let time = |secs: f64| FramePosition::Time(Duration::from_secs_f64(secs));
+        assert_eq!(
+            "120".parse::<FramePosition>().unwrap(),
+            FramePosition::Frame(120)
+        );
+        assert_eq!("90s".parse::<FramePosition>().unwrap(), time(90.0));
+        assert_eq!("1.5s".parse::<FramePosition>().unwrap(), time(1.5));
+        assert_eq!("1:30".parse::<FramePosition>().unwrap(), time(90.0));
+        assert_eq!("00:01:30.5".parse::<FramePosition>().unwrap(), time(90.5));
+        assert_eq!("2:00:00".parse::<FramePosition>().unwrap(), time(7200.0));
+        for invalid in ["", "-1", "1:2:3:4", "1.5:00", "-5s", "abc"] {
+            assert!(invalid.parse::<FramePosition>().is_err(), "{}", invalid);
+        }
+
+        let range = FrameRange {
+            start: Some(FramePosition::Frame(2)),
+            end: Some(time(1.0)),
+        };
+        assert!(range.before_start(1, Duration::ZERO));
+        assert!(!range.before_start(2, Duration::from_millis(900)));
+        assert!(!range.past_end(2, Duration::from_millis(900)));
+        assert!(range.past_end(3, Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_blocks_are_written_as_they_are_recovered() {
+        let mut state: u32 = 1;
+        let content: Vec<u8> = (0..20_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect();
+        let metadata = FileMetadata {
+            sha256: Some(sha256_digest(&content)),
+            ..FileMetadata::default()
+        };
+        let packed = pack_data_with_metadata(&content, "blocks.bin", &metadata);
+        let object = compress(&packed, CompressionAlgorithm::Zlib).unwrap();
+
+        // Small blocks, so the object spans several of them
+        let config = ObjectTransmissionInformation::new(object.len() as u64, 64, 4, 1, 8);
+        let packets = Encoder::new(&object, config).get_encoded_packets(2);
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let spool = FileData::new(Some(dir.path())).unwrap();
+        let mut decoder = BlockDecoder::new(config, spool);
+        assert_eq!(decoder.blocks.len(), 4);
+
+        // Packets come block by block, so the first blocks are stored before the last arrive
+        let mut complete = None;
+        let mut stored_early = false;
+        for packet in packets {
+            if let Some(object) = decoder.decode(packet).unwrap() {
+                complete = Some(object);
+            }
+            if (1..4).contains(&decoder.stored_blocks()) {
+                stored_early = decoder.object.len().unwrap() > 0;
+            }
+        }
+        assert!(stored_early);
+        let complete = complete.expect("every block is recovered");
+        assert_eq!(complete.len().unwrap(), object.len() as u64);
+
+        let decoded = finish_raptorq(complete).unwrap();
+        assert_eq!(decoded.filename, "blocks.bin");
+        assert_eq!(decoded.sha256, sha256_digest(&content));
+        let output = dir.path().join("blocks.bin");
+        let FileData::Spooled(temp) = decoded.data else {
+            panic!("decoded data should be spooled");
+        };
+        temp.persist(&output).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), content);
+
+        // The spooled object was removed once unpacked
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,167 @@
+//! Content-defined deduplication, an optional pass before compression for files with regions
+//! repeated further apart than the compressor's window, such as disk images or long logs.
+//!
+//! The data is cut where a rolling hash of the last bytes matches a pattern, so a repeated
+//! region is cut the same way wherever it occurs. Each cut is either stored as a literal or, if
+//! an identical one came earlier, replaced by a reference to it. The result is a sequence of
+//! records, each starting with a LEB128 varint: `len << 1` followed by `len` literal bytes, or
+//! `index << 1 | 1` naming the `index`-th literal of the stream.
+
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/// Cuts are at least this long, so short repeats do not turn into many tiny records.
+const MIN_CUT: usize = 1024;
+/// Cuts are at most this long, so one changed byte in a long run costs only one cut.
+const MAX_CUT: usize = 16 * 1024;
+/// A cut ends where these bits of the rolling hash are all zero: 4 KiB apart on average.
+const CUT_MASK: u64 = (1 << 12) - 1;
+
+/// Random values the rolling hash adds per byte, generated with SplitMix64.
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Replaces repeated regions of `data` by references, or returns `None` if nothing repeats.
+pub fn deduplicate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut seen: HashMap<[u8; 32], u64> = HashMap::new();
+    let mut result = Vec::with_capacity(data.len() + data.len() / MIN_CUT * 2);
+    let mut repeated = false;
+    let mut start = 0;
+    while start < data.len() {
+        let cut = &data[start..start + cut_length(&data[start..])];
+        let literals = seen.len() as u64;
+        match seen.entry(Sha256::digest(cut).into()) {
+            Entry::Occupied(entry) => {
+                write_varint(&mut result, entry.get() << 1 | 1);
+                repeated = true;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(literals);
+                write_varint(&mut result, (cut.len() as u64) << 1);
+                result.extend_from_slice(cut);
+            }
+        }
+        start += cut.len();
+    }
+    repeated.then_some(result)
+}
+
+/// Rebuilds the data [`deduplicate`] was given from its records.
+pub fn expand(records: &[u8]) -> Result<Vec<u8>> {
+    let mut literals: Vec<(usize, usize)> = Vec::new();
+    let mut result = Vec::with_capacity(records.len());
+    let mut pos = 0;
+    while pos < records.len() {
+        let value = read_varint(records, &mut pos)
+            .ok_or_else(|| anyhow!("Invalid deduplicated data: truncated record"))?;
+        let n = usize::try_from(value >> 1).unwrap_or(usize::MAX);
+        if value & 1 == 1 {
+            let &(start, len) = literals
+                .get(n)
+                .ok_or_else(|| anyhow!("Invalid deduplicated data: no literal {}", n))?;
+            result.extend_from_within(start..start + len);
+        } else {
+            let literal = records
+                .get(pos..pos.saturating_add(n))
+                .ok_or_else(|| anyhow!("Invalid deduplicated data: truncated literal"))?;
+            pos += n;
+            literals.push((result.len(), n));
+            result.extend_from_slice(literal);
+        }
+    }
+    Ok(result)
+}
+
+/// Length of the cut at the start of `data`.
+fn cut_length(data: &[u8]) -> usize {
+    if data.len() <= MIN_CUT {
+        return data.len();
+    }
+    let mut hash = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(MAX_CUT).skip(MIN_CUT) {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        if hash & CUT_MASK == 0 {
+            return i + 1;
+        }
+    }
+    data.len().min(MAX_CUT)
+}
+
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads the varint at `pos` and moves past it, or returns `None` if it is cut off or too long.
+pub(crate) fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(len: usize, mut state: u32) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_deduplicate_roundtrip() {
+        // The same region twice, far apart and at different alignments
+        let block = noise(100_000, 1);
+        let mut data = noise(777, 2);
+        data.extend_from_slice(&block);
+        data.extend_from_slice(&noise(50_000, 3));
+        data.extend_from_slice(&block);
+        data.extend_from_slice(&noise(123, 4));
+
+        let records = deduplicate(&data).unwrap();
+        assert!(records.len() < data.len() - block.len() / 2);
+        assert_eq!(expand(&records).unwrap(), data);
+    }
+
+    #[test]
+    fn test_deduplicate_without_repeats() {
+        assert!(deduplicate(&noise(50_000, 5)).is_none());
+        assert!(deduplicate(&[]).is_none());
+    }
+
+    #[test]
+    fn test_expand_rejects_bad_records() {
+        // Reference before any literal, truncated literal, unterminated varint
+        assert!(expand(&[0x01]).is_err());
+        assert!(expand(&[0x08, 1, 2]).is_err());
+        assert!(expand(&[0x80]).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Binary deltas, for re-sending a new version of a file the receiver already has an older
+//! version of. Only the differences travel; the receiver rebuilds the file from its copy.
+//!
+//! The base is indexed in blocks of [`BLOCK`] bytes, and a rolling hash finds those blocks
+//! anywhere in the target, as rsync does. Each match is grown in both directions as far as
+//! the bytes agree. A delta is the SHA-256 of the target followed by records, each starting
+//! with a LEB128 varint: `len << 1` followed by `len` new bytes, or `len << 1 | 1` followed by
+//! the varint offset of `len` bytes to copy from the base.
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+use crate::chunk::sha256_digest;
+use crate::dedup::{read_varint, write_varint};
+
+/// Size of the base blocks looked for in the target. Shorter common runs are sent as new bytes.
+const BLOCK: usize = 32;
+/// Multiplier of the polynomial rolling hash.
+const PRIME: u64 = 0x0100_0000_01B3;
+
+/// Returns the delta that turns `base` into `target`.
+pub fn diff(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = sha256_digest(target).to_vec();
+    let mut literal_start = 0;
+    if base.len() >= BLOCK && target.len() >= BLOCK {
+        let mut index: HashMap<u64, usize> = HashMap::new();
+        for offset in (0..=base.len() - BLOCK).step_by(BLOCK) {
+            index
+                .entry(block_hash(&base[offset..offset + BLOCK]))
+                .or_insert(offset);
+        }
+        // Weight of the byte leaving the window
+        let leaving = (1..BLOCK).fold(1u64, |weight, _| weight.wrapping_mul(PRIME));
+
+        let mut pos = 0;
+        let mut hash = block_hash(&target[..BLOCK]);
+        while pos + BLOCK <= target.len() {
+            let matched = index
+                .get(&hash)
+                .filter(|&&offset| base[offset..offset + BLOCK] == target[pos..pos + BLOCK]);
+            if let Some(&offset) = matched {
+                let (mut start, mut base_start) = (pos, offset);
+                while start > literal_start
+                    && base_start > 0
+                    && target[start - 1] == base[base_start - 1]
+                {
+                    start -= 1;
+                    base_start -= 1;
+                }
+                let common = target[pos..]
+                    .iter()
+                    .zip(&base[offset..])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                let end = pos + common;
+
+                write_literal(&mut delta, &target[literal_start..start]);
+                write_varint(&mut delta, ((end - start) as u64) << 1 | 1);
+                write_varint(&mut delta, base_start as u64);
+                literal_start = end;
+                pos = end;
+                if pos + BLOCK <= target.len() {
+                    hash = block_hash(&target[pos..pos + BLOCK]);
+                }
+                continue;
+            }
+            if pos + BLOCK < target.len() {
+                hash = hash
+                    .wrapping_sub(u64::from(target[pos]).wrapping_mul(leaving))
+                    .wrapping_mul(PRIME)
+                    .wrapping_add(u64::from(target[pos + BLOCK]));
+            }
+            pos += 1;
+        }
+    }
+    write_literal(&mut delta, &target[literal_start..]);
+    delta
+}
+
+/// Rebuilds the target of `delta` from `base`, checking it against the SHA-256 in the delta.
+pub fn apply(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let (expected, records) = delta
+        .split_at_checked(32)
+        .ok_or_else(|| anyhow!("Invalid delta: too short"))?;
+    let truncated = || anyhow!("Invalid delta: truncated record");
+    let mut target = Vec::new();
+    let mut pos = 0;
+    while pos < records.len() {
+        let value = read_varint(records, &mut pos).ok_or_else(truncated)?;
+        let len = usize::try_from(value >> 1).unwrap_or(usize::MAX);
+        let bytes = if value & 1 == 1 {
+            let offset = read_varint(records, &mut pos).ok_or_else(truncated)?;
+            let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+            base.get(offset..offset.saturating_add(len))
+                .ok_or_else(|| anyhow!("Invalid delta: copies past the end of the base"))?
+        } else {
+            let bytes = records
+                .get(pos..pos.saturating_add(len))
+                .ok_or_else(truncated)?;
+            pos += len;
+            bytes
+        };
+        target.extend_from_slice(bytes);
+    }
+    if sha256_digest(&target) != expected {
+        return Err(anyhow!(
+            "Delta does not rebuild the file; the base differs from the one it was made against"
+        ));
+    }
+    Ok(target)
+}
+
+fn block_hash(block: &[u8]) -> u64 {
+    block.iter().fold(0u64, |hash, &byte| {
+        hash.wrapping_mul(PRIME).wrapping_add(u64::from(byte))
+    })
+}
+
+fn write_literal(out: &mut Vec<u8>, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        write_varint(out, (bytes.len() as u64) << 1);
+        out.extend_from_slice(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(len: usize, mut state: u32) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_delta_roundtrip() {
+        let base = noise(100_000, 1);
+        // An insertion, a changed byte, a deletion and an appended tail
+        let mut target = base[..30_000].to_vec();
+        target.extend_from_slice(b"inserted text");
+        target.extend_from_slice(&base[30_000..60_000]);
+        target[45_000] ^= 0xFF;
+        target.extend_from_slice(&base[70_000..]);
+        target.extend_from_slice(&noise(500, 2));
+
+        let delta = diff(&base, &target);
+        assert!(delta.len() < 1_000, "delta of {} bytes", delta.len());
+        assert_eq!(apply(&base, &delta).unwrap(), target);
+
+        // Another base does not pass for the right one
+        assert!(apply(&noise(100_000, 3), &delta).is_err());
+    }
+
+    #[test]
+    fn test_delta_edge_cases() {
+        let base = noise(1_000, 4);
+        for target in [Vec::new(), b"short".to_vec(), noise(2_000, 5), base.clone()] {
+            for base in [&[][..], &base[..]] {
+                assert_eq!(apply(base, &diff(base, &target)).unwrap(), target);
+            }
+        }
+        assert!(apply(&base, &[0; 10]).is_err());
+    }
+}
@@ -1,19 +1,710 @@
 use anyhow::{anyhow, Result};
-use image::codecs::gif::GifEncoder;
-use image::{Delay, Frame, RgbaImage};
+use image::{Rgb, RgbImage};
 use qrcode::Version;
-use raptorq::Encoder as RQEncoder;
+use raptorq::{
+    extended_source_block_symbols, partition, Encoder as RQEncoder, EncodingPacket,
+    ObjectTransmissionInformation, SourceBlockEncoder,
+};
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::time::Duration;
 
-use crate::chunk::{compress, pack_data, Chunk, ChunkHeader, DEFAULT_PAYLOAD_SIZE, HEADER_SIZE};
-use crate::qr::{generate_qr_image, render_qr_to_terminal, save_qr_image, QR_FILE_EXTENSION};
+use crate::cancel::check_cancelled;
+use crate::chunk::{
+    pack_data_with_metadata, sha256_hex, to_single_code_payload, Chunk, ChunkHeader,
+    CompressionAlgorithm, CompressionSetting, FileMetadata, PayloadEncoding, SyncInfo, Volume,
+    WireFormat, CHUNK_VERSION, DEFAULT_PAYLOAD_SIZE, HEADER_SIZE, HEADER_SIZE_V3,
+    VOLUME_CHUNK_VERSION,
+};
+use crate::packets::{
+    compressed_object, encoded_packets, first_repair_id, repair_packets_per_block, splitmix64,
+    validate_redundancy_factor,
+};
+use crate::qr::{
+    add_caption, generate_qr_image, merge_color_planes, qr_version_for, render_qr_to_terminal,
+    save_qr_image, tile_qr_images, EcLevel, GridLayout, QrStyle, TerminalRender, TerminalSize,
+    DEFAULT_EC_LEVEL, QR_FILE_EXTENSION,
+};
+use crate::receive::ChunkId;
+use crate::ur::{UrEncoder, UR_FILENAME};
+
+pub use crate::packets::DEFAULT_REDUNDANCY_FACTOR;
+/// Terminal carousels loop and are easier to miss frames on, so they carry more repair packets.
+pub const DEFAULT_TERMINAL_REDUNDANCY_FACTOR: f64 = 2.0;
 
 pub struct EncodeResult {
     pub num_chunks: usize,
     pub output_files: Vec<String>,
     pub effective_size: usize,
+    /// SHA-256 of the input as lowercase hex, comparable with `sha256sum` output.
+    pub sha256: String,
+    /// Frame durations as stored, for GIF and APNG output.
+    pub timing: Option<AnimationTiming>,
+    /// Sizes of the QR codes and images, for outputs that draw codes.
+    pub qr: Option<QrSizing>,
+}
+
+/// Sizes of the QR codes of an output, for checking that they print with modules large
+/// enough for the camera to resolve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrSizing {
+    /// QR version of the codes; all codes of one output share it. With volumes, the largest.
+    pub version: Version,
+    /// Pixels per module.
+    pub pixel_scale: u32,
+    /// Width and height in pixels of each image or frame as written, with captions, grids and
+    /// tiles. With volumes, the largest.
+    pub image_px: (u32, u32),
+    /// Bytes each code carries, in the order they are written; sync markers are left out.
+    pub chunk_bytes: Vec<usize>,
+}
+
+impl QrSizing {
+    /// Modules per side of each code, without the quiet zone.
+    pub fn modules(&self) -> u32 {
+        self.version.width() as u32
+    }
+
+    /// Sizing of codes of `version` in images of `image`'s size, with no codes recorded yet.
+    fn new(version: Version, image: &RgbImage, options: &EncodeOptions) -> Self {
+        Self {
+            version,
+            pixel_scale: options.pixel_scale,
+            image_px: image.dimensions(),
+            chunk_bytes: Vec::new(),
+        }
+    }
+
+    /// Adds the codes of another volume.
+    fn merge(&mut self, other: QrSizing) {
+        if other.version.width() > self.version.width() {
+            self.version = other.version;
+        }
+        self.image_px = (
+            self.image_px.0.max(other.image_px.0),
+            self.image_px.1.max(other.image_px.1),
+        );
+        self.chunk_bytes.extend(other.chunk_bytes);
+    }
+}
+
+/// Frame durations of a GIF or APNG as written, which can differ from those asked for: GIF
+/// counts in hundredths of a second and APNG in milliseconds up to 65535.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationTiming {
+    /// Duration of every frame in milliseconds, sync frames included. Repeated frames are
+    /// stored as one. With volumes, the frames of every volume one after another.
+    pub frame_ms: Vec<u64>,
+    /// Duration of a plain data frame in milliseconds.
+    pub interval_ms: u64,
+}
+
+impl AnimationTiming {
+    /// Time to show every frame once.
+    pub fn total(&self) -> Duration {
+        Duration::from_millis(self.frame_ms.iter().sum())
+    }
+}
+
+/// File name pattern for image output, e.g. `{stem}-{index:05}-of-{total}.png`.
+///
+/// Placeholders: `{name}` (embedded filename with dots replaced by underscores), `{stem}` and
+/// `{ext}` (of the embedded filename), `{index}` (1-based chunk or page number) and `{total}`.
+/// `{index}` and `{total}` accept a zero-padded width such as `{index:04}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    source: String,
+    segments: Vec<NameSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NameSegment {
+    Literal(String),
+    Name,
+    Stem,
+    Ext,
+    Index(usize),
+    Total(usize),
+}
+
+/// Default names for one QR code per image.
+pub const DEFAULT_NAME_TEMPLATE: &str = "{name}_{index:04}.png";
+/// Default names for tiled pages.
+pub const DEFAULT_PAGE_NAME_TEMPLATE: &str = "{name}_page_{index:04}.png";
+
+impl NameTemplate {
+    /// Fills in the template for the `index`-th (1-based) of `total` images of `filename`.
+    pub fn render(&self, filename: &str, index: usize, total: usize) -> String {
+        let path = Path::new(filename);
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(filename);
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                NameSegment::Literal(text) => out.push_str(text),
+                NameSegment::Name => out.push_str(&filename.replace('.', "_")),
+                NameSegment::Stem => out.push_str(stem),
+                NameSegment::Ext => out.push_str(ext),
+                NameSegment::Index(width) => out.push_str(&format!("{:0width$}", index)),
+                NameSegment::Total(width) => out.push_str(&format!("{:0width$}", total)),
+            }
+        }
+        out
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut rest = s;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                segments.push(NameSegment::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .map(|pos| open + pos)
+                .ok_or_else(|| anyhow!("Invalid name template: {}. Unclosed '{{'.", s))?;
+            let (key, width) = match rest[open + 1..close].split_once(':') {
+                Some((key, width)) => {
+                    let width = width.parse::<usize>().map_err(|_| {
+                        anyhow!("Invalid name template: {}. Bad width in {{{}}}.", s, key)
+                    })?;
+                    (key, Some(width))
+                }
+                None => (&rest[open + 1..close], None),
+            };
+            segments.push(match (key, width) {
+                ("name", None) => NameSegment::Name,
+                ("stem", None) => NameSegment::Stem,
+                ("ext", None) => NameSegment::Ext,
+                ("index", width) => NameSegment::Index(width.unwrap_or(0)),
+                ("total", width) => NameSegment::Total(width.unwrap_or(0)),
+                _ => {
+                    return Err(anyhow!(
+                        "Invalid name template: {}. Unknown placeholder {{{}}}.",
+                        s,
+                        &rest[open + 1..close]
+                    ))
+                }
+            });
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(NameSegment::Literal(rest.to_string()));
+        }
+
+        // Without the index every image would get the same name and overwrite the previous one
+        if !segments
+            .iter()
+            .any(|seg| matches!(seg, NameSegment::Index(_)))
+        {
+            return Err(anyhow!(
+                "Invalid name template: {}. It must contain {{index}} so names do not collide.",
+                s
+            ));
+        }
+        if segments
+            .iter()
+            .any(|seg| matches!(seg, NameSegment::Literal(text) if text.contains(['/', '\\', '}'])))
+        {
+            return Err(anyhow!(
+                "Invalid name template: {}. Names cannot contain path separators or a stray '}}'.",
+                s
+            ));
+        }
+        let extension = format!(".{}", QR_FILE_EXTENSION);
+        if !s.to_ascii_lowercase().ends_with(&extension) {
+            return Err(anyhow!(
+                "Invalid name template: {}. It must end with {} so the decoder finds the images.",
+                s,
+                extension
+            ));
+        }
+
+        Ok(NameTemplate {
+            source: s.to_string(),
+            segments,
+        })
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Default GIF frame duration in milliseconds.
+pub const DEFAULT_GIF_INTERVAL_MS: u64 = 2000;
+
+/// Order in which GIF and APNG frames and terminal carousel codes are shown. Chunk indices do
+/// not change, and the decoder accepts chunks in any order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameOrder {
+    /// By chunk index: the packets of each RaptorQ source block in turn.
+    #[default]
+    Sequential,
+    /// Round-robin over the source blocks, so a stretch of missed frames costs every block a
+    /// few packets instead of one block many. Streams of a single block stay sequential.
+    Interleaved,
+    /// A pseudo-random permutation, fixed by the seed (or 0 without one), which also mixes
+    /// repair packets in among the source packets.
+    Shuffled,
+}
+
+impl FromStr for FrameOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "sequential" => Ok(FrameOrder::Sequential),
+            "interleaved" | "interleave" => Ok(FrameOrder::Interleaved),
+            "shuffled" | "shuffle" => Ok(FrameOrder::Shuffled),
+            _ => Err(anyhow!(
+                "Invalid frame order: {}. Expected sequential, interleaved or shuffled.",
+                s
+            )),
+        }
+    }
+}
+
+/// How many times GIF and APNG players play an animation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopCount {
+    #[default]
+    Infinite,
+    /// This many times, at least once. A single pass suits animations embedded in documents.
+    Times(u16),
+}
+
+impl FromStr for LoopCount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "infinite" | "forever" => Ok(LoopCount::Infinite),
+            "once" => Ok(LoopCount::Times(1)),
+            n => match n.parse::<u16>() {
+                Ok(times) if times > 0 => Ok(LoopCount::Times(times)),
+                _ => Err(anyhow!(
+                    "Invalid loop count: {}. Expected infinite, once or a number from 1 to {}.",
+                    s,
+                    u16::MAX
+                )),
+            },
+        }
+    }
+}
+
+/// Positions of the packets to show, in order, given the source block of each packet.
+fn frame_order(blocks: &[u8], order: FrameOrder, seed: Option<u64>) -> Vec<usize> {
+    let mut positions: Vec<usize> = (0..blocks.len()).collect();
+    match order {
+        FrameOrder::Sequential => {}
+        FrameOrder::Interleaved => {
+            // The n-th packet of every block comes before the (n+1)-th of any
+            let mut seen = [0usize; 256];
+            let rank: Vec<(usize, u8)> = blocks
+                .iter()
+                .map(|&block| {
+                    seen[block as usize] += 1;
+                    (seen[block as usize], block)
+                })
+                .collect();
+            positions.sort_by_key(|&i| rank[i]);
+        }
+        FrameOrder::Shuffled => {
+            // Fisher-Yates
+            let mut state = seed.unwrap_or(0);
+            for i in (1..positions.len()).rev() {
+                let j = (splitmix64(&mut state) % (i as u64 + 1)) as usize;
+                positions.swap(i, j);
+            }
+        }
+    }
+    positions
+}
+
+/// Source block of the packet a chunk carries.
+fn chunk_block(chunk: &Chunk) -> u8 {
+    chunk.data.first().copied().unwrap_or(0)
+}
+
+/// Reorders `chunks` into the frame order of `options`. Each `cycle` of an animation is
+/// shuffled differently.
+fn order_chunks(chunks: Vec<Chunk>, options: &EncodeOptions, cycle: u32) -> Vec<Chunk> {
+    let blocks: Vec<u8> = chunks.iter().map(chunk_block).collect();
+    let seed = options.seed.unwrap_or(0).wrapping_add(cycle as u64);
+    let mut chunks: Vec<Option<Chunk>> = chunks.into_iter().map(Some).collect();
+    frame_order(&blocks, options.frame_order, Some(seed))
+        .into_iter()
+        .filter_map(|i| chunks[i].take())
+        .collect()
+}
+
+/// Settings shared by all encode entry points, built up from the defaults:
+///
+/// ```no_run
+/// # use fountain::{EcLevel, EncodeOptions};
+/// let options = EncodeOptions::new().ec_level(EcLevel::H).chunk_size(400).redundancy(2.0);
+/// ```
+///
+/// Options that do not apply to an output are ignored, e.g. the pixel scale in the terminal.
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    chunk_size: Option<usize>,
+    pixel_scale: u32,
+    ec_level: EcLevel,
+    style: QrStyle,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionSetting,
+    dedup: bool,
+    redundancy: Option<f64>,
+    name_template: Option<NameTemplate>,
+    overwrite: bool,
+    interval_ms: u64,
+    first_frame_ms: Option<u64>,
+    repeat_interval_ms: Option<u64>,
+    terminal_render: TerminalRender,
+    terminal_size: Option<(usize, usize)>,
+    shrink_to_terminal: bool,
+    qr_version: Option<Version>,
+    volume_size: Option<usize>,
+    seed: Option<u64>,
+    frame_order: FrameOrder,
+    cycles: u32,
+    fresh_cycles: bool,
+    frame_grid: Option<GridLayout>,
+    color_planes: bool,
+    gif_delta_frames: bool,
+    loop_count: LoopCount,
+    sync_frames: bool,
+    captions: bool,
+    wire_format: WireFormat,
+    pub(crate) cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: None,
+            pixel_scale: 4,
+            ec_level: DEFAULT_EC_LEVEL,
+            style: QrStyle::default(),
+            payload_encoding: PayloadEncoding::Base45,
+            compression: CompressionAlgorithm::Zlib.into(),
+            dedup: false,
+            redundancy: None,
+            name_template: None,
+            overwrite: false,
+            interval_ms: DEFAULT_GIF_INTERVAL_MS,
+            first_frame_ms: None,
+            repeat_interval_ms: None,
+            terminal_render: TerminalRender::default(),
+            terminal_size: None,
+            shrink_to_terminal: true,
+            qr_version: None,
+            volume_size: None,
+            seed: None,
+            frame_order: FrameOrder::default(),
+            cycles: 1,
+            fresh_cycles: false,
+            frame_grid: None,
+            color_planes: false,
+            gif_delta_frames: false,
+            loop_count: LoopCount::Infinite,
+            sync_frames: false,
+            captions: false,
+            wire_format: WireFormat::Native,
+            cancel: None,
+        }
+    }
+}
+
+impl EncodeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum payload bytes per QR code. Defaults to the most the output can hold,
+    /// and is reduced automatically when a code would not fit.
+    pub fn chunk_size(self, chunk_size: usize) -> Self {
+        self.maybe_chunk_size(Some(chunk_size))
+    }
+
+    fn maybe_chunk_size(mut self, chunk_size: Option<usize>) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn pixel_scale(mut self, pixel_scale: u32) -> Self {
+        self.pixel_scale = pixel_scale;
+        self
+    }
+
+    pub fn ec_level(mut self, ec_level: EcLevel) -> Self {
+        self.ec_level = ec_level;
+        self
+    }
+
+    pub fn style(mut self, style: QrStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn payload_encoding(mut self, payload_encoding: PayloadEncoding) -> Self {
+        self.payload_encoding = payload_encoding;
+        self
+    }
+
+    /// Compression algorithm, or a [`CompressionSetting`] that also picks its level.
+    pub fn compression(mut self, compression: impl Into<CompressionSetting>) -> Self {
+        self.compression = compression.into();
+        self
+    }
+
+    /// Replaces regions repeated anywhere in the file by references to their first copy
+    /// before compressing, so repeats further apart than the compressor's window, as in disk
+    /// images or long logs, are sent once. Decoders expand them without any option.
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Terminal size to fit terminal codes to: the one given, or else the detected one.
+    fn fitted_terminal_size(&self) -> TerminalSize {
+        match self.terminal_size {
+            Some((width, height)) => TerminalSize::Given { width, height },
+            None => TerminalSize::detect(),
+        }
+    }
+
+    /// Compression with deduplication applied when either asked for it.
+    fn compression_setting(&self) -> CompressionSetting {
+        CompressionSetting {
+            dedup: self.compression.dedup || self.dedup,
+            ..self.compression
+        }
+    }
+
+    /// Ratio of generated packets to source packets. Defaults to
+    /// [`DEFAULT_REDUNDANCY_FACTOR`], or [`DEFAULT_TERMINAL_REDUNDANCY_FACTOR`] in the terminal.
+    pub fn redundancy(mut self, redundancy_factor: f64) -> Self {
+        self.redundancy = Some(redundancy_factor);
+        self
+    }
+
+    pub fn name_template(mut self, name_template: NameTemplate) -> Self {
+        self.name_template = Some(name_template);
+        self
+    }
+
+    /// Replace files in the image output directory that have the names of the images about
+    /// to be written. By default the encode fails before writing any image instead.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// GIF frame duration.
+    pub fn interval_ms(mut self, interval_ms: u64) -> Self {
+        self.interval_ms = interval_ms;
+        self
+    }
+
+    /// Duration of the first frame of an animation: the start marker with
+    /// [`sync_frames`](Self::sync_frames), otherwise the first code. A longer dwell gives a
+    /// receiver time to find the code before the frames start changing.
+    pub fn first_frame_ms(mut self, ms: u64) -> Self {
+        self.first_frame_ms = Some(ms);
+        self
+    }
+
+    /// Frame duration of the [`cycles`](Self::cycles) after the first, whose codes a receiver
+    /// may already have.
+    pub fn repeat_interval_ms(mut self, ms: u64) -> Self {
+        self.repeat_interval_ms = Some(ms);
+        self
+    }
+
+    /// Characters used to draw QR codes in terminal output.
+    pub fn terminal_render(mut self, render: TerminalRender) -> Self {
+        self.terminal_render = render;
+        self
+    }
+
+    /// Fits terminal codes to a terminal of `width` x `height` characters instead of the
+    /// detected size, e.g. when the output is piped and no size can be read.
+    pub fn terminal_size(mut self, width: usize, height: usize) -> Self {
+        self.terminal_size = Some((width, height));
+        self
+    }
+
+    /// Whether terminal output reduces the payload size until the codes fit the terminal
+    /// (the default), or fails instead.
+    pub fn shrink_to_terminal(mut self, shrink: bool) -> Self {
+        self.shrink_to_terminal = shrink;
+        self
+    }
+
+    /// Pins every image and GIF frame to this QR version so all outputs have identical
+    /// dimensions. The payload size is reduced to what one code of that version holds.
+    pub fn qr_version(mut self, version: Version) -> Self {
+        self.qr_version = Some(version);
+        self
+    }
+
+    /// Splits files larger than `bytes` into volumes of that size, each encoded as its own
+    /// stream that decodes without the others, e.g. to print a large file over several
+    /// sessions. Decoding all volumes together reassembles the file. Image output names get a
+    /// `vol001_` prefix and GIF or APNG output one file per volume, such as `backup.001.gif`.
+    pub fn volume_size(mut self, bytes: usize) -> Self {
+        self.volume_size = Some(bytes);
+        self
+    }
+
+    /// Draws the repair packets from a stretch of the RaptorQ symbol space chosen by `seed`
+    /// instead of its start. Encoding is deterministic either way, so the same input and
+    /// options always give identical output. The seed is recorded in the stream, so whoever
+    /// holds a published backup can regenerate it bit for bit and compare.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Order of GIF and APNG frames and terminal carousel codes.
+    pub fn frame_order(mut self, order: FrameOrder) -> Self {
+        self.frame_order = order;
+        self
+    }
+
+    /// Writes the chunk sequence this many times into GIF and APNG output, so a single
+    /// recording of the animation has retransmissions built in. Shuffled frame orders use a
+    /// different permutation in each cycle.
+    pub fn cycles(mut self, cycles: u32) -> Self {
+        self.cycles = cycles.max(1);
+        self
+    }
+
+    /// With [`cycles`](Self::cycles), fill the cycles after the first with new repair packets
+    /// rather than repeating the first, so every frame of a recording is new to the decoder.
+    pub fn fresh_cycles(mut self, fresh: bool) -> Self {
+        self.fresh_cycles = fresh;
+        self
+    }
+
+    /// Shows a grid of codes in each GIF and APNG frame, each carrying its own chunk, so a
+    /// frame moves several times the data for screen-to-camera transfers. The codes are not
+    /// labelled, and the last frame is filled up with the first chunks again. Each code gets
+    /// a share of the frame, so the receiving camera must resolve smaller modules.
+    pub fn frame_grid(mut self, layout: GridLayout) -> Self {
+        self.frame_grid = Some(layout);
+        self
+    }
+
+    /// Overlays three codes in each GIF and APNG frame, or grid tile, one in each of the red,
+    /// green and blue planes, for three times the data per frame. Experimental, and only for
+    /// transfers where colours arrive exactly, such as decoding the GIF file itself rather than
+    /// filming it. Decoders separate the planes of such images by themselves.
+    pub fn color_planes(mut self, color_planes: bool) -> Self {
+        self.color_planes = color_planes;
+        self
+    }
+
+    /// Stores only the rectangle that changed in each GIF frame after the first, leaving out
+    /// the quiet zone, finder patterns and captions that stay the same, which saves about a
+    /// tenth of the GIF size. Players and decoders draw it over the frame before.
+    pub fn gif_delta_frames(mut self, delta_frames: bool) -> Self {
+        self.gif_delta_frames = delta_frames;
+        self
+    }
+
+    /// How many times GIF and APNG output plays; endlessly by default.
+    pub fn loop_count(mut self, loop_count: LoopCount) -> Self {
+        self.loop_count = loop_count;
+        self
+    }
+
+    /// Images tiled into each GIF and APNG frame.
+    fn tiles_per_frame(&self) -> usize {
+        self.frame_grid.map_or(1, |layout| layout.codes_per_image())
+    }
+
+    /// Codes shown in each GIF and APNG frame.
+    fn codes_per_frame(&self) -> usize {
+        self.tiles_per_frame() * if self.color_planes { 3 } else { 1 }
+    }
+
+    /// Starts GIF and APNG output with a black and a white frame, for the receiver's camera to
+    /// settle its exposure, and then a start-marker code carrying the frame count, interval and
+    /// transfer parameters. Terminal carousels show just the start marker. Decoders skip all
+    /// of them.
+    pub fn sync_frames(mut self, sync_frames: bool) -> Self {
+        self.sync_frames = sync_frames;
+        self
+    }
+
+    /// Stamps a caption under each code of image, GIF and APNG output: the chunk number out of
+    /// the total, a session ID and the embedded filename, so printed or screenshotted codes
+    /// can be sorted and checked by hand. The session ID is the first 8 hex digits of the
+    /// input's SHA-256. Tiled pages keep their own numbering instead.
+    pub fn captions(mut self, captions: bool) -> Self {
+        self.captions = captions;
+        self
+    }
+
+    /// Framing of the codes: this crate's chunks (the default), or BC-UR `ur:bytes` parts that
+    /// UR scanners and hardware wallets read. UR is available for GIF, APNG and terminal
+    /// carousel output. It sends the contents as they are, without filename, metadata or
+    /// compression, and does not combine with start markers, captions or volumes.
+    pub fn wire_format(mut self, wire_format: WireFormat) -> Self {
+        self.wire_format = wire_format;
+        self
+    }
+
+    /// Flag checked before each QR image is rendered; once set, the encode stops with
+    /// [`Cancelled`](crate::Cancelled). Terminal output is rendered up front and not affected.
+    pub fn cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Options matching the positional parameters of the image and GIF functions.
+    #[allow(clippy::too_many_arguments)]
+    fn for_images(
+        chunk_size: Option<usize>,
+        pixel_scale: u32,
+        ec_level: EcLevel,
+        style: &QrStyle,
+        payload_encoding: PayloadEncoding,
+        compression: CompressionAlgorithm,
+        redundancy_factor: f64,
+        name_template: Option<&NameTemplate>,
+    ) -> Self {
+        Self {
+            chunk_size,
+            pixel_scale,
+            ec_level,
+            style: style.clone(),
+            payload_encoding,
+            compression: compression.into(),
+            redundancy: Some(redundancy_factor),
+            name_template: name_template.cloned(),
+            ..Self::default()
+        }
+    }
 }
 
 pub struct TerminalQrData {
@@ -21,261 +712,2987 @@ pub struct TerminalQrData {
     pub total: usize,
     pub qr_strings: Vec<String>,
     pub effective_size: usize,
+    /// Name of the file each code belongs to, when the codes carry several files; empty
+    /// when they all carry `filename`.
+    pub frame_filenames: Vec<String>,
+    /// QR version of the largest code; terminal codes take the smallest version that holds
+    /// their payload.
+    pub qr_version: Version,
+    /// Bytes each code carries, in the order of `qr_strings`.
+    pub chunk_bytes: Vec<usize>,
+    /// Chunk each code carries, in the order of `qr_strings`, so receiver feedback can be
+    /// matched to codes; `None` for start markers and codes in other wire formats.
+    pub chunk_ids: Vec<Option<ChunkId>>,
+    /// Terminal size the codes were fitted to.
+    pub fit: TerminalFit,
 }
 
-/// Internal helper to handle the common logic of reading, compressing, and finding the optimal
-/// packet size for RaptorQ encoding while ensuring it fits via a provided check.
-fn prepare_chunks<F>(
-    input_path: &Path,
-    chunk_size: Option<usize>,
-    default_size: usize,
-    min_size: usize,
-    reduction_step: usize,
-    redundancy_factor: f64,
-    fit_check_fn: F,
-) -> Result<(Vec<Chunk>, usize, String)>
+/// Terminal size the codes of terminal output were fitted to, and whether the payload size
+/// was reduced to fit it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalFit {
+    pub size: TerminalSize,
+    /// Whether the payload size is below the requested one because larger codes did not fit.
+    pub shrunk: bool,
+}
+
+impl TerminalQrData {
+    /// Modules per side of the largest code, without the quiet zone.
+    pub fn modules(&self) -> u32 {
+        self.qr_version.width() as u32
+    }
+
+    /// Name of the file the code at `index` carries.
+    pub fn filename_of(&self, index: usize) -> &str {
+        self.frame_filenames.get(index).unwrap_or(&self.filename)
+    }
+}
+
+/// Compressed file contents together with the RaptorQ encoder for the packet size that fits.
+struct PreparedPayload {
+    encoder: RQEncoder,
+    transfer_length: u32,
+    packet_size: u16,
+    original_size: u32,
+    effective_size: usize,
+    filename: String,
+    volume: Option<Volume>,
+    /// Repair symbol id the repair packets of every block start at.
+    first_repair_id: u32,
+}
+
+impl PreparedPayload {
+    fn make_chunk(&self, index: u32, packet: &EncodingPacket) -> Chunk {
+        Chunk {
+            header: ChunkHeader {
+                version: if self.volume.is_some() {
+                    VOLUME_CHUNK_VERSION
+                } else {
+                    CHUNK_VERSION
+                },
+                total: self.transfer_length,
+                index,
+                packet_size: self.packet_size,
+                source_symbols: Some(self.source_packet_count()),
+                original_size: Some(self.original_size),
+                volume: self.volume,
+            },
+            data: packet.serialize(),
+        }
+    }
+
+    /// Start marker for an animation of `frames` data frames shown every `interval_ms`.
+    fn sync_info(&self, frames: usize, interval_ms: u64) -> SyncInfo {
+        SyncInfo {
+            frames: frames as u32,
+            interval_ms: interval_ms.min(u32::MAX as u64) as u32,
+            transfer_length: self.transfer_length,
+            packet_size: self.packet_size,
+            source_symbols: self.source_packet_count(),
+            original_size: self.original_size,
+            volume: self.volume,
+        }
+    }
+
+    fn source_packet_count(&self) -> u32 {
+        (self.transfer_length as usize).div_ceil(self.packet_size as usize) as u32
+    }
+
+    /// First repair id whose ESI would not fit RaptorQ's 24 bits in the largest source block.
+    fn repair_id_limit(&self) -> u32 {
+        let blocks = self.encoder.get_block_encoders().len() as u32;
+        let (largest, _, _, _) = partition(self.source_packet_count(), blocks);
+        (1 << 24) - extended_source_block_symbols(largest)
+    }
+
+    fn chunks(&self, redundancy_factor: f64) -> Vec<Chunk> {
+        encoded_packets(&self.encoder, self.first_repair_id, redundancy_factor)
+            .into_iter()
+            .enumerate()
+            .map(|(i, packet)| self.make_chunk(i as u32, &packet))
+            .collect()
+    }
+
+    /// Chunks for `cycle` (1 or later) of an animation whose first cycle is `first`: as many
+    /// packets per source block, all repair packets with symbol ids no other cycle uses.
+    /// Indices continue after the earlier cycles.
+    fn fresh_cycle_chunks(&self, first: &[Chunk], cycle: u32) -> Vec<Chunk> {
+        let mut per_block = vec![0u32; self.encoder.get_block_encoders().len()];
+        for chunk in first {
+            if let Some(count) = per_block.get_mut(chunk_block(chunk) as usize) {
+                *count += 1;
+            }
+        }
+
+        // The first cycle's repair ids of a block stay below first_repair_id + its count
+        let limit = self.repair_id_limit();
+        self.encoder
+            .get_block_encoders()
+            .iter()
+            .zip(per_block)
+            .flat_map(|(block, count)| {
+                let start = (u64::from(self.first_repair_id) + u64::from(cycle) * u64::from(count))
+                    % u64::from(limit);
+                wrapping_repair_packets(block, start as u32, count, limit)
+            })
+            .enumerate()
+            .map(|(i, packet)| self.make_chunk(cycle * first.len() as u32 + i as u32, &packet))
+            .collect()
+    }
+}
+
+/// `count` repair packets of `block` from repair id `start` on, starting over at 0 at `limit`
+/// so the ESIs stay within RaptorQ's 24 bits.
+fn wrapping_repair_packets(
+    block: &SourceBlockEncoder,
+    start: u32,
+    count: u32,
+    limit: u32,
+) -> Vec<EncodingPacket> {
+    let before_limit = count.min(limit - start);
+    let mut packets = block.repair_packets(start, before_limit);
+    packets.extend(block.repair_packets(0, count - before_limit));
+    packets
+}
+
+/// Frames of a GIF or APNG animation: the chunks in frame order, once per cycle.
+fn animation_frames(
+    prepared: &PreparedPayload,
+    chunks: Vec<Chunk>,
+    options: &EncodeOptions,
+) -> Vec<Chunk> {
+    (0..options.cycles)
+        .flat_map(|cycle| {
+            let cycle_chunks = if cycle > 0 && options.fresh_cycles {
+                prepared.fresh_cycle_chunks(&chunks, cycle)
+            } else {
+                chunks.clone()
+            };
+            order_chunks(cycle_chunks, options, cycle)
+        })
+        .collect()
+}
+
+/// Renders the data frames of a GIF or APNG animation of `chunks`, passing each to `processor`
+/// with its position and the total: one code per frame, or several with
+/// [`EncodeOptions::color_planes`] and [`EncodeOptions::frame_grid`].
+fn process_animation_frames<F>(
+    chunks: &[Chunk],
+    caption: Option<&Caption>,
+    options: &EncodeOptions,
+    mut processor: F,
+) -> Result<Option<QrSizing>>
 where
-    F: Fn(&[u8]) -> Result<bool>,
+    F: FnMut(RgbImage, usize, usize) -> Result<()>,
 {
+    if options.color_planes && caption.is_some() {
+        return Err(anyhow!("Captions are not available with colour planes"));
+    }
+
+    // Every frame is filled up with the first chunks again, so they all have the same size
+    let per_tile = options.codes_per_frame() / options.tiles_per_frame();
+    let per_frame = options.codes_per_frame();
+    let total = chunks.len().div_ceil(per_frame);
+    let chunks: Vec<Chunk> = chunks
+        .iter()
+        .cycle()
+        .take(total * per_frame)
+        .cloned()
+        .collect();
+    let mut planes = Vec::with_capacity(per_tile);
+    let mut tiles = Vec::with_capacity(options.tiles_per_frame());
+    let mut frame_px = None;
+    let sizing =
+        process_chunks_as_qr_images(&chunks, None, caption, options, |_, qr_image, i, _| {
+            planes.push(qr_image);
+            if planes.len() < per_tile {
+                return Ok(());
+            }
+            let tile = if options.color_planes {
+                merge_color_planes(&planes)?
+            } else {
+                planes.remove(0)
+            };
+            planes.clear();
+
+            let frame = match options.frame_grid {
+                Some(layout) => {
+                    tiles.push((tile, String::new()));
+                    if tiles.len() < layout.codes_per_image() {
+                        return Ok(());
+                    }
+                    let frame =
+                        tile_qr_images(&tiles, layout, options.pixel_scale, &options.style)?;
+                    tiles.clear();
+                    frame
+                }
+                None => tile,
+            };
+            frame_px.get_or_insert(frame.dimensions());
+            processor(frame, i / per_frame, total)
+        })?;
+    Ok(sizing.map(|sizing| QrSizing {
+        image_px: frame_px.unwrap_or(sizing.image_px),
+        ..sizing
+    }))
+}
+
+/// Number of frames [`sync_frames`] puts before the data of an animation.
+fn sync_frame_count(options: &EncodeOptions) -> usize {
+    if options.sync_frames {
+        3
+    } else {
+        0
+    }
+}
+
+/// Requested duration in milliseconds of each frame of an animation of `chunks` chunks, as
+/// [`animation_frames`] returns them: the sync frames, then the data frames.
+fn frame_durations(chunks: usize, options: &EncodeOptions) -> Vec<u64> {
+    let per_frame = options.codes_per_frame();
+    let first_cycle = (chunks / options.cycles.max(1) as usize).div_ceil(per_frame);
+    let repeat_ms = options.repeat_interval_ms.unwrap_or(options.interval_ms);
+    let mut durations: Vec<u64> = (0..sync_frame_count(options))
+        .map(|_| options.interval_ms)
+        .chain((0..chunks.div_ceil(per_frame)).map(|i| {
+            if i < first_cycle {
+                options.interval_ms
+            } else {
+                repeat_ms
+            }
+        }))
+        .collect();
+    // The start marker, or the first code
+    let first = sync_frame_count(options).saturating_sub(1);
+    if let (Some(ms), Some(duration)) = (options.first_frame_ms, durations.get_mut(first)) {
+        *duration = ms;
+    }
+    durations
+}
+
+/// Frames shown before the data of an animation whose frames are `width` x `height`: a black
+/// and a white one for the receiver's exposure, then `marker` on the quiet zone's colour.
+fn sync_frames(
+    marker: &SyncInfo,
+    width: u32,
+    height: u32,
+    options: &EncodeOptions,
+) -> Result<Vec<RgbImage>> {
+    let (code, _) = generate_qr_image(
+        &marker.to_qr_payload(options.payload_encoding),
+        options.qr_version,
+        options.pixel_scale,
+        options.ec_level,
+        &options.style,
+    )?;
+    if code.width() > width || code.height() > height {
+        return Err(anyhow!(
+            "Start marker does not fit the {}x{} frames of this animation",
+            width,
+            height
+        ));
+    }
+    let mut marker_frame = RgbImage::from_pixel(width, height, *code.get_pixel(0, 0));
+    image::imageops::overlay(
+        &mut marker_frame,
+        &code,
+        ((width - code.width()) / 2) as i64,
+        ((height - code.height()) / 2) as i64,
+    );
+    Ok(vec![
+        RgbImage::from_pixel(width, height, Rgb([0, 0, 0])),
+        RgbImage::from_pixel(width, height, Rgb([255, 255, 255])),
+        marker_frame,
+    ])
+}
+
+fn resolve_name_template(template: Option<&NameTemplate>, default: &str) -> NameTemplate {
+    template
+        .cloned()
+        .unwrap_or_else(|| default.parse().expect("default name templates are valid"))
+}
+
+/// Reads a file and returns its contents with the file name and attributes to embed in the stream.
+fn read_input_file(input_path: &Path) -> Result<(Vec<u8>, String, FileMetadata)> {
     let data = fs::read(input_path)?;
     let filename = input_path
         .file_name()
         .and_then(|s| s.to_str())
         .ok_or_else(|| anyhow!("Invalid filename"))?
         .to_string();
+    let metadata = read_file_metadata(input_path)?;
+    Ok((data, filename, metadata))
+}
+
+/// Reads the permission bits (on Unix) and modification time of `path`. Setuid, setgid and
+/// sticky bits are left out; the decoder would not restore them.
+pub fn read_file_metadata(path: &Path) -> Result<FileMetadata> {
+    let fs_metadata = fs::metadata(path)?;
+
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(fs_metadata.permissions().mode() & 0o777)
+    };
+    #[cfg(not(unix))]
+    let mode = None;
+
+    let mtime = fs_metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_secs() as i64);
+
+    Ok(FileMetadata {
+        mode,
+        mtime,
+        ..FileMetadata::default()
+    })
+}
+
+/// Internal helper to handle the common logic of packing, compressing, and finding the optimal
+/// packet size for RaptorQ encoding while ensuring it fits via a provided check.
+///
+/// The requested size is tried first; if it does not fit, the largest fitting size down to
+/// `min_size` is found by binary search, relying on smaller payloads never needing larger codes.
+#[allow(clippy::too_many_arguments)]
+fn prepare_payload<F>(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    chunk_size: Option<usize>,
+    default_size: usize,
+    min_size: usize,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionSetting,
+    volume: Option<Volume>,
+    seed: Option<u64>,
+    fit_check_fn: F,
+) -> Result<PreparedPayload>
+where
+    F: Fn(&[u8]) -> Result<bool>,
+{
+    let (compressed, original_size) =
+        compressed_object(data, filename, metadata, compression, seed)?;
+
+    let header_size = if volume.is_some() {
+        HEADER_SIZE_V3
+    } else {
+        HEADER_SIZE
+    };
+    let prepare_at = |size: usize| -> Result<Option<PreparedPayload>> {
+        // Ensure packet size is even for RaptorQ
+        let packet_size = size.saturating_sub(header_size).min(u16::MAX as usize) as u16;
+        let packet_size = packet_size - (packet_size % 2);
+        if packet_size < 4 {
+            return Ok(None);
+        }
+
+        let prepared = PreparedPayload {
+            encoder: RQEncoder::with_defaults(&compressed, packet_size),
+            transfer_length: compressed.len() as u32,
+            packet_size,
+            original_size,
+            effective_size: size,
+            filename: filename.to_string(),
+            volume,
+            first_repair_id: first_repair_id(seed),
+        };
+
+        // Generate one packet to test fit
+        let test_packets = prepared.encoder.get_encoded_packets(1);
+        let Some(first_packet) = test_packets.first() else {
+            return Ok(None);
+        };
+        let payload = prepared
+            .make_chunk(0, first_packet)
+            .to_qr_payload(payload_encoding)?;
+        Ok(fit_check_fn(&payload)?.then_some(prepared))
+    };
+
+    largest_fitting(chunk_size.unwrap_or(default_size), min_size, prepare_at)
+}
+
+/// Tries `max_size` first, then binary-searches down to `min_size` for the largest payload
+/// size that `prepare_at` accepts.
+fn largest_fitting<T, F>(max_size: usize, min_size: usize, prepare_at: F) -> Result<T>
+where
+    F: Fn(usize) -> Result<Option<T>>,
+{
+    if let Some(prepared) = prepare_at(max_size)? {
+        return Ok(prepared);
+    }
+
+    // `low` is the smallest size not yet ruled out; `high` is known not to fit
+    let (mut low, mut high) = (min_size.min(max_size), max_size);
+    let mut best = None;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match prepare_at(mid)? {
+            Some(prepared) => {
+                best = Some(prepared);
+                low = mid + 1;
+            }
+            None => high = mid,
+        }
+    }
+
+    best.ok_or_else(|| {
+        anyhow!(
+            "Data too large to fit in QR code even at minimum payload size ({} bytes).",
+            min_size
+        )
+    })
+}
+
+/// Prepares the payload and generates a fixed set of packets for it.
+#[allow(clippy::too_many_arguments)]
+fn prepare_chunks<F>(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    chunk_size: Option<usize>,
+    default_size: usize,
+    min_size: usize,
+    redundancy_factor: f64,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionSetting,
+    volume: Option<Volume>,
+    seed: Option<u64>,
+    fit_check_fn: F,
+) -> Result<(PreparedPayload, Vec<Chunk>)>
+where
+    F: Fn(&[u8]) -> Result<bool>,
+{
+    validate_redundancy_factor(redundancy_factor)?;
+
+    let prepared = prepare_payload(
+        data,
+        filename,
+        metadata,
+        chunk_size,
+        default_size,
+        min_size,
+        payload_encoding,
+        compression,
+        volume,
+        seed,
+        fit_check_fn,
+    )?;
+
+    let chunks = prepared.chunks(redundancy_factor);
+    Ok((prepared, chunks))
+}
+
+/// Helper function to split data into chunks using RaptorQ and ensure they fit into QR codes.
+/// Returns the prepared payload, which holds the effective payload size and the filename,
+/// and the chunks. `volume` marks the chunks as one volume of a split file.
+fn prepare_chunks_for_img(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    volume: Option<Volume>,
+    options: &EncodeOptions,
+) -> Result<(PreparedPayload, Vec<Chunk>)> {
+    ensure_native_wire_format(options)?;
+    let (pixel_scale, ec_level, version) =
+        (options.pixel_scale, options.ec_level, options.qr_version);
+    prepare_chunks(
+        data,
+        filename,
+        metadata,
+        options.chunk_size,
+        crate::chunk::MAX_PAYLOAD_SIZE,
+        min_image_payload_size(options),
+        options.redundancy.unwrap_or(DEFAULT_REDUNDANCY_FACTOR),
+        options.payload_encoding,
+        options.compression_setting(),
+        volume,
+        options.seed,
+        |encoded| {
+            Ok(
+                generate_qr_image(encoded, version, pixel_scale, ec_level, &QrStyle::default())
+                    .is_ok(),
+            )
+        },
+    )
+    .map_err(|e| anyhow!("Failed to generate QR codes: {}", e))
+}
+
+/// Smallest payload size tried for image and GIF output.
+fn min_image_payload_size(options: &EncodeOptions) -> usize {
+    // Small pinned versions hold less than the usual minimum
+    if options.qr_version.is_some() {
+        HEADER_SIZE + 4
+    } else {
+        100
+    }
+}
+
+/// Fails for outputs that only write this crate's own chunks.
+fn ensure_native_wire_format(options: &EncodeOptions) -> Result<()> {
+    match options.wire_format {
+        WireFormat::Native => Ok(()),
+        WireFormat::Ur => Err(anyhow!(
+            "The UR wire format is only available for GIF, APNG and terminal carousel output"
+        )),
+    }
+}
+
+/// Splits `data` into UR parts, as many as the redundancy factor asks of the fragment count,
+/// with the largest fragments whose parts `fit_check_fn` accepts. Returns the fragment size
+/// and the parts.
+fn prepare_ur_parts<F>(
+    data: &[u8],
+    options: &EncodeOptions,
+    default_size: usize,
+    min_size: usize,
+    default_redundancy: f64,
+    fit_check_fn: F,
+) -> Result<(usize, Vec<String>)>
+where
+    F: Fn(&[u8]) -> Result<bool>,
+{
+    if options.sync_frames || options.captions || options.volume_size.is_some() {
+        return Err(anyhow!(
+            "Start markers, captions and volumes are not available with the UR wire format"
+        ));
+    }
+    let redundancy_factor = options.redundancy.unwrap_or(default_redundancy);
+    validate_redundancy_factor(redundancy_factor)?;
+
+    let prepare_at = |size: usize| -> Result<Option<(UrEncoder, u32)>> {
+        let Ok(encoder) = UrEncoder::new(data, size) else {
+            return Ok(None);
+        };
+        // A single-part UR is complete on its own, so it is not repeated
+        let num_parts = match encoder.seq_len() {
+            1 => 1,
+            seq_len => (seq_len as f64 * redundancy_factor).ceil() as u32,
+        };
+        // The last part has the longest sequence number
+        Ok(fit_check_fn(encoder.part(num_parts).as_bytes())?.then_some((encoder, num_parts)))
+    };
+    let (encoder, num_parts) = largest_fitting(
+        options.chunk_size.unwrap_or(default_size),
+        min_size,
+        prepare_at,
+    )?;
+    let parts = (1..=num_parts)
+        .map(|seq_num| encoder.part(seq_num))
+        .collect();
+    Ok((encoder.fragment_len(), parts))
+}
+
+/// [`prepare_ur_parts`] for GIF and APNG output.
+fn prepare_ur_parts_for_img(data: &[u8], options: &EncodeOptions) -> Result<(usize, Vec<String>)> {
+    if options.frame_grid.is_some() || options.color_planes {
+        return Err(anyhow!(
+            "Frame grids and colour planes are not available with the UR wire format"
+        ));
+    }
+    let (pixel_scale, ec_level, version) =
+        (options.pixel_scale, options.ec_level, options.qr_version);
+    prepare_ur_parts(
+        data,
+        options,
+        crate::chunk::MAX_PAYLOAD_SIZE,
+        min_image_payload_size(options),
+        DEFAULT_REDUNDANCY_FACTOR,
+        |encoded| {
+            Ok(
+                generate_qr_image(encoded, version, pixel_scale, ec_level, &QrStyle::default())
+                    .is_ok(),
+            )
+        },
+    )
+    .map_err(|e| anyhow!("Failed to generate QR codes: {}", e))
+}
+
+/// Renders UR parts as QR images of one common version and passes each to `processor`
+/// with its position and the total.
+fn process_ur_parts_as_qr_images<F>(
+    parts: &[String],
+    options: &EncodeOptions,
+    mut processor: F,
+) -> Result<Option<QrSizing>>
+where
+    F: FnMut(image::RgbImage, usize, usize) -> Result<()>,
+{
+    let mut fixed_version = options.qr_version;
+    if fixed_version.is_none() {
+        for part in parts {
+            let version = qr_version_for(part.as_bytes(), options.ec_level)?;
+            if fixed_version.is_none_or(|v| version.width() > v.width()) {
+                fixed_version = Some(version);
+            }
+        }
+    }
+
+    let mut sizing: Option<QrSizing> = None;
+    for (i, part) in parts.iter().enumerate() {
+        check_cancelled(options.cancel.as_deref())?;
+        let (qr_image, version) = generate_qr_image(
+            part.as_bytes(),
+            fixed_version,
+            options.pixel_scale,
+            options.ec_level,
+            &options.style,
+        )?;
+        sizing
+            .get_or_insert_with(|| QrSizing::new(version, &qr_image, options))
+            .chunk_bytes
+            .push(part.len());
+        processor(qr_image, i, parts.len())?;
+        if parts.len() <= 10 || ((i + 1) % 10 == 0 || i + 1 == parts.len()) {
+            log::info!("  Processed frame {}/{}", i + 1, parts.len());
+        }
+    }
+    Ok(sizing)
+}
+
+/// Predicted outcome of encoding to images or a GIF, from [`estimate_encoding`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeEstimate {
+    /// Number of QR codes, i.e. images, or the codes of one GIF cycle.
+    pub chunks: usize,
+    /// Payload bytes per QR code, after reducing the requested size to what fits.
+    pub effective_size: usize,
+    /// QR version of every code.
+    pub qr_version: Version,
+    /// Width and height of each image in pixels; captions add to the height, and frame grids
+    /// tile several such images.
+    pub image_px: u32,
+    /// Approximate size of the GIF in bytes, with all its cycles and any sync frames.
+    pub gif_bytes_approx: u64,
+    /// Time to show every frame of the GIF once with the configured frame durations, as a GIF
+    /// stores them, sync frames included.
+    pub duration_at_interval: Duration,
+}
+
+/// Predicts what encoding `len` bytes to images or a GIF with `options` produces, without
+/// compressing or encoding any data, so callers can show "~2100 QR codes (~35 min)" upfront.
+///
+/// The contents are assumed not to compress, so typical files need at most the estimated
+/// number of chunks. The GIF size is extrapolated from a single rendered frame.
+pub fn estimate_encoding(len: u64, options: &EncodeOptions) -> Result<EncodeEstimate> {
+    let redundancy_factor = options.redundancy.unwrap_or(DEFAULT_REDUNDANCY_FACTOR);
+    validate_redundancy_factor(redundancy_factor)?;
+
+    let original_size =
+        u32::try_from(len).map_err(|_| anyhow!("Input of {} bytes is too large to encode", len))?;
+    let metadata = FileMetadata {
+        sha256: Some([0; 32]),
+        seed: options.seed,
+        ..FileMetadata::default()
+    };
+    let packed_len = len + pack_data_with_metadata(&[], "", &metadata).len() as u64;
+    // Data that does not compress is stored, which adds only the one-byte compression tag
+    let transfer_length = u32::try_from(packed_len + 1)
+        .map_err(|_| anyhow!("Input of {} bytes is too large to encode", len))?;
+
+    let (pixel_scale, ec_level, version) =
+        (options.pixel_scale, options.ec_level, options.qr_version);
+    let probe_at = |size: usize| -> Result<Option<(usize, u16, Vec<u8>)>> {
+        let packet_size = size.saturating_sub(HEADER_SIZE).min(u16::MAX as usize) as u16;
+        let packet_size = packet_size - (packet_size % 2);
+        if packet_size < 4 {
+            return Ok(None);
+        }
+
+        // How tightly the QR modes pack a payload depends on its content, so take the
+        // largest of a few chunks carrying random packets
+        let symbol_size =
+            ObjectTransmissionInformation::with_defaults(transfer_length as u64, packet_size)
+                .symbol_size();
+        let header = ChunkHeader {
+            version: CHUNK_VERSION,
+            total: transfer_length,
+            index: 0,
+            packet_size,
+            source_symbols: Some(transfer_length.div_ceil(packet_size as u32)),
+            original_size: Some(original_size),
+            volume: None,
+        };
+        let mut payload = Vec::new();
+        let mut widest = 0;
+        for seed in 1..=ESTIMATE_SAMPLES {
+            let sample =
+                probe_chunk(&header, symbol_size, seed).to_qr_payload(options.payload_encoding)?;
+            let width = qr_version_for(&sample, ec_level).map_or(i16::MAX, |v| v.width());
+            if width > widest {
+                (payload, widest) = (sample, width);
+            }
+        }
+        let fits = generate_qr_image(
+            &payload,
+            version,
+            pixel_scale,
+            ec_level,
+            &QrStyle::default(),
+        )
+        .is_ok();
+        Ok(fits.then_some((size, packet_size, payload)))
+    };
+    let (effective_size, packet_size, payload) = largest_fitting(
+        options.chunk_size.unwrap_or(crate::chunk::MAX_PAYLOAD_SIZE),
+        min_image_payload_size(options),
+        probe_at,
+    )?;
+
+    let info = ObjectTransmissionInformation::with_defaults(transfer_length as u64, packet_size);
+    let num_blocks = info.source_blocks() as u32;
+    let repair_per_block = repair_packets_per_block(
+        transfer_length.div_ceil(packet_size as u32),
+        num_blocks,
+        redundancy_factor,
+    );
+    let source_symbols = transfer_length.div_ceil(info.symbol_size() as u32);
+    let chunks = (source_symbols + repair_per_block * num_blocks) as usize;
+
+    let (frame, qr_version) =
+        generate_qr_image(&payload, version, pixel_scale, ec_level, &options.style)?;
+    let image_px = frame.width();
+    let mut gif = GifWriter::new(Vec::new(), options);
+    gif.push(&frame, options.interval_ms)?;
+    let frame_gif = gif.finish()?;
+
+    let per_frame = options.codes_per_frame() as u64;
+    let data_frames = (chunks as u64 * options.cycles as u64).div_ceil(per_frame);
+    let duration_ms = frame_durations(chunks * options.cycles as usize, options)
+        .into_iter()
+        .map(|ms| u64::from(gif_delay(ms)) * 10)
+        .sum();
+    Ok(EncodeEstimate {
+        chunks,
+        effective_size,
+        qr_version,
+        image_px,
+        gif_bytes_approx: frame_gif.len() as u64
+            * (data_frames * options.tiles_per_frame() as u64 + sync_frame_count(options) as u64),
+        duration_at_interval: Duration::from_millis(duration_ms),
+    })
+}
+
+/// Number of random payloads [`estimate_encoding`] sizes to find the QR version.
+const ESTIMATE_SAMPLES: u32 = 8;
+
+/// Millimetres per inch, to convert print resolutions.
+const MM_PER_INCH: f64 = 25.4;
+
+/// Pixel scale and code size for printing, from [`print_sizing`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintSizing {
+    /// Pixels per module that print modules at least as large as asked for.
+    pub pixel_scale: u32,
+    /// Size of each module as printed, in millimetres; pixel scales are whole, so this can
+    /// be larger than the size asked for.
+    pub module_mm: f64,
+    /// Largest QR version whose codes, quiet zone included, fit the print width.
+    pub max_version: Version,
+    /// Payload bytes per code at `max_version`, i.e. the largest chunk size that keeps codes
+    /// within the print width.
+    pub max_payload: usize,
+}
+
+/// Works out the pixel scale that prints QR modules of at least `module_mm` millimetres at
+/// `dpi`, and the largest code that fits `width_mm` at that scale with the quiet zone, error
+/// correction and payload encoding of `options`.
+///
+/// Scanners resolve a module size that depends on the camera distance, e.g. about 0.5 mm at
+/// arm's length, so this keeps printed codes readable while packing in as much as fits.
+pub fn print_sizing(
+    dpi: u32,
+    module_mm: f64,
+    width_mm: f64,
+    options: &EncodeOptions,
+) -> Result<PrintSizing> {
+    let positive = |mm: f64| mm.is_finite() && mm > 0.0;
+    if dpi == 0 || !positive(module_mm) || !positive(width_mm) {
+        return Err(anyhow!(
+            "Print resolution, module size and width must be positive"
+        ));
+    }
+    let pixel_scale = (module_mm / MM_PER_INCH * dpi as f64).ceil() as u32;
+    let width_px = (width_mm / MM_PER_INCH * dpi as f64).floor() as u32;
+    let margin = options.style.margin;
+    let max_version = (1..=40)
+        .rev()
+        .map(Version::Normal)
+        .find(|version| (version.width() as u32 + 2 * margin) * pixel_scale <= width_px)
+        .ok_or_else(|| {
+            anyhow!(
+                "Not even a version 1 QR code fits {} mm with {} mm modules at {} dpi",
+                width_mm,
+                module_mm,
+                dpi
+            )
+        })?;
+
+    // The payload per code hardly depends on the input length, so any large input will do
+    let sized = options
+        .clone()
+        .pixel_scale(pixel_scale)
+        .qr_version(max_version);
+    let max_payload = estimate_encoding(1 << 20, &sized)?.effective_size;
+    Ok(PrintSizing {
+        pixel_scale,
+        module_mm: pixel_scale as f64 * MM_PER_INCH / dpi as f64,
+        max_version,
+        max_payload,
+    })
+}
+
+/// Chunk with `header` and a random packet: the payload id of symbol `seed` in the
+/// first source block, followed by one symbol.
+fn probe_chunk(header: &ChunkHeader, symbol_size: u16, seed: u32) -> Chunk {
+    let mut state = seed;
+    let mut data = seed.to_be_bytes().to_vec();
+    data.extend((0..symbol_size).map(|_| {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (state >> 24) as u8
+    }));
+    Chunk {
+        header: ChunkHeader {
+            index: seed,
+            ..header.clone()
+        },
+        data,
+    }
+}
+
+fn prepare_terminal_payload(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    size: TerminalSize,
+    options: &EncodeOptions,
+) -> Result<PreparedPayload> {
+    ensure_native_wire_format(options)?;
+    let (ec_level, render) = (options.ec_level, options.terminal_render);
+    prepare_payload(
+        data,
+        filename,
+        metadata,
+        options.chunk_size,
+        DEFAULT_PAYLOAD_SIZE,
+        min_terminal_payload_size(options),
+        options.payload_encoding,
+        options.compression_setting(),
+        None,
+        options.seed,
+        |encoded| crate::qr::fits_in_terminal_of_size(encoded, ec_level, render, size),
+    )
+    .map_err(|e| terminal_too_small(e, size, options))
+}
+
+/// Smallest payload size tried for terminal output: the requested one when shrinking is off.
+fn min_terminal_payload_size(options: &EncodeOptions) -> usize {
+    if options.shrink_to_terminal {
+        50
+    } else {
+        options.chunk_size.unwrap_or(DEFAULT_PAYLOAD_SIZE)
+    }
+}
+
+/// Error for terminal codes that do not fit a terminal of `size` at any payload size tried.
+fn terminal_too_small(
+    e: anyhow::Error,
+    size: TerminalSize,
+    options: &EncodeOptions,
+) -> anyhow::Error {
+    let (width, height) = size.dimensions();
+    if !options.shrink_to_terminal {
+        return anyhow!(
+            "QR codes of {} bytes do not fit a {}x{} terminal, and shrinking them is off",
+            options.chunk_size.unwrap_or(DEFAULT_PAYLOAD_SIZE),
+            width,
+            height
+        );
+    }
+    anyhow!("Terminal too small ({}x{}) to display QR codes even at minimum payload size. Please increase terminal size. Underlying error: {}", width, height, e)
+}
+
+/// How terminal codes of `effective_size` bytes were fitted to a terminal of `size`.
+fn terminal_fit(size: TerminalSize, effective_size: usize, options: &EncodeOptions) -> TerminalFit {
+    TerminalFit {
+        size,
+        shrunk: effective_size < options.chunk_size.unwrap_or(DEFAULT_PAYLOAD_SIZE),
+    }
+}
+
+/// Encodes in-memory `data` for terminal display. `filename` is the name embedded in the
+/// stream, which the decoder uses when saving the file, and `metadata` the attributes it
+/// restores on the saved file.
+pub fn encode_bytes_for_terminal_with_options(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    options: &EncodeOptions,
+) -> Result<TerminalQrData> {
+    if options.wire_format == WireFormat::Ur {
+        return encode_ur_for_terminal(data, options);
+    }
+    let redundancy_factor = options
+        .redundancy
+        .unwrap_or(DEFAULT_TERMINAL_REDUNDANCY_FACTOR);
+    validate_redundancy_factor(redundancy_factor)?;
+
+    let size = options.fitted_terminal_size();
+    let prepared = prepare_terminal_payload(data, filename, metadata, size, options)?;
+    let chunks = order_chunks(prepared.chunks(redundancy_factor), options, 0);
+
+    let mut payloads = Vec::with_capacity(chunks.len() + 1);
+    let mut chunk_ids = Vec::with_capacity(chunks.len() + 1);
+    if options.sync_frames {
+        let marker = prepared.sync_info(chunks.len(), options.interval_ms);
+        payloads.push(marker.to_qr_payload(options.payload_encoding));
+        chunk_ids.push(None);
+    }
+    for chunk in chunks {
+        payloads.push(chunk.to_qr_payload(options.payload_encoding)?);
+        chunk_ids.push(Some(ChunkId::from(&chunk.header)));
+    }
+
+    let qr_strings = payloads
+        .iter()
+        .map(|payload| render_qr_to_terminal(payload, options.ec_level, options.terminal_render))
+        .collect::<Result<Vec<_>>>()?;
+    let (qr_version, chunk_bytes) = terminal_code_sizes(&payloads, options.ec_level)?;
+    Ok(TerminalQrData {
+        filename: prepared.filename,
+        total: qr_strings.len(),
+        qr_strings,
+        effective_size: prepared.effective_size,
+        frame_filenames: Vec::new(),
+        qr_version,
+        chunk_bytes,
+        chunk_ids,
+        fit: terminal_fit(size, prepared.effective_size, options),
+    })
+}
+
+/// QR version of the largest of `payloads` as terminal codes, and the size of each.
+fn terminal_code_sizes<P: AsRef<[u8]>>(
+    payloads: &[P],
+    ec_level: EcLevel,
+) -> Result<(Version, Vec<usize>)> {
+    let mut largest = Version::Normal(1);
+    for payload in payloads {
+        let version = qr_version_for(payload.as_ref(), ec_level)?;
+        if version.width() > largest.width() {
+            largest = version;
+        }
+    }
+    let sizes = payloads.iter().map(|payload| payload.as_ref().len());
+    Ok((largest, sizes.collect()))
+}
+
+/// Encodes several files into one terminal carousel that cycles between them, showing a
+/// code of each file in turn. Every file is its own stream, told apart from the others by
+/// its transfer length and packet size, so receivers assemble and complete each file on
+/// its own. `files` holds the contents, embedded name and attributes of each file.
+pub fn encode_files_for_terminal_with_options(
+    files: &[(Vec<u8>, String, FileMetadata)],
+    options: &EncodeOptions,
+) -> Result<TerminalQrData> {
+    if options.sync_frames {
+        return Err(anyhow!(
+            "A start marker describes a single stream; several files cannot have one"
+        ));
+    }
+    let redundancy_factor = options
+        .redundancy
+        .unwrap_or(DEFAULT_TERMINAL_REDUNDANCY_FACTOR);
+    validate_redundancy_factor(redundancy_factor)?;
+
+    let mut streams: Vec<(String, Vec<Vec<u8>>)> = Vec::with_capacity(files.len());
+    let mut stream_ids: Vec<Vec<ChunkId>> = Vec::with_capacity(files.len());
+    let mut keys: Vec<(u32, u16)> = Vec::with_capacity(files.len());
+    let mut effective_size = usize::MAX;
+    let size = options.fitted_terminal_size();
+    for (data, filename, metadata) in files {
+        let mut prepared = prepare_terminal_payload(data, filename, metadata, size, options)?;
+        // Receivers tell streams apart by transfer length and packet size, so a file that
+        // matches an earlier one on both gets slightly smaller packets
+        while keys.contains(&(prepared.transfer_length, prepared.packet_size)) {
+            let smaller = options.clone().chunk_size(prepared.effective_size - 2);
+            prepared = prepare_terminal_payload(data, filename, metadata, size, &smaller)?;
+        }
+        keys.push((prepared.transfer_length, prepared.packet_size));
+        effective_size = effective_size.min(prepared.effective_size);
+
+        let chunks = order_chunks(prepared.chunks(redundancy_factor), options, 0);
+        let payloads = chunks
+            .iter()
+            .map(|chunk| chunk.to_qr_payload(options.payload_encoding))
+            .collect::<Result<Vec<_>>>()?;
+        stream_ids.push(chunks.iter().map(|chunk| (&chunk.header).into()).collect());
+        streams.push((prepared.filename, payloads));
+    }
+
+    // One code of each file in turn; files with fewer codes drop out of later rounds
+    let longest = streams
+        .iter()
+        .map(|(_, codes)| codes.len())
+        .max()
+        .unwrap_or(0);
+    let mut payloads = Vec::new();
+    let mut chunk_ids = Vec::new();
+    let mut frame_filenames = Vec::new();
+    for round in 0..longest {
+        for ((filename, codes), ids) in streams.iter().zip(&stream_ids) {
+            if let Some(payload) = codes.get(round) {
+                payloads.push(payload);
+                chunk_ids.push(Some(ids[round]));
+                frame_filenames.push(filename.clone());
+            }
+        }
+    }
+    let qr_strings = payloads
+        .iter()
+        .map(|payload| render_qr_to_terminal(payload, options.ec_level, options.terminal_render))
+        .collect::<Result<Vec<_>>>()?;
+    let (qr_version, chunk_bytes) = terminal_code_sizes(&payloads, options.ec_level)?;
+
+    Ok(TerminalQrData {
+        filename: streams
+            .iter()
+            .map(|(filename, _)| filename.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        total: qr_strings.len(),
+        qr_strings,
+        effective_size,
+        frame_filenames,
+        qr_version,
+        chunk_bytes,
+        chunk_ids,
+        fit: terminal_fit(size, effective_size, options),
+    })
+}
+
+/// Terminal output in the UR wire format. The filename shown is [`UR_FILENAME`], which is
+/// what this crate's decoder saves UR streams as.
+fn encode_ur_for_terminal(data: &[u8], options: &EncodeOptions) -> Result<TerminalQrData> {
+    let (ec_level, render) = (options.ec_level, options.terminal_render);
+    let size = options.fitted_terminal_size();
+    let (effective_size, parts) = prepare_ur_parts(
+        data,
+        options,
+        DEFAULT_PAYLOAD_SIZE,
+        min_terminal_payload_size(options),
+        DEFAULT_TERMINAL_REDUNDANCY_FACTOR,
+        |encoded| crate::qr::fits_in_terminal_of_size(encoded, ec_level, render, size),
+    )
+    .map_err(|e| terminal_too_small(e, size, options))?;
+
+    let qr_strings = parts
+        .iter()
+        .map(|part| render_qr_to_terminal(part.as_bytes(), ec_level, render))
+        .collect::<Result<Vec<_>>>()?;
+    let (qr_version, chunk_bytes) = terminal_code_sizes(&parts, ec_level)?;
+    Ok(TerminalQrData {
+        filename: UR_FILENAME.to_string(),
+        total: qr_strings.len(),
+        qr_strings,
+        effective_size,
+        frame_filenames: Vec::new(),
+        qr_version,
+        chunk_ids: vec![None; chunk_bytes.len()],
+        chunk_bytes,
+        // Fragments split the data evenly, so compare with those of the requested size
+        fit: TerminalFit {
+            size,
+            shrunk: UrEncoder::new(data, options.chunk_size.unwrap_or(DEFAULT_PAYLOAD_SIZE))
+                .is_ok_and(|requested| effective_size < requested.fragment_len()),
+        },
+    })
+}
+
+/// Reads `input_path` and encodes it with [`encode_bytes_for_terminal_with_options`].
+pub fn encode_file_for_terminal_with_options(
+    input_path: &Path,
+    options: &EncodeOptions,
+) -> Result<TerminalQrData> {
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_for_terminal_with_options(&data, &filename, &metadata, options)
+}
+
+/// Encodes in-memory `data` for terminal display. `filename` is the name embedded in the
+/// stream, which the decoder uses when saving the file, and `metadata` the attributes it
+/// restores on the saved file.
+#[allow(clippy::too_many_arguments)]
+pub fn encode_bytes_for_terminal(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    chunk_size: Option<usize>,
+    ec_level: EcLevel,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
+    redundancy_factor: f64,
+) -> Result<TerminalQrData> {
+    let options = EncodeOptions::new()
+        .ec_level(ec_level)
+        .payload_encoding(payload_encoding)
+        .compression(compression)
+        .redundancy(redundancy_factor)
+        .maybe_chunk_size(chunk_size);
+    encode_bytes_for_terminal_with_options(data, filename, metadata, &options)
+}
+
+/// Reads `input_path` and encodes it with [`encode_bytes_for_terminal`].
+pub fn encode_file_for_terminal(
+    input_path: &Path,
+    chunk_size: Option<usize>,
+    ec_level: EcLevel,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
+    redundancy_factor: f64,
+) -> Result<TerminalQrData> {
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_for_terminal(
+        &data,
+        &filename,
+        &metadata,
+        chunk_size,
+        ec_level,
+        payload_encoding,
+        compression,
+        redundancy_factor,
+    )
+}
+
+/// Fails with a helpful message when `payload` needs more than one QR code.
+fn ensure_fits_one_code(
+    payload: &[u8],
+    name: &str,
+    flag: &str,
+    options: &EncodeOptions,
+) -> Result<()> {
+    let fits = match options.qr_version {
+        Some(version) => qrcode::QrCode::with_version(payload, version, options.ec_level).is_ok(),
+        None => qr_version_for(payload, options.ec_level).is_ok(),
+    };
+    if !fits {
+        return Err(anyhow!(
+            "{} is too large for a single QR code ({} byte payload); encode it without {}",
+            name,
+            payload.len(),
+            flag
+        ));
+    }
+    Ok(())
+}
+
+/// Saves `payload` as the only QR image in `output_dir` and returns its file name and sizing.
+fn write_one_code_image(
+    payload: &[u8],
+    name: &str,
+    output_dir: &Path,
+    options: &EncodeOptions,
+) -> Result<(String, QrSizing)> {
+    let (qr_image, version) = generate_qr_image(
+        payload,
+        options.qr_version,
+        options.pixel_scale,
+        options.ec_level,
+        &options.style,
+    )?;
+
+    fs::create_dir_all(output_dir)?;
+    let name_template =
+        resolve_name_template(options.name_template.as_ref(), DEFAULT_NAME_TEMPLATE);
+    let output_filename = name_template.render(name, 1, 1);
+    ensure_new_images(output_dir, std::slice::from_ref(&output_filename), options)?;
+    save_qr_image(&qr_image, &output_dir.join(&output_filename))?;
+    log::info!("  Generated QR code 1/1: {}", &output_filename);
+    let mut sizing = QrSizing::new(version, &qr_image, options);
+    sizing.chunk_bytes.push(payload.len());
+    Ok((output_filename, sizing))
+}
+
+fn render_one_code_for_terminal(
+    payload: &[u8],
+    name: &str,
+    options: &EncodeOptions,
+) -> Result<TerminalQrData> {
+    let qr_string = render_qr_to_terminal(payload, options.ec_level, options.terminal_render)?;
+    Ok(TerminalQrData {
+        filename: name.to_string(),
+        total: 1,
+        qr_strings: vec![qr_string],
+        effective_size: payload.len(),
+        frame_filenames: Vec::new(),
+        qr_version: qr_version_for(payload, options.ec_level)?,
+        chunk_bytes: vec![payload.len()],
+        chunk_ids: vec![None],
+        // A single code is never shrunk; it either fits the QR capacity or fails
+        fit: TerminalFit {
+            size: options.fitted_terminal_size(),
+            shrunk: false,
+        },
+    })
+}
+
+/// Writes `data` as one self-contained QR code into `output_dir`, for short secrets such
+/// as keys or TOTP seeds. It has no chunk header or RaptorQ packets, and is compressed only
+/// when that helps, so the code is as small as the content allows. File metadata is not
+/// carried. Fails if the file does not fit in one code.
+pub fn encode_bytes_to_single_image_with_options(
+    data: &[u8],
+    filename: &str,
+    output_dir: &Path,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    let payload = to_single_code_payload(data, filename, options.payload_encoding)?;
+    ensure_fits_one_code(&payload, filename, "--single", options)?;
+    let (output_filename, sizing) = write_one_code_image(&payload, filename, output_dir, options)?;
+
+    Ok(EncodeResult {
+        num_chunks: 1,
+        output_files: vec![output_filename],
+        effective_size: payload.len(),
+        sha256: sha256_hex(data),
+        timing: None,
+        qr: Some(sizing),
+    })
+}
+
+/// Like [`encode_bytes_to_single_image_with_options`], rendered for terminal display.
+pub fn encode_bytes_for_terminal_single_with_options(
+    data: &[u8],
+    filename: &str,
+    options: &EncodeOptions,
+) -> Result<TerminalQrData> {
+    let payload = to_single_code_payload(data, filename, options.payload_encoding)?;
+    ensure_fits_one_code(&payload, filename, "--single", options)?;
+    render_one_code_for_terminal(&payload, filename, options)
+}
+
+/// Writes `text` as a plain QR code into `output_dir`, with no fountain header, so any phone
+/// camera app can read it. `name` only sets the image file name. Fails if the text does
+/// not fit in one code.
+pub fn encode_text_to_image_with_options(
+    text: &str,
+    name: &str,
+    output_dir: &Path,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    ensure_fits_one_code(text.as_bytes(), name, "--raw-text", options)?;
+    let (output_filename, sizing) =
+        write_one_code_image(text.as_bytes(), name, output_dir, options)?;
+
+    Ok(EncodeResult {
+        num_chunks: 1,
+        output_files: vec![output_filename],
+        effective_size: text.len(),
+        sha256: sha256_hex(text.as_bytes()),
+        timing: None,
+        qr: Some(sizing),
+    })
+}
+
+/// Like [`encode_text_to_image_with_options`], rendered for terminal display.
+pub fn encode_text_for_terminal_with_options(
+    text: &str,
+    name: &str,
+    options: &EncodeOptions,
+) -> Result<TerminalQrData> {
+    ensure_fits_one_code(text.as_bytes(), name, "--raw-text", options)?;
+    render_one_code_for_terminal(text.as_bytes(), name, options)
+}
+
+/// Endless stream of terminal QR codes for a single file.
+///
+/// All source packets are emitted first, followed by freshly generated repair packets with
+/// increasing ESIs (round-robin over source blocks), so every frame shown is new information
+/// and a receiver that joins late or drops frames still converges. The repair ids start over
+/// at 0 before the ESIs would leave RaptorQ's 24-bit range.
+pub struct TerminalQrStream {
+    pub filename: String,
+    /// Number of source packets; a receiver needs slightly more than this to decode.
+    pub source_packets: usize,
+    pub effective_size: usize,
+    /// Terminal size the codes are fitted to.
+    pub fit: TerminalFit,
+    prepared: PreparedPayload,
+    ec_level: EcLevel,
+    render: TerminalRender,
+    payload_encoding: PayloadEncoding,
+    source_queue: Vec<EncodingPacket>,
+    next_index: u32,
+    next_repair_id: u32,
+    /// First repair id whose ESI would not fit 24 bits in the largest block.
+    repair_id_limit: u32,
+    next_block: usize,
+}
+
+impl TerminalQrStream {
+    /// Number of packets emitted so far.
+    pub fn emitted(&self) -> u32 {
+        self.next_index
+    }
+
+    fn next_packet(&mut self) -> EncodingPacket {
+        if let Some(packet) = self.source_queue.get(self.next_index as usize) {
+            self.next_index += 1;
+            return packet.clone();
+        }
+
+        let blocks = self.prepared.encoder.get_block_encoders();
+        let packet = blocks[self.next_block]
+            .repair_packets(self.next_repair_id, 1)
+            .pop()
+            .expect("repair_packets(_, 1) returns one packet");
+
+        self.next_block += 1;
+        if self.next_block == blocks.len() {
+            self.next_block = 0;
+            self.next_repair_id = (self.next_repair_id + 1) % self.repair_id_limit;
+        }
+        self.next_index += 1;
+        packet
+    }
+}
+
+impl Iterator for TerminalQrStream {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.next_index;
+        let packet = self.next_packet();
+        let chunk = self.prepared.make_chunk(index, &packet);
+
+        Some(
+            chunk
+                .to_qr_payload(self.payload_encoding)
+                .and_then(|payload| render_qr_to_terminal(&payload, self.ec_level, self.render)),
+        )
+    }
+}
+
+/// Like [`encode_bytes_for_terminal_with_options`], but generates packets endlessly.
+/// The redundancy option does not apply.
+pub fn encode_bytes_for_terminal_stream_with_options(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    options: &EncodeOptions,
+) -> Result<TerminalQrStream> {
+    let size = options.fitted_terminal_size();
+    let prepared = prepare_terminal_payload(data, filename, metadata, size, options)?;
+    let next_repair_id = prepared.first_repair_id;
+
+    let source_queue: Vec<EncodingPacket> = prepared
+        .encoder
+        .get_block_encoders()
+        .iter()
+        .flat_map(|block| block.source_packets())
+        .collect();
+    let repair_id_limit = prepared.repair_id_limit();
+
+    Ok(TerminalQrStream {
+        filename: prepared.filename.clone(),
+        source_packets: source_queue.len(),
+        effective_size: prepared.effective_size,
+        fit: terminal_fit(size, prepared.effective_size, options),
+        prepared,
+        ec_level: options.ec_level,
+        render: options.terminal_render,
+        payload_encoding: options.payload_encoding,
+        source_queue,
+        next_index: 0,
+        next_repair_id,
+        repair_id_limit,
+        next_block: 0,
+    })
+}
+
+/// Reads `input_path` and encodes it with [`encode_bytes_for_terminal_stream_with_options`].
+pub fn encode_file_for_terminal_stream_with_options(
+    input_path: &Path,
+    options: &EncodeOptions,
+) -> Result<TerminalQrStream> {
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_for_terminal_stream_with_options(&data, &filename, &metadata, options)
+}
+
+pub fn encode_bytes_for_terminal_stream(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    chunk_size: Option<usize>,
+    ec_level: EcLevel,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
+) -> Result<TerminalQrStream> {
+    let options = EncodeOptions::new()
+        .ec_level(ec_level)
+        .payload_encoding(payload_encoding)
+        .compression(compression)
+        .maybe_chunk_size(chunk_size);
+    encode_bytes_for_terminal_stream_with_options(data, filename, metadata, &options)
+}
+
+/// Reads `input_path` and encodes it with [`encode_bytes_for_terminal_stream`].
+pub fn encode_file_for_terminal_stream(
+    input_path: &Path,
+    chunk_size: Option<usize>,
+    ec_level: EcLevel,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
+) -> Result<TerminalQrStream> {
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_for_terminal_stream(
+        &data,
+        &filename,
+        &metadata,
+        chunk_size,
+        ec_level,
+        payload_encoding,
+        compression,
+    )
+}
+
+/// Text stamped under each code of one encoding when [`EncodeOptions::captions`] is on.
+struct Caption {
+    filename: String,
+    session: String,
+    /// Highest chunk index plus one, the 87 in `12/87`.
+    total: u32,
+}
+
+impl Caption {
+    fn new(options: &EncodeOptions, filename: &str, data: &[u8], chunks: &[Chunk]) -> Option<Self> {
+        options.captions.then(|| Caption {
+            filename: filename.to_string(),
+            session: sha256_hex(data)[..8].to_string(),
+            total: chunks
+                .iter()
+                .map(|chunk| chunk.header.index + 1)
+                .max()
+                .unwrap_or(0),
+        })
+    }
+
+    fn lines(&self, chunk: &Chunk) -> Vec<String> {
+        let mut first = format!("{}/{} {}", chunk.header.index + 1, self.total, self.session);
+        if let Some(volume) = chunk.header.volume {
+            first.push_str(&format!(" vol {}/{}", volume.index + 1, volume.count));
+        }
+        vec![first, self.filename.clone()]
+    }
+}
+
+/// Internal helper to process a sequence of chunks as QR images with a consistent version.
+/// Renders `chunks` as QR images, passing each to `processor` with its position and the total.
+/// When `only` is given, just the chunks with those indices are rendered, at the same size
+/// the full set would have.
+fn process_chunks_as_qr_images<F>(
+    chunks: &[Chunk],
+    only: Option<&[u32]>,
+    caption: Option<&Caption>,
+    options: &EncodeOptions,
+    mut processor: F,
+) -> Result<Option<QrSizing>>
+where
+    F: FnMut(&Chunk, image::RgbImage, usize, usize) -> Result<()>,
+{
+    if let Some(&index) = only
+        .unwrap_or_default()
+        .iter()
+        .find(|&&index| index as usize >= chunks.len())
+    {
+        return Err(anyhow!(
+            "Chunk index {} is out of range: this encoding has chunks 0-{}",
+            index,
+            chunks.len() - 1
+        ));
+    }
+    let selected: Vec<usize> = match only {
+        Some(indices) => (0..chunks.len())
+            .filter(|&i| indices.contains(&chunks[i].header.index))
+            .collect(),
+        None => (0..chunks.len()).collect(),
+    };
+
+    let total = selected.len();
+    let payloads = chunks
+        .iter()
+        .map(|chunk| chunk.to_qr_payload(options.payload_encoding))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Payloads of equal length can still need different versions depending on how their
+    // content segments, so use the largest one for all images to keep dimensions identical.
+    let mut fixed_version = options.qr_version;
+    if fixed_version.is_none() {
+        for payload in &payloads {
+            let version = qr_version_for(payload, options.ec_level)?;
+            if fixed_version.is_none_or(|v| version.width() > v.width()) {
+                fixed_version = Some(version);
+            }
+        }
+    }
+
+    let mut sizing: Option<QrSizing> = None;
+    for (i, &chunk_pos) in selected.iter().enumerate() {
+        check_cancelled(options.cancel.as_deref())?;
+        let (qr_image, version) = generate_qr_image(
+            &payloads[chunk_pos],
+            fixed_version,
+            options.pixel_scale,
+            options.ec_level,
+            &options.style,
+        )?;
+        let qr_image = match caption {
+            Some(caption) => add_caption(
+                &qr_image,
+                &caption.lines(&chunks[chunk_pos]),
+                options.pixel_scale,
+                &options.style,
+            ),
+            None => qr_image,
+        };
+        sizing
+            .get_or_insert_with(|| QrSizing::new(version, &qr_image, options))
+            .chunk_bytes
+            .push(payloads[chunk_pos].len());
+
+        processor(&chunks[chunk_pos], qr_image, i, total)?;
+    }
+
+    Ok(sizing)
+}
+
+/// Writes one PNG per chunk of `data` into `output_dir`.
+pub fn encode_bytes_to_images_with_options(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    encode_volumes(data, options, |part, volume| {
+        write_chunk_images(part, filename, metadata, output_dir, None, volume, options)
+    })
+}
+
+/// Reads `input_path` and encodes it with [`encode_bytes_to_images_with_options`].
+pub fn encode_file_to_images_with_options(
+    input_path: &Path,
+    output_dir: &Path,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_to_images_with_options(&data, &filename, &metadata, output_dir, options)
+}
+
+/// Like [`encode_bytes_to_images_with_options`], but only writes the chunks with the given
+/// indices, e.g. the ones a receiver reported missing. `metadata` and the options must match
+/// the original encoding so that the same chunks are regenerated under the same file names.
+/// Writes each chunk to a file of its own in `output_dir`, as the raw bytes a QR code would
+/// carry in binary payload encoding, so that other transports (NFC tags, Bluetooth, a
+/// messenger) can carry the stream. `decode_from_chunk_files` reads them back.
+///
+/// The payload size defaults to [`crate::chunk::MAX_PAYLOAD_SIZE`]; no QR code limits it.
+pub fn encode_bytes_to_chunk_files_with_options(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    ensure_native_wire_format(options)?;
+    encode_volumes(data, options, |part, volume| {
+        write_chunk_files(part, filename, metadata, output_dir, volume, options)
+    })
+}
+
+fn write_chunk_files(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
+    volume: Option<Volume>,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    fs::create_dir_all(output_dir)?;
+
+    let size = options.chunk_size.unwrap_or(crate::chunk::MAX_PAYLOAD_SIZE);
+    let (prepared, chunks) = prepare_chunks(
+        data,
+        filename,
+        metadata,
+        Some(size),
+        size,
+        HEADER_SIZE_V3 + 8,
+        options.redundancy.unwrap_or(DEFAULT_REDUNDANCY_FACTOR),
+        PayloadEncoding::Binary,
+        options.compression_setting(),
+        volume,
+        options.seed,
+        |payload| Ok(payload.len() <= size),
+    )?;
+
+    let mut output_files = Vec::with_capacity(chunks.len());
+    for chunk in &chunks {
+        check_cancelled(options.cancel.as_deref())?;
+        let mut output_filename = format!(
+            "{}_{:04}.chunk",
+            prepared.filename.replace('.', "_"),
+            chunk.header.index + 1
+        );
+        if let Some(volume) = volume {
+            output_filename = format!("vol{:03}_{}", volume.index + 1, output_filename);
+        }
+        fs::write(
+            output_dir.join(&output_filename),
+            chunk.to_qr_payload(PayloadEncoding::Binary)?,
+        )?;
+        log::info!(
+            "  Wrote chunk {}/{}: {}",
+            output_files.len() + 1,
+            chunks.len(),
+            &output_filename
+        );
+        output_files.push(output_filename);
+    }
+
+    Ok(EncodeResult {
+        num_chunks: output_files.len(),
+        output_files,
+        effective_size: prepared.effective_size,
+        sha256: sha256_hex(data),
+        timing: None,
+        qr: None,
+    })
+}
+
+pub fn encode_bytes_to_selected_images_with_options(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
+    indices: &[u32],
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    ensure_single_volume(data, options, "Selected chunks")?;
+    write_chunk_images(
+        data,
+        filename,
+        metadata,
+        output_dir,
+        Some(indices),
+        None,
+        options,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn encode_bytes_to_images(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
+    chunk_size: Option<usize>,
+    pixel_scale: u32,
+    ec_level: EcLevel,
+    style: &QrStyle,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
+    redundancy_factor: f64,
+    name_template: Option<&NameTemplate>,
+) -> Result<EncodeResult> {
+    let options = EncodeOptions::for_images(
+        chunk_size,
+        pixel_scale,
+        ec_level,
+        style,
+        payload_encoding,
+        compression,
+        redundancy_factor,
+        name_template,
+    );
+    encode_bytes_to_images_with_options(data, filename, metadata, output_dir, &options)
+}
+
+/// Like [`encode_bytes_to_images`], but only writes the chunks with the given indices, e.g.
+/// the ones a receiver reported missing. The other parameters, including `metadata`, must
+/// match the original encoding so that the same chunks are regenerated under the same file names.
+#[allow(clippy::too_many_arguments)]
+pub fn encode_bytes_to_selected_images(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
+    chunk_size: Option<usize>,
+    pixel_scale: u32,
+    ec_level: EcLevel,
+    style: &QrStyle,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
+    redundancy_factor: f64,
+    indices: &[u32],
+    name_template: Option<&NameTemplate>,
+) -> Result<EncodeResult> {
+    let options = EncodeOptions::for_images(
+        chunk_size,
+        pixel_scale,
+        ec_level,
+        style,
+        payload_encoding,
+        compression,
+        redundancy_factor,
+        name_template,
+    );
+    encode_bytes_to_selected_images_with_options(
+        data, filename, metadata, output_dir, indices, &options,
+    )
+}
+
+/// Splits `data` into the volumes `options` asks for, or keeps it whole when it fits in one.
+fn split_volumes<'a>(
+    data: &'a [u8],
+    options: &EncodeOptions,
+) -> Result<Vec<(&'a [u8], Option<Volume>)>> {
+    let volume_size = match options.volume_size {
+        Some(0) => return Err(anyhow!("Volume size must be at least one byte")),
+        Some(size) if data.len() > size => size,
+        _ => return Ok(vec![(data, None)]),
+    };
+    let parts: Vec<&[u8]> = data.chunks(volume_size).collect();
+    let count = u16::try_from(parts.len()).map_err(|_| {
+        anyhow!(
+            "A volume size of {} bytes splits the input into {} volumes; at most {} are supported",
+            volume_size,
+            parts.len(),
+            u16::MAX
+        )
+    })?;
+    Ok(parts
+        .into_iter()
+        .zip(0..)
+        .map(|(part, index)| (part, Some(Volume { index, count })))
+        .collect())
+}
+
+/// Encodes every volume of `data` with `encode_volume` and merges the results.
+fn encode_volumes<F>(
+    data: &[u8],
+    options: &EncodeOptions,
+    mut encode_volume: F,
+) -> Result<EncodeResult>
+where
+    F: FnMut(&[u8], Option<Volume>) -> Result<EncodeResult>,
+{
+    let volumes = split_volumes(data, options)?;
+    if let [(whole, None)] = volumes[..] {
+        return encode_volume(whole, None);
+    }
+
+    let mut merged = EncodeResult {
+        num_chunks: 0,
+        output_files: Vec::new(),
+        effective_size: usize::MAX,
+        sha256: sha256_hex(data),
+        timing: None,
+        qr: None,
+    };
+    for (part, volume) in volumes {
+        if let Some(volume) = volume {
+            log::info!(
+                "Volume {}/{} ({} bytes):",
+                volume.index + 1,
+                volume.count,
+                part.len()
+            );
+        }
+        let result = encode_volume(part, volume)?;
+        merged.num_chunks += result.num_chunks;
+        merged.output_files.extend(result.output_files);
+        merged.effective_size = merged.effective_size.min(result.effective_size);
+        match (&mut merged.qr, result.qr) {
+            (Some(merged), Some(qr)) => merged.merge(qr),
+            (merged, qr) => *merged = merged.take().or(qr),
+        }
+        if let Some(timing) = result.timing {
+            match &mut merged.timing {
+                Some(merged) => merged.frame_ms.extend(timing.frame_ms),
+                None => merged.timing = Some(timing),
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// Fails when `options` would split `data` into volumes, for outputs that cannot hold them.
+fn ensure_single_volume(data: &[u8], options: &EncodeOptions, output: &str) -> Result<()> {
+    if split_volumes(data, options)?.len() > 1 {
+        return Err(anyhow!("{} cannot be split into volumes", output));
+    }
+    Ok(())
+}
+
+/// `path` with the volume number inserted before the extension, e.g. `backup.002.gif`.
+fn volume_path(path: &Path, volume: Option<Volume>) -> PathBuf {
+    let Some(volume) = volume else {
+        return path.to_path_buf();
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}", volume.part_name(&stem), ext.to_string_lossy()),
+        None => volume.part_name(&stem),
+    };
+    path.with_file_name(name)
+}
+
+fn write_chunk_images(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
+    only: Option<&[u32]>,
+    volume: Option<Volume>,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    fs::create_dir_all(output_dir)?;
+
+    let (prepared, chunks) = prepare_chunks_for_img(data, filename, metadata, volume, options)?;
+    let (effective_size, filename) = (prepared.effective_size, prepared.filename);
+
+    let name_template =
+        resolve_name_template(options.name_template.as_ref(), DEFAULT_NAME_TEMPLATE);
+    let image_name = |chunk: &Chunk| {
+        let name = name_template.render(&filename, chunk.header.index as usize + 1, chunks.len());
+        match volume {
+            Some(volume) => format!("vol{:03}_{}", volume.index + 1, name),
+            None => name,
+        }
+    };
+    let names: Vec<String> = chunks
+        .iter()
+        .filter(|chunk| only.is_none_or(|only| only.contains(&chunk.header.index)))
+        .map(image_name)
+        .collect();
+    ensure_new_images(output_dir, &names, options)?;
+    let mut output_files = Vec::with_capacity(chunks.len());
+
+    let caption = Caption::new(options, &filename, data, &chunks);
+    let qr = process_chunks_as_qr_images(
+        &chunks,
+        only,
+        caption.as_ref(),
+        options,
+        |chunk, qr_image, i, total| {
+            let output_filename = image_name(chunk);
+            let output_path = output_dir.join(&output_filename);
+            save_qr_image(&qr_image, &output_path)?;
+
+            log::info!(
+                "  Generated QR code {}/{}: {}",
+                i + 1,
+                total,
+                &output_filename
+            );
+
+            output_files.push(output_filename);
+            Ok(())
+        },
+    )?;
+
+    Ok(EncodeResult {
+        num_chunks: output_files.len(),
+        output_files,
+        effective_size,
+        sha256: sha256_hex(data),
+        timing: None,
+        qr,
+    })
+}
+
+/// Fails if a file named like one of the images in `names` is already in `output_dir`, so a
+/// second encode into the same directory cannot silently replace the first one's images.
+fn ensure_new_images(output_dir: &Path, names: &[String], options: &EncodeOptions) -> Result<()> {
+    if options.overwrite {
+        return Ok(());
+    }
+    let existing: Vec<&str> = names
+        .iter()
+        .filter(|name| output_dir.join(name).exists())
+        .map(String::as_str)
+        .collect();
+    match existing.as_slice() {
+        [] => Ok(()),
+        [first, ..] => Err(anyhow!(
+            "{} of the images to write already exist in {}, e.g. {}; remove them, pick another \
+             name template, or overwrite them with --force",
+            existing.len(),
+            output_dir.display(),
+            first
+        )),
+    }
+}
+
+/// Reads `input_path` and encodes it with [`encode_bytes_to_images`].
+#[allow(clippy::too_many_arguments)]
+pub fn encode_file_to_images(
+    input_path: &Path,
+    output_dir: &Path,
+    chunk_size: Option<usize>,
+    pixel_scale: u32,
+    ec_level: EcLevel,
+    style: &QrStyle,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
+    redundancy_factor: f64,
+    name_template: Option<&NameTemplate>,
+) -> Result<EncodeResult> {
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_to_images(
+        &data,
+        &filename,
+        &metadata,
+        output_dir,
+        chunk_size,
+        pixel_scale,
+        ec_level,
+        style,
+        payload_encoding,
+        compression,
+        redundancy_factor,
+        name_template,
+    )
+}
 
-    let packed = pack_data(&data, &filename);
-    let compressed = compress(&packed)?;
+/// Like [`encode_bytes_to_images_with_options`], but tiles several QR codes into each output
+/// image so fewer files need to be printed or shared.
+pub fn encode_bytes_to_tiled_images_with_options(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
+    layout: GridLayout,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    if layout.codes_per_image() == 0 {
+        return Err(anyhow!(
+            "Grid layout must have at least one column and one row"
+        ));
+    }
 
-    let mut current_size = chunk_size.unwrap_or(default_size);
+    ensure_single_volume(data, options, "Tiled output")?;
+    fs::create_dir_all(output_dir)?;
 
-    loop {
-        // Ensure packet size is even for RaptorQ
-        let packet_size = (current_size.saturating_sub(HEADER_SIZE)) as u16;
-        let packet_size = packet_size - (packet_size % 2);
+    let (prepared, chunks) = prepare_chunks_for_img(data, filename, metadata, None, options)?;
+    let (effective_size, filename) = (prepared.effective_size, prepared.filename);
 
-        if packet_size < 4 {
-            if current_size <= min_size {
-                break;
-            }
-            current_size = current_size.saturating_sub(reduction_step).max(min_size);
-            continue;
-        }
+    let name_template =
+        resolve_name_template(options.name_template.as_ref(), DEFAULT_PAGE_NAME_TEMPLATE);
+    let mut output_files = Vec::new();
+    let mut tiles = Vec::with_capacity(layout.codes_per_image());
+    let num_pages = chunks.len().div_ceil(layout.codes_per_image());
+    let names: Vec<String> = (1..=num_pages)
+        .map(|page| name_template.render(&filename, page, num_pages))
+        .collect();
+    ensure_new_images(output_dir, &names, options)?;
 
-        let rq_encoder = RQEncoder::with_defaults(&compressed, packet_size);
+    let mut page_px = None;
+    let qr = process_chunks_as_qr_images(&chunks, None, None, options, |_, qr_image, i, total| {
+        tiles.push((qr_image, format!("{}/{}", i + 1, total)));
+        if tiles.len() < layout.codes_per_image() && i + 1 < total {
+            return Ok(());
+        }
 
-        // Generate one packet to test fit
-        let test_packets = rq_encoder.get_encoded_packets(1);
-        if let Some(first_packet) = test_packets.first() {
-            let chunk = Chunk {
-                header: ChunkHeader {
-                    version: 1,
-                    total: compressed.len() as u32,
-                    index: 0,
-                    packet_size,
-                },
-                data: first_packet.serialize(),
-            };
+        let page = tile_qr_images(&tiles, layout, options.pixel_scale, &options.style)?;
+        tiles.clear();
+        page_px.get_or_insert(page.dimensions());
 
-            let chunk_bytes = chunk.to_bytes()?;
-            let encoded = base45::encode(&chunk_bytes);
-
-            if fit_check_fn(encoded.as_bytes())? {
-                // Fits. Generate all packets.
-                let source_packets = (compressed.len() as f64 / packet_size as f64).ceil() as u32;
-                let total_packets = (source_packets as f64 * redundancy_factor).ceil() as u32;
-                let total_packets = total_packets.max(source_packets + 2);
-
-                let packets_data = rq_encoder.get_encoded_packets(total_packets);
-                let mut chunks = Vec::with_capacity(packets_data.len());
-
-                for (i, packet) in packets_data.into_iter().enumerate() {
-                    chunks.push(Chunk {
-                        header: ChunkHeader {
-                            version: 1,
-                            total: compressed.len() as u32,
-                            index: i as u32,
-                            packet_size,
-                        },
-                        data: packet.serialize(),
-                    });
-                }
+        let output_filename = name_template.render(&filename, output_files.len() + 1, num_pages);
+        let output_path = output_dir.join(&output_filename);
+        save_qr_image(&page, &output_path)?;
 
-                return Ok((chunks, current_size, filename));
-            }
-        }
+        log::info!(
+            "  Generated page {}/{}: {}",
+            output_files.len() + 1,
+            num_pages,
+            &output_filename
+        );
 
-        if current_size > min_size {
-            current_size = current_size.saturating_sub(reduction_step).max(min_size);
-        } else {
-            break;
-        }
-    }
+        output_files.push(output_filename);
+        Ok(())
+    })?;
 
-    Err(anyhow!(
-        "Data too large to fit in QR code even at minimum payload size ({} bytes).",
-        min_size
-    ))
+    Ok(EncodeResult {
+        num_chunks: chunks.len(),
+        output_files,
+        effective_size,
+        sha256: sha256_hex(data),
+        timing: None,
+        qr: qr.map(|qr| QrSizing {
+            image_px: page_px.unwrap_or(qr.image_px),
+            ..qr
+        }),
+    })
 }
 
-/// Helper function to split data into chunks using RaptorQ and ensure they fit into QR codes.
-/// Returns the chunks, the effective payload size used, and the filename string.
-fn prepare_chunks_for_img(
+/// Reads `input_path` and encodes it with [`encode_bytes_to_tiled_images_with_options`].
+pub fn encode_file_to_tiled_images_with_options(
     input_path: &Path,
+    output_dir: &Path,
+    layout: GridLayout,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_to_tiled_images_with_options(
+        &data, &filename, &metadata, output_dir, layout, options,
+    )
+}
+
+/// Like [`encode_bytes_to_images`], but tiles several QR codes into each output image
+/// so fewer files need to be printed or shared.
+#[allow(clippy::too_many_arguments)]
+pub fn encode_bytes_to_tiled_images(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_dir: &Path,
     chunk_size: Option<usize>,
     pixel_scale: u32,
+    ec_level: EcLevel,
+    style: &QrStyle,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
     redundancy_factor: f64,
-) -> Result<(Vec<Chunk>, usize, String)> {
-    prepare_chunks(
-        input_path,
+    layout: GridLayout,
+    name_template: Option<&NameTemplate>,
+) -> Result<EncodeResult> {
+    let options = EncodeOptions::for_images(
         chunk_size,
-        crate::chunk::MAX_PAYLOAD_SIZE,
-        100, // min_size
-        50,  // reduction_step
+        pixel_scale,
+        ec_level,
+        style,
+        payload_encoding,
+        compression,
         redundancy_factor,
-        |encoded| Ok(generate_qr_image(encoded, None, pixel_scale).is_ok()),
+        name_template,
+    );
+    encode_bytes_to_tiled_images_with_options(
+        data, filename, metadata, output_dir, layout, &options,
     )
-    .map_err(|e| anyhow!("Failed to generate QR codes: {}", e))
 }
 
-pub fn encode_file_for_terminal(
+/// Like [`encode_file_to_images`], but tiles several QR codes into each output image
+/// so fewer files need to be printed or shared.
+#[allow(clippy::too_many_arguments)]
+pub fn encode_file_to_tiled_images(
     input_path: &Path,
+    output_dir: &Path,
     chunk_size: Option<usize>,
-) -> Result<TerminalQrData> {
-    let (chunks, effective_size, filename) = prepare_chunks(
-        input_path,
+    pixel_scale: u32,
+    ec_level: EcLevel,
+    style: &QrStyle,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
+    redundancy_factor: f64,
+    layout: GridLayout,
+    name_template: Option<&NameTemplate>,
+) -> Result<EncodeResult> {
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_to_tiled_images(
+        &data,
+        &filename,
+        &metadata,
+        output_dir,
         chunk_size,
-        DEFAULT_PAYLOAD_SIZE,
-        50, // min_size
-        20, // reduction_step
-        2.0, // redundancy_factor
-        |encoded| crate::qr::fits_in_terminal(encoded),
+        pixel_scale,
+        ec_level,
+        style,
+        payload_encoding,
+        compression,
+        redundancy_factor,
+        layout,
+        name_template,
     )
-    .map_err(|e| anyhow!("Terminal too small to display QR codes even at minimum payload size. Please increase terminal size. Underlying error: {}", e))?;
-
-    let total = chunks.len();
-    let mut qr_strings = Vec::with_capacity(total);
+}
 
-    for chunk in chunks {
-        let chunk_bytes = chunk.to_bytes()?;
-        let encoded = base45::encode(&chunk_bytes);
-        let qr_string = render_qr_to_terminal(encoded.as_bytes())?;
-        qr_strings.push(qr_string);
+/// Writes all chunks of `data` as frames of a GIF, which loops endlessly unless
+/// [`EncodeOptions::loop_count`] says otherwise.
+pub fn encode_bytes_to_gif_with_options(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_gif: &Path,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    if options.wire_format == WireFormat::Ur {
+        return write_ur_gif(data, output_gif, options);
     }
+    encode_volumes(data, options, |part, volume| {
+        let output_gif = volume_path(output_gif, volume);
+        write_gif(part, filename, metadata, &output_gif, volume, options)
+    })
+}
 
-    Ok(TerminalQrData {
-        filename,
-        total,
-        qr_strings,
+fn write_gif(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_gif: &Path,
+    volume: Option<Volume>,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    let (prepared, chunks) = prepare_chunks_for_img(data, filename, metadata, volume, options)?;
+    let effective_size = prepared.effective_size;
+    let chunks = animation_frames(&prepared, chunks, options);
+
+    let mut gif = start_gif(output_gif, options)?;
+    let durations = frame_durations(chunks.len(), options);
+    warn_short_gif_delays(&durations);
+    let interval_ms = u64::from(gif_delay(options.interval_ms)) * 10;
+    let frames = chunks.len().div_ceil(options.codes_per_frame());
+    let marker = prepared.sync_info(frames, interval_ms);
+    let caption = Caption::new(options, &prepared.filename, data, &chunks);
+
+    let qr = process_animation_frames(&chunks, caption.as_ref(), options, |qr_image, i, total| {
+        if i == 0 && options.sync_frames {
+            let frames = sync_frames(&marker, qr_image.width(), qr_image.height(), options)?;
+            for (frame, &ms) in frames.iter().zip(&durations) {
+                gif.push(frame, ms)?;
+            }
+        }
+        gif.push(&qr_image, durations[sync_frame_count(options) + i])?;
+
+        if total <= 10 || ((i + 1) % 10 == 0 || i + 1 == total) {
+            log::info!("  Processed frame {}/{}", i + 1, total);
+        }
+        Ok(())
+    })?;
+    let timing = AnimationTiming {
+        frame_ms: gif.frame_ms.clone(),
+        interval_ms,
+    };
+    gif.finish()?;
+
+    Ok(EncodeResult {
+        num_chunks: chunks.len(),
+        output_files: vec![output_gif.to_string_lossy().to_string()],
         effective_size,
+        sha256: sha256_hex(data),
+        timing: Some(timing),
+        qr,
     })
 }
 
-/// Internal helper to process a sequence of chunks as QR images with a consistent version.
-fn process_chunks_as_qr_images<F>(
-    chunks: &[Chunk],
-    pixel_scale: u32,
-    mut processor: F,
-) -> Result<()>
-where
-    F: FnMut(&Chunk, image::RgbImage, usize, usize) -> Result<()>,
-{
-    let mut fixed_version: Option<Version> = None;
-    let total = chunks.len();
+/// Starts a GIF, creating its directory.
+fn start_gif(output_gif: &Path, options: &EncodeOptions) -> Result<GifWriter<BufWriter<fs::File>>> {
+    if let Some(parent) = output_gif.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file = BufWriter::new(fs::File::create(output_gif)?);
+    Ok(GifWriter::new(file, options))
+}
+
+/// Writes GIF frames with a palette of exactly their colours, which is two for plain codes,
+/// so they need no quantizing unless a logo brings more than 256. A frame that repeats the
+/// one before only lengthens its delay. With [`EncodeOptions::gif_delta_frames`], frames
+/// after the first store just the rectangle that changed.
+struct GifWriter<W: Write> {
+    writer: Option<W>,
+    encoder: Option<gif::Encoder<W>>,
+    /// Duration of each frame written or pending, as stored.
+    frame_ms: Vec<u64>,
+    delta_frames: bool,
+    loop_count: LoopCount,
+    /// The last frame as shown, and its encoding, held back in case the next frame repeats it.
+    previous: Option<RgbImage>,
+    pending: Option<gif::Frame<'static>>,
+}
+
+impl<W: Write> GifWriter<W> {
+    fn new(writer: W, options: &EncodeOptions) -> Self {
+        Self {
+            writer: Some(writer),
+            encoder: None,
+            frame_ms: Vec::new(),
+            delta_frames: options.gif_delta_frames,
+            loop_count: options.loop_count,
+            previous: None,
+            pending: None,
+        }
+    }
+
+    /// Adds a frame shown for `delay_ms`, rounded to the nearest hundredth of a second. The
+    /// first one sets the size of the animation.
+    fn push(&mut self, image: &RgbImage, delay_ms: u64) -> Result<()> {
+        let (width, height) = (
+            gif_dimension(image.width())?,
+            gif_dimension(image.height())?,
+        );
+        let delay = gif_delay(delay_ms);
+        if self.previous.as_ref() == Some(image) {
+            if let (Some(pending), Some(ms)) = (&mut self.pending, self.frame_ms.last_mut()) {
+                pending.delay = pending.delay.saturating_add(delay);
+                *ms = u64::from(pending.delay) * 10;
+            }
+            return Ok(());
+        }
 
-    for (i, chunk) in chunks.iter().enumerate() {
-        let chunk_bytes = chunk.to_bytes()?;
-        let encoded = base45::encode(&chunk_bytes);
+        let delta = match &self.previous {
+            Some(previous) if self.delta_frames && previous.dimensions() == image.dimensions() => {
+                delta_gif_frame(previous, image)
+            }
+            _ => None,
+        };
+        let mut frame = match delta.or_else(|| indexed_gif_frame(image, 0, 0, width, height)) {
+            Some(frame) => frame,
+            // More colours than a palette holds
+            None => gif::Frame::from_rgb_speed(width, height, image.as_raw(), 10),
+        };
+        frame.delay = delay;
+        self.frame_ms.push(u64::from(delay) * 10);
 
-        let (qr_image, version) =
-            generate_qr_image(encoded.as_bytes(), fixed_version, pixel_scale)?;
+        self.write_pending()?;
+        if self.encoder.is_none() {
+            let writer = self
+                .writer
+                .take()
+                .ok_or_else(|| anyhow!("GIF already finished"))?;
+            let mut encoder = gif::Encoder::new(writer, width, height, &[])?;
+            // GIF counts the plays after the first, and a single play needs no count at all
+            match self.loop_count {
+                LoopCount::Infinite => encoder.set_repeat(gif::Repeat::Infinite)?,
+                LoopCount::Times(1) => {}
+                LoopCount::Times(times) => encoder.set_repeat(gif::Repeat::Finite(times - 1))?,
+            }
+            self.encoder = Some(encoder);
+        }
+        self.pending = Some(frame);
+        self.previous = Some(image.clone());
+        Ok(())
+    }
 
-        if fixed_version.is_none() {
-            fixed_version = Some(version);
+    fn write_pending(&mut self) -> Result<()> {
+        if let (Some(encoder), Some(frame)) = (&mut self.encoder, self.pending.take()) {
+            encoder.write_frame(&frame)?;
         }
+        Ok(())
+    }
 
-        processor(chunk, qr_image, i, total)?;
+    /// Writes the last frame and the GIF trailer, and returns the writer.
+    fn finish(mut self) -> Result<W> {
+        self.write_pending()?;
+        match self.encoder.take() {
+            Some(encoder) => Ok(encoder.into_inner()?),
+            None => Err(anyhow!("A GIF needs at least one frame")),
+        }
     }
+}
 
-    Ok(())
+/// `ms` in the hundredths of a second a GIF stores, rounded to the nearest but never to 0 for
+/// a frame that should show.
+fn gif_delay(ms: u64) -> u16 {
+    let delay = (ms.saturating_add(5) / 10).max(u64::from(ms > 0));
+    delay.try_into().unwrap_or(u16::MAX)
 }
 
-pub fn encode_file_to_images(
+/// Warns of GIF frames shorter than browsers play: they show delays under 20ms for 100ms.
+fn warn_short_gif_delays(durations: &[u64]) {
+    if let Some(ms) = durations
+        .iter()
+        .copied()
+        .filter(|&ms| gif_delay(ms) < 2)
+        .min()
+    {
+        log::warn!(
+            "GIF frames of {}ms are shown for 100ms by most browsers; use at least 20ms",
+            ms
+        );
+    }
+}
+
+fn gif_dimension(pixels: u32) -> Result<u16> {
+    u16::try_from(pixels)
+        .map_err(|_| anyhow!("Frames of {} pixels are too large for a GIF", pixels))
+}
+
+/// The `width` x `height` rectangle of `image` at `left`, `top` as a frame with a palette of
+/// its colours, or `None` when they do not fit in one.
+fn indexed_gif_frame(
+    image: &RgbImage,
+    left: u16,
+    top: u16,
+    width: u16,
+    height: u16,
+) -> Option<gif::Frame<'static>> {
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut indices = Vec::with_capacity(width as usize * height as usize);
+    for y in top as u32..top as u32 + height as u32 {
+        for x in left as u32..left as u32 + width as u32 {
+            let color = image.get_pixel(x, y).0;
+            let index = match palette.iter().position(|&c| c == color) {
+                Some(index) => index,
+                None if palette.len() < 256 => {
+                    palette.push(color);
+                    palette.len() - 1
+                }
+                None => return None,
+            };
+            indices.push(index as u8);
+        }
+    }
+
+    let mut frame = gif::Frame::from_palette_pixels(width, height, indices, palette.concat(), None);
+    (frame.left, frame.top) = (left, top);
+    Some(frame)
+}
+
+/// The rectangle of `image` that differs from `previous`, as a frame drawn over it, or `None`
+/// if its colours do not fit in a palette. Marking the unchanged pixels in it transparent
+/// would cost more than it saves, as the modules of different codes agree only at random.
+fn delta_gif_frame(previous: &RgbImage, image: &RgbImage) -> Option<gif::Frame<'static>> {
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if previous.get_pixel(x, y) != pixel {
+            (left, top) = (left.min(x), top.min(y));
+            (right, bottom) = (right.max(x + 1), bottom.max(y + 1));
+        }
+    }
+    if left == u32::MAX {
+        // Repeated frames are merged before this
+        return None;
+    }
+    indexed_gif_frame(
+        image,
+        left as u16,
+        top as u16,
+        (right - left) as u16,
+        (bottom - top) as u16,
+    )
+}
+
+/// GIF output in the UR wire format.
+fn write_ur_gif(data: &[u8], output_gif: &Path, options: &EncodeOptions) -> Result<EncodeResult> {
+    let (effective_size, parts) = prepare_ur_parts_for_img(data, options)?;
+    let mut gif = start_gif(output_gif, options)?;
+    let first_ms = options.first_frame_ms.unwrap_or(options.interval_ms);
+    warn_short_gif_delays(&[first_ms, options.interval_ms]);
+    let qr = process_ur_parts_as_qr_images(&parts, options, |qr_image, i, _| {
+        gif.push(
+            &qr_image,
+            if i == 0 {
+                first_ms
+            } else {
+                options.interval_ms
+            },
+        )
+    })?;
+    let timing = AnimationTiming {
+        frame_ms: gif.frame_ms.clone(),
+        interval_ms: u64::from(gif_delay(options.interval_ms)) * 10,
+    };
+    gif.finish()?;
+
+    Ok(EncodeResult {
+        num_chunks: parts.len(),
+        output_files: vec![output_gif.to_string_lossy().to_string()],
+        effective_size,
+        sha256: sha256_hex(data),
+        timing: Some(timing),
+        qr,
+    })
+}
+
+/// Reads `input_path` and encodes it with [`encode_bytes_to_gif_with_options`].
+pub fn encode_file_to_gif_with_options(
     input_path: &Path,
-    output_dir: &Path,
-    chunk_size: Option<usize>,
-    pixel_scale: u32,
+    output_gif: &Path,
+    options: &EncodeOptions,
 ) -> Result<EncodeResult> {
-    fs::create_dir_all(output_dir)?;
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_to_gif_with_options(&data, &filename, &metadata, output_gif, options)
+}
+
+/// Starts an APNG with `num_frames` RGB frames of the given size, played as
+/// [`EncodeOptions::loop_count`] says.
+fn start_apng(
+    output_apng: &Path,
+    width: u32,
+    height: u32,
+    num_frames: u32,
+    options: &EncodeOptions,
+) -> Result<png::Writer<BufWriter<fs::File>>> {
+    let file = BufWriter::new(fs::File::create(output_apng)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let plays = match options.loop_count {
+        LoopCount::Infinite => 0,
+        LoopCount::Times(times) => u32::from(times),
+    };
+    encoder.set_animated(num_frames, plays)?;
+    Ok(encoder.write_header()?)
+}
 
-    let (chunks, effective_size, filename) =
-        prepare_chunks_for_img(input_path, chunk_size, pixel_scale, 1.5)?;
+/// Writes `image` as the next APNG frame, shown for `delay_ms` up to 65535, and records the
+/// stored duration in `frame_ms`.
+fn write_apng_frame<W: Write>(
+    writer: &mut png::Writer<W>,
+    image: &RgbImage,
+    delay_ms: u64,
+    frame_ms: &mut Vec<u64>,
+) -> Result<()> {
+    let delay = delay_ms.min(u16::MAX as u64) as u16;
+    writer.set_frame_delay(delay, 1000)?;
+    writer.write_image_data(image.as_raw())?;
+    frame_ms.push(u64::from(delay));
+    Ok(())
+}
 
-    let mut output_files = Vec::with_capacity(chunks.len());
+/// Writes all chunks of `data` as frames of an APNG, which loops like GIF output. Unlike GIF,
+/// frames keep full colour, so styled codes are not quantized to a 256-colour palette.
+pub fn encode_bytes_to_apng_with_options(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_apng: &Path,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    if options.wire_format == WireFormat::Ur {
+        return write_ur_apng(data, output_apng, options);
+    }
+    encode_volumes(data, options, |part, volume| {
+        let output_apng = volume_path(output_apng, volume);
+        write_apng(part, filename, metadata, &output_apng, volume, options)
+    })
+}
 
-    process_chunks_as_qr_images(&chunks, pixel_scale, |chunk, qr_image, i, total| {
-        let output_filename = format!(
-            "{}_{:04}.{}",
-            filename.replace('.', "_"),
-            chunk.header.index + 1,
-            QR_FILE_EXTENSION
-        );
-        let output_path = output_dir.join(&output_filename);
-        save_qr_image(&qr_image, &output_path)?;
+fn write_apng(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_apng: &Path,
+    volume: Option<Volume>,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    let (prepared, chunks) = prepare_chunks_for_img(data, filename, metadata, volume, options)?;
+    let effective_size = prepared.effective_size;
+    let chunks = animation_frames(&prepared, chunks, options);
 
-        println!(
-            "  Generated QR code {}/{}: {}",
-            i + 1,
-            total,
-            &output_filename
-        );
+    if let Some(parent) = output_apng.parent() {
+        fs::create_dir_all(parent)?;
+    }
 
-        output_files.push(output_filename);
+    // The canvas size comes from the first frame; all frames share one QR version.
+    let mut apng = None;
+    let durations = frame_durations(chunks.len(), options);
+    let interval_ms = options.interval_ms.min(u16::MAX as u64);
+    let mut frame_ms = Vec::with_capacity(durations.len());
+    let frames = chunks.len().div_ceil(options.codes_per_frame());
+    let marker = prepared.sync_info(frames, interval_ms);
+    let caption = Caption::new(options, &prepared.filename, data, &chunks);
+    let qr = process_animation_frames(&chunks, caption.as_ref(), options, |qr_image, i, total| {
+        let mut writer = match apng.take() {
+            Some(writer) => writer,
+            None => {
+                let (width, height) = (qr_image.width(), qr_image.height());
+                let mut writer = start_apng(
+                    output_apng,
+                    width,
+                    height,
+                    (total + sync_frame_count(options)) as u32,
+                    options,
+                )?;
+                if options.sync_frames {
+                    let frames = sync_frames(&marker, width, height, options)?;
+                    for (frame, &ms) in frames.iter().zip(&durations) {
+                        write_apng_frame(&mut writer, frame, ms, &mut frame_ms)?;
+                    }
+                }
+                writer
+            }
+        };
+        let ms = durations[sync_frame_count(options) + i];
+        write_apng_frame(&mut writer, &qr_image, ms, &mut frame_ms)?;
+        apng = Some(writer);
+
+        if total <= 10 || ((i + 1) % 10 == 0 || i + 1 == total) {
+            log::info!("  Processed frame {}/{}", i + 1, total);
+        }
         Ok(())
     })?;
+    if let Some(writer) = apng {
+        writer.finish()?;
+    }
 
     Ok(EncodeResult {
         num_chunks: chunks.len(),
-        output_files,
+        output_files: vec![output_apng.to_string_lossy().to_string()],
+        effective_size,
+        sha256: sha256_hex(data),
+        timing: Some(AnimationTiming {
+            frame_ms,
+            interval_ms,
+        }),
+        qr,
+    })
+}
+
+/// APNG output in the UR wire format.
+fn write_ur_apng(data: &[u8], output_apng: &Path, options: &EncodeOptions) -> Result<EncodeResult> {
+    let (effective_size, parts) = prepare_ur_parts_for_img(data, options)?;
+    if let Some(parent) = output_apng.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut apng = None;
+    let mut frame_ms = Vec::with_capacity(parts.len());
+    let qr = process_ur_parts_as_qr_images(&parts, options, |qr_image, i, total| {
+        let mut writer = match apng.take() {
+            Some(writer) => writer,
+            None => start_apng(
+                output_apng,
+                qr_image.width(),
+                qr_image.height(),
+                total as u32,
+                options,
+            )?,
+        };
+        let ms = match (i, options.first_frame_ms) {
+            (0, Some(ms)) => ms,
+            _ => options.interval_ms,
+        };
+        write_apng_frame(&mut writer, &qr_image, ms, &mut frame_ms)?;
+        apng = Some(writer);
+        Ok(())
+    })?;
+    if let Some(writer) = apng {
+        writer.finish()?;
+    }
+
+    Ok(EncodeResult {
+        num_chunks: parts.len(),
+        output_files: vec![output_apng.to_string_lossy().to_string()],
         effective_size,
+        sha256: sha256_hex(data),
+        timing: Some(AnimationTiming {
+            frame_ms,
+            interval_ms: options.interval_ms.min(u16::MAX as u64),
+        }),
+        qr,
     })
 }
 
+/// Reads `input_path` and encodes it with [`encode_bytes_to_apng_with_options`].
+pub fn encode_file_to_apng_with_options(
+    input_path: &Path,
+    output_apng: &Path,
+    options: &EncodeOptions,
+) -> Result<EncodeResult> {
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_to_apng_with_options(&data, &filename, &metadata, output_apng, options)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn encode_bytes_to_gif(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    output_gif: &Path,
+    chunk_size: Option<usize>,
+    interval_ms: u64,
+    pixel_scale: u32,
+    ec_level: EcLevel,
+    style: &QrStyle,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
+    redundancy_factor: f64,
+) -> Result<EncodeResult> {
+    let options = EncodeOptions::for_images(
+        chunk_size,
+        pixel_scale,
+        ec_level,
+        style,
+        payload_encoding,
+        compression,
+        redundancy_factor,
+        None,
+    )
+    .interval_ms(interval_ms);
+    encode_bytes_to_gif_with_options(data, filename, metadata, output_gif, &options)
+}
+
+/// Reads `input_path` and encodes it with [`encode_bytes_to_gif`].
+#[allow(clippy::too_many_arguments)]
 pub fn encode_file_to_gif(
     input_path: &Path,
     output_gif: &Path,
     chunk_size: Option<usize>,
     interval_ms: u64,
     pixel_scale: u32,
+    ec_level: EcLevel,
+    style: &QrStyle,
+    payload_encoding: PayloadEncoding,
+    compression: CompressionAlgorithm,
+    redundancy_factor: f64,
 ) -> Result<EncodeResult> {
-    let (chunks, effective_size, _filename) =
-        prepare_chunks_for_img(input_path, chunk_size, pixel_scale, 1.5)?;
+    let (data, filename, metadata) = read_input_file(input_path)?;
+    encode_bytes_to_gif(
+        &data,
+        &filename,
+        &metadata,
+        output_gif,
+        chunk_size,
+        interval_ms,
+        pixel_scale,
+        ec_level,
+        style,
+        payload_encoding,
+        compression,
+        redundancy_factor,
+    )
+}
 
-    if let Some(parent) = output_gif.parent() {
-        fs::create_dir_all(parent)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gif_writer_merges_repeats_and_stores_deltas() {
+        use image::AnimationDecoder;
+
+        let code = |text: &[u8]| {
+            let version = Some(Version::Normal(2));
+            generate_qr_image(text, version, 2, EcLevel::M, &QrStyle::default())
+                .unwrap()
+                .0
+        };
+        // More colours than a palette holds are quantized
+        let gradient = RgbImage::from_fn(66, 66, |x, y| Rgb([x as u8 * 3, y as u8 * 3, 128]));
+        let frames = [code(b"one"), code(b"one"), code(b"two"), gradient];
+        for delta_frames in [false, true] {
+            let options = EncodeOptions::new()
+                .interval_ms(100)
+                .gif_delta_frames(delta_frames);
+            let mut gif = GifWriter::new(Vec::new(), &options);
+            for frame in &frames {
+                gif.push(frame, 100).unwrap();
+            }
+            let bytes = gif.finish().unwrap();
+
+            let decoded: Vec<image::Frame> =
+                image::codecs::gif::GifDecoder::new(std::io::Cursor::new(bytes))
+                    .unwrap()
+                    .into_frames()
+                    .map(|frame| frame.unwrap())
+                    .collect();
+            assert_eq!(decoded.len(), 3);
+            assert_eq!(decoded[0].delay().numer_denom_ms(), (200, 1));
+            for (decoded, expected) in decoded.iter().zip([&frames[0], &frames[2]]) {
+                let rgb = image::DynamicImage::ImageRgba8(decoded.buffer().clone()).to_rgb8();
+                assert_eq!(&rgb, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_terminal_data_reports_code_sizes() {
+        let options = EncodeOptions::new();
+        let data = encode_text_for_terminal_with_options("hello", "hello", &options).unwrap();
+        assert_eq!((data.qr_version, data.modules()), (Version::Normal(1), 21));
+        assert_eq!(data.chunk_bytes, [5]);
+
+        let long = "x".repeat(100);
+        let data = encode_text_for_terminal_with_options(&long, "long", &options).unwrap();
+        assert_eq!(
+            data.qr_version,
+            qr_version_for(long.as_bytes(), options.ec_level).unwrap()
+        );
+        assert!(data.modules() > 21);
     }
 
-    let file = fs::File::create(output_gif)?;
-    let mut encoder = GifEncoder::new(file);
-    encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+    #[test]
+    fn test_terminal_size_override() {
+        let data = vec![42u8; 2000];
+        let encode = |options: &EncodeOptions| {
+            encode_bytes_for_terminal_with_options(
+                &data,
+                "data.bin",
+                &FileMetadata::default(),
+                &options.clone().compression(CompressionAlgorithm::None),
+            )
+        };
+
+        // Codes of the default size are 45 columns wide with their quiet zone
+        let options = EncodeOptions::new().terminal_size(40, 30);
+        let fitted = encode(&options).unwrap();
+        let size = TerminalSize::Given {
+            width: 40,
+            height: 30,
+        };
+        assert_eq!(fitted.fit, TerminalFit { size, shrunk: true });
+        assert!(fitted.effective_size < DEFAULT_PAYLOAD_SIZE);
+        assert!(fitted.modules() + 4 <= 40);
 
-    process_chunks_as_qr_images(&chunks, pixel_scale, |_, qr_image, i, total| {
-        let rgba_image: RgbaImage = image::DynamicImage::ImageRgb8(qr_image).into_rgba8();
+        let roomy = encode(&EncodeOptions::new().terminal_size(200, 120)).unwrap();
+        assert!(!roomy.fit.shrunk);
+        assert_eq!(roomy.effective_size, DEFAULT_PAYLOAD_SIZE);
 
-        let delay = Delay::from_saturating_duration(Duration::from_millis(interval_ms));
-        let frame = Frame::from_parts(rgba_image, 0, 0, delay);
+        let Err(err) = encode(&options.shrink_to_terminal(false)) else {
+            panic!("Codes too large for the terminal were accepted");
+        };
+        assert!(err.to_string().contains("40x30"));
+    }
 
-        encoder.encode_frame(frame)?;
+    #[test]
+    fn test_frame_durations() {
+        // 6 chunks in 2 cycles of 3 frames, behind the black, white and marker frames
+        let options = EncodeOptions::new()
+            .interval_ms(100)
+            .first_frame_ms(2000)
+            .repeat_interval_ms(50)
+            .cycles(2)
+            .sync_frames(true);
+        assert_eq!(
+            frame_durations(6, &options),
+            [100, 100, 2000, 100, 100, 100, 50, 50, 50]
+        );
+        let options = EncodeOptions::new().interval_ms(100).first_frame_ms(2000);
+        assert_eq!(frame_durations(3, &options), [2000, 100, 100]);
 
-        if total <= 10 || ((i + 1) % 10 == 0 || i + 1 == total) {
-            println!("  Processed frame {}/{}", i + 1, total);
+        assert_eq!(gif_delay(0), 0);
+        assert_eq!(gif_delay(3), 1);
+        assert_eq!(gif_delay(33), 3);
+        assert_eq!(gif_delay(35), 4);
+        assert_eq!(gif_delay(u64::MAX), u16::MAX);
+    }
+
+    #[test]
+    fn test_prepare_payload_picks_largest_fitting_size() {
+        let data: Vec<u8> = (0..2_000u32).map(|i| (i * 7919 % 251) as u8).collect();
+        for limit in [120, 333, 800] {
+            let prepare = |chunk_size: usize| {
+                prepare_payload(
+                    &data,
+                    "noise.bin",
+                    &FileMetadata::default(),
+                    Some(chunk_size),
+                    chunk_size,
+                    50,
+                    PayloadEncoding::Base45,
+                    CompressionAlgorithm::None.into(),
+                    None,
+                    None,
+                    |payload| Ok(payload.len() <= limit),
+                )
+            };
+
+            let prepared = prepare(2000).unwrap();
+            let size = prepared.effective_size;
+            assert!(size < 2000);
+            // Asking for one byte more falls back to the same size, so nothing larger fits
+            assert_eq!(
+                prepare(size + 1).unwrap().effective_size,
+                size,
+                "limit {}",
+                limit
+            );
         }
-        Ok(())
-    })?;
 
-    Ok(EncodeResult {
-        num_chunks: chunks.len(),
-        output_files: vec![output_gif.to_string_lossy().to_string()],
-        effective_size,
-    })
+        assert!(prepare_payload(
+            &data,
+            "noise.bin",
+            &FileMetadata::default(),
+            None,
+            2000,
+            50,
+            PayloadEncoding::Base45,
+            CompressionAlgorithm::None.into(),
+            None,
+            None,
+            |_| Ok(false),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_frame_order() {
+        let blocks = [0, 0, 0, 1, 1, 1, 2, 2];
+        assert_eq!(
+            frame_order(&blocks, FrameOrder::Sequential, None),
+            (0..8).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            frame_order(&blocks, FrameOrder::Interleaved, None),
+            [0, 3, 6, 1, 4, 7, 2, 5]
+        );
+
+        let shuffled = frame_order(&[0; 50], FrameOrder::Shuffled, Some(3));
+        assert_eq!(
+            shuffled,
+            frame_order(&[0; 50], FrameOrder::Shuffled, Some(3))
+        );
+        assert_ne!(
+            shuffled,
+            frame_order(&[0; 50], FrameOrder::Shuffled, Some(4))
+        );
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..50).collect::<Vec<_>>());
+        assert_ne!(shuffled, sorted);
+    }
+
+    #[test]
+    fn test_estimate_encoding_matches_encoder() {
+        let mut state = 1u32;
+        let data: Vec<u8> = (0..10_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect();
+        let cases = [
+            EncodeOptions::new(),
+            EncodeOptions::new().chunk_size(400).redundancy(2.0),
+            EncodeOptions::new()
+                .payload_encoding(PayloadEncoding::Binary)
+                .compression(CompressionAlgorithm::None),
+            EncodeOptions::new().compression(CompressionAlgorithm::Zstd),
+        ];
+        for options in cases {
+            let estimate = estimate_encoding(data.len() as u64, &options).unwrap();
+            let (prepared, chunks) =
+                prepare_chunks_for_img(&data, "", &FileMetadata::default(), None, &options)
+                    .unwrap();
+            assert_eq!(estimate.chunks, chunks.len());
+            assert_eq!(estimate.effective_size, prepared.effective_size);
+            assert_eq!(
+                estimate.duration_at_interval,
+                Duration::from_millis(DEFAULT_GIF_INTERVAL_MS * chunks.len() as u64)
+            );
+            let version = chunks
+                .iter()
+                .map(|chunk| {
+                    let payload = chunk.to_qr_payload(options.payload_encoding).unwrap();
+                    qr_version_for(&payload, options.ec_level).unwrap()
+                })
+                .max_by_key(|version| version.width())
+                .unwrap();
+            assert_eq!(estimate.qr_version, version);
+        }
+    }
+
+    #[test]
+    fn test_print_sizing() {
+        let options = EncodeOptions::new();
+        // 0.5 mm at 300 dpi is 5.9 px, so modules print at 6 px, 0.508 mm
+        let sizing = print_sizing(300, 0.5, 100.0, &options).unwrap();
+        assert_eq!(sizing.pixel_scale, 6);
+        assert!((sizing.module_mm - 0.508).abs() < 1e-9);
+        // 100 mm is 1181 px, room for 196 modules: version 40 (177) and a 4-module margin
+        assert_eq!(sizing.max_version, Version::Normal(40));
+
+        let sizing = print_sizing(300, 0.5, 50.0, &options).unwrap();
+        // 590 px holds 98 modules: version 18 (89) and the margin
+        assert_eq!(sizing.max_version, Version::Normal(18));
+        let options = options.pixel_scale(6).chunk_size(sizing.max_payload);
+        let (_, chunks) =
+            prepare_chunks_for_img(&[7; 5000], "", &FileMetadata::default(), None, &options)
+                .unwrap();
+        let payload = chunks[0].to_qr_payload(options.payload_encoding).unwrap();
+        assert!(qr_version_for(&payload, options.ec_level).unwrap().width() <= 89);
+
+        assert!(print_sizing(300, 2.0, 10.0, &options).is_err());
+        assert!(print_sizing(0, 0.5, 100.0, &options).is_err());
+    }
+
+    #[test]
+    fn test_terminal_stream_wraps_repair_ids() {
+        let data: Vec<u8> = (0..3000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let options = EncodeOptions::new()
+            .chunk_size(100)
+            .compression(CompressionAlgorithm::None);
+        let mut stream = encode_bytes_for_terminal_stream_with_options(
+            &data,
+            "wrap.bin",
+            &FileMetadata::default(),
+            &options,
+        )
+        .unwrap();
+        stream.next_index = stream.source_packets as u32;
+        stream.next_repair_id = stream.repair_id_limit - 1;
+
+        let blocks = stream.prepared.encoder.get_block_encoders().len();
+        for _ in 0..blocks * 2 {
+            let packet = stream.next_packet();
+            assert!(packet.payload_id().encoding_symbol_id() < 1 << 24);
+        }
+        assert_eq!(stream.next_repair_id, 1);
+    }
+
+    #[test]
+    fn test_fresh_cycles_wrap_repair_ids() {
+        let data: Vec<u8> = (0..3000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let options = EncodeOptions::new()
+            .chunk_size(100)
+            .compression(CompressionAlgorithm::None)
+            .seed(7);
+        let stream = encode_bytes_for_terminal_stream_with_options(
+            &data,
+            "cycles.bin",
+            &FileMetadata::default(),
+            &options,
+        )
+        .unwrap();
+        let prepared = stream.prepared;
+        let first = prepared.chunks(1.0);
+        let esis = |chunks: &[Chunk]| -> Vec<u32> {
+            chunks
+                .iter()
+                .map(|chunk| EncodingPacket::deserialize(&chunk.data))
+                .map(|packet| packet.payload_id().encoding_symbol_id())
+                .collect()
+        };
+
+        // Cycles on both sides of the point where the repair ids start over
+        let wrap = (prepared.repair_id_limit() - prepared.first_repair_id) / first.len() as u32;
+        let mut previous = Vec::new();
+        for cycle in (1..200).chain(wrap - 100..wrap + 100) {
+            let chunks = prepared.fresh_cycle_chunks(&first, cycle);
+            assert_eq!(chunks.len(), first.len());
+            let ids = esis(&chunks);
+            assert!(ids.iter().all(|&esi| esi < 1 << 24));
+            assert!(ids.iter().all(|esi| !previous.contains(esi)));
+            previous = ids;
+        }
+    }
+
+    #[test]
+    fn test_name_template_render() {
+        let template: NameTemplate = "{stem}-{index:05}-of-{total}.png".parse().unwrap();
+        assert_eq!(
+            template.render("report.pdf", 7, 120),
+            "report-00007-of-120.png"
+        );
+
+        let template: NameTemplate = DEFAULT_NAME_TEMPLATE.parse().unwrap();
+        assert_eq!(template.render("a.tar.gz", 3, 10), "a_tar_gz_0003.png");
+
+        let template: NameTemplate = "{ext}_{index}.PNG".parse().unwrap();
+        assert_eq!(template.render("notes", 12, 20), "_12.PNG");
+    }
+
+    #[test]
+    fn test_name_template_rejects_invalid() {
+        assert!("{stem}.png".parse::<NameTemplate>().is_err());
+        assert!("{index}".parse::<NameTemplate>().is_err());
+        assert!("{index.png".parse::<NameTemplate>().is_err());
+        assert!("{idx}.png".parse::<NameTemplate>().is_err());
+        assert!("{stem:3}_{index}.png".parse::<NameTemplate>().is_err());
+        assert!("{index:x}.png".parse::<NameTemplate>().is_err());
+        assert!("out/{index}.png".parse::<NameTemplate>().is_err());
+        assert!("{index}}.png".parse::<NameTemplate>().is_err());
+    }
 }
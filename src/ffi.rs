@@ -0,0 +1,174 @@
+//! C interface to the reassembly engine, for mobile apps that scan QR codes natively and only
+//! need the payloads turned back into a file. It mirrors the wasm `QrStreamDecoder`; the
+//! declarations are in `include/fountain.h`.
+
+use crate::receive::{PayloadReceiver, ReceiveStatus};
+use anyhow::{anyhow, Result};
+use std::ffi::{c_char, CString};
+use std::ptr;
+
+/// Decoder state behind the opaque `FountainDecoder *` handed to C.
+#[derive(Default)]
+pub struct FountainDecoder {
+    receiver: PayloadReceiver,
+    /// Message of the last payload that failed to decode.
+    last_error: Option<CString>,
+}
+
+/// Outcome of feeding one payload, with the same values as the wasm `ScanStatus`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FountainStatus {
+    /// Not a chunk or UR part, or one already seen.
+    Scanning = 0,
+    ChunkFound = 1,
+    /// The file is ready for `fountain_decoder_poll_result`.
+    Complete = 2,
+    Error = 3,
+}
+
+/// A decoded file. Release it with `fountain_file_free`.
+#[repr(C)]
+pub struct FountainFile {
+    /// NUL-terminated UTF-8 filename; the part name (`name.001`) for a volume of a split file.
+    pub filename: *mut c_char,
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl FountainDecoder {
+    fn feed(&mut self, payload: &[u8]) -> Result<FountainStatus> {
+        Ok(match self.receiver.feed(payload)? {
+            ReceiveStatus::Scanning => FountainStatus::Scanning,
+            ReceiveStatus::ChunkFound => FountainStatus::ChunkFound,
+            ReceiveStatus::Complete => FountainStatus::Complete,
+        })
+    }
+}
+
+/// Creates a decoder. Release it with `fountain_decoder_free`.
+#[no_mangle]
+pub extern "C" fn fountain_decoder_new() -> *mut FountainDecoder {
+    Box::into_raw(Box::default())
+}
+
+/// Feeds the content of one QR code, as Base45 text, raw bytes or a UR part. Content in no
+/// known wire format is ignored with `FOUNTAIN_STATUS_SCANNING`.
+///
+/// # Safety
+///
+/// `decoder` must come from `fountain_decoder_new` and `payload` must point to `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_feed_payload(
+    decoder: *mut FountainDecoder,
+    payload: *const u8,
+    len: usize,
+) -> FountainStatus {
+    let Some(decoder) = decoder.as_mut() else {
+        return FountainStatus::Error;
+    };
+    let result = if payload.is_null() {
+        Err(anyhow!("Payload is null"))
+    } else {
+        decoder.feed(std::slice::from_raw_parts(payload, len))
+    };
+    result.unwrap_or_else(|e| {
+        decoder.last_error = CString::new(e.to_string()).ok();
+        FountainStatus::Error
+    })
+}
+
+/// Writes the number of chunks received and the number needed, both 0 before the first chunk.
+///
+/// # Safety
+///
+/// `decoder` must come from `fountain_decoder_new`; `current` and `total` must each be null or
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_progress(
+    decoder: *const FountainDecoder,
+    current: *mut u32,
+    total: *mut u32,
+) {
+    let Some(decoder) = decoder.as_ref() else {
+        return;
+    };
+    let (received, needed) = decoder.receiver.progress();
+    if let Some(current) = current.as_mut() {
+        *current = received;
+    }
+    if let Some(total) = total.as_mut() {
+        *total = needed;
+    }
+}
+
+/// Takes the decoded file once a feed returned `FOUNTAIN_STATUS_COMPLETE`, or returns null.
+///
+/// # Safety
+///
+/// `decoder` must come from `fountain_decoder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_poll_result(
+    decoder: *mut FountainDecoder,
+) -> *mut FountainFile {
+    let Some((filename, data)) = decoder
+        .as_mut()
+        .and_then(|decoder| decoder.receiver.take_result())
+    else {
+        return ptr::null_mut();
+    };
+    // Packing strips NUL bytes from filenames, so this cannot fail on decoded files
+    let filename = CString::new(filename).unwrap_or_default();
+    let len = data.len();
+    Box::into_raw(Box::new(FountainFile {
+        filename: filename.into_raw(),
+        data: Box::into_raw(data.into_boxed_slice()) as *mut u8,
+        len,
+    }))
+}
+
+/// Message of the last payload that returned `FOUNTAIN_STATUS_ERROR`, or null if none has.
+/// The string stays valid until the next error or `fountain_decoder_free`.
+///
+/// # Safety
+///
+/// `decoder` must come from `fountain_decoder_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_last_error(
+    decoder: *const FountainDecoder,
+) -> *const c_char {
+    decoder
+        .as_ref()
+        .and_then(|decoder| decoder.last_error.as_ref())
+        .map_or(ptr::null(), |message| message.as_ptr())
+}
+
+/// Releases a decoder. Null is ignored.
+///
+/// # Safety
+///
+/// `decoder` must come from `fountain_decoder_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_decoder_free(decoder: *mut FountainDecoder) {
+    if !decoder.is_null() {
+        drop(Box::from_raw(decoder));
+    }
+}
+
+/// Releases a file returned by `fountain_decoder_poll_result`. Null is ignored.
+///
+/// # Safety
+///
+/// `file` must come from `fountain_decoder_poll_result` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fountain_file_free(file: *mut FountainFile) {
+    if file.is_null() {
+        return;
+    }
+    let file = Box::from_raw(file);
+    drop(CString::from_raw(file.filename));
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+        file.data, file.len,
+    )));
+}
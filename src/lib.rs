@@ -1,4 +1,21 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "audio")]
+pub mod audio;
+
+#[cfg(feature = "std")]
+pub mod cancel;
+#[cfg(feature = "std")]
 pub mod chunk;
+pub mod core;
+
+#[cfg(feature = "std")]
+pub mod dedup;
+
+#[cfg(feature = "std")]
+pub mod delta;
 
 #[cfg(feature = "decode")]
 pub mod decode;
@@ -6,26 +23,104 @@ pub mod decode;
 #[cfg(feature = "encode")]
 pub mod encode;
 
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "std")]
+pub mod packets;
+
+#[cfg(any(feature = "encode", feature = "decode", feature = "wasm"))]
 pub mod qr;
 
+#[cfg(all(feature = "wasm", not(all(feature = "encode", feature = "decode"))))]
+pub mod raster;
+
+#[cfg(feature = "std")]
+pub mod receive;
+
 #[cfg(feature = "encode")]
 pub mod terminal;
 
+pub mod ur;
+
+#[cfg(feature = "async")]
+pub mod nonblocking;
+
 #[cfg(feature = "wasm")]
 pub mod wasm;
 
-pub use chunk::{
-    Chunk, ChunkHeader, DEFAULT_PAYLOAD_SIZE, MAX_PAYLOAD_SIZE,
+#[cfg(feature = "std")]
+pub use cancel::Cancelled;
+
+#[cfg(feature = "std")]
+pub use chunk::{CompressionAlgorithm, CompressionSetting};
+
+#[cfg(feature = "std")]
+pub use packets::{raptorq_packets, PacketAssembler, PacketOptions};
+
+pub use crate::core::{
+    sha256_hex, Chunk, ChunkHeader, FileMetadata, ObjectDecoder, PayloadEncoding, Reassembler,
+    Reassembly, SyncInfo, Volume, WireFormat, CHUNK_VERSION, DEFAULT_PAYLOAD_SIZE, MAX_PAYLOAD_SIZE,
 };
 
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub use qr::{EcLevel, QrStyle, DEFAULT_EC_LEVEL};
+
+#[cfg(feature = "encode")]
+pub use qr::{GridLayout, TerminalRender, TerminalSize};
+
+#[cfg(feature = "decode")]
+pub use qr::ScanEffort;
+
 #[cfg(feature = "decode")]
-pub use decode::{decode_from_gif, decode_from_images, DecodeResult};
+pub use decode::{
+    decode_from_chunk_files, decode_from_chunk_files_to_vec,
+    decode_from_chunk_files_with_options, decode_from_dynamic_images, decode_from_gif, decode_from_gif_to_vec,
+    decode_from_gif_with, decode_from_gif_with_options, decode_from_image_buffers, decode_from_images,
+    decode_from_images_to_vec, decode_from_images_with_options, decode_from_paths,
+    decode_from_paths_to_vec, decode_from_paths_with_options, inspect_gif, inspect_image,
+    inspect_images, read_raw_gif, read_raw_image, read_raw_images, verify_gif, verify_images,
+    DecodeOptions, DecodeResult, FrameEvent, FrameOutcome, FramePosition, GlobPattern, StreamInfo,
+    VerifyReport, IMAGE_EXTENSIONS,
+};
+
+#[cfg(feature = "watch")]
+pub use decode::{decode_from_watched_dir, decode_from_watched_dir_with_options};
 
 #[cfg(feature = "encode")]
 pub use encode::{
-    encode_file_for_terminal, encode_file_to_gif,
-    encode_file_to_images, EncodeResult, TerminalQrData,
+    encode_bytes_for_terminal, encode_bytes_for_terminal_single_with_options,
+    encode_bytes_for_terminal_stream, encode_bytes_for_terminal_stream_with_options,
+    encode_bytes_for_terminal_with_options, encode_bytes_to_apng_with_options,
+    encode_bytes_to_chunk_files_with_options, encode_bytes_to_gif,
+    encode_bytes_to_gif_with_options, encode_file_to_apng_with_options,
+    encode_bytes_to_images, encode_bytes_to_images_with_options,
+    encode_bytes_to_selected_images, encode_bytes_to_single_image_with_options,
+    encode_bytes_to_selected_images_with_options, encode_bytes_to_tiled_images,
+    encode_bytes_to_tiled_images_with_options, encode_file_for_terminal,
+    encode_file_for_terminal_stream, encode_file_for_terminal_stream_with_options,
+    encode_file_for_terminal_with_options, encode_file_to_gif, encode_file_to_gif_with_options,
+    encode_file_to_images, encode_file_to_images_with_options, encode_file_to_tiled_images,
+    encode_file_to_tiled_images_with_options, encode_files_for_terminal_with_options,
+    encode_text_for_terminal_with_options,
+    encode_text_to_image_with_options, estimate_encoding, print_sizing, read_file_metadata,
+    AnimationTiming, EncodeEstimate, EncodeOptions, EncodeResult, FrameOrder, LoopCount,
+    NameTemplate, PrintSizing, QrSizing, TerminalFit, TerminalQrData, TerminalQrStream,
+    DEFAULT_GIF_INTERVAL_MS, DEFAULT_NAME_TEMPLATE, DEFAULT_PAGE_NAME_TEMPLATE,
+    DEFAULT_REDUNDANCY_FACTOR, DEFAULT_TERMINAL_REDUNDANCY_FACTOR,
+};
+
+#[cfg(all(feature = "async", feature = "encode"))]
+pub use nonblocking::{
+    encode_bytes_to_gif_async, encode_bytes_to_images_async, encode_file_to_gif_async,
+    encode_file_to_images_async,
 };
 
+#[cfg(all(feature = "async", feature = "decode"))]
+pub use nonblocking::{decode_from_gif_async, decode_from_images_async};
+
 #[cfg(feature = "encode")]
-pub use terminal::{display_qr_carousel, display_qr_once};
+pub use terminal::{
+    display_qr_carousel, display_qr_carousel_refitting, display_qr_carousel_with,
+    display_qr_once, display_qr_stream, CarouselOptions,
+};
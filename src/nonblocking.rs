@@ -0,0 +1,146 @@
+//! Async variants of the long-running entry points for callers inside a tokio runtime.
+//!
+//! Each function runs its blocking counterpart on tokio's blocking thread pool, so GUI and
+//! server integrations keep their async workers free while frames are rendered or scanned.
+//! Dropping the returned future sets the operation's cancel flag, creating one if the options
+//! carry none, so an abandoned task stops at its next image instead of running to completion.
+
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[cfg(feature = "encode")]
+use crate::chunk::FileMetadata;
+#[cfg(feature = "decode")]
+use crate::decode::{
+    decode_from_gif_with_options, decode_from_images_with_options, DecodeOptions, DecodeResult,
+};
+#[cfg(feature = "encode")]
+use crate::encode::{
+    encode_bytes_to_gif_with_options, encode_bytes_to_images_with_options,
+    encode_file_to_gif_with_options, encode_file_to_images_with_options, EncodeOptions,
+    EncodeResult,
+};
+
+/// Sets the cancel flag when dropped, unless the task finished first.
+struct CancelOnDrop(Option<Arc<AtomicBool>>);
+
+impl CancelOnDrop {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(flag) = &self.0 {
+            flag.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+async fn run_blocking<T, F>(cancel: Arc<AtomicBool>, task: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let guard = CancelOnDrop(Some(cancel));
+    let result = tokio::task::spawn_blocking(task).await;
+    guard.disarm();
+    result.map_err(|e| anyhow!("Background task failed: {}", e))?
+}
+
+/// Async version of [`encode_file_to_gif_with_options`].
+#[cfg(feature = "encode")]
+pub async fn encode_file_to_gif_async(
+    input_path: PathBuf,
+    output_gif: PathBuf,
+    options: EncodeOptions,
+) -> Result<EncodeResult> {
+    let cancel = options.cancel.clone().unwrap_or_default();
+    let options = options.cancel_flag(cancel.clone());
+    run_blocking(cancel, move || {
+        encode_file_to_gif_with_options(&input_path, &output_gif, &options)
+    })
+    .await
+}
+
+/// Async version of [`encode_file_to_images_with_options`].
+#[cfg(feature = "encode")]
+pub async fn encode_file_to_images_async(
+    input_path: PathBuf,
+    output_dir: PathBuf,
+    options: EncodeOptions,
+) -> Result<EncodeResult> {
+    let cancel = options.cancel.clone().unwrap_or_default();
+    let options = options.cancel_flag(cancel.clone());
+    run_blocking(cancel, move || {
+        encode_file_to_images_with_options(&input_path, &output_dir, &options)
+    })
+    .await
+}
+
+/// Async version of [`encode_bytes_to_gif_with_options`], for payloads generated in memory.
+#[cfg(feature = "encode")]
+pub async fn encode_bytes_to_gif_async(
+    data: Vec<u8>,
+    filename: String,
+    metadata: FileMetadata,
+    output_gif: PathBuf,
+    options: EncodeOptions,
+) -> Result<EncodeResult> {
+    let cancel = options.cancel.clone().unwrap_or_default();
+    let options = options.cancel_flag(cancel.clone());
+    run_blocking(cancel, move || {
+        encode_bytes_to_gif_with_options(&data, &filename, &metadata, &output_gif, &options)
+    })
+    .await
+}
+
+/// Async version of [`encode_bytes_to_images_with_options`], for payloads generated in memory.
+#[cfg(feature = "encode")]
+pub async fn encode_bytes_to_images_async(
+    data: Vec<u8>,
+    filename: String,
+    metadata: FileMetadata,
+    output_dir: PathBuf,
+    options: EncodeOptions,
+) -> Result<EncodeResult> {
+    let cancel = options.cancel.clone().unwrap_or_default();
+    let options = options.cancel_flag(cancel.clone());
+    run_blocking(cancel, move || {
+        encode_bytes_to_images_with_options(&data, &filename, &metadata, &output_dir, &options)
+    })
+    .await
+}
+
+/// Async version of [`decode_from_gif_with_options`].
+#[cfg(feature = "decode")]
+pub async fn decode_from_gif_async(
+    input_file: PathBuf,
+    output_file: Option<PathBuf>,
+    options: DecodeOptions,
+) -> Result<DecodeResult> {
+    let cancel = options.cancel.clone().unwrap_or_default();
+    let options = options.cancel_flag(cancel.clone());
+    run_blocking(cancel, move || {
+        decode_from_gif_with_options(&input_file, output_file.as_deref(), &options)
+    })
+    .await
+}
+
+/// Async version of [`decode_from_images_with_options`].
+#[cfg(feature = "decode")]
+pub async fn decode_from_images_async(
+    input_dir: PathBuf,
+    output_file: Option<PathBuf>,
+    options: DecodeOptions,
+) -> Result<DecodeResult> {
+    let cancel = options.cancel.clone().unwrap_or_default();
+    let options = options.cancel_flag(cancel.clone());
+    run_blocking(cancel, move || {
+        decode_from_images_with_options(&input_dir, output_file.as_deref(), &options)
+    })
+    .await
+}
@@ -0,0 +1,309 @@
+//! The fountain-code layer on its own, for carrying files over links other than QR codes, such
+//! as LoRa radios or audio modems.
+//!
+//! [`raptorq_packets`] packs, compresses and RaptorQ-encodes a file into [`Chunk`]s exactly as
+//! the QR encoder does, and [`PacketAssembler`] turns any large enough subset of them back into
+//! the file. Chunks travel as the bytes of [`Chunk::to_bytes`], in any order.
+
+use anyhow::{anyhow, Result};
+use raptorq::{Encoder as RQEncoder, EncodingPacket};
+
+use crate::chunk::{
+    compress_or_store, decompress, pack_data_with_metadata, sha256_digest,
+    unpack_data_with_metadata, Chunk, ChunkHeader, CompressionSetting, FileMetadata, Reassembler,
+    Reassembly, CHUNK_VERSION, DEFAULT_PAYLOAD_SIZE, HEADER_SIZE, MAX_PAYLOAD_SIZE,
+};
+
+/// Ratio of generated packets to source packets for image and GIF output, and for
+/// [`raptorq_packets`].
+pub const DEFAULT_REDUNDANCY_FACTOR: f64 = 1.5;
+/// Bytes a serialized RaptorQ packet spends on its block and symbol ids.
+const PAYLOAD_ID_SIZE: usize = 4;
+/// Smallest packet size: the chunk header, the payload id and a symbol of 4 bytes.
+const MIN_PACKET_SIZE: usize = HEADER_SIZE + PAYLOAD_ID_SIZE + 4;
+
+/// Options for [`raptorq_packets`].
+#[derive(Debug, Clone)]
+pub struct PacketOptions {
+    packet_size: usize,
+    compression: CompressionSetting,
+    redundancy: f64,
+    seed: Option<u64>,
+    metadata: FileMetadata,
+}
+
+impl Default for PacketOptions {
+    fn default() -> Self {
+        Self {
+            packet_size: DEFAULT_PAYLOAD_SIZE,
+            compression: CompressionSetting::default(),
+            redundancy: DEFAULT_REDUNDANCY_FACTOR,
+            seed: None,
+            metadata: FileMetadata::default(),
+        }
+    }
+}
+
+impl PacketOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Size of each chunk as [`Chunk::to_bytes`] serializes it, header included, so that it
+    /// matches the frame size of the link.
+    pub fn packet_size(mut self, size: usize) -> Self {
+        self.packet_size = size;
+        self
+    }
+
+    pub fn compression(mut self, compression: impl Into<CompressionSetting>) -> Self {
+        self.compression = compression.into();
+        self
+    }
+
+    /// Ratio of generated packets to source packets, at least 1.0.
+    pub fn redundancy(mut self, factor: f64) -> Self {
+        self.redundancy = factor;
+        self
+    }
+
+    /// Starts the repair packets at symbol ids derived from `seed`, so that runs with
+    /// different seeds send different repair packets.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// File mode, modification time and the like, restored by the receiver.
+    pub fn metadata(mut self, metadata: FileMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+/// Encodes `data` under `filename` into RaptorQ packets of `options.packet_size` bytes each:
+/// the source packets of every block, then its repair packets. Any source-packet-count of them
+/// (and usually a few more) rebuild the file.
+pub fn raptorq_packets(
+    data: &[u8],
+    filename: &str,
+    options: &PacketOptions,
+) -> Result<impl Iterator<Item = Chunk>> {
+    validate_redundancy_factor(options.redundancy)?;
+    if !(MIN_PACKET_SIZE..=MAX_PAYLOAD_SIZE).contains(&options.packet_size) {
+        return Err(anyhow!(
+            "Invalid packet size: {}. It must be between {} and {} bytes.",
+            options.packet_size,
+            MIN_PACKET_SIZE,
+            MAX_PAYLOAD_SIZE
+        ));
+    }
+    let (object, original_size) = compressed_object(
+        data,
+        filename,
+        &options.metadata,
+        options.compression,
+        options.seed,
+    )?;
+
+    // RaptorQ symbols have an even size
+    let symbol_size = (options.packet_size - HEADER_SIZE - PAYLOAD_ID_SIZE) as u16 & !1;
+    let encoder = RQEncoder::with_defaults(&object, symbol_size);
+    let transfer_length = object.len() as u32;
+    let source_symbols = transfer_length.div_ceil(u32::from(symbol_size));
+    let packets = encoded_packets(&encoder, first_repair_id(options.seed), options.redundancy);
+    Ok(packets
+        .into_iter()
+        .enumerate()
+        .map(move |(index, packet)| Chunk {
+            header: ChunkHeader {
+                version: CHUNK_VERSION,
+                total: transfer_length,
+                index: index as u32,
+                packet_size: symbol_size,
+                source_symbols: Some(source_symbols),
+                original_size: Some(original_size),
+                volume: None,
+            },
+            data: packet.serialize(),
+        }))
+}
+
+/// Rebuilds files from the chunks of [`raptorq_packets`], or of QR codes, fed in any order.
+///
+/// Like [`crate::receive::PayloadReceiver`] it can be fed on after a file completes, and then
+/// assembles the next file whose chunks arrive.
+#[derive(Default)]
+pub struct PacketAssembler {
+    reassembler: Reassembler,
+}
+
+impl PacketAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds one chunk and returns the filename, contents and metadata of the file it
+    /// completes, if any. A file with `base_sha256` in its metadata was sent as a delta, to
+    /// be applied with [`crate::delta::apply`].
+    pub fn add(&mut self, chunk: Chunk) -> Result<Option<(String, Vec<u8>, FileMetadata)>> {
+        let Reassembly::Complete(object) = self.reassembler.add(chunk) else {
+            return Ok(None);
+        };
+        let (filename, data, metadata) = unpack_data_with_metadata(&decompress(&object)?)?;
+        // One volume arrives at a time; name it so the parts concatenate
+        let filename = match self.reassembler.header().and_then(|h| h.volume) {
+            Some(volume) => volume.part_name(&filename),
+            None => filename,
+        };
+        Ok(Some((filename, data, metadata)))
+    }
+
+    /// As [`add`](Self::add), for a chunk serialized by [`Chunk::to_bytes`].
+    pub fn add_bytes(&mut self, bytes: &[u8]) -> Result<Option<(String, Vec<u8>, FileMetadata)>> {
+        self.add(Chunk::from_bytes(bytes)?)
+    }
+
+    /// Chunks received and the number needed, both 0 before the first chunk.
+    pub fn progress(&self) -> (u32, u32) {
+        (
+            self.reassembler.received_count() as u32,
+            self.reassembler
+                .header()
+                .map_or(0, |header| header.source_symbol_count()),
+        )
+    }
+
+    /// Header of the stream being assembled, once a chunk of it arrived.
+    pub fn header(&self) -> Option<&ChunkHeader> {
+        self.reassembler.header()
+    }
+}
+
+/// Packs `data` with its metadata and compresses it into the object RaptorQ encodes. Also
+/// returns the size of `data`, which chunk headers carry.
+pub(crate) fn compressed_object(
+    data: &[u8],
+    filename: &str,
+    metadata: &FileMetadata,
+    compression: CompressionSetting,
+    seed: Option<u64>,
+) -> Result<(Vec<u8>, u32)> {
+    let original_size = u32::try_from(data.len())
+        .map_err(|_| anyhow!("Input of {} bytes is too large to encode", data.len()))?;
+    // The full digest lets the decoder confirm the file beyond the short packed checksum
+    let metadata = FileMetadata {
+        sha256: Some(sha256_digest(data)),
+        seed,
+        ..*metadata
+    };
+    let packed = pack_data_with_metadata(data, filename, &metadata);
+    // Already-compressed inputs would only grow, so they are stored as they are
+    Ok((compress_or_store(&packed, compression)?, original_size))
+}
+
+/// The source packets of every block followed by its repair packets, as
+/// `get_encoded_packets()` returns them but with the repair packets starting at
+/// `first_repair_id`, so that `redundancy_factor` times the source packets are generated.
+pub(crate) fn encoded_packets(
+    encoder: &RQEncoder,
+    first_repair_id: u32,
+    redundancy_factor: f64,
+) -> Vec<EncodingPacket> {
+    let blocks = encoder.get_block_encoders();
+    let source_packets: usize = blocks
+        .iter()
+        .map(|block| block.source_packets().len())
+        .sum();
+    let repair_per_block = repair_packets_per_block(
+        source_packets as u32,
+        blocks.len() as u32,
+        redundancy_factor,
+    );
+    blocks
+        .iter()
+        .flat_map(|block| {
+            let mut packets = block.source_packets();
+            packets.extend(block.repair_packets(first_repair_id, repair_per_block));
+            packets
+        })
+        .collect()
+}
+
+/// Number of repair packets to generate for each source block, as `get_encoded_packets()`
+/// takes them, so that the total reaches `redundancy_factor` times the source packets.
+pub(crate) fn repair_packets_per_block(
+    source_packets: u32,
+    num_blocks: u32,
+    redundancy_factor: f64,
+) -> u32 {
+    let total_packets = (source_packets as f64 * redundancy_factor).ceil() as u32;
+    let total_packets = total_packets.max(source_packets + 2);
+    (total_packets - source_packets).div_ceil(num_blocks)
+}
+
+pub(crate) fn validate_redundancy_factor(redundancy_factor: f64) -> Result<()> {
+    if redundancy_factor.is_nan() || redundancy_factor < 1.0 {
+        return Err(anyhow!(
+            "Invalid redundancy factor: {}. It must be at least 1.0.",
+            redundancy_factor
+        ));
+    }
+    Ok(())
+}
+
+/// Repair symbol id that seeded runs start their repair packets at: a SplitMix64 hash of the
+/// seed, kept in the lower half of the 24-bit symbol id space so the ids cannot overflow.
+pub(crate) fn first_repair_id(seed: Option<u64>) -> u32 {
+    let Some(mut seed) = seed else {
+        return 0;
+    };
+    (splitmix64(&mut seed) % (1 << 23)) as u32
+}
+
+/// Advances a SplitMix64 generator and returns its next output.
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packets_roundtrip_out_of_order() {
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let options = PacketOptions::new().packet_size(64).redundancy(2.0);
+        let packets: Vec<Vec<u8>> = raptorq_packets(&data, "data.bin", &options)
+            .unwrap()
+            .map(|chunk| chunk.to_bytes().unwrap())
+            .collect();
+        assert!(packets.iter().all(|packet| packet.len() <= 64));
+
+        // Every other packet, last first, as a lossy link might deliver them
+        let mut assembler = PacketAssembler::new();
+        let mut file = None;
+        for packet in packets.iter().rev().step_by(2) {
+            if let Some(done) = assembler.add_bytes(packet).unwrap() {
+                file = Some(done);
+                break;
+            }
+        }
+        let (filename, decoded, metadata) = file.expect("half the packets rebuild the file");
+        assert_eq!(filename, "data.bin");
+        assert_eq!(decoded, data);
+        assert_eq!(metadata.sha256, Some(sha256_digest(&data)));
+    }
+
+    #[test]
+    fn test_packet_options_are_validated() {
+        let options = PacketOptions::new().packet_size(HEADER_SIZE);
+        assert!(raptorq_packets(b"data", "a", &options).is_err());
+        let options = PacketOptions::new().redundancy(0.5);
+        assert!(raptorq_packets(b"data", "a", &options).is_err());
+    }
+}
@@ -1,104 +1,1251 @@
 use anyhow::{anyhow, Result};
 
 #[cfg(feature = "encode")]
-use image::{Rgb, RgbImage};
+use image::{Rgb, RgbImage, RgbaImage};
 
-#[cfg(any(feature = "decode", feature = "wasm"))]
-use image::{DynamicImage, GrayImage};
+#[cfg(feature = "decode")]
+use image::{DynamicImage, GrayImage, Luma};
 
-#[cfg(feature = "encode")]
-use qrcode::{Color, EcLevel, QrCode, Version};
+// Without the native encoder and decoder, the wasm build renders and scans plain buffers
+#[cfg(all(feature = "wasm", not(feature = "encode")))]
+use crate::raster::{Rgb, RgbImage};
+
+#[cfg(all(feature = "wasm", not(feature = "decode")))]
+use crate::raster::{GrayImage, Luma};
+
+#[cfg(any(feature = "encode", feature = "wasm"))]
+use qrcode::QrCode;
+
+#[cfg(any(feature = "encode", feature = "wasm"))]
+use qrcode::Color;
+
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub use qrcode::{EcLevel, Version};
 
 #[cfg(any(feature = "decode", feature = "wasm"))]
-use rqrr::PreparedImage;
+use rqrr::{DeQRError, PreparedImage};
 
-#[cfg(any(feature = "encode", feature = "decode"))]
+#[cfg(feature = "encode")]
 use std::path::Path;
 
+#[cfg(any(feature = "encode", feature = "decode"))]
+use std::str::FromStr;
+
 pub const QR_FILE_EXTENSION: &str = "png";
 
+/// Luma below this counts as ink when looking for blank gaps between codes.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const INK_THRESHOLD: u8 = 128;
+/// Regions smaller than a version 1 code at one pixel per module cannot hold a QR code.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const MIN_REGION_SIZE: u32 = 21;
+
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub const DEFAULT_EC_LEVEL: EcLevel = EcLevel::M;
+
+/// Parses an error-correction level name (`L`, `M`, `Q` or `H`, case-insensitive).
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub fn parse_ec_level(s: &str) -> Result<EcLevel> {
+    match s.to_ascii_uppercase().as_str() {
+        "L" => Ok(EcLevel::L),
+        "M" => Ok(EcLevel::M),
+        "Q" => Ok(EcLevel::Q),
+        "H" => Ok(EcLevel::H),
+        _ => Err(anyhow!(
+            "Invalid error correction level: {}. Expected one of L, M, Q, H.",
+            s
+        )),
+    }
+}
+
+/// Parses a QR code version number from 1 to 40.
 #[cfg(feature = "encode")]
+pub fn parse_qr_version(s: &str) -> Result<Version> {
+    match s.trim().parse::<i16>() {
+        Ok(n) if (1..=40).contains(&n) => Ok(Version::Normal(n)),
+        _ => Err(anyhow!(
+            "Invalid QR version: {}. Expected a number from 1 to 40.",
+            s
+        )),
+    }
+}
+
+/// Scanners need dark modules on a light background with at least this contrast ratio
+/// (WCAG definition, from 1:1 to 21:1).
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub const MIN_CONTRAST_RATIO: f64 = 4.5;
+/// Narrowest quiet zone, in modules, that still lets detectors find the code reliably.
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub const MIN_MARGIN_MODULES: u32 = 2;
+/// Largest share of the symbol width a centre logo may cover, including its clear border.
+/// About 6% of the modules, which level H (30% recoverable) absorbs with room to spare.
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub const MAX_LOGO_FRACTION: f64 = 0.25;
+
+/// Colors, module shape, quiet zone and optional centre logo used when rendering QR images.
+#[cfg(any(feature = "encode", feature = "wasm"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrStyle {
+    pub foreground: Rgb<u8>,
+    pub background: Rgb<u8>,
+    /// Draw data modules as dots. Finder patterns stay square so codes remain easy to locate.
+    pub rounded: bool,
+    /// Quiet zone around the code, in modules.
+    pub margin: u32,
+    /// Image drawn over the centre of the code, relying on error correction to recover the
+    /// hidden modules. Requires [`EcLevel::H`].
+    #[cfg(feature = "encode")]
+    pub logo: Option<RgbaImage>,
+}
+
+#[cfg(any(feature = "encode", feature = "wasm"))]
+impl Default for QrStyle {
+    fn default() -> Self {
+        QrStyle {
+            foreground: Rgb([0, 0, 0]),
+            background: Rgb([255, 255, 255]),
+            rounded: false,
+            margin: 4,
+            #[cfg(feature = "encode")]
+            logo: None,
+        }
+    }
+}
+
+#[cfg(any(feature = "encode", feature = "wasm"))]
+impl QrStyle {
+    /// Rejects styles decoders are unlikely to read: light-on-dark or low-contrast colors,
+    /// and quiet zones narrower than [`MIN_MARGIN_MODULES`].
+    pub fn validate(&self) -> Result<()> {
+        let fg = relative_luminance(self.foreground);
+        let bg = relative_luminance(self.background);
+        if fg >= bg {
+            return Err(anyhow!(
+                "QR foreground must be darker than the background; inverted codes are not readable by most scanners"
+            ));
+        }
+        let ratio = (bg + 0.05) / (fg + 0.05);
+        if ratio < MIN_CONTRAST_RATIO {
+            return Err(anyhow!(
+                "QR colors have a contrast ratio of {:.1}:1, below the minimum of {}:1",
+                ratio,
+                MIN_CONTRAST_RATIO
+            ));
+        }
+        if self.margin < MIN_MARGIN_MODULES {
+            return Err(anyhow!(
+                "QR margin of {} module(s) is too small; use at least {}",
+                self.margin,
+                MIN_MARGIN_MODULES
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Relative luminance of an sRGB color, from 0 (black) to 1 (white).
+#[cfg(any(feature = "encode", feature = "wasm"))]
+fn relative_luminance(color: Rgb<u8>) -> f64 {
+    let linear = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let Rgb([r, g, b]) = color;
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+/// Parses a hex color such as `#1a2b3c`, `1a2b3c` or `#abc`.
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub fn parse_color(s: &str) -> Result<Rgb<u8>> {
+    let invalid = || anyhow!("Invalid color: {}. Expected a hex color like #RRGGBB.", s);
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect::<String>(),
+        6 => hex.to_string(),
+        _ => return Err(invalid()),
+    };
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    Ok(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+/// Whether module (`x`, `y`) is part of one of the three 7x7 finder patterns.
+#[cfg(any(feature = "encode", feature = "wasm"))]
+fn in_finder_pattern(x: u32, y: u32, width: u32) -> bool {
+    let near_start = |v: u32| v < 7;
+    let near_end = |v: u32| v + 7 >= width;
+    (near_start(x) && near_start(y))
+        || (near_end(x) && near_start(y))
+        || (near_start(x) && near_end(y))
+}
+
+#[cfg(any(feature = "encode", feature = "wasm"))]
 pub fn generate_qr_image(
     data: &[u8],
     specific_version: Option<Version>,
     pixel_scale: u32,
+    ec_level: EcLevel,
+    style: &QrStyle,
 ) -> Result<(RgbImage, Version)> {
+    style.validate()?;
+    #[cfg(feature = "encode")]
+    if style.logo.is_some() && ec_level != EcLevel::H {
+        return Err(anyhow!(
+            "A logo hides part of the code and needs error correction level H"
+        ));
+    }
+
     let code = if let Some(v) = specific_version {
-        QrCode::with_version(data, v, EcLevel::M)
+        QrCode::with_version(data, v, ec_level)
             .map_err(|e| anyhow!("Failed to create QR code with specific version: {}", e))?
     } else {
-        QrCode::with_error_correction_level(data, EcLevel::M)
+        QrCode::with_error_correction_level(data, ec_level)
             .map_err(|e| anyhow!("Failed to create QR code: {}", e))?
     };
 
     let version = code.version();
 
-    let image = code
-        .render::<Rgb<u8>>()
-        .min_dimensions(200, 200)
-        .quiet_zone(true)
-        .module_dimensions(pixel_scale, pixel_scale)
-        .build();
+    let scale = pixel_scale.max(1);
+    let width = code.width() as u32;
+    let size = (width + 2 * style.margin) * scale;
+    let mut image = RgbImage::from_pixel(size, size, style.background);
+
+    for (i, color) in code.to_colors().iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        let dot = style.rounded && !in_finder_pattern(x, y, width);
+        let left = (x + style.margin) * scale;
+        let top = (y + style.margin) * scale;
+        for dy in 0..scale {
+            for dx in 0..scale {
+                // Inside the circle inscribed in the module, measured from pixel centres
+                let (cx, cy) = (
+                    (2 * dx + 1) as i64 - scale as i64,
+                    (2 * dy + 1) as i64 - scale as i64,
+                );
+                if !dot || cx * cx + cy * cy <= (scale * scale) as i64 {
+                    image.put_pixel(left + dx, top + dy, style.foreground);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "encode")]
+    if let Some(logo) = &style.logo {
+        draw_logo(
+            &mut image,
+            logo,
+            width,
+            style.margin,
+            scale,
+            style.background,
+        );
+    }
 
     Ok((image, version))
 }
 
+/// Clears a square of whole modules in the centre of the code and draws `logo` inside it,
+/// scaled to fit with a one-module border and blended onto `background`.
+#[cfg(feature = "encode")]
+fn draw_logo(
+    image: &mut RgbImage,
+    logo: &RgbaImage,
+    width: u32,
+    margin: u32,
+    scale: u32,
+    background: Rgb<u8>,
+) {
+    let mut box_modules = (width as f64 * MAX_LOGO_FRACTION).floor() as u32;
+    // Keep the box centred on the module grid
+    if box_modules % 2 != width % 2 {
+        box_modules -= 1;
+    }
+    if box_modules < 3 || logo.width() == 0 || logo.height() == 0 {
+        return;
+    }
+
+    let box_start = (margin + (width - box_modules) / 2) * scale;
+    let box_size = box_modules * scale;
+    for y in box_start..box_start + box_size {
+        for x in box_start..box_start + box_size {
+            image.put_pixel(x, y, background);
+        }
+    }
+
+    let inner = (box_modules - 2) * scale;
+    let ratio = (inner as f64 / logo.width() as f64).min(inner as f64 / logo.height() as f64);
+    let logo_width = ((logo.width() as f64 * ratio).round() as u32).max(1);
+    let logo_height = ((logo.height() as f64 * ratio).round() as u32).max(1);
+    let resized = image::imageops::resize(
+        logo,
+        logo_width,
+        logo_height,
+        image::imageops::FilterType::Triangle,
+    );
+
+    let left = box_start + (box_size - logo_width) / 2;
+    let top = box_start + (box_size - logo_height) / 2;
+    for (x, y, pixel) in resized.enumerate_pixels() {
+        let [r, g, b, a] = pixel.0;
+        let alpha = a as u32;
+        let blend = |fg: u8, bg: u8| ((fg as u32 * alpha + bg as u32 * (255 - alpha)) / 255) as u8;
+        let Rgb([br, bg, bb]) = background;
+        image.put_pixel(
+            left + x,
+            top + y,
+            Rgb([blend(r, br), blend(g, bg), blend(b, bb)]),
+        );
+    }
+}
+
+/// Returns the smallest QR version that holds `data` at the given error-correction level.
+#[cfg(any(feature = "encode", feature = "wasm"))]
+pub fn qr_version_for(data: &[u8], ec_level: EcLevel) -> Result<Version> {
+    let code = QrCode::with_error_correction_level(data, ec_level)
+        .map_err(|e| anyhow!("Failed to create QR code: {}", e))?;
+    Ok(code.version())
+}
+
+/// Number of QR codes per row and per column when several are tiled into one image.
+#[cfg(feature = "encode")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridLayout {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+#[cfg(feature = "encode")]
+impl GridLayout {
+    pub fn codes_per_image(&self) -> usize {
+        (self.columns * self.rows) as usize
+    }
+}
+
+/// Parses a layout written as `COLUMNSxROWS`, e.g. `3x3`.
+#[cfg(feature = "encode")]
+impl FromStr for GridLayout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "Invalid grid layout: {}. Expected COLUMNSxROWS, e.g. 3x3.",
+                s
+            )
+        };
+        let (columns, rows) = s
+            .to_ascii_lowercase()
+            .split_once('x')
+            .map(|(c, r)| (c.trim().parse::<u32>(), r.trim().parse::<u32>()))
+            .ok_or_else(invalid)?;
+        match (columns, rows) {
+            (Ok(columns), Ok(rows)) if columns > 0 && rows > 0 => Ok(GridLayout { columns, rows }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Blank space between neighbouring tiles, in QR modules, on top of each code's quiet zone.
+#[cfg(feature = "encode")]
+const GRID_SPACING_MODULES: u32 = 4;
+
+/// 5x7 bitmaps for the characters used in tile labels and captions, one byte per row.
+/// Lowercase letters are drawn with the uppercase glyphs.
+#[cfg(feature = "encode")]
+const LABEL_GLYPHS: [(char, [u8; 7]); 48] = [
+    ('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+    ('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+    ('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+    ('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+    ('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+    ('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+    ('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+    ('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+    ('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+    ('A', [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11]),
+    ('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+    ('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+    ('D', [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C]),
+    ('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+    ('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+    ('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+    ('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+    ('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+    ('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+    ('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+    ('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+    ('Y', [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04]),
+    ('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+    (',', [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08]),
+    ('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+    (':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+    ('#', [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A]),
+    ('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+    (')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+    ('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+    ('=', [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00]),
+    ('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+];
+
+/// Draws `text` centred horizontally on `center_x`, with each font pixel `scale` pixels wide.
+/// Characters without a glyph are left blank.
+#[cfg(feature = "encode")]
+fn draw_label(
+    canvas: &mut RgbImage,
+    text: &str,
+    center_x: u32,
+    top: u32,
+    scale: u32,
+    color: Rgb<u8>,
+) {
+    let advance = 6 * scale;
+    let text_width = (text.chars().count() as u32 * advance).saturating_sub(scale);
+    let left = center_x.saturating_sub(text_width / 2);
+
+    for (i, ch) in text.chars().enumerate() {
+        let ch = ch.to_ascii_uppercase();
+        let Some((_, rows)) = LABEL_GLYPHS.iter().find(|(c, _)| *c == ch) else {
+            continue;
+        };
+        let glyph_x = left + i as u32 * advance;
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..5 {
+                if bits & (0x10 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let x = glyph_x + col * scale + dx;
+                        let y = top + row as u32 * scale + dy;
+                        if x < canvas.width() && y < canvas.height() {
+                            canvas.put_pixel(x, y, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Places equally sized QR images on a canvas in the style's background color, left to right
+/// and top to bottom, with a text label (digits and `/`) under each one. When every label is
+/// empty, no room is left for them.
+///
+/// `pixel_scale` is the module size the codes were rendered with; it sets the spacing
+/// between tiles and the size of the labels.
+#[cfg(feature = "encode")]
+pub fn tile_qr_images(
+    tiles: &[(RgbImage, String)],
+    layout: GridLayout,
+    pixel_scale: u32,
+    style: &QrStyle,
+) -> Result<RgbImage> {
+    let (first, _) = tiles
+        .first()
+        .ok_or_else(|| anyhow!("No QR images to tile"))?;
+    if tiles.len() > layout.codes_per_image() {
+        return Err(anyhow!(
+            "{} QR images do not fit in a {}x{} grid",
+            tiles.len(),
+            layout.columns,
+            layout.rows
+        ));
+    }
+
+    let scale = pixel_scale.max(1);
+    let spacing = GRID_SPACING_MODULES * scale;
+    let label_height = if tiles.iter().all(|(_, label)| label.is_empty()) {
+        0
+    } else {
+        8 * scale
+    };
+
+    let (tile_width, tile_height) = first.dimensions();
+    let cell_width = tile_width + spacing;
+    let cell_height = tile_height + label_height + spacing;
+    let columns = layout.columns.min(tiles.len() as u32);
+    let rows = (tiles.len() as u32).div_ceil(layout.columns);
+
+    let mut canvas = RgbImage::from_pixel(
+        cell_width * columns - spacing,
+        cell_height * rows - spacing,
+        style.background,
+    );
+    for (i, (image, label)) in tiles.iter().enumerate() {
+        let x = (i as u32 % layout.columns) * cell_width;
+        let y = (i as u32 / layout.columns) * cell_height;
+        image::imageops::replace(&mut canvas, image, x as i64, y as i64);
+        draw_label(
+            &mut canvas,
+            label,
+            x + tile_width / 2,
+            y + tile_height,
+            scale,
+            style.foreground,
+        );
+    }
+
+    Ok(canvas)
+}
+
+/// Extends a QR image downwards with `lines` of text centred under the code, in the style's
+/// colours. Lines too long for the image width are cut short and end in `..`. The font is
+/// half the module size, so a caption fits about one character per three modules.
+#[cfg(feature = "encode")]
+pub fn add_caption(
+    image: &RgbImage,
+    lines: &[String],
+    pixel_scale: u32,
+    style: &QrStyle,
+) -> RgbImage {
+    let scale = (pixel_scale / 2).max(1);
+    let line_height = 8 * scale;
+    let max_chars = (image.width() / (6 * scale)) as usize;
+
+    let mut canvas = RgbImage::from_pixel(
+        image.width(),
+        image.height() + lines.len() as u32 * line_height + scale,
+        style.background,
+    );
+    image::imageops::replace(&mut canvas, image, 0, 0);
+    for (i, line) in lines.iter().enumerate() {
+        let text: String = if line.chars().count() > max_chars {
+            let kept: String = line.chars().take(max_chars.saturating_sub(2)).collect();
+            format!("{}..", kept)
+        } else {
+            line.clone()
+        };
+        draw_label(
+            &mut canvas,
+            &text,
+            image.width() / 2,
+            image.height() + scale + i as u32 * line_height,
+            scale,
+            style.foreground,
+        );
+    }
+    canvas
+}
+
+/// Overlays three equally sized QR images into the red, green and blue planes of one image,
+/// each plane taking the brightness of its code. Only for transfers where colours arrive
+/// exactly, such as GIF files; `decode_color_planes` separates them again.
+#[cfg(feature = "encode")]
+pub fn merge_color_planes(planes: &[RgbImage]) -> Result<RgbImage> {
+    let [red, green, blue] = planes else {
+        return Err(anyhow!(
+            "Colour planes take 3 QR images, not {}",
+            planes.len()
+        ));
+    };
+    if red.dimensions() != green.dimensions() || red.dimensions() != blue.dimensions() {
+        return Err(anyhow!(
+            "QR images of different sizes do not share one image"
+        ));
+    }
+    let luma = |image: &RgbImage, x, y| {
+        let Rgb([r, g, b]) = *image.get_pixel(x, y);
+        ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+    };
+    Ok(RgbImage::from_fn(red.width(), red.height(), |x, y| {
+        Rgb([luma(red, x, y), luma(green, x, y), luma(blue, x, y)])
+    }))
+}
+
 #[cfg(feature = "encode")]
 pub fn save_qr_image(image: &RgbImage, path: &Path) -> Result<()> {
     image.save(path)?;
     Ok(())
 }
 
-#[cfg(any(feature = "decode", feature = "wasm"))]
+#[cfg(feature = "decode")]
 pub fn decode_qr_from_dynamic_image(img: &DynamicImage) -> Result<Vec<u8>> {
     let gray = img.to_luma8();
     decode_qr_from_gray(&gray)
 }
 
+/// Side of the thumbnail a frame is reduced to when comparing it with the previous one.
 #[cfg(any(feature = "decode", feature = "wasm"))]
-pub fn decode_qr_from_gray(gray: &GrayImage) -> Result<Vec<u8>> {
-    let mut prepared = PreparedImage::prepare(gray.clone());
-    let grids = prepared.detect_grids();
+const SIGNATURE_SIZE: u32 = 32;
+/// Largest per-cell brightness change still treated as the same frame, to absorb
+/// video compression noise.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const SIGNATURE_TOLERANCE: u8 = 6;
+
+/// Small grayscale summary of a frame, used to skip QR detection on frames of a
+/// recording that repeat the previous one.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FrameSignature(Vec<u8>);
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+impl FrameSignature {
+    /// Averages the frame over a grid of [`SIGNATURE_SIZE`] cells a side, or fewer for
+    /// frames smaller than that.
+    pub(crate) fn of(gray: &GrayImage) -> Self {
+        let (width, height) = gray.dimensions();
+        let (columns, rows) = (width.min(SIGNATURE_SIZE), height.min(SIGNATURE_SIZE));
+        let mut sums = vec![(0u64, 0u64); (columns * rows) as usize];
+        for (i, &pixel) in gray.iter().enumerate() {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            let cell = (y * rows / height * columns + x * columns / width) as usize;
+            sums[cell].0 += pixel as u64;
+            sums[cell].1 += 1;
+        }
+        Self(
+            sums.iter()
+                .map(|&(sum, count)| (sum / count.max(1)) as u8)
+                .collect(),
+        )
+    }
+
+    /// True when no cell differs by more than the noise tolerance. Any change to the
+    /// code shifts some cells well beyond it, so a new chunk is never mistaken for a repeat.
+    pub(crate) fn matches(&self, other: &FrameSignature) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(&other.0)
+                .all(|(a, b)| a.abs_diff(*b) <= SIGNATURE_TOLERANCE)
+    }
+}
+
+/// Largest brightness range of a frame still treated as blank, such as the black and white
+/// calibration frames at the start of an animation, which hold no code to look for.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+const BLANK_FRAME_RANGE: u8 = 16;
+
+/// True for a frame of nearly one brightness, where QR detection can be skipped.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+pub(crate) fn is_blank_frame(gray: &GrayImage) -> bool {
+    let (min, max) = gray.iter().fold((u8::MAX, u8::MIN), |(min, max), &p| {
+        (min.min(p), max.max(p))
+    });
+    max.saturating_sub(min) <= BLANK_FRAME_RANGE
+}
+
+/// Decodes every QR code found in the image, skipping grids that fail to decode. An image of
+/// mostly saturated colours is read one colour plane at a time.
+#[cfg(feature = "decode")]
+pub fn decode_all_qr_from_dynamic_image(img: &DynamicImage) -> Vec<Vec<u8>> {
+    decode_color_planes(img).unwrap_or_else(|| decode_all_qr_from_gray(&img.to_luma8()))
+}
+
+/// Share of pixels whose channels differ widely above which an image is taken to hold codes
+/// in its colour planes. Codes in plain or styled colours have next to none.
+#[cfg(feature = "decode")]
+const COLOR_PLANE_SHARE: f64 = 0.2;
+
+/// Decodes the codes of an image whose red, green and blue planes each hold one, as
+/// `merge_color_planes` draws them, one plane at a time. Returns `None` for images with little
+/// saturated colour, which hold ordinary codes if any. Their brightness would mostly show
+/// the green plane's code.
+#[cfg(feature = "decode")]
+pub fn decode_color_planes(img: &DynamicImage) -> Option<Vec<Vec<u8>>> {
+    let rgb = img.to_rgb8();
+    let saturated = rgb
+        .pixels()
+        .filter(|pixel| {
+            let [r, g, b] = pixel.0;
+            r.max(g).max(b) - r.min(g).min(b) > 128
+        })
+        .count();
+    if (saturated as f64) < rgb.pixels().len() as f64 * COLOR_PLANE_SHARE {
+        return None;
+    }
+
+    let mut found = Vec::new();
+    for channel in 0..3 {
+        let plane = GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+            Luma([rgb.get_pixel(x, y)[channel]])
+        });
+        for payload in decode_all_qr_from_gray(&plane) {
+            if !found.contains(&payload) {
+                found.push(payload);
+            }
+        }
+    }
+    Some(found)
+}
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+pub fn decode_all_qr_from_gray(gray: &GrayImage) -> Vec<Vec<u8>> {
+    decode_all_qr_located(gray)
+        .into_iter()
+        .map(|(payload, _)| payload)
+        .collect()
+}
+
+/// Like [`decode_all_qr_from_gray`], also returning where in the image each code was found.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn decode_all_qr_located(gray: &GrayImage) -> Vec<(Vec<u8>, Region)> {
+    let mut payloads = detect_and_decode(gray);
+
+    // rqrr can group finder patterns of neighbouring codes into one bogus code and then
+    // miss all of them, so codes tiled side by side are also scanned one region at a time
+    let mut regions = Vec::new();
+    split_on_blank_lines(gray, (0, 0, gray.width(), gray.height()), &mut regions);
+    if regions.len() > 1 {
+        for (x, y, width, height) in regions {
+            if width < MIN_REGION_SIZE || height < MIN_REGION_SIZE {
+                continue;
+            }
+            let margin = (width.max(height) / 10).max(8);
+            let padded = GrayImage::from_fn(width + 2 * margin, height + 2 * margin, |px, py| {
+                let inside = (margin..margin + width).contains(&px)
+                    && (margin..margin + height).contains(&py);
+                if inside {
+                    Luma([gray.get_pixel(x + px - margin, y + py - margin)[0]])
+                } else {
+                    Luma([255])
+                }
+            });
+
+            for (payload, (px, py, w, h)) in detect_and_decode(&padded) {
+                if !payloads.iter().any(|(known, _)| *known == payload) {
+                    let px = (x + px).saturating_sub(margin);
+                    let py = (y + py).saturating_sub(margin);
+                    payloads.push((payload, (px, py, w, h)));
+                }
+            }
+        }
+    }
+
+    payloads
+}
+
+/// Every grid rqrr detects in `gray`, decoded to raw bytes so binary (byte mode) payloads
+/// survive without UTF-8 validation, with its bounds. rqrr reads the raw buffer, so this
+/// works for the `image` crate's buffers and the wasm build's alike.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn decode_grids(gray: &GrayImage) -> Vec<(std::result::Result<Vec<u8>, DeQRError>, Region)> {
+    let (width, pixels) = (gray.width() as usize, gray.as_raw());
+    let mut prepared =
+        PreparedImage::prepare_from_greyscale(width, gray.height() as usize, |x, y| {
+            pixels[y * width + x]
+        });
+    prepared
+        .detect_grids()
+        .iter()
+        .map(|grid| {
+            let mut content = Vec::new();
+            let decoded = grid.decode_to(&mut content).map(|_| content);
+            let xs = grid.bounds.iter().map(|p| p.x.max(0) as u32);
+            let ys = grid.bounds.iter().map(|p| p.y.max(0) as u32);
+            let (left, right) = (xs.clone().min().unwrap_or(0), xs.max().unwrap_or(0));
+            let (top, bottom) = (ys.clone().min().unwrap_or(0), ys.max().unwrap_or(0));
+            (decoded, (left, top, right - left, bottom - top))
+        })
+        .collect()
+}
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn detect_and_decode(gray: &GrayImage) -> Vec<(Vec<u8>, Region)> {
+    decode_grids(gray)
+        .into_iter()
+        .filter_map(|(decoded, region)| Some((decoded.ok()?, region)))
+        .collect()
+}
+
+/// Frames scanned within the tracked region before the whole frame is scanned again,
+/// to pick up codes that appeared elsewhere.
+#[cfg(feature = "decode")]
+const FULL_SCAN_INTERVAL: u32 = 30;
+
+/// Remembers where codes were found in a sequence of frames, such as a GIF or a recording,
+/// and scans only that area of later frames. The whole frame is scanned again when the
+/// area yields nothing and every [`FULL_SCAN_INTERVAL`] frames.
+#[cfg(feature = "decode")]
+#[derive(Debug, Default)]
+pub(crate) struct RoiTracker {
+    region: Option<Region>,
+    frames_since_full_scan: u32,
+    /// Codes the last full scan found, which a scan of the area must match.
+    codes: usize,
+}
+
+#[cfg(feature = "decode")]
+impl RoiTracker {
+    pub(crate) fn decode(&mut self, gray: &GrayImage) -> Vec<Vec<u8>> {
+        let mut partial = Vec::new();
+        if let Some((x, y, width, height)) = self.region {
+            if self.frames_since_full_scan < FULL_SCAN_INTERVAL {
+                self.frames_since_full_scan += 1;
+                // Leave room for the quiet zone and a little movement between frames
+                let margin = width.max(height) / 5;
+                let left = x.saturating_sub(margin);
+                let top = y.saturating_sub(margin);
+                let right = (x + width + margin).min(gray.width());
+                let bottom = (y + height + margin).min(gray.height());
+                let crop = image::imageops::crop_imm(gray, left, top, right - left, bottom - top)
+                    .to_image();
+                let found: Vec<_> = decode_all_qr_located(&crop)
+                    .into_iter()
+                    .map(|(payload, (x, y, w, h))| (payload, (x + left, y + top, w, h)))
+                    .collect();
+                // In frames of several codes, one that moved out of the area or was not read
+                // this time calls for a full scan, so the area does not shrink to the rest
+                if !found.is_empty() && found.len() >= self.codes {
+                    return self.track(found);
+                }
+                partial = found;
+            }
+        }
+
+        self.frames_since_full_scan = 0;
+        let found = decode_all_qr_located(gray);
+        let found = if found.len() >= partial.len() {
+            found
+        } else {
+            partial
+        };
+        self.codes = found.len();
+        self.track(found)
+    }
+
+    /// Remembers the area covering every code found and returns their payloads.
+    fn track(&mut self, found: Vec<(Vec<u8>, Region)>) -> Vec<Vec<u8>> {
+        self.region =
+            found
+                .iter()
+                .map(|&(_, region)| region)
+                .reduce(|(ax, ay, aw, ah), (bx, by, bw, bh)| {
+                    let (left, top) = (ax.min(bx), ay.min(by));
+                    let right = (ax + aw).max(bx + bw);
+                    let bottom = (ay + ah).max(by + bh);
+                    (left, top, right - left, bottom - top)
+                });
+        found.into_iter().map(|(payload, _)| payload).collect()
+    }
+}
+
+/// Global luma thresholds tried by [`ScanEffort::Thorough`], from dark to light.
+#[cfg(feature = "decode")]
+const THOROUGH_THRESHOLDS: [u8; 5] = [64, 96, 128, 160, 192];
+
+/// How much work to spend on an image before concluding it holds no readable code.
+#[cfg(feature = "decode")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScanEffort {
+    /// One detection pass over the image as it is.
+    #[default]
+    Normal,
+    /// When the normal pass reads nothing, also try the image binarized at several threshold
+    /// levels and turned by 90, 180 and 270 degrees. Costs several times the CPU on images
+    /// without a readable code, so it is meant for tough scans.
+    Thorough,
+}
+
+#[cfg(feature = "decode")]
+impl FromStr for ScanEffort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "normal" => Ok(ScanEffort::Normal),
+            "thorough" => Ok(ScanEffort::Thorough),
+            _ => Err(anyhow!(
+                "Invalid scan effort: {}. Expected normal or thorough.",
+                s
+            )),
+        }
+    }
+}
+
+/// The extra passes of [`ScanEffort::Thorough`], for an image where a normal scan found
+/// nothing. Stops at the first pass that reads a code.
+#[cfg(feature = "decode")]
+pub fn decode_all_qr_thorough(gray: &GrayImage) -> Vec<Vec<u8>> {
+    for level in THOROUGH_THRESHOLDS {
+        let mut binary = gray.clone();
+        for pixel in binary.pixels_mut() {
+            pixel[0] = if pixel[0] < level { 0 } else { 255 };
+        }
+        let found = decode_all_qr_from_gray(&binary);
+        if !found.is_empty() {
+            return found;
+        }
+    }
+
+    let rotations: [fn(&GrayImage) -> GrayImage; 3] = [
+        image::imageops::rotate90,
+        image::imageops::rotate180,
+        image::imageops::rotate270,
+    ];
+    for rotate in rotations {
+        let found = decode_all_qr_from_gray(&rotate(gray));
+        if !found.is_empty() {
+            return found;
+        }
+    }
+    Vec::new()
+}
+
+/// Share of the darkest and brightest pixels clipped when stretching contrast.
+#[cfg(feature = "decode")]
+const STRETCH_CLIP_PERCENT: usize = 1;
+/// A pixel must be this many percent darker than its neighbourhood to count as ink.
+#[cfg(feature = "decode")]
+const THRESHOLD_OFFSET_PERCENT: u64 = 10;
+
+/// Cleans up a photographed or filmed code before detection. Contrast is stretched to the
+/// full range, then every pixel is compared with the mean of its neighbourhood, so uneven
+/// lighting, glare and moiré do not decide which modules read as dark.
+#[cfg(feature = "decode")]
+pub fn enhance_for_detection(gray: &GrayImage) -> GrayImage {
+    let (width, height) = gray.dimensions();
+    if width == 0 || height == 0 {
+        return gray.clone();
+    }
+
+    let mut histogram = [0usize; 256];
+    for pixel in gray.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let clip = (width * height) as usize * STRETCH_CLIP_PERCENT / 100;
+    let mut seen = 0;
+    let low = (0..256)
+        .find(|&level| {
+            seen += histogram[level];
+            seen > clip
+        })
+        .unwrap_or(0);
+    let mut seen = 0;
+    let high = (0..256)
+        .rev()
+        .find(|&level| {
+            seen += histogram[level];
+            seen > clip
+        })
+        .unwrap_or(255)
+        .max(low + 1);
+    let stretch = |value: u8| {
+        let value = (value as usize).clamp(low, high);
+        ((value - low) * 255 / (high - low)) as u64
+    };
+
+    // Summed-area table, so each neighbourhood mean costs four lookups
+    let stride = width as usize + 1;
+    let mut integral = vec![0u64; stride * (height as usize + 1)];
+    for y in 0..height as usize {
+        let mut row_sum = 0;
+        for x in 0..width as usize {
+            row_sum += stretch(gray.get_pixel(x as u32, y as u32)[0]);
+            integral[(y + 1) * stride + x + 1] = integral[y * stride + x + 1] + row_sum;
+        }
+    }
+
+    // Wide enough to span several modules of a code filling the frame
+    let radius = (width.max(height) / 16).max(7) as usize;
+    GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as usize, y as usize);
+        let (left, top) = (x.saturating_sub(radius), y.saturating_sub(radius));
+        let right = (x + radius + 1).min(width as usize);
+        let bottom = (y + radius + 1).min(height as usize);
+        let sum = integral[bottom * stride + right] + integral[top * stride + left]
+            - integral[top * stride + right]
+            - integral[bottom * stride + left];
+        let count = ((right - left) * (bottom - top)) as u64;
+        let value = stretch(gray.get_pixel(x as u32, y as u32)[0]);
+        if value * count * 100 < sum * (100 - THRESHOLD_OFFSET_PERCENT) {
+            Luma([0])
+        } else {
+            Luma([255])
+        }
+    })
+}
+
+/// Pixel rectangle as (x, y, width, height).
+#[cfg(any(feature = "decode", feature = "wasm"))]
+type Region = (u32, u32, u32, u32);
+
+/// Recursively splits `rect` along rows and columns that contain no ink,
+/// collecting the tight bounds of every region that cannot be split further.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn split_on_blank_lines(gray: &GrayImage, rect: Region, out: &mut Vec<Region>) {
+    let (x, y, width, _) = rect;
+
+    let row_spans = ink_spans(gray, rect, true);
+    if row_spans.len() > 1 {
+        for (start, end) in row_spans {
+            split_on_blank_lines(gray, (x, y + start, width, end - start), out);
+        }
+        return;
+    }
+    let Some(&(row_start, row_end)) = row_spans.first() else {
+        return;
+    };
+    let (y, height) = (y + row_start, row_end - row_start);
+
+    let column_spans = ink_spans(gray, (x, y, width, height), false);
+    if column_spans.len() > 1 {
+        for (start, end) in column_spans {
+            split_on_blank_lines(gray, (x + start, y, end - start, height), out);
+        }
+        return;
+    }
+    if let Some(&(column_start, column_end)) = column_spans.first() {
+        out.push((x + column_start, y, column_end - column_start, height));
+    }
+}
+
+/// Returns the half-open spans of rows (or columns) inside `rect` that contain ink.
+#[cfg(any(feature = "decode", feature = "wasm"))]
+fn ink_spans(gray: &GrayImage, rect: Region, rows: bool) -> Vec<(u32, u32)> {
+    let (x, y, width, height) = rect;
+    let (length, breadth) = if rows {
+        (height, width)
+    } else {
+        (width, height)
+    };
+
+    let mut spans = Vec::new();
+    let mut start = None;
+    for i in 0..length {
+        let has_ink = (0..breadth).any(|j| {
+            let (px, py) = if rows { (x + j, y + i) } else { (x + i, y + j) };
+            gray.get_pixel(px, py)[0] < INK_THRESHOLD
+        });
+        match (has_ink, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, length));
+    }
 
+    spans
+}
+
+#[cfg(any(feature = "decode", feature = "wasm"))]
+pub fn decode_qr_from_gray(gray: &GrayImage) -> Result<Vec<u8>> {
+    let grids = decode_grids(gray);
     if grids.is_empty() {
         return Err(anyhow!("No QR code found in image"));
     }
 
-    let (_, content) = grids[0]
-        .decode()
-        .map_err(|e| anyhow!("Failed to decode QR code: {:?}", e))?;
+    // A false grid can be detected ahead of the real one, so every grid gets a chance
+    let mut last_error = None;
+    for (decoded, _) in grids {
+        match decoded {
+            Ok(content) => return Ok(content),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(anyhow!("Failed to decode QR code: {:?}", last_error))
+}
+
+/// How QR codes are drawn in terminal mode.
+///
+/// `HalfBlock` draws one module per column and two per row with `▀`/`▄`/`█`.
+/// `Braille` packs 2x4 modules into each braille character, so codes about four times
+/// as large fit the same terminal, at the cost of small gaps between dots.
+///
+/// `Kitty`, `Iterm2` and `Sixel` send a real raster image through that terminal graphics
+/// protocol, which cameras read far more reliably than block characters; the image takes
+/// the space the half-block rendering would. `Image` picks one of them from the
+/// environment and falls back to `HalfBlock` when none is known to be supported.
+#[cfg(feature = "encode")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TerminalRender {
+    #[default]
+    HalfBlock,
+    Braille,
+    Kitty,
+    Iterm2,
+    Sixel,
+    Image,
+}
+
+#[cfg(feature = "encode")]
+impl TerminalRender {
+    /// Resolves `Image` to the graphics protocol the terminal advertises, if any.
+    pub fn resolve(self) -> TerminalRender {
+        if self != TerminalRender::Image {
+            return self;
+        }
+
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        let (term, term_program) = (var("TERM"), var("TERM_PROGRAM"));
+        if term == "xterm-kitty" || term_program == "ghostty" || !var("KITTY_WINDOW_ID").is_empty()
+        {
+            TerminalRender::Kitty
+        } else if matches!(term_program.as_str(), "iTerm.app" | "WezTerm")
+            || var("LC_TERMINAL") == "iTerm2"
+        {
+            TerminalRender::Iterm2
+        } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+            TerminalRender::Sixel
+        } else {
+            TerminalRender::HalfBlock
+        }
+    }
+
+    /// Modules covered by one character, as (columns, rows).
+    fn cell_size(self) -> (usize, usize) {
+        match self {
+            TerminalRender::Braille => (2, 4),
+            // Images are sized to the area the half-block rendering would take
+            _ => (1, 2),
+        }
+    }
 
-    Ok(content.into_bytes())
+    /// Columns and rows of text needed for `modules` x `modules` modules.
+    fn display_size(self, modules: usize) -> (usize, usize) {
+        let (cell_width, cell_height) = self.cell_size();
+        (modules.div_ceil(cell_width), modules.div_ceil(cell_height))
+    }
+
+    /// Character for the cell whose top-left module is (`row`, `col`).
+    fn cell_char(self, is_dark: impl Fn(usize, usize) -> bool, row: usize, col: usize) -> char {
+        match self {
+            TerminalRender::Braille => {
+                // Unicode braille dot numbering: dots 1-3 and 7 run down the left column,
+                // 4-6 and 8 down the right one
+                const DOTS: [(usize, usize, u32); 8] = [
+                    (0, 0, 0x01),
+                    (1, 0, 0x02),
+                    (2, 0, 0x04),
+                    (0, 1, 0x08),
+                    (1, 1, 0x10),
+                    (2, 1, 0x20),
+                    (3, 0, 0x40),
+                    (3, 1, 0x80),
+                ];
+                let bits = DOTS
+                    .iter()
+                    .filter(|&&(dy, dx, _)| is_dark(row + dy, col + dx))
+                    .fold(0, |bits, &(_, _, bit)| bits | bit);
+                char::from_u32(0x2800 + bits).unwrap_or(' ')
+            }
+            // Graphics modes draw characters only when falling back to half blocks
+            _ => match (is_dark(row, col), is_dark(row + 1, col)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            },
+        }
+    }
 }
 
 #[cfg(feature = "encode")]
-pub fn render_qr_to_terminal(data: &[u8]) -> Result<String> {
-    use terminal_size::{terminal_size, Height, Width};
+impl FromStr for TerminalRender {
+    type Err = anyhow::Error;
 
-    let code = QrCode::with_error_correction_level(data, EcLevel::M)
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "half-block" | "halfblock" | "blocks" => Ok(TerminalRender::HalfBlock),
+            "braille" => Ok(TerminalRender::Braille),
+            "kitty" => Ok(TerminalRender::Kitty),
+            "iterm2" | "iterm" => Ok(TerminalRender::Iterm2),
+            "sixel" => Ok(TerminalRender::Sixel),
+            "image" | "auto" => Ok(TerminalRender::Image),
+            _ => Err(anyhow!(
+                "Invalid terminal render mode: {}. Expected half-block, braille, image, kitty, iterm2 or sixel.",
+                s
+            )),
+        }
+    }
+}
+
+/// Terminal size in characters that codes are fitted to, and where it came from.
+#[cfg(feature = "encode")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalSize {
+    /// Read from the terminal.
+    Detected { width: usize, height: usize },
+    /// Given by the caller, e.g. for output piped to a file or another terminal.
+    Given { width: usize, height: usize },
+    /// Assumed because the size could not be read, as for piped output, or was implausibly
+    /// small.
+    Assumed { width: usize, height: usize },
+}
+
+#[cfg(feature = "encode")]
+impl TerminalSize {
+    /// Size assumed when the terminal's own size cannot be used.
+    pub const FALLBACK: (usize, usize) = (120, 60);
+
+    /// Reads the size of the terminal on stdout, falling back to [`Self::FALLBACK`].
+    pub fn detect() -> Self {
+        use terminal_size::{terminal_size, Height, Width};
+
+        match terminal_size() {
+            Some((Width(w), Height(h))) if w >= 40 && h >= 30 => TerminalSize::Detected {
+                width: w as usize,
+                height: h as usize,
+            },
+            _ => {
+                let (width, height) = Self::FALLBACK;
+                TerminalSize::Assumed { width, height }
+            }
+        }
+    }
+
+    /// Width and height in characters.
+    pub fn dimensions(self) -> (usize, usize) {
+        match self {
+            TerminalSize::Detected { width, height }
+            | TerminalSize::Given { width, height }
+            | TerminalSize::Assumed { width, height } => (width, height),
+        }
+    }
+}
+
+#[cfg(feature = "encode")]
+fn terminal_dimensions() -> (usize, usize) {
+    TerminalSize::detect().dimensions()
+}
+
+#[cfg(feature = "encode")]
+pub fn render_qr_to_terminal(
+    data: &[u8],
+    ec_level: EcLevel,
+    render: TerminalRender,
+) -> Result<String> {
+    let code = QrCode::with_error_correction_level(data, ec_level)
         .map_err(|e| anyhow!("Failed to create QR code: {}", e))?;
 
     let qr_size = code.width();
     let colors = code.to_colors();
+    let render = render.resolve();
 
-    let (term_width, term_height) = terminal_size()
-        .map(|(Width(w), Height(h))| {
-            if w < 40 || h < 30 {
-                (120, 60)
-            } else {
-                (w as usize, h as usize)
-            }
-        })
-        .unwrap_or((120, 60));
+    let (term_width, term_height) = terminal_dimensions();
 
     let qr_with_quiet = qr_size + 4; // Add quiet zone
-
-    // Fixed scale=1: each QR module = 1 char wide, uses half-blocks for height
-    // This gives the most compact and square appearance
-    let scale: usize = 1;
-
-    let display_width = qr_with_quiet * scale;
-    let display_height = (qr_with_quiet + 1) / 2 * scale;
+    let (display_width, display_height) = render.display_size(qr_with_quiet);
 
     // Center padding
     let pad_left = term_width.saturating_sub(display_width) / 2;
@@ -112,6 +1259,29 @@ pub fn render_qr_to_terminal(data: &[u8]) -> Result<String> {
         result.push('\n');
     }
 
+    let graphics = match render {
+        TerminalRender::Kitty | TerminalRender::Iterm2 | TerminalRender::Sixel => {
+            // Same quiet zone as the character renderings, so the image fills the same area
+            let style = QrStyle {
+                margin: 2,
+                ..QrStyle::default()
+            };
+            let (image, _) = generate_qr_image(data, None, TERMINAL_IMAGE_SCALE, ec_level, &style)?;
+            Some(match render {
+                TerminalRender::Kitty => kitty_image(&image, display_width, display_height)?,
+                TerminalRender::Iterm2 => iterm2_image(&image, display_width, display_height)?,
+                _ => sixel_image(&image),
+            })
+        }
+        _ => None,
+    };
+    if let Some(graphics) = graphics {
+        result.push_str(&left_pad);
+        result.push_str(&graphics);
+        result.push('\n');
+        return Ok(result);
+    }
+
     // Helper to check if a position is dark
     let is_dark = |row: usize, col: usize| -> bool {
         if row >= 2 && row < qr_size + 2 && col >= 2 && col < qr_size + 2 {
@@ -123,66 +1293,133 @@ pub fn render_qr_to_terminal(data: &[u8]) -> Result<String> {
         }
     };
 
-    // Render using half-block characters
-    // Process 2 QR rows at a time, each becomes 1 terminal row (with scale repetition)
-    for qr_row_pair in 0..((qr_with_quiet + 1) / 2) {
-        let top_row = qr_row_pair * 2;
-        let bottom_row = top_row + 1;
+    // Each character covers a cell of modules: 1x2 for half-blocks, 2x4 for braille
+    let (cell_width, cell_height) = render.cell_size();
+    for text_row in 0..display_height {
+        result.push_str(&left_pad);
+        for text_col in 0..display_width {
+            result.push(render.cell_char(is_dark, text_row * cell_height, text_col * cell_width));
+        }
+        result.push('\n');
+    }
 
-        // Repeat each output row 'scale' times for vertical scaling
-        for _ in 0..scale {
-            result.push_str(&left_pad);
+    Ok(result)
+}
 
-            for qr_col in 0..qr_with_quiet {
-                let top_dark = is_dark(top_row, qr_col);
-                let bottom_dark = if bottom_row < qr_with_quiet {
-                    is_dark(bottom_row, qr_col)
-                } else {
-                    false
-                };
+/// Pixels per module in images sent through a terminal graphics protocol.
+/// Kitty and iTerm2 scale the image to its cell area; sixel shows it as is.
+#[cfg(feature = "encode")]
+const TERMINAL_IMAGE_SCALE: u32 = 4;
 
-                let ch = match (top_dark, bottom_dark) {
-                    (true, true) => '█',
-                    (true, false) => '▀',
-                    (false, true) => '▄',
-                    (false, false) => ' ',
-                };
+#[cfg(feature = "encode")]
+fn encode_png_base64(image: &RgbImage) -> Result<String> {
+    use base64::Engine;
 
-                // Repeat char 'scale' times for horizontal scaling
-                for _ in 0..scale {
-                    result.push(ch);
-                }
+    let mut png = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(png))
+}
+
+/// Kitty graphics protocol: a PNG sent in 4096-byte chunks, scaled to `columns` x `rows` cells.
+#[cfg(feature = "encode")]
+fn kitty_image(image: &RgbImage, columns: usize, rows: usize) -> Result<String> {
+    let payload = encode_png_base64(image)?;
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(4096).collect();
+
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk)?;
+        if i == 0 {
+            out.push_str(&format!(
+                "\x1b_Ga=T,f=100,q=2,c={},r={},m={};{}\x1b\\",
+                columns, rows, more, chunk
+            ));
+        } else {
+            out.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+        }
+    }
+    Ok(out)
+}
+
+/// iTerm2 inline image protocol, also understood by WezTerm.
+#[cfg(feature = "encode")]
+fn iterm2_image(image: &RgbImage, columns: usize, rows: usize) -> Result<String> {
+    let payload = encode_png_base64(image)?;
+    Ok(format!(
+        "\x1b]1337;File=inline=1;width={};height={};preserveAspectRatio=1:{}\x07",
+        columns, rows, payload
+    ))
+}
+
+/// Two-color sixel image: each band of six pixel rows is drawn once per color,
+/// with runs of identical columns compressed.
+#[cfg(feature = "encode")]
+fn sixel_image(image: &RgbImage) -> String {
+    let (width, height) = image.dimensions();
+    let mut out = format!("\x1bPq\"1;1;{};{}#0;2;100;100;100#1;2;0;0;0", width, height);
+
+    let push_run = |out: &mut String, ch: char, len: usize| {
+        if len > 3 {
+            out.push_str(&format!("!{}{}", len, ch));
+        } else {
+            out.extend(std::iter::repeat_n(ch, len));
+        }
+    };
+
+    for band in (0..height).step_by(6) {
+        for (color, dark) in [(0, false), (1, true)] {
+            out.push_str(&format!("#{}", color));
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..width {
+                let bits = (0..6)
+                    .filter(|dy| band + dy < height)
+                    .filter(|dy| (image.get_pixel(x, band + dy)[0] < 128) == dark)
+                    .fold(0u8, |bits, dy| bits | (1 << dy));
+                let ch = (63 + bits) as char;
+                run = match run {
+                    Some((prev, len)) if prev == ch => Some((prev, len + 1)),
+                    Some((prev, len)) => {
+                        push_run(&mut out, prev, len);
+                        Some((ch, 1))
+                    }
+                    None => Some((ch, 1)),
+                };
             }
-            result.push('\n');
+            if let Some((ch, len)) = run {
+                push_run(&mut out, ch, len);
+            }
+            // Return to the start of the band for the next color
+            out.push('$');
         }
+        out.push('-');
     }
 
-    Ok(result)
+    out.push_str("\x1b\\");
+    out
 }
 
 #[cfg(feature = "encode")]
-pub fn fits_in_terminal(data: &[u8]) -> Result<bool> {
-    use terminal_size::{terminal_size, Height, Width};
+pub fn fits_in_terminal(data: &[u8], ec_level: EcLevel, render: TerminalRender) -> Result<bool> {
+    fits_in_terminal_of_size(data, ec_level, render, TerminalSize::detect())
+}
 
-    let code = QrCode::with_error_correction_level(data, EcLevel::M)
+/// Whether the code of `data` fits a terminal of `size`, like [`fits_in_terminal`].
+#[cfg(feature = "encode")]
+pub fn fits_in_terminal_of_size(
+    data: &[u8],
+    ec_level: EcLevel,
+    render: TerminalRender,
+    size: TerminalSize,
+) -> Result<bool> {
+    let code = QrCode::with_error_correction_level(data, ec_level)
         .map_err(|e| anyhow!("Failed to create QR code: {}", e))?;
 
     let qr_size = code.width();
     let qr_with_quiet = qr_size + 4; // Add quiet zone
+    let (display_width, display_height) = render.display_size(qr_with_quiet);
 
-    let scale: usize = 1;
-    let display_width = qr_with_quiet * scale;
-    let display_height = (qr_with_quiet + 1) / 2 * scale;
-
-    let (term_width, term_height) = terminal_size()
-        .map(|(Width(w), Height(h))| {
-            if w < 40 || h < 30 {
-                (120, 60)
-            } else {
-                (w as usize, h as usize)
-            }
-        })
-        .unwrap_or((120, 60));
+    let (term_width, term_height) = size.dimensions();
 
     // Check if it fits (allow 6 lines for header/footer/spacing)
     if display_width > term_width || display_height + 6 > term_height {
@@ -199,7 +1436,7 @@ mod tests {
     #[test]
     fn test_qr_generation() {
         let data = b"Hello, World!";
-        let (image, _) = generate_qr_image(data, None, 4).unwrap();
+        let (image, _) = generate_qr_image(data, None, 4, EcLevel::M, &QrStyle::default()).unwrap();
         assert!(image.width() > 0);
         assert!(image.height() > 0);
     }
@@ -207,7 +1444,7 @@ mod tests {
     #[test]
     fn test_qr_roundtrip() {
         let data = b"Test data for QR code roundtrip";
-        let (image, _) = generate_qr_image(data, None, 4).unwrap();
+        let (image, _) = generate_qr_image(data, None, 4, EcLevel::M, &QrStyle::default()).unwrap();
 
         // Convert to grayscale for decoding
         let gray: GrayImage = image::DynamicImage::ImageRgb8(image).to_luma8();
@@ -215,4 +1452,360 @@ mod tests {
         let decoded = decode_qr_from_gray(&gray).unwrap();
         assert_eq!(decoded, data);
     }
+
+    #[test]
+    fn test_ec_level_affects_version() {
+        let data = b"Some payload long enough to need more than the smallest version";
+        let (_, low) = generate_qr_image(data, None, 4, EcLevel::L, &QrStyle::default()).unwrap();
+        let (image, high) =
+            generate_qr_image(data, None, 4, EcLevel::H, &QrStyle::default()).unwrap();
+        assert!(high.width() > low.width());
+
+        let gray: GrayImage = image::DynamicImage::ImageRgb8(image).to_luma8();
+        assert_eq!(decode_qr_from_gray(&gray).unwrap(), data);
+    }
+
+    #[test]
+    fn test_parse_ec_level() {
+        assert_eq!(parse_ec_level("h").unwrap(), EcLevel::H);
+        assert_eq!(parse_ec_level("L").unwrap(), EcLevel::L);
+        assert!(parse_ec_level("X").is_err());
+    }
+
+    #[test]
+    fn test_decode_all_tiled_codes() {
+        // Side-by-side codes of equal size are the case rqrr struggles with on its own
+        let payloads: Vec<Vec<u8>> = (0..9u8).map(|i| vec![b'A' + i; 300]).collect();
+        let tiles: Vec<(RgbImage, String)> = payloads
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let (image, _) =
+                    generate_qr_image(p, None, 4, EcLevel::M, &QrStyle::default()).unwrap();
+                (image, format!("{}/9", i + 1))
+            })
+            .collect();
+        let layout: GridLayout = "3x3".parse().unwrap();
+        let page = tile_qr_images(&tiles, layout, 4, &QrStyle::default()).unwrap();
+
+        let gray: GrayImage = image::DynamicImage::ImageRgb8(page).to_luma8();
+        let mut decoded = decode_all_qr_from_gray(&gray);
+        decoded.sort();
+        assert_eq!(decoded, payloads);
+    }
+
+    #[test]
+    fn test_color_planes_roundtrip() {
+        let codes: Vec<RgbImage> = [&b"red plane"[..], b"green plane", b"blue plane"]
+            .iter()
+            .map(|payload| {
+                let version = Version::Normal(3);
+                generate_qr_image(payload, Some(version), 4, EcLevel::M, &QrStyle::default())
+                    .unwrap()
+                    .0
+            })
+            .collect();
+        let merged = DynamicImage::ImageRgb8(merge_color_planes(&codes).unwrap());
+        let mut found = decode_all_qr_from_dynamic_image(&merged);
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                b"blue plane".to_vec(),
+                b"green plane".to_vec(),
+                b"red plane".to_vec()
+            ]
+        );
+
+        // An ordinary code is not mistaken for planes
+        let plain = DynamicImage::ImageRgb8(codes[0].clone());
+        assert!(decode_color_planes(&plain).is_none());
+        assert!(merge_color_planes(&codes[..2]).is_err());
+    }
+
+    #[test]
+    fn test_parse_grid_layout() {
+        let layout: GridLayout = "4X2".parse().unwrap();
+        assert_eq!(
+            layout,
+            GridLayout {
+                columns: 4,
+                rows: 2
+            }
+        );
+        assert_eq!(layout.codes_per_image(), 8);
+        assert!("3".parse::<GridLayout>().is_err());
+        assert!("0x3".parse::<GridLayout>().is_err());
+    }
+
+    #[test]
+    fn test_default_style_matches_plain_rendering() {
+        let data = b"Plain black on white";
+        let (image, _) = generate_qr_image(data, None, 3, EcLevel::M, &QrStyle::default()).unwrap();
+        let expected = QrCode::with_error_correction_level(data, EcLevel::M)
+            .unwrap()
+            .render::<Rgb<u8>>()
+            .quiet_zone(true)
+            .module_dimensions(3, 3)
+            .build();
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn test_styled_qr_roundtrip() {
+        let data = b"Branded and rounded";
+        let style = QrStyle {
+            foreground: parse_color("#1a237e").unwrap(),
+            background: parse_color("#fff8e1").unwrap(),
+            rounded: true,
+            margin: 2,
+            logo: None,
+        };
+        let (image, version) = generate_qr_image(data, None, 6, EcLevel::M, &style).unwrap();
+        assert_eq!(image.width(), (version.width() as u32 + 4) * 6);
+        assert_eq!(*image.get_pixel(0, 0), style.background);
+
+        let gray: GrayImage = image::DynamicImage::ImageRgb8(image).to_luma8();
+        assert_eq!(decode_qr_from_gray(&gray).unwrap(), data);
+    }
+
+    #[test]
+    fn test_style_validation() {
+        assert!(QrStyle::default().validate().is_ok());
+
+        let inverted = QrStyle {
+            foreground: Rgb([255, 255, 255]),
+            background: Rgb([0, 0, 0]),
+            ..QrStyle::default()
+        };
+        assert!(inverted.validate().is_err());
+
+        let low_contrast = QrStyle {
+            foreground: parse_color("#777").unwrap(),
+            ..QrStyle::default()
+        };
+        assert!(low_contrast.validate().is_err());
+
+        let no_margin = QrStyle {
+            margin: 0,
+            ..QrStyle::default()
+        };
+        assert!(generate_qr_image(b"x", None, 4, EcLevel::M, &no_margin).is_err());
+    }
+
+    #[test]
+    fn test_logo_qr_roundtrip() {
+        let logo = RgbaImage::from_fn(40, 30, |x, _| {
+            if x < 20 {
+                image::Rgba([200, 30, 30, 255])
+            } else {
+                image::Rgba([0, 0, 0, 0])
+            }
+        });
+        let style = QrStyle {
+            logo: Some(logo),
+            ..QrStyle::default()
+        };
+
+        for len in [20, 200, 700] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 7 % 251) as u8).collect();
+            let (image, _) = generate_qr_image(&data, None, 4, EcLevel::H, &style).unwrap();
+            let center = image.width() / 2;
+            assert_ne!(*image.get_pixel(center - 2, center), Rgb([0, 0, 0]));
+
+            let gray: GrayImage = image::DynamicImage::ImageRgb8(image).to_luma8();
+            assert_eq!(decode_qr_from_gray(&gray).unwrap(), data);
+        }
+
+        assert!(generate_qr_image(b"x", None, 4, EcLevel::M, &style).is_err());
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#1A2b3c").unwrap(), Rgb([0x1a, 0x2b, 0x3c]));
+        assert_eq!(parse_color("fff").unwrap(), Rgb([255, 255, 255]));
+        assert!(parse_color("#12345").is_err());
+        assert!(parse_color("#gggggg").is_err());
+    }
+
+    #[test]
+    fn test_braille_render_matches_half_blocks() {
+        let data = b"Braille packs eight modules into every character";
+        let code = QrCode::with_error_correction_level(data, EcLevel::M).unwrap();
+        let modules = code.width() + 4;
+
+        let half = render_qr_to_terminal(data, EcLevel::M, TerminalRender::HalfBlock).unwrap();
+        let braille = render_qr_to_terminal(data, EcLevel::M, TerminalRender::Braille).unwrap();
+
+        let rows = |text: &str| text.lines().filter(|line| !line.is_empty()).count();
+        assert_eq!(rows(&half), modules.div_ceil(2));
+        assert_eq!(rows(&braille), modules.div_ceil(4));
+
+        // Both renderings show the same number of dark modules
+        let half_dark: u32 = half
+            .chars()
+            .map(|ch| match ch {
+                '█' => 2,
+                '▀' | '▄' => 1,
+                _ => 0,
+            })
+            .sum();
+        let braille_dark: u32 = braille
+            .chars()
+            .filter(|ch| ('\u{2800}'..='\u{28ff}').contains(ch))
+            .map(|ch| (ch as u32 - 0x2800).count_ones())
+            .sum();
+        let dark = code
+            .to_colors()
+            .iter()
+            .filter(|&&c| c == Color::Dark)
+            .count();
+        assert_eq!(half_dark as usize, dark);
+        assert_eq!(braille_dark as usize, dark);
+
+        assert_eq!(
+            "braille".parse::<TerminalRender>().unwrap(),
+            TerminalRender::Braille
+        );
+        assert!("ascii".parse::<TerminalRender>().is_err());
+    }
+
+    #[test]
+    fn test_roi_tracker_follows_code() {
+        let (code, _) =
+            generate_qr_image(b"tracked", None, 4, EcLevel::M, &QrStyle::default()).unwrap();
+        let code = DynamicImage::ImageRgb8(code).to_luma8();
+        let frame_at = |x: i64, y: i64| {
+            let mut frame = GrayImage::from_pixel(640, 480, Luma([255]));
+            image::imageops::replace(&mut frame, &code, x, y);
+            frame
+        };
+
+        let mut tracker = RoiTracker::default();
+        assert_eq!(
+            tracker.decode(&frame_at(400, 300)),
+            vec![b"tracked".to_vec()]
+        );
+        let (x, y, _, _) = tracker.region.unwrap();
+        assert!((400..440).contains(&x) && (300..340).contains(&y));
+
+        // Later frames are read from the tracked area, and a code that moved away
+        // is still found by falling back to a full scan
+        assert_eq!(
+            tracker.decode(&frame_at(400, 300)),
+            vec![b"tracked".to_vec()]
+        );
+        assert_eq!(tracker.frames_since_full_scan, 1);
+        assert_eq!(tracker.decode(&frame_at(10, 10)), vec![b"tracked".to_vec()]);
+        assert_eq!(tracker.frames_since_full_scan, 0);
+        assert!(tracker.region.unwrap().0 < 100);
+    }
+
+    #[test]
+    fn test_roi_tracker_keeps_every_code_of_a_frame() {
+        let code = |text: &[u8]| {
+            let (code, _) =
+                generate_qr_image(text, None, 4, EcLevel::M, &QrStyle::default()).unwrap();
+            DynamicImage::ImageRgb8(code).to_luma8()
+        };
+        let (left, right) = (code(b"left"), code(b"right"));
+        let frame_with_right_at = |x: i64| {
+            let mut frame = GrayImage::from_pixel(900, 400, Luma([255]));
+            image::imageops::replace(&mut frame, &left, 20, 20);
+            image::imageops::replace(&mut frame, &right, x, 20);
+            frame
+        };
+
+        let mut tracker = RoiTracker::default();
+        assert_eq!(tracker.decode(&frame_with_right_at(200)).len(), 2);
+        // The right code moved beyond the tracked area; the left one alone is not enough
+        let mut found = tracker.decode(&frame_with_right_at(700));
+        found.sort();
+        assert_eq!(found, vec![b"left".to_vec(), b"right".to_vec()]);
+        assert_eq!(tracker.frames_since_full_scan, 0);
+    }
+
+    #[test]
+    fn test_enhance_for_detection() {
+        let data = b"Photographed under a desk lamp";
+        let (image, _) = generate_qr_image(data, None, 4, EcLevel::M, &QrStyle::default()).unwrap();
+        let gray = image::DynamicImage::ImageRgb8(image).to_luma8();
+
+        // Washed-out contrast with light falling off across the page
+        let width = gray.width();
+        let photo = GrayImage::from_fn(width, gray.height(), |x, y| {
+            let shade = 60 * x / width;
+            Luma([(90 + gray.get_pixel(x, y)[0] as u32 / 6 + shade) as u8])
+        });
+
+        let enhanced = enhance_for_detection(&photo);
+        assert!(enhanced.pixels().all(|p| p[0] == 0 || p[0] == 255));
+        assert_eq!(decode_all_qr_from_gray(&enhanced), vec![data.to_vec()]);
+    }
+
+    #[test]
+    fn test_thorough_scan() {
+        let data = b"Scanned at an odd exposure";
+        let (image, _) = generate_qr_image(data, None, 4, EcLevel::M, &QrStyle::default()).unwrap();
+        let gray = image::DynamicImage::ImageRgb8(image).to_luma8();
+        assert_eq!(decode_all_qr_thorough(&gray), vec![data.to_vec()]);
+        assert!(decode_all_qr_thorough(&GrayImage::from_pixel(64, 64, Luma([255]))).is_empty());
+
+        assert_eq!(
+            "Thorough".parse::<ScanEffort>().unwrap(),
+            ScanEffort::Thorough
+        );
+        assert!("maximum".parse::<ScanEffort>().is_err());
+    }
+
+    #[test]
+    fn test_frame_signature() {
+        let render = |data: &[u8]| {
+            let (image, _) =
+                generate_qr_image(data, None, 4, EcLevel::M, &QrStyle::default()).unwrap();
+            DynamicImage::ImageRgb8(image).to_luma8()
+        };
+        let first = render(b"chunk 0001 of a long recording");
+        let second = render(b"chunk 0002 of a long recording");
+
+        let signature = FrameSignature::of(&first);
+        assert!(signature.matches(&FrameSignature::of(&first.clone())));
+        assert!(!signature.matches(&FrameSignature::of(&second)));
+
+        // Slight noise on every pixel still counts as the same frame
+        let mut noisy = first.clone();
+        for pixel in noisy.iter_mut() {
+            *pixel = pixel.saturating_sub(3);
+        }
+        assert!(signature.matches(&FrameSignature::of(&noisy)));
+
+        assert!(!is_blank_frame(&first));
+        assert!(is_blank_frame(&GrayImage::from_pixel(64, 64, Luma([12]))));
+        assert!(is_blank_frame(&GrayImage::from_pixel(64, 64, Luma([240]))));
+    }
+
+    #[test]
+    fn test_graphics_render_modes() {
+        let data = b"Terminals with graphics get a real image";
+
+        let sixel = render_qr_to_terminal(data, EcLevel::M, TerminalRender::Sixel).unwrap();
+        let sixel = sixel.trim();
+        assert!(sixel.starts_with("\x1bPq"));
+        assert!(sixel.ends_with("\x1b\\"));
+        // The carousel rewrites newlines, so the image itself must not contain any
+        assert!(!sixel.contains('\n'));
+
+        let kitty = render_qr_to_terminal(data, EcLevel::M, TerminalRender::Kitty).unwrap();
+        assert!(kitty.contains("\x1b_Ga=T,f=100"));
+        assert!(kitty.trim_end().ends_with("\x1b\\"));
+
+        let iterm = render_qr_to_terminal(data, EcLevel::M, TerminalRender::Iterm2).unwrap();
+        assert!(iterm.contains("\x1b]1337;File=inline=1;"));
+
+        assert_eq!(
+            "auto".parse::<TerminalRender>().unwrap(),
+            TerminalRender::Image
+        );
+        assert_ne!(TerminalRender::Image.resolve(), TerminalRender::Image);
+    }
 }
@@ -0,0 +1,149 @@
+//! Minimal stand-ins for the pixel buffers of the `image` crate, for builds with only the
+//! `wasm` feature. QR rendering and detection need little more than a grid of bytes, and
+//! leaving out `image` keeps its decoders and encoders out of the web bundle.
+//!
+//! The types mirror the parts of the `image` API that [`crate::qr`] uses, so the same code
+//! compiles against either.
+
+use std::marker::PhantomData;
+use std::ops::Index;
+
+/// A pixel made of `CHANNELS` bytes.
+pub trait Pixel: Copy {
+    const CHANNELS: usize;
+
+    fn from_channels(channels: &[u8]) -> Self;
+    fn channels(&self) -> &[u8];
+}
+
+/// A grayscale pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Luma<T>(pub [T; 1]);
+
+/// An RGB pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb<T>(pub [T; 3]);
+
+impl Pixel for Luma<u8> {
+    const CHANNELS: usize = 1;
+
+    fn from_channels(channels: &[u8]) -> Self {
+        Luma([channels[0]])
+    }
+
+    fn channels(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Pixel for Rgb<u8> {
+    const CHANNELS: usize = 3;
+
+    fn from_channels(channels: &[u8]) -> Self {
+        Rgb([channels[0], channels[1], channels[2]])
+    }
+
+    fn channels(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<T> Index<usize> for Luma<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.0[index]
+    }
+}
+
+impl<T> Index<usize> for Rgb<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.0[index]
+    }
+}
+
+/// Pixels stored row by row, channel bytes interleaved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageBuffer<P> {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+    pixel: PhantomData<P>,
+}
+
+pub type GrayImage = ImageBuffer<Luma<u8>>;
+pub type RgbImage = ImageBuffer<Rgb<u8>>;
+
+impl<P: Pixel> ImageBuffer<P> {
+    pub fn from_pixel(width: u32, height: u32, pixel: P) -> Self {
+        let len = width as usize * height as usize;
+        let data = pixel.channels().repeat(len);
+        Self::from_raw(width, height, data).expect("buffer matches its dimensions")
+    }
+
+    pub fn from_fn<F: FnMut(u32, u32) -> P>(width: u32, height: u32, mut f: F) -> Self {
+        let mut data = Vec::with_capacity(width as usize * height as usize * P::CHANNELS);
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(f(x, y).channels());
+            }
+        }
+        Self::from_raw(width, height, data).expect("buffer matches its dimensions")
+    }
+
+    /// Wraps `data`, or returns `None` when it does not hold exactly `width` x `height` pixels.
+    pub fn from_raw(width: u32, height: u32, data: Vec<u8>) -> Option<Self> {
+        (data.len() == width as usize * height as usize * P::CHANNELS).then_some(Self {
+            width,
+            height,
+            data,
+            pixel: PhantomData,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn offset(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        (y as usize * self.width as usize + x as usize) * P::CHANNELS
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> P {
+        let offset = self.offset(x, y);
+        P::from_channels(&self.data[offset..offset + P::CHANNELS])
+    }
+
+    pub fn put_pixel(&mut self, x: u32, y: u32, pixel: P) {
+        let offset = self.offset(x, y);
+        self.data[offset..offset + P::CHANNELS].copy_from_slice(pixel.channels());
+    }
+
+    /// Channel bytes of every pixel, row by row.
+    pub fn iter(&self) -> std::slice::Iter<'_, u8> {
+        self.data.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, u8> {
+        self.data.iter_mut()
+    }
+
+    pub fn as_raw(&self) -> &Vec<u8> {
+        &self.data
+    }
+
+    pub fn into_raw(self) -> Vec<u8> {
+        self.data
+    }
+}
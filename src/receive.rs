@@ -0,0 +1,696 @@
+//! Reassembly of QR payloads that were scanned elsewhere, e.g. by a phone camera or a native
+//! scanner library, into the file they carry. Used by the C interface, the web scanner and the
+//! HTTP receiver.
+
+use crate::chunk::{
+    decompress, from_single_code_payload, unpack_data_with_metadata, Chunk, ChunkHeader,
+    PayloadEncoding, Reassembler, Reassembly, SyncInfo, WireFormat,
+};
+use crate::core::qr_payload_bytes;
+use crate::ur::{UrDecoder, UR_FILENAME};
+use anyhow::{anyhow, Context, Result};
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// First line of a [`Feedback`] document.
+const FEEDBACK_MAGIC: &str = "fountain-feedback 1";
+/// How long fetching feedback over HTTP may take before the sender gives up on it.
+const FEEDBACK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Most index ranges a [`Feedback`] document may list. A receiver missing every other
+/// chunk of a very long stream stays well below it.
+const MAX_FEEDBACK_RANGES: usize = 1 << 16;
+/// Extensions of camera snapshots, which are scanned for an ACK code instead of read as text.
+const SNAPSHOT_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+/// Outcome of feeding one payload to a [`PayloadReceiver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiveStatus {
+    /// Not a chunk or UR part, or one already seen.
+    Scanning,
+    ChunkFound,
+    /// The file is ready for [`PayloadReceiver::take_result`].
+    Complete,
+}
+
+/// Routes payloads of every wire format to the matching decoder until a file is complete.
+/// It can be fed on after that: a carousel of several files completes once per file, and
+/// chunks of files already completed are ignored.
+#[derive(Default)]
+pub struct PayloadReceiver {
+    reassembler: Reassembler,
+    /// Collects the parts of a stream in the UR wire format.
+    ur_decoder: UrDecoder,
+    /// Start marker of the latest stream, once seen.
+    sync: Option<SyncInfo>,
+    /// Decoded file, held until `take_result` takes it.
+    result: Option<(String, Vec<u8>)>,
+}
+
+impl PayloadReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds the content of one QR code, as Base45 text, raw bytes or a UR part. Content in
+    /// no known wire format is ignored.
+    pub fn feed(&mut self, payload: &[u8]) -> Result<ReceiveStatus> {
+        match WireFormat::detect(payload) {
+            Some(WireFormat::Native) => {}
+            Some(WireFormat::Ur) => return self.feed_ur(payload),
+            None => return Ok(ReceiveStatus::Scanning),
+        }
+        // Start markers announce a stream, which may repeat one received before
+        if let Some(sync) = SyncInfo::from_qr_payload(payload) {
+            if let Ok(sync) = sync {
+                self.reassembler.start_transfer(&sync);
+                self.sync = Some(sync);
+            }
+            return Ok(ReceiveStatus::Scanning);
+        }
+        if let Some(file) = from_single_code_payload(payload) {
+            self.result = Some(file?);
+            return Ok(ReceiveStatus::Complete);
+        }
+        let Ok(chunk) = Chunk::from_qr_payload(payload) else {
+            return Ok(ReceiveStatus::Scanning);
+        };
+        match self.reassembler.add(chunk) {
+            Reassembly::Ignored => Ok(ReceiveStatus::Scanning),
+            Reassembly::Pending => Ok(ReceiveStatus::ChunkFound),
+            Reassembly::Complete(object) => {
+                let (filename, data, metadata) = unpack_data_with_metadata(&decompress(&object)?)?;
+                if metadata.base_sha256.is_some() {
+                    return Err(anyhow!(
+                        "{} was sent as a delta; decode it with fountain decode --base",
+                        filename
+                    ));
+                }
+                // One volume arrives at a time; name it so the parts concatenate
+                let filename = match self.reassembler.header().and_then(|h| h.volume) {
+                    Some(volume) => volume.part_name(&filename),
+                    None => filename,
+                };
+                self.result = Some((filename, data));
+                Ok(ReceiveStatus::Complete)
+            }
+        }
+    }
+
+    fn feed_ur(&mut self, payload: &[u8]) -> Result<ReceiveStatus> {
+        let received = self.ur_decoder.parts_received();
+        match self.ur_decoder.receive(payload)? {
+            Some(data) => {
+                // Ready for another UR message
+                self.ur_decoder = UrDecoder::new();
+                self.result = Some((UR_FILENAME.to_string(), data));
+                Ok(ReceiveStatus::Complete)
+            }
+            None if self.ur_decoder.parts_received() > received => Ok(ReceiveStatus::ChunkFound),
+            None => Ok(ReceiveStatus::Scanning),
+        }
+    }
+
+    /// Chunks received and the number needed, both 0 before the first chunk; fragments
+    /// recovered and needed for a UR stream.
+    pub fn progress(&self) -> (u32, u32) {
+        match (self.reassembler.header(), self.ur_decoder.progress()) {
+            (None, Some((received, needed))) => (received as u32, needed as u32),
+            (header, _) => (
+                self.reassembler.received_count() as u32,
+                header.map_or(0, |header| header.source_symbol_count()),
+            ),
+        }
+    }
+
+    /// Header of the native stream being decoded, once a chunk of it arrived.
+    pub fn header(&self) -> Option<&ChunkHeader> {
+        self.reassembler.header()
+    }
+
+    /// Start marker of the latest stream, once one was seen.
+    pub fn sync(&self) -> Option<&SyncInfo> {
+        self.sync.as_ref()
+    }
+
+    /// Sorted indices of the native chunks received.
+    pub fn received(&self) -> Vec<u32> {
+        self.reassembler.received()
+    }
+
+    /// What has been received so far, for the sender's carousel to skip.
+    pub fn feedback(&self) -> Feedback {
+        Feedback::of(&self.reassembler)
+    }
+
+    /// Takes the decoded filename and contents once a feed returned
+    /// [`ReceiveStatus::Complete`].
+    pub fn take_result(&mut self) -> Option<(String, Vec<u8>)> {
+        self.result.take()
+    }
+}
+
+/// Stream parameters and index of one chunk, which a sender looks up in [`Feedback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkId {
+    pub transfer_length: u32,
+    pub packet_size: u16,
+    pub index: u32,
+}
+
+impl From<&ChunkHeader> for ChunkId {
+    fn from(header: &ChunkHeader) -> Self {
+        Self {
+            transfer_length: header.total,
+            packet_size: header.packet_size,
+            index: header.index,
+        }
+    }
+}
+
+/// Chunks of the stream being received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamProgress {
+    pub transfer_length: u32,
+    pub packet_size: u16,
+    /// Chunks needed to decode.
+    pub needed: u32,
+    /// Indices of the chunks received.
+    pub received: ChunkRanges,
+}
+
+/// Set of chunk indices, kept as sorted runs of consecutive indices so that feedback from
+/// elsewhere costs memory by the runs it lists, not by the chunks they cover.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkRanges(Vec<RangeInclusive<u32>>);
+
+impl ChunkRanges {
+    /// Whether `index` is in the set.
+    pub fn contains(&self, index: u32) -> bool {
+        let after = self.0.partition_point(|range| *range.end() < index);
+        self.0
+            .get(after)
+            .is_some_and(|range| range.contains(&index))
+    }
+
+    /// Number of indices in the set.
+    pub fn count(&self) -> u64 {
+        self.0
+            .iter()
+            .map(|range| u64::from(range.end() - range.start()) + 1)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Highest index in the set.
+    pub fn last(&self) -> Option<u32> {
+        self.0.last().map(|range| *range.end())
+    }
+
+    /// The runs of consecutive indices, in ascending order.
+    pub fn ranges(&self) -> &[RangeInclusive<u32>] {
+        &self.0
+    }
+
+    /// The indices in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.0.iter().flat_map(|range| range.clone())
+    }
+
+    /// Adds the run `range`, merging it with the runs it touches.
+    fn insert(&mut self, range: RangeInclusive<u32>) {
+        let (mut start, mut end) = range.into_inner();
+        // Runs ending just before `start` or later, and starting just after `end` or earlier
+        let first = self
+            .0
+            .partition_point(|run| run.end().saturating_add(1) < start);
+        let last = self
+            .0
+            .partition_point(|run| *run.start() <= end.saturating_add(1));
+        if first < last {
+            start = start.min(*self.0[first].start());
+            end = end.max(*self.0[last - 1].end());
+        }
+        self.0.splice(first..last, [start..=end]);
+    }
+}
+
+impl FromIterator<u32> for ChunkRanges {
+    fn from_iter<I: IntoIterator<Item = u32>>(indices: I) -> Self {
+        let mut ranges = Self::default();
+        for index in indices {
+            ranges.insert(index..=index);
+        }
+        ranges
+    }
+}
+
+/// The runs, e.g. `0-41,43,45-60`.
+impl fmt::Display for ChunkRanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, range) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            if range.start() == range.end() {
+                write!(f, "{}", range.start())?;
+            } else {
+                write!(f, "{}-{}", range.start(), range.end())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ChunkRanges {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid chunk ranges '{}'", s);
+        let mut ranges = Self::default();
+        for range in s.split(',') {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            let (start, end): (u32, u32) = (
+                start.parse().map_err(|_| invalid())?,
+                end.parse().map_err(|_| invalid())?,
+            );
+            if end < start {
+                return Err(invalid());
+            }
+            ranges.insert(start..=end);
+            if ranges.0.len() > MAX_FEEDBACK_RANGES {
+                return Err(anyhow!(
+                    "Feedback lists more than {} chunk ranges",
+                    MAX_FEEDBACK_RANGES
+                ));
+            }
+        }
+        Ok(ranges)
+    }
+}
+
+/// What a receiver has of the native streams it is shown, so the sender's carousel can
+/// skip the codes already received and show only the missing ones. Receivers write it to
+/// a file or serve it over HTTP, in a line-based text form:
+///
+/// ```text
+/// fountain-feedback 1
+/// stream 52340 800 66 0-41,43,45-60
+/// done 1200 400
+/// ```
+///
+/// `stream` gives the transfer length, packet size, chunks needed and chunk indices
+/// received of the stream being decoded; each `done` line a stream already decoded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Feedback {
+    pub receiving: Option<StreamProgress>,
+    /// Transfer length and packet size of the streams already decoded.
+    pub done: Vec<(u32, u16)>,
+}
+
+impl Feedback {
+    /// Feedback on the chunks `reassembler` was fed.
+    pub fn of(reassembler: &Reassembler) -> Self {
+        let receiving = reassembler
+            .header()
+            .filter(|_| reassembler.received_count() > 0)
+            .map(|header| StreamProgress {
+                transfer_length: header.total,
+                packet_size: header.packet_size,
+                needed: header.source_symbol_count(),
+                received: reassembler.received().into_iter().collect(),
+            });
+        Self {
+            receiving,
+            done: reassembler.finished_streams(),
+        }
+    }
+
+    /// Whether the receiver has the chunk `id`, or the whole stream it belongs to.
+    pub fn has(&self, id: ChunkId) -> bool {
+        let stream = (id.transfer_length, id.packet_size);
+        self.done.contains(&stream)
+            || self.receiving.as_ref().is_some_and(|progress| {
+                (progress.transfer_length, progress.packet_size) == stream
+                    && progress.received.contains(id.index)
+            })
+    }
+
+    /// Reads feedback from `source`: an `http://` URL such as the `/api/feedback` of
+    /// `fountain serve`, the path of a file a receiver writes, or a PNG, JPEG or BMP
+    /// snapshot of a camera pointed at the ACK code a receiver shows (see
+    /// [`to_ack_payload`]). HTTPS is not supported.
+    pub fn fetch(source: &str) -> Result<Self> {
+        let is_snapshot = Path::new(source)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SNAPSHOT_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        let text = match source.strip_prefix("http://") {
+            Some(rest) => http_get(rest)?,
+            None if is_snapshot => return read_ack_snapshot(Path::new(source)),
+            None => std::fs::read_to_string(source)
+                .with_context(|| format!("Failed to read feedback from {}", source))?,
+        };
+        text.parse()
+    }
+}
+
+impl fmt::Display for Feedback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", FEEDBACK_MAGIC)?;
+        if let Some(progress) = &self.receiving {
+            writeln!(
+                f,
+                "stream {} {} {} {}",
+                progress.transfer_length, progress.packet_size, progress.needed, progress.received
+            )?;
+        }
+        for (transfer_length, packet_size) in &self.done {
+            writeln!(f, "done {} {}", transfer_length, packet_size)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Feedback {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some(FEEDBACK_MAGIC) {
+            return Err(anyhow!(
+                "Not a feedback document: expected '{}'",
+                FEEDBACK_MAGIC
+            ));
+        }
+        let mut feedback = Feedback::default();
+        for line in lines {
+            let invalid = || anyhow!("Invalid feedback line '{}'", line);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["stream", transfer_length, packet_size, needed, ranges] => {
+                    feedback.receiving = Some(StreamProgress {
+                        transfer_length: transfer_length.parse().map_err(|_| invalid())?,
+                        packet_size: packet_size.parse().map_err(|_| invalid())?,
+                        needed: needed.parse().map_err(|_| invalid())?,
+                        received: ranges
+                            .parse()
+                            .with_context(|| format!("Invalid feedback line '{}'", line))?,
+                    })
+                }
+                ["done", transfer_length, packet_size] => feedback.done.push((
+                    transfer_length.parse().map_err(|_| invalid())?,
+                    packet_size.parse().map_err(|_| invalid())?,
+                )),
+                // Lines of later versions
+                _ => {}
+            }
+        }
+        Ok(feedback)
+    }
+}
+
+/// First byte of an ACK payload, the code a receiver shows back to the sender. Like
+/// [`SYNC_MARKER`], it is neither a chunk version nor a Base45 character.
+pub const ACK_MARKER: u8 = 0xFE;
+/// Transfer length and packet size identifying a stream in an ACK payload.
+const ACK_STREAM_SIZE: usize = 6;
+/// Longest bitmap of chunks received in an ACK payload, covering the first 16384 chunks.
+/// Chunks past it are left out and shown again by the sender.
+const MAX_ACK_BITMAP: usize = 2048;
+
+/// Packs receiver feedback into an ACK payload, small enough for a code a sender's camera
+/// reads from across the room: [`ACK_MARKER`], the number of streams already decoded and
+/// the transfer length and packet size of each, then, while a stream is being received, its
+/// transfer length, packet size and chunks needed and a bitmap of the chunks received,
+/// lowest index in the lowest bit, without trailing zero bytes and at most
+/// [`MAX_ACK_BITMAP`] bytes long. The clear bits are the
+/// chunks the sender should show again.
+pub fn to_ack_payload(feedback: &Feedback, encoding: PayloadEncoding) -> Vec<u8> {
+    let done = &feedback.done[..feedback.done.len().min(u8::MAX as usize)];
+    let mut bytes = vec![ACK_MARKER, done.len() as u8];
+    for (transfer_length, packet_size) in done {
+        bytes.extend_from_slice(&transfer_length.to_be_bytes());
+        bytes.extend_from_slice(&packet_size.to_be_bytes());
+    }
+    if let Some(progress) = &feedback.receiving {
+        bytes.extend_from_slice(&progress.transfer_length.to_be_bytes());
+        bytes.extend_from_slice(&progress.packet_size.to_be_bytes());
+        bytes.extend_from_slice(&progress.needed.to_be_bytes());
+        let limit = (MAX_ACK_BITMAP * 8) as u32;
+        let highest = progress
+            .received
+            .last()
+            .map_or(0, |i| i.min(limit - 1) as usize + 1);
+        let mut bitmap = vec![0u8; highest.div_ceil(8)];
+        for range in progress.received.ranges() {
+            for index in *range.start()..=(*range.end()).min(limit - 1) {
+                bitmap[index as usize / 8] |= 1 << (index % 8);
+            }
+        }
+        bytes.extend_from_slice(&bitmap);
+    }
+    match encoding {
+        PayloadEncoding::Base45 => base45::encode(&bytes).into_bytes(),
+        PayloadEncoding::Binary => bytes,
+    }
+}
+
+/// Parses a payload made by [`to_ack_payload`]. Returns `None` for chunks and any other QR
+/// content.
+pub fn from_ack_payload(payload: &[u8]) -> Option<Result<Feedback>> {
+    let bytes = qr_payload_bytes(payload).ok()?;
+    let (&marker, rest) = bytes.split_first()?;
+    if marker != ACK_MARKER {
+        return None;
+    }
+    let Some((&done_count, rest)) = rest.split_first() else {
+        return Some(Err(anyhow!("Invalid ACK code: too short")));
+    };
+    let stream_at = |i: usize| {
+        let transfer_length = u32::from_be_bytes(rest.get(i..i + 4)?.try_into().ok()?);
+        let packet_size = u16::from_be_bytes(rest.get(i + 4..i + 6)?.try_into().ok()?);
+        Some((transfer_length, packet_size))
+    };
+
+    let mut feedback = Feedback::default();
+    for n in 0..done_count as usize {
+        match stream_at(n * ACK_STREAM_SIZE) {
+            Some(stream) => feedback.done.push(stream),
+            None => return Some(Err(anyhow!("Invalid ACK code: too short"))),
+        }
+    }
+    let start = done_count as usize * ACK_STREAM_SIZE;
+    if rest.len() > start {
+        let needed_at = start + ACK_STREAM_SIZE;
+        let (Some((transfer_length, packet_size)), Some(needed)) =
+            (stream_at(start), rest.get(needed_at..needed_at + 4))
+        else {
+            return Some(Err(anyhow!("Invalid ACK code: too short")));
+        };
+        let bitmap = &rest[needed_at + 4..];
+        if bitmap.len() > MAX_ACK_BITMAP {
+            return Some(Err(anyhow!(
+                "Invalid ACK code: bitmap of {} bytes is longer than {}",
+                bitmap.len(),
+                MAX_ACK_BITMAP
+            )));
+        }
+        let received = bitmap
+            .iter()
+            .enumerate()
+            .flat_map(|(byte, &bits)| {
+                (0..8)
+                    .filter(move |bit| bits & (1 << bit) != 0)
+                    .map(move |bit| (byte * 8 + bit) as u32)
+            })
+            .collect();
+        feedback.receiving = Some(StreamProgress {
+            transfer_length,
+            packet_size,
+            needed: u32::from_be_bytes(needed.try_into().ok()?),
+            received,
+        });
+    }
+    Some(Ok(feedback))
+}
+
+/// Feedback from the ACK code in a camera snapshot.
+#[cfg(feature = "decode")]
+fn read_ack_snapshot(path: &Path) -> Result<Feedback> {
+    let image = image::open(path)
+        .with_context(|| format!("Failed to read the snapshot {}", path.display()))?
+        .to_luma8();
+    crate::qr::decode_all_qr_from_gray(&image)
+        .iter()
+        .find_map(|payload| from_ack_payload(payload))
+        .unwrap_or_else(|| Err(anyhow!("No ACK code in {}", path.display())))
+}
+
+#[cfg(not(feature = "decode"))]
+fn read_ack_snapshot(path: &Path) -> Result<Feedback> {
+    Err(anyhow!(
+        "Scanning {} for an ACK code needs the decode feature",
+        path.display()
+    ))
+}
+
+/// Body of a plain HTTP GET of `url`, given without its `http://` scheme.
+fn http_get(url: &str) -> Result<String> {
+    let (authority, path) = match url.find('/') {
+        Some(slash) => (&url[..slash], &url[slash..]),
+        None => (url, "/"),
+    };
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+    let socket = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Cannot resolve {}", authority))?;
+    let mut stream = TcpStream::connect_timeout(&socket, FEEDBACK_TIMEOUT)
+        .with_context(|| format!("Failed to connect to {}", authority))?;
+    stream.set_read_timeout(Some(FEEDBACK_TIMEOUT))?;
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, authority
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow!("Malformed HTTP response from {}", authority))?;
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(anyhow!("http://{} answered '{}'", url, status));
+    }
+    Ok(body.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feedback_round_trip() {
+        let feedback = Feedback {
+            receiving: Some(StreamProgress {
+                transfer_length: 52340,
+                packet_size: 800,
+                needed: 66,
+                received: (0..42).chain([43]).chain(45..61).collect(),
+            }),
+            done: vec![(1200, 400)],
+        };
+        let text = feedback.to_string();
+        assert!(text.contains("stream 52340 800 66 0-41,43,45-60\n"));
+        assert!(text.contains("done 1200 400\n"));
+        assert_eq!(text.parse::<Feedback>().unwrap(), feedback);
+
+        let id = |transfer_length, packet_size, index| ChunkId {
+            transfer_length,
+            packet_size,
+            index,
+        };
+        assert!(feedback.has(id(52340, 800, 43)));
+        assert!(!feedback.has(id(52340, 800, 44)));
+        assert!(!feedback.has(id(52340, 400, 43)));
+        assert!(feedback.has(id(1200, 400, 9)));
+
+        assert!("stream 1 2 3 4".parse::<Feedback>().is_err());
+        assert!(format!("{}\nstream 1 2 3 5-4", FEEDBACK_MAGIC)
+            .parse::<Feedback>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_ack_payload() {
+        let feedback = Feedback {
+            receiving: Some(StreamProgress {
+                transfer_length: 52340,
+                packet_size: 800,
+                needed: 66,
+                received: [0, 1, 2, 9, 17].into_iter().collect(),
+            }),
+            done: vec![(1200, 400)],
+        };
+        let binary = to_ack_payload(&feedback, PayloadEncoding::Binary);
+        // Marker, count, one done stream, the stream received and a 3-byte bitmap
+        assert_eq!(binary.len(), 1 + 1 + 6 + 10 + 3);
+        for encoding in [PayloadEncoding::Base45, PayloadEncoding::Binary] {
+            let payload = to_ack_payload(&feedback, encoding);
+            assert_eq!(from_ack_payload(&payload).unwrap().unwrap(), feedback);
+        }
+
+        let waiting = Feedback::default();
+        let payload = to_ack_payload(&waiting, PayloadEncoding::Base45);
+        assert_eq!(from_ack_payload(&payload).unwrap().unwrap(), waiting);
+
+        assert!(from_ack_payload(&binary[..12]).unwrap().is_err());
+        assert!(from_ack_payload(b"https://example.com").is_none());
+        let single =
+            crate::chunk::to_single_code_payload(b"secret", "a.txt", PayloadEncoding::Base45)
+                .unwrap();
+        assert!(from_ack_payload(&single).is_none());
+
+        // Only the chunks the bitmap can hold are acknowledged, and longer bitmaps are refused
+        let far = Feedback {
+            receiving: Some(StreamProgress {
+                transfer_length: 52340,
+                packet_size: 800,
+                needed: 66,
+                received: "3,16380-4294967295".parse().unwrap(),
+            }),
+            done: Vec::new(),
+        };
+        let payload = to_ack_payload(&far, PayloadEncoding::Binary);
+        assert_eq!(payload.len(), 1 + 1 + 10 + MAX_ACK_BITMAP);
+        let read = from_ack_payload(&payload).unwrap().unwrap();
+        assert_eq!(
+            read.receiving.unwrap().received.to_string(),
+            "3,16380-16383"
+        );
+        let mut longer = payload;
+        longer.push(0xFF);
+        assert!(from_ack_payload(&longer).unwrap().is_err());
+    }
+
+    #[test]
+    fn test_chunk_ranges() {
+        let ranges: ChunkRanges = [9, 3, 4, 8, 1, 2, 4, 12].into_iter().collect();
+        assert_eq!(ranges.to_string(), "1-4,8-9,12");
+        assert_eq!(ranges.count(), 7);
+        assert!(ranges.contains(8) && !ranges.contains(7) && !ranges.contains(13));
+        assert_eq!(
+            ranges.iter().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 8, 9, 12]
+        );
+        assert_eq!(
+            "5-9,1-4,12,10".parse::<ChunkRanges>().unwrap().to_string(),
+            "1-10,12"
+        );
+
+        // Huge runs are kept as they are, not expanded
+        let everything: ChunkRanges = "0-4294967295".parse().unwrap();
+        assert_eq!(everything.ranges().len(), 1);
+        assert_eq!(everything.count(), 1 << 32);
+        assert!(everything.contains(u32::MAX));
+
+        let scattered: Vec<String> = (0..=MAX_FEEDBACK_RANGES as u32)
+            .map(|i| (i * 2).to_string())
+            .collect();
+        assert!(scattered.join(",").parse::<ChunkRanges>().is_err());
+        assert!("".parse::<ChunkRanges>().is_err());
+    }
+}
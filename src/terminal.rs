@@ -1,15 +1,271 @@
-use crate::encode::TerminalQrData;
-use std::io::{self, Write};
+use crate::encode::{TerminalQrData, TerminalQrStream};
+use crate::qr::TerminalSize;
+use crate::receive::Feedback;
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use std::io::{self, IsTerminal, Write};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, Once};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-const CLEAR_SCREEN: &str = "\x1B[2J\x1B[H";
-const HIDE_CURSOR: &str = "\x1B[?25l";
-const SHOW_CURSOR: &str = "\x1B[?25h";
+const INTERVAL_STEP_MS: u64 = 250;
+const MIN_INTERVAL_MS: u64 = 100;
+const KEY_POLL_MS: u64 = 50;
+const PROGRESS_BAR_WIDTH: usize = 20;
+/// Refresh the stats line only after at least this long, to avoid flicker.
+const STATS_REFRESH_MS: u64 = 200;
+/// Wait this long after the last resize before refitting, as dragging a window edge sends
+/// a burst of them.
+const RESIZE_SETTLE_MS: u64 = 200;
+/// How often the carousel reads receiver feedback.
+const FEEDBACK_POLL_MS: u64 = 1000;
 
-pub fn display_qr_carousel(data: &TerminalQrData, interval_ms: u64) {
+/// Re-encodes the codes shown, the second argument, for a terminal resized to the given size.
+type Refit<'a> = dyn FnMut(TerminalSize, &TerminalQrData) -> Result<TerminalQrData> + 'a;
+
+/// Whether a display has changed the terminal state, so a panic must restore it.
+static SCREEN_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Terminal state of the displays: the alternate screen when stdout is a terminal, a hidden
+/// cursor and optionally raw mode. Dropping it restores the terminal, also while a panic
+/// unwinds, so the shell is not left without a cursor, in raw mode or on the alternate
+/// screen, in tmux, screen and Windows consoles alike.
+struct ScreenGuard;
+
+impl ScreenGuard {
+    /// Enters the display state; `raw` also enables raw mode to read single key presses.
+    fn enter(raw: bool) -> Result<Self> {
+        static HOOK: Once = Once::new();
+        HOOK.call_once(|| {
+            // The panic message is printed before unwinding, so restore the terminal first
+            // or it lands on the alternate screen and vanishes with it
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                if SCREEN_ACTIVE.swap(false, Ordering::SeqCst) {
+                    restore_terminal();
+                }
+                previous(info);
+            }));
+        });
+
+        SCREEN_ACTIVE.store(true, Ordering::SeqCst);
+        let guard = ScreenGuard;
+        if raw {
+            terminal::enable_raw_mode()?;
+        }
+        let mut stdout = io::stdout();
+        if stdout.is_terminal() {
+            execute!(stdout, EnterAlternateScreen)?;
+        }
+        execute!(stdout, cursor::Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for ScreenGuard {
+    fn drop(&mut self) {
+        if SCREEN_ACTIVE.swap(false, Ordering::SeqCst) {
+            restore_terminal();
+        }
+    }
+}
+
+/// Shows the cursor and leaves the alternate screen and raw mode. Errors are ignored, as
+/// nothing more can be done about them on the way out.
+fn restore_terminal() {
+    let mut stdout = io::stdout();
+    let _ = execute!(stdout, cursor::Show);
+    if stdout.is_terminal() {
+        let _ = execute!(stdout, LeaveAlternateScreen);
+    }
+    let _ = terminal::disable_raw_mode();
+}
+
+/// Lines on screen, so each frame rewrites only the lines that changed instead of clearing
+/// the screen and drawing everything again, which flickers at short intervals and makes
+/// scanners lose the code.
+struct ScreenBuffer {
+    lines: Vec<String>,
+    /// Whether the screen holds something other than `lines`, as after a resize.
+    stale: bool,
+}
+
+impl ScreenBuffer {
+    fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            stale: true,
+        }
+    }
+
+    /// Forgets what is on screen, so the next frame is drawn on a cleared screen; for
+    /// resizes, which reflow the lines.
+    fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// Draws `frame` from the top left corner, rewriting only the lines that differ from
+    /// the previous frame. Text lines are cut at `width` characters, so none wraps onto the
+    /// next. Inline images cannot be patched line by line, so frames with them are drawn
+    /// on a cleared screen.
+    fn draw(&mut self, out: &mut impl Write, frame: &str, width: usize) -> io::Result<()> {
+        let lines: Vec<String> = frame
+            .lines()
+            .map(|line| {
+                if line.contains('\x1b') {
+                    line.to_string()
+                } else {
+                    line.chars().take(width).collect()
+                }
+            })
+            .collect();
+        let graphics = lines.iter().any(|line| line.contains('\x1b'));
+        if self.stale || graphics {
+            queue!(out, cursor::MoveTo(0, 0), Clear(ClearType::All))?;
+            self.lines.clear();
+            self.stale = false;
+        }
+
+        for (row, line) in lines.iter().enumerate() {
+            if self.lines.get(row) == Some(line) {
+                continue;
+            }
+            queue!(out, cursor::MoveTo(0, row as u16))?;
+            out.write_all(line.as_bytes())?;
+            queue!(out, Clear(ClearType::UntilNewLine))?;
+        }
+        if lines.len() < self.lines.len() {
+            queue!(
+                out,
+                cursor::MoveTo(0, lines.len() as u16),
+                Clear(ClearType::FromCursorDown)
+            )?;
+        }
+        out.flush()?;
+        self.lines = lines;
+        Ok(())
+    }
+}
+
+/// Width of the terminal in characters, or unlimited when it cannot be read.
+fn screen_width() -> usize {
+    terminal::size().map_or(usize::MAX, |(width, _)| width as usize)
+}
+
+/// Reads receiver feedback on a thread of its own, so a slow URL does not hold up the
+/// carousel.
+struct FeedbackPoller {
+    updates: mpsc::Receiver<Result<Feedback, String>>,
+    stop: Arc<AtomicBool>,
+    latest: Option<Feedback>,
+    /// Why the last read failed, until one succeeds.
+    error: Option<String>,
+}
+
+impl FeedbackPoller {
+    fn start(source: String) -> Self {
+        let (sender, updates) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        thread::spawn(move || {
+            while !stopped.load(Ordering::SeqCst) {
+                let feedback = Feedback::fetch(&source)
+                    .map_err(|e| format!("No receiver feedback from {}: {}", source, e));
+                if sender.send(feedback).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(FEEDBACK_POLL_MS));
+            }
+        });
+        Self {
+            updates,
+            stop,
+            latest: None,
+            error: None,
+        }
+    }
+
+    /// Takes the reads that arrived since the last call; returns whether anything changed.
+    fn update(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok(read) = self.updates.try_recv() {
+            match read {
+                Ok(feedback) => {
+                    changed |= self.latest.as_ref() != Some(&feedback) || self.error.is_some();
+                    self.latest = Some(feedback);
+                    self.error = None;
+                }
+                Err(message) => {
+                    changed |= self.error.as_ref() != Some(&message);
+                    self.error = Some(message);
+                }
+            }
+        }
+        changed
+    }
+
+    /// Which codes of `data` the receiver reported having.
+    fn received_codes(&self, data: &TerminalQrData) -> Vec<bool> {
+        let Some(feedback) = &self.latest else {
+            return Vec::new();
+        };
+        data.chunk_ids
+            .iter()
+            .map(|id| id.is_some_and(|id| feedback.has(id)))
+            .collect()
+    }
+
+    /// Line telling what the receiver has, or why its feedback cannot be read.
+    fn notice(&self, received: &[bool]) -> Option<String> {
+        if let Some(error) = &self.error {
+            return Some(error.clone());
+        }
+        let feedback = self.latest.as_ref()?;
+        let lacking = received.iter().filter(|&&has| !has).count();
+        Some(match &feedback.receiving {
+            Some(progress) => format!(
+                "Receiver has {}/{} chunks | Showing the {} codes it lacks",
+                progress.received.count(),
+                progress.needed,
+                lacking
+            ),
+            None => format!(
+                "Receiver is waiting | Showing the {} codes it lacks",
+                lacking
+            ),
+        })
+    }
+}
+
+impl Drop for FeedbackPoller {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Whether the receiver reported having every chunk shown, so the transfer is complete.
+fn has_every_code(data: &TerminalQrData, received: &[bool]) -> bool {
+    let chunks = data.chunk_ids.iter().filter(|id| id.is_some()).count();
+    chunks > 0 && received.iter().filter(|&&has| has).count() == chunks
+}
+
+/// Code `steps` positions on from `current` out of `total`, passing over the codes the
+/// receiver has. With nothing left to pass over to, it moves on one step regardless.
+fn next_code(current: usize, total: usize, steps: usize, received: &[bool]) -> usize {
+    let mut index = current;
+    for _ in 0..total {
+        index = (index + steps) % total;
+        if !received.get(index).copied().unwrap_or(false) {
+            return index;
+        }
+    }
+    (current + steps) % total
+}
+
+fn install_interrupt_flag() -> Arc<AtomicBool> {
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
 
@@ -18,72 +274,590 @@ pub fn display_qr_carousel(data: &TerminalQrData, interval_ms: u64) {
     })
     .expect("Error setting Ctrl-C handler");
 
+    running
+}
+
+/// Sleeps for `interval_ms`, returning early once `running` is cleared.
+fn wait_interval(running: &AtomicBool, interval_ms: u64) {
+    let start = std::time::Instant::now();
+    let duration = Duration::from_millis(interval_ms);
+
+    while start.elapsed() < duration {
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(std::cmp::min(50, interval_ms)));
+    }
+}
+
+/// Like [`wait_interval`], but returns the new terminal size as soon as it differs from
+/// `size` and has stayed the same for [`RESIZE_SETTLE_MS`].
+fn wait_interval_or_resize(
+    running: &AtomicBool,
+    interval_ms: u64,
+    size: Option<(u16, u16)>,
+) -> Option<(u16, u16)> {
+    let start = Instant::now();
+    let mut changed: Option<((u16, u16), Instant)> = None;
+    while start.elapsed() < Duration::from_millis(interval_ms) && running.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(KEY_POLL_MS.min(interval_ms)));
+        let Ok(now) = terminal::size() else {
+            continue;
+        };
+        match changed {
+            Some((last, _)) if last != now => changed = Some((now, Instant::now())),
+            Some((last, at)) if at.elapsed() >= Duration::from_millis(RESIZE_SETTLE_MS) => {
+                return Some(last)
+            }
+            None if Some(now) != size => changed = Some((now, Instant::now())),
+            _ => {}
+        }
+    }
+    changed.map(|(last, _)| last)
+}
+
+/// What the carousel loop should do after a key press.
+#[derive(Debug, PartialEq, Eq)]
+enum CarouselAction {
+    Ignore,
+    Redraw,
+    Quit,
+}
+
+/// Playback state of the interactive carousel.
+struct CarouselState {
+    current: usize,
+    total: usize,
+    interval_ms: u64,
+    paused: bool,
+    /// Digits typed after `g`, while a jump target is being entered.
+    jump_input: Option<String>,
+    /// Codes the receiver reported having, which stepping passes over.
+    received: Vec<bool>,
+}
+
+impl CarouselState {
+    fn new(total: usize, interval_ms: u64) -> Self {
+        Self {
+            current: 0,
+            total,
+            interval_ms: interval_ms.max(MIN_INTERVAL_MS),
+            paused: false,
+            jump_input: None,
+            received: Vec::new(),
+        }
+    }
+
+    fn advance(&mut self) {
+        self.current = next_code(self.current, self.total, 1, &self.received);
+    }
+
+    fn step_back(&mut self) {
+        self.current = next_code(self.current, self.total, self.total - 1, &self.received);
+    }
+
+    /// Auto-advance is suspended while paused or while a jump is being typed.
+    fn is_holding(&self) -> bool {
+        self.paused || self.jump_input.is_some()
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> CarouselAction {
+        // Raw mode swallows SIGINT, so Ctrl+C arrives as a key press
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return CarouselAction::Quit;
+        }
+
+        if let Some(input) = &mut self.jump_input {
+            match key.code {
+                KeyCode::Char(c) if c.is_ascii_digit() => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    if let Ok(n) = input.parse::<usize>() {
+                        if (1..=self.total).contains(&n) {
+                            self.current = n - 1;
+                        }
+                    }
+                    self.jump_input = None;
+                }
+                KeyCode::Esc => self.jump_input = None,
+                _ => return CarouselAction::Ignore,
+            }
+            return CarouselAction::Redraw;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return CarouselAction::Quit,
+            KeyCode::Char(' ') => self.paused = !self.paused,
+            KeyCode::Right => self.advance(),
+            KeyCode::Left => self.step_back(),
+            KeyCode::Char('+') | KeyCode::Char('=') => self.interval_ms += INTERVAL_STEP_MS,
+            KeyCode::Char('-') => {
+                self.interval_ms = self
+                    .interval_ms
+                    .saturating_sub(INTERVAL_STEP_MS)
+                    .max(MIN_INTERVAL_MS)
+            }
+            KeyCode::Char('g') => self.jump_input = Some(String::new()),
+            _ => return CarouselAction::Ignore,
+        }
+        CarouselAction::Redraw
+    }
+
+    fn status_line(&self) -> String {
+        if let Some(input) = &self.jump_input {
+            format!(
+                "Jump to QR code (1-{}): {}_ | Enter: confirm  Esc: cancel",
+                self.total, input
+            )
+        } else if self.paused {
+            "Paused | Space: resume  Left/Right: step  g: jump  q: quit".to_string()
+        } else {
+            format!(
+                "Auto-switching every {}ms | Space: pause  Left/Right: step  +/-: speed  g: jump  q: quit",
+                self.interval_ms
+            )
+        }
+    }
+
+    /// Cycle progress bar and playback stats shown below the key help.
+    fn stats_line(&self, elapsed: Duration, chunk_bytes: usize) -> String {
+        format_stats_line(
+            self.current,
+            self.total,
+            elapsed,
+            chunk_bytes,
+            self.interval_ms,
+        )
+    }
+}
+
+/// `[#####---------------] 5/20 | Elapsed 00:12 | 100 bytes/chunk | Cycle ~40s`
+fn format_stats_line(
+    current: usize,
+    total: usize,
+    elapsed: Duration,
+    chunk_bytes: usize,
+    interval_ms: u64,
+) -> String {
+    let filled = ((current + 1) * PROGRESS_BAR_WIDTH)
+        .checked_div(total)
+        .unwrap_or(0)
+        .min(PROGRESS_BAR_WIDTH);
+    let secs = elapsed.as_secs();
+    let cycle_secs = (total as u64 * interval_ms).div_ceil(1000);
+    format!(
+        "[{}{}] {}/{} | Elapsed {:02}:{:02} | {} bytes/chunk | Cycle ~{}s",
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled),
+        current + 1,
+        total,
+        secs / 60,
+        secs % 60,
+        chunk_bytes,
+        cycle_secs
+    )
+}
+
+/// Cycles through the QR codes, with keyboard controls when stdin is a terminal.
+///
+/// Space pauses, the arrow keys step, `+`/`-` change the interval, `g` jumps to
+/// a code by number and `q` quits. Without a terminal on stdin the carousel
+/// just auto-advances until Ctrl+C.
+///
+/// The code shown is redrawn when the terminal is resized, but keeps the centering it was
+/// rendered with; [`display_qr_carousel_refitting`] renders the codes anew.
+pub fn display_qr_carousel(data: &TerminalQrData, interval_ms: u64) -> Result<()> {
+    display_qr_carousel_with(data, interval_ms, CarouselOptions::new())
+}
+
+/// Like [`display_qr_carousel`], but when the terminal is resized, calls `refit` with the
+/// new size and the codes shown, and shows the codes it returns instead, so they stay
+/// centred and scannable. `refit` typically encodes the data again for that size.
+///
+/// Codes of a different payload size form a new stream, so receivers start over; keeping
+/// the payload size of the codes shown when they still fit avoids that. When `refit`
+/// fails, e.g. because the terminal became too small, the codes shown stay on screen with
+/// a notice.
+pub fn display_qr_carousel_refitting<F>(
+    data: &TerminalQrData,
+    interval_ms: u64,
+    refit: F,
+) -> Result<()>
+where
+    F: FnMut(TerminalSize, &TerminalQrData) -> Result<TerminalQrData>,
+{
+    display_qr_carousel_with(data, interval_ms, CarouselOptions::new().refit(refit))
+}
+
+/// Optional behaviour of [`display_qr_carousel_with`].
+#[derive(Default)]
+pub struct CarouselOptions<'a> {
+    refit: Option<Box<Refit<'a>>>,
+    feedback: Option<String>,
+}
+
+impl<'a> CarouselOptions<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refits the codes when the terminal is resized, as [`display_qr_carousel_refitting`]
+    /// does.
+    pub fn refit<F>(mut self, refit: F) -> Self
+    where
+        F: FnMut(TerminalSize, &TerminalQrData) -> Result<TerminalQrData> + 'a,
+    {
+        self.refit = Some(Box::new(refit));
+        self
+    }
+
+    /// Reads the receiver's [`Feedback`] every second from `source`, a file path or an
+    /// `http://` URL, and passes over the codes it reports having, so only the missing ones
+    /// are shown. The carousel ends once the receiver has every file.
+    pub fn feedback(mut self, source: impl Into<String>) -> Self {
+        self.feedback = Some(source.into());
+        self
+    }
+}
+
+/// Shows a carousel like [`display_qr_carousel`], with the behaviour set in `options`.
+pub fn display_qr_carousel_with(
+    data: &TerminalQrData,
+    interval_ms: u64,
+    mut options: CarouselOptions,
+) -> Result<()> {
+    let mut feedback = options.feedback.take().map(FeedbackPoller::start);
+    let refit = options.refit.as_deref_mut();
+    let complete = if io::stdin().is_terminal() {
+        let screen = ScreenGuard::enter(true)?;
+        let result = run_interactive_carousel(data, interval_ms, refit, feedback.as_mut());
+        drop(screen);
+        result?
+    } else {
+        display_auto_carousel(data, interval_ms, refit, feedback.as_mut())?
+    };
+    if complete {
+        println!("The receiver has every file.");
+    } else {
+        println!("Exited.");
+    }
+    Ok(())
+}
+
+/// Codes of `data` refitted to a terminal of `width` x `height`, or a notice why the codes
+/// shown stay.
+fn refit_codes(
+    refit: &mut Refit,
+    shown: &TerminalQrData,
+    (width, height): (u16, u16),
+) -> Result<TerminalQrData, String> {
+    let size = TerminalSize::Given {
+        width: width as usize,
+        height: height as usize,
+    };
+    refit(size, shown).map_err(|e| {
+        log::warn!("Could not refit the codes to {}x{}: {}", width, height, e);
+        format!(
+            "The codes do not fit {}x{}; enlarge the terminal to scan them",
+            width, height
+        )
+    })
+}
+
+/// Runs the carousel with keyboard controls; returns whether it ended because the receiver
+/// has every code.
+fn run_interactive_carousel(
+    original: &TerminalQrData,
+    interval_ms: u64,
+    mut refit: Option<&mut Refit>,
+    mut feedback: Option<&mut FeedbackPoller>,
+) -> Result<bool> {
+    let mut state = CarouselState::new(original.qr_strings.len(), interval_ms);
+    let started_at = Instant::now();
+    let mut shown_at = Instant::now();
+    let mut stats_at = Instant::now();
+    let mut needs_redraw = true;
+    let mut refitted: Option<TerminalQrData> = None;
+    let mut resized: Option<((u16, u16), Instant)> = None;
+    let mut notice: Option<String> = None;
+    let mut screen = ScreenBuffer::new();
+
+    loop {
+        let mut codes_changed = false;
+        if let Some((size, at)) = resized {
+            if at.elapsed() >= Duration::from_millis(RESIZE_SETTLE_MS) {
+                resized = None;
+                if let Some(refit) = refit.as_deref_mut() {
+                    let shown = refitted.as_ref().unwrap_or(original);
+                    match refit_codes(refit, shown, size) {
+                        Ok(data) => {
+                            state.total = data.qr_strings.len();
+                            state.current %= state.total.max(1);
+                            refitted = Some(data);
+                            notice = None;
+                            codes_changed = true;
+                        }
+                        Err(message) => notice = Some(message),
+                    }
+                }
+                needs_redraw = true;
+            }
+        }
+        let data = refitted.as_ref().unwrap_or(original);
+
+        if let Some(feedback) = feedback.as_deref_mut() {
+            if feedback.update() || codes_changed {
+                state.received = feedback.received_codes(data);
+                if has_every_code(data, &state.received) {
+                    return Ok(true);
+                }
+                // Move off a code the receiver just reported
+                if state.received.get(state.current) == Some(&true) && !state.is_holding() {
+                    state.advance();
+                }
+                needs_redraw = true;
+            }
+        }
+
+        // Only changed lines are rewritten, so refreshing the stats line leaves the code alone
+        let stats_due = stats_at.elapsed() >= Duration::from_millis(STATS_REFRESH_MS);
+        if needs_redraw || stats_due {
+            let notices: String = [
+                notice.clone(),
+                feedback.as_deref().and_then(|f| f.notice(&state.received)),
+            ]
+            .into_iter()
+            .flatten()
+            .map(|n| format!("{}\n", n))
+            .collect();
+            let frame = format!(
+                "{}\n{}{}\n{}",
+                format_single_qr(
+                    &data.qr_strings[state.current],
+                    data.filename_of(state.current),
+                    state.current + 1,
+                    state.total,
+                ),
+                notices,
+                state.status_line(),
+                state.stats_line(started_at.elapsed(), data.effective_size)
+            );
+            screen.draw(&mut io::stdout().lock(), &frame, screen_width())?;
+
+            if needs_redraw {
+                shown_at = Instant::now();
+            }
+            stats_at = Instant::now();
+            needs_redraw = false;
+        }
+
+        if event::poll(Duration::from_millis(KEY_POLL_MS))? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match state.handle_key(key) {
+                    CarouselAction::Quit => break,
+                    CarouselAction::Redraw => needs_redraw = true,
+                    CarouselAction::Ignore => {}
+                },
+                Event::Resize(width, height) => {
+                    resized = Some(((width, height), Instant::now()));
+                    screen.invalidate();
+                    needs_redraw = true;
+                }
+                _ => {}
+            }
+        } else if state.total > 1
+            && !state.is_holding()
+            && shown_at.elapsed() >= Duration::from_millis(state.interval_ms)
+        {
+            state.advance();
+            needs_redraw = true;
+        }
+    }
+
+    Ok(false)
+}
+
+/// Runs the carousel without keyboard controls until Ctrl+C; returns whether it ended
+/// because the receiver has every code.
+fn display_auto_carousel(
+    original: &TerminalQrData,
+    interval_ms: u64,
+    mut refit: Option<&mut Refit>,
+    mut feedback: Option<&mut FeedbackPoller>,
+) -> Result<bool> {
+    let running = install_interrupt_flag();
+    let screen = ScreenGuard::enter(false)?;
+
+    let data = original;
     let total = data.qr_strings.len();
+    let mut buffer = ScreenBuffer::new();
+    let mut complete = false;
 
     if total == 1 {
         // Single QR code, just display it
-        display_single_qr(&data.qr_strings[0], &data.filename, 1, 1);
-        println!("\nPress Ctrl+C to exit...");
+        let frame = format!(
+            "{}\nPress Ctrl+C to exit...",
+            format_single_qr(&data.qr_strings[0], &data.filename, 1, 1)
+        );
+        buffer.draw(&mut io::stdout().lock(), &frame, screen_width())?;
 
         while running.load(Ordering::SeqCst) {
             thread::sleep(Duration::from_millis(100));
         }
     } else {
         // Multiple QR codes, carousel mode
-        print!("{}", HIDE_CURSOR);
-        io::stdout().flush().unwrap();
-
         let mut current = 0;
+        let started_at = Instant::now();
+        let mut refitted: Option<TerminalQrData> = None;
+        let mut notice: Option<String> = None;
+        let mut size = terminal::size().ok();
+        let mut received = Vec::new();
 
         while running.load(Ordering::SeqCst) {
-            display_single_qr(
-                &data.qr_strings[current],
-                &data.filename,
-                current + 1,
-                total,
-            );
-            println!(
-                "\nAuto-switching in {}ms | Press Ctrl+C to exit...",
-                interval_ms
-            );
-
-            // Wait for interval or until interrupted
-            let start = std::time::Instant::now();
-            let duration = Duration::from_millis(interval_ms);
-
-            while start.elapsed() < duration {
-                if !running.load(Ordering::SeqCst) {
+            let data = refitted.as_ref().unwrap_or(original);
+            let total = data.qr_strings.len();
+            if let Some(feedback) = feedback.as_deref_mut() {
+                feedback.update();
+                received = feedback.received_codes(data);
+                if has_every_code(data, &received) {
+                    complete = true;
                     break;
                 }
-                thread::sleep(Duration::from_millis(std::cmp::min(50, interval_ms)));
+                if received.get(current) == Some(&true) {
+                    current = next_code(current, total, 1, &received);
+                }
             }
+            let notice_lines: String = [
+                notice.clone(),
+                feedback.as_deref().and_then(|f| f.notice(&received)),
+            ]
+            .into_iter()
+            .flatten()
+            .map(|n| format!("\n{}\n", n))
+            .collect();
+            let frame = format!(
+                "{}{}\nAuto-switching in {}ms | Press Ctrl+C to exit...\n{}",
+                format_single_qr(
+                    &data.qr_strings[current],
+                    data.filename_of(current),
+                    current + 1,
+                    total,
+                ),
+                notice_lines,
+                interval_ms,
+                format_stats_line(
+                    current,
+                    total,
+                    started_at.elapsed(),
+                    data.effective_size,
+                    interval_ms
+                )
+            );
+            buffer.draw(&mut io::stdout().lock(), &frame, screen_width())?;
 
-            current = (current + 1) % total;
+            // Wait for interval or until interrupted; stdin gives no resize events here, so
+            // the size is compared instead
+            let resized = wait_interval_or_resize(&running, interval_ms, size);
+            current = next_code(current, total, 1, &received);
+            let Some(new_size) = resized else {
+                continue;
+            };
+            size = Some(new_size);
+            buffer.invalidate();
+            let Some(refit) = refit.as_deref_mut() else {
+                continue;
+            };
+            match refit_codes(refit, data, new_size) {
+                Ok(new_data) => {
+                    current %= new_data.qr_strings.len().max(1);
+                    refitted = Some(new_data);
+                    notice = None;
+                }
+                Err(message) => notice = Some(message),
+            }
         }
-
-        print!("{}", SHOW_CURSOR);
-        io::stdout().flush().unwrap();
     }
 
-    // Clean exit
-    print!("{}", CLEAR_SCREEN);
-    println!("Exited.");
+    drop(screen);
+    Ok(complete)
 }
 
-fn display_single_qr(qr_string: &str, filename: &str, current: usize, total: usize) {
-    print!("{}", CLEAR_SCREEN);
+/// Shows an endless stream of fresh fountain packets until Ctrl+C.
+pub fn display_qr_stream(stream: &mut TerminalQrStream, interval_ms: u64) -> Result<()> {
+    let running = install_interrupt_flag();
+    let screen = ScreenGuard::enter(false)?;
+
+    let result = (|| -> Result<()> {
+        let mut buffer = ScreenBuffer::new();
+        while running.load(Ordering::SeqCst) {
+            let Some(qr_string) = stream.next().transpose()? else {
+                break;
+            };
 
-    println!("File: {}  |  QR Code {}/{}", filename, current, total);
-    println!("{}", "=".repeat(50));
-    println!();
-    println!("{}", qr_string);
+            let frame = format!(
+                "File: {}  |  Packet {}  |  ~{} needed to decode\n{}\n\n{}\n\nStreaming a new packet every {}ms | Press Ctrl+C to exit...",
+                stream.filename,
+                stream.emitted(),
+                stream.source_packets,
+                "=".repeat(50),
+                qr_string,
+                interval_ms
+            );
+            buffer.draw(&mut io::stdout().lock(), &frame, screen_width())?;
+
+            wait_interval(&running, interval_ms);
+        }
+        Ok(())
+    })();
+
+    drop(screen);
+    println!("Exited.");
+
+    result
 }
 
-pub fn display_qr_once(data: &TerminalQrData) {
+fn format_single_qr(qr_string: &str, filename: &str, current: usize, total: usize) -> String {
+    format!(
+        "File: {}  |  QR Code {}/{}\n{}\n\n{}\n",
+        filename,
+        current,
+        total,
+        "=".repeat(50),
+        qr_string
+    )
+}
+
+/// Prints every QR code at once, packed side by side in as many columns as the terminal
+/// width allows, or exactly `columns` when given. Inline images and codes too wide for two
+/// columns are stacked vertically.
+pub fn display_qr_once(data: &TerminalQrData, columns: Option<usize>) {
     let total = data.qr_strings.len();
+    let blocks: Vec<Vec<&str>> = data.qr_strings.iter().map(|s| trim_padding(s)).collect();
+    // Graphics escape sequences cannot be cut into rows
+    let is_text = !data.qr_strings.iter().any(|s| s.contains('\x1b'));
+    let block_width = blocks.iter().map(|b| block_width(b)).max().unwrap_or(0);
+    let term_width = terminal::size().map(|(w, _)| w as usize).unwrap_or(80);
+    let columns = columns
+        .unwrap_or_else(|| (term_width + COLUMN_GAP) / (block_width + COLUMN_GAP))
+        .clamp(1, total.max(1));
+
+    if is_text && columns > 1 {
+        println!("File: {}  |  {} QR codes", data.filename, total);
+        println!("{}", "=".repeat(50));
+        println!();
+        print!("{}", layout_side_by_side(&blocks, columns));
+        return;
+    }
 
     for (i, qr_string) in data.qr_strings.iter().enumerate() {
-        println!("File: {}  |  QR Code {}/{}", data.filename, i + 1, total);
+        println!(
+            "File: {}  |  QR Code {}/{}",
+            data.filename_of(i),
+            i + 1,
+            total
+        );
         println!("{}", "=".repeat(50));
         println!();
         println!("{}", qr_string);
@@ -95,3 +869,264 @@ pub fn display_qr_once(data: &TerminalQrData) {
         }
     }
 }
+
+/// Spaces between QR codes laid out side by side; the quiet zone adds more.
+const COLUMN_GAP: usize = 2;
+
+/// Rows of a rendered QR code without the blank lines and left indent that center it,
+/// keeping a quiet zone on the left as wide as the one on the right.
+fn trim_padding(qr_string: &str) -> Vec<&str> {
+    let rows: Vec<&str> = qr_string.lines().filter(|row| !row.is_empty()).collect();
+    let inked = rows.iter().filter(|row| !row.trim().is_empty());
+    let leading = inked
+        .clone()
+        .map(|row| row.len() - row.trim_start().len())
+        .min();
+    let trailing = inked.map(|row| row.len() - row.trim_end().len()).min();
+    let indent = match (leading, trailing) {
+        (Some(leading), Some(trailing)) => leading.saturating_sub(trailing),
+        _ => 0,
+    };
+    rows.into_iter()
+        .map(|row| row.get(indent..).unwrap_or(""))
+        .collect()
+}
+
+fn block_width(rows: &[&str]) -> usize {
+    rows.iter()
+        .map(|row| row.chars().count())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Lays out trimmed QR codes in rows of `columns`, each labelled with its number.
+fn layout_side_by_side(blocks: &[Vec<&str>], columns: usize) -> String {
+    let total = blocks.len();
+    let width = blocks.iter().map(|b| block_width(b)).max().unwrap_or(0);
+    let gap = " ".repeat(COLUMN_GAP);
+    let mut out = String::new();
+
+    for (row_index, row) in blocks.chunks(columns.max(1)).enumerate() {
+        let first = row_index * columns.max(1);
+        let labels: Vec<String> = (0..row.len())
+            .map(|i| format!("{:^width$}", format!("{}/{}", first + i + 1, total)))
+            .collect();
+        out.push_str(labels.join(&gap).trim_end());
+        out.push('\n');
+
+        let height = row.iter().map(|b| b.len()).max().unwrap_or(0);
+        for line in 0..height {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|block| format!("{:<width$}", block.get(line).copied().unwrap_or("")))
+                .collect();
+            out.push_str(cells.join(&gap).trim_end());
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(state: &mut CarouselState, code: KeyCode) -> CarouselAction {
+        state.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn test_screen_buffer_redraws_changed_lines() {
+        let mut screen = ScreenBuffer::new();
+        let mut out = Vec::new();
+        screen
+            .draw(&mut out, "code\nline one\nline two", 80)
+            .unwrap();
+        let first = String::from_utf8(out).unwrap();
+        assert!(first.contains("\x1b[2J"));
+        assert!(first.contains("line one") && first.contains("line two"));
+
+        let mut out = Vec::new();
+        screen.draw(&mut out, "code\nline one\nline 2", 80).unwrap();
+        let second = String::from_utf8(out).unwrap();
+        assert!(!second.contains("\x1b[2J"));
+        assert!(!second.contains("code") && !second.contains("line one"));
+        assert!(second.contains("line 2"));
+
+        let mut out = Vec::new();
+        screen.draw(&mut out, "code", 3).unwrap();
+        let shorter = String::from_utf8(out).unwrap();
+        assert!(shorter.contains("cod") && !shorter.contains("code"));
+        assert!(shorter.contains("\x1b[J"));
+
+        screen.invalidate();
+        let mut out = Vec::new();
+        screen.draw(&mut out, "cod", 3).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("\x1b[2J"));
+    }
+
+    #[test]
+    fn test_carousel_navigation_keys() {
+        let mut state = CarouselState::new(5, 1000);
+
+        press(&mut state, KeyCode::Left);
+        assert_eq!(state.current, 4);
+        press(&mut state, KeyCode::Right);
+        assert_eq!(state.current, 0);
+
+        press(&mut state, KeyCode::Char(' '));
+        assert!(state.is_holding());
+        press(&mut state, KeyCode::Char(' '));
+        assert!(!state.is_holding());
+
+        press(&mut state, KeyCode::Char('+'));
+        assert_eq!(state.interval_ms, 1250);
+        for _ in 0..10 {
+            press(&mut state, KeyCode::Char('-'));
+        }
+        assert_eq!(state.interval_ms, MIN_INTERVAL_MS);
+
+        assert_eq!(press(&mut state, KeyCode::Char('q')), CarouselAction::Quit);
+    }
+
+    #[test]
+    fn test_carousel_jump_to_chunk() {
+        let mut state = CarouselState::new(12, 1000);
+
+        press(&mut state, KeyCode::Char('g'));
+        press(&mut state, KeyCode::Char('1'));
+        press(&mut state, KeyCode::Char('1'));
+        // 'q' is ignored while typing a jump target
+        assert_eq!(
+            press(&mut state, KeyCode::Char('q')),
+            CarouselAction::Ignore
+        );
+        press(&mut state, KeyCode::Enter);
+        assert_eq!(state.current, 10);
+
+        // Out-of-range targets leave the position unchanged
+        press(&mut state, KeyCode::Char('g'));
+        press(&mut state, KeyCode::Char('9'));
+        press(&mut state, KeyCode::Char('9'));
+        press(&mut state, KeyCode::Enter);
+        assert_eq!(state.current, 10);
+        assert!(state.jump_input.is_none());
+    }
+
+    #[test]
+    fn test_side_by_side_layout() {
+        let code = "\n\n      ██  \n      █▀  \n";
+        let blocks: Vec<Vec<&str>> = (0..3).map(|_| trim_padding(code)).collect();
+        assert_eq!(blocks[0], vec!["  ██  ", "  █▀  "]);
+
+        let out = layout_side_by_side(&blocks, 2);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], " 1/3     2/3");
+        assert_eq!(lines[1], "  ██      ██");
+        assert_eq!(lines[2], "  █▀      █▀");
+        assert_eq!(lines[4], " 3/3");
+        assert_eq!(lines[5], "  ██");
+    }
+
+    #[test]
+    fn test_refit_codes_for_new_size() {
+        let options = crate::EncodeOptions::new();
+        let shown =
+            crate::encode_text_for_terminal_with_options("hello", "hello", &options).unwrap();
+
+        let mut seen = None;
+        let mut refit = |size: TerminalSize, shown: &TerminalQrData| {
+            seen = Some((size, shown.effective_size));
+            crate::encode_text_for_terminal_with_options("hi", "hi", &options)
+        };
+        let data = refit_codes(&mut refit, &shown, (100, 50)).unwrap();
+        assert_eq!(data.filename, "hi");
+        let size = TerminalSize::Given {
+            width: 100,
+            height: 50,
+        };
+        assert_eq!(seen, Some((size, 5)));
+
+        let mut failing =
+            |_: TerminalSize, _: &TerminalQrData| Err(anyhow::anyhow!("Terminal too small"));
+        let Err(notice) = refit_codes(&mut failing, &shown, (20, 10)) else {
+            panic!("A failed refit replaced the codes shown");
+        };
+        assert!(notice.contains("20x10"));
+    }
+
+    #[test]
+    fn test_carousel_skips_received_codes() {
+        let options = crate::EncodeOptions::new().chunk_size(100);
+        let mut seed = 1u32;
+        let data: Vec<u8> = (0..1000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        let metadata = crate::FileMetadata::default();
+        let codes =
+            crate::encode_bytes_for_terminal_with_options(&data, "data.bin", &metadata, &options)
+                .unwrap();
+        assert!(codes.total > 4);
+        let id = |index: usize| codes.chunk_ids[index].unwrap();
+
+        let (_, updates) = mpsc::channel();
+        let mut poller = FeedbackPoller {
+            updates,
+            stop: Arc::new(AtomicBool::new(false)),
+            latest: Some(Feedback {
+                receiving: Some(crate::receive::StreamProgress {
+                    transfer_length: id(0).transfer_length,
+                    packet_size: id(0).packet_size,
+                    needed: codes.total as u32,
+                    received: [id(1).index, id(2).index].into_iter().collect(),
+                }),
+                done: Vec::new(),
+            }),
+            error: None,
+        };
+        let mut state = CarouselState::new(codes.total, 1000);
+        state.received = poller.received_codes(&codes);
+        state.advance();
+        assert_eq!(state.current, 3);
+        state.step_back();
+        assert_eq!(state.current, 0);
+        assert!(!has_every_code(&codes, &state.received));
+        let lacking = codes.total - 2;
+        assert!(poller
+            .notice(&state.received)
+            .unwrap()
+            .ends_with(&format!("Showing the {} codes it lacks", lacking)));
+
+        poller.latest = Some(Feedback {
+            receiving: None,
+            done: vec![(id(0).transfer_length, id(0).packet_size)],
+        });
+        assert!(has_every_code(&codes, &poller.received_codes(&codes)));
+    }
+
+    #[test]
+    fn test_carousel_stats_line() {
+        let mut state = CarouselState::new(20, 2000);
+        for _ in 0..4 {
+            state.advance();
+        }
+        assert_eq!(
+            state.stats_line(Duration::from_secs(75), 120),
+            "[#####---------------] 5/20 | Elapsed 01:15 | 120 bytes/chunk | Cycle ~40s"
+        );
+
+        state.step_back();
+        state.step_back();
+        state.step_back();
+        state.step_back();
+        state.step_back();
+        assert!(state
+            .stats_line(Duration::ZERO, 120)
+            .starts_with("[####################] 20/20"));
+    }
+}
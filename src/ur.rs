@@ -0,0 +1,524 @@
+//! Blockchain Commons Uniform Resources (BCR-2020-005), the `ur:bytes/...` codes that
+//! hardware wallets and UR scanners exchange. A file is sent as the CBOR byte string of its
+//! contents, split by the UR fountain code into parts that each fit one QR code.
+//!
+//! UR carries no filename or metadata; decoded files are named [`UR_FILENAME`].
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+
+/// UR type of the messages this module writes and reads.
+pub const UR_TYPE: &str = "bytes";
+/// Name given to files received over UR, which carries none.
+pub const UR_FILENAME: &str = "ur.bin";
+const MIN_FRAGMENT_LEN: usize = 10;
+
+// BCR-2020-012; the minimal form of each word is its first and last letter
+const BYTEWORDS: [&str; 256] = [
+    "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away", "axis", "back", "bald",
+    "barn", "belt", "beta", "bias", "blue", "body", "brag", "brew", "bulb", "buzz", "calm", "cash",
+    "cats", "chef", "city", "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+    "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw", "drop", "drum", "dull",
+    "duty", "each", "easy", "echo", "edge", "epic", "even", "exam", "exit", "eyes", "fact", "fair",
+    "fern", "figs", "film", "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+    "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good", "gray", "grim", "guru",
+    "gush", "gyro", "half", "hang", "hard", "hawk", "heat", "help", "high", "hill", "holy", "hope",
+    "horn", "huts", "iced", "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+    "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury", "keep", "keno", "kept",
+    "keys", "kick", "kiln", "king", "kite", "kiwi", "knob", "lamb", "lava", "lazy", "leaf", "legs",
+    "liar", "limp", "lion", "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+    "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk", "nail", "navy", "need",
+    "news", "next", "noon", "note", "numb", "obey", "oboe", "omit", "onyx", "open", "oval", "owls",
+    "paid", "part", "peck", "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+    "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof", "ruby", "ruin", "runs",
+    "rust", "safe", "saga", "scar", "sets", "silk", "skew", "slot", "soap", "solo", "song", "stub",
+    "surf", "swan", "taco", "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+    "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast", "very", "veto", "vial",
+    "vibe", "view", "visa", "void", "vows", "wall", "wand", "warm", "wasp", "wave", "waxy", "webs",
+    "what", "when", "whiz", "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+    "zest", "zinc", "zone", "zoom",
+];
+
+/// CRC-32 (ISO-HDLC, as in zlib) used by Bytewords and the fountain parts.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Minimal Bytewords for `data` followed by its CRC-32, two letters per byte.
+pub fn bytewords_encode(data: &[u8]) -> String {
+    let checksum = crc32(data).to_be_bytes();
+    let mut text = String::with_capacity((data.len() + 4) * 2);
+    for &byte in data.iter().chain(&checksum) {
+        let word = BYTEWORDS[byte as usize].as_bytes();
+        text.push(word[0] as char);
+        text.push(word[3] as char);
+    }
+    text
+}
+
+/// Parses minimal Bytewords in either case and checks the trailing CRC-32.
+pub fn bytewords_decode(text: &str) -> Result<Vec<u8>> {
+    let mut lookup = [None; 26 * 26];
+    for (byte, word) in BYTEWORDS.iter().enumerate() {
+        let word = word.as_bytes();
+        lookup[(word[0] - b'a') as usize * 26 + (word[3] - b'a') as usize] = Some(byte as u8);
+    }
+
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(2) {
+        return Err(anyhow!("Invalid Bytewords: odd length"));
+    }
+    let mut bytes = text
+        .chunks_exact(2)
+        .map(|pair| {
+            let letter = |c: u8| {
+                let c = c.to_ascii_lowercase();
+                c.is_ascii_lowercase().then(|| (c - b'a') as usize)
+            };
+            letter(pair[0])
+                .zip(letter(pair[1]))
+                .and_then(|(first, last)| lookup[first * 26 + last])
+                .ok_or_else(|| anyhow!("Invalid Bytewords: unknown word"))
+        })
+        .collect::<Result<Vec<u8>>>()?;
+    if bytes.len() < 4 {
+        return Err(anyhow!("Invalid Bytewords: too short"));
+    }
+    let checksum = bytes.split_off(bytes.len() - 4);
+    if crc32(&bytes).to_be_bytes()[..] != checksum[..] {
+        return Err(anyhow!("Invalid Bytewords: checksum mismatch"));
+    }
+    Ok(bytes)
+}
+
+/// The xoshiro256** generator seeded from the SHA-256 of a byte string, as the UR fountain
+/// code uses to pick the fragments mixed into each part.
+struct Xoshiro256 {
+    s: [u64; 4],
+}
+
+impl Xoshiro256 {
+    fn from_seed(seed: &[u8]) -> Self {
+        let digest: [u8; 32] = Sha256::digest(seed).into();
+        let mut s = [0u64; 4];
+        for (i, word) in s.iter_mut().enumerate() {
+            *word = u64::from_be_bytes(digest[i * 8..i * 8 + 8].try_into().unwrap_or_default());
+        }
+        Self { s }
+    }
+
+    fn next(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+
+    fn next_double(&mut self) -> f64 {
+        self.next() as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    fn next_int(&mut self, low: usize, high: usize) -> usize {
+        (self.next_double() * (high - low + 1) as f64) as usize + low
+    }
+}
+
+/// Number of fragments mixed into a part: `d` with probability proportional to `1/d`, drawn
+/// with Vose's alias method exactly as the reference implementation does.
+fn choose_degree(seq_len: usize, rng: &mut Xoshiro256) -> usize {
+    let weights: Vec<f64> = (1..=seq_len).map(|i| 1.0 / i as f64).collect();
+    let sum: f64 = weights.iter().sum();
+    let mut p: Vec<f64> = weights.iter().map(|w| w * seq_len as f64 / sum).collect();
+
+    let (mut small, mut large): (Vec<usize>, Vec<usize>) = (Vec::new(), Vec::new());
+    for i in (0..seq_len).rev() {
+        if p[i] < 1.0 {
+            small.push(i);
+        } else {
+            large.push(i);
+        }
+    }
+    let mut probs = alloc::vec![0.0; seq_len];
+    let mut aliases = alloc::vec![0; seq_len];
+    while let (Some(&a), Some(&g)) = (small.last(), large.last()) {
+        small.pop();
+        large.pop();
+        probs[a] = p[a];
+        aliases[a] = g;
+        p[g] += p[a] - 1.0;
+        if p[g] < 1.0 {
+            small.push(g);
+        } else {
+            large.push(g);
+        }
+    }
+    for i in large.into_iter().chain(small) {
+        probs[i] = 1.0;
+    }
+
+    let r1 = rng.next_double();
+    let r2 = rng.next_double();
+    let i = (seq_len as f64 * r1) as usize;
+    (if r2 < probs[i] { i } else { aliases[i] }) + 1
+}
+
+/// Indices of the fragments XORed into part `seq_num` (1-based) of a message.
+fn choose_fragments(seq_num: u32, seq_len: usize, checksum: u32) -> BTreeSet<usize> {
+    if seq_num as usize <= seq_len {
+        return BTreeSet::from([seq_num as usize - 1]);
+    }
+    let mut seed = seq_num.to_be_bytes().to_vec();
+    seed.extend_from_slice(&checksum.to_be_bytes());
+    let mut rng = Xoshiro256::from_seed(&seed);
+    let degree = choose_degree(seq_len, &mut rng);
+
+    let mut remaining: Vec<usize> = (0..seq_len).collect();
+    let mut chosen = BTreeSet::new();
+    while chosen.len() < degree {
+        let index = rng.next_int(0, remaining.len() - 1);
+        chosen.insert(remaining.remove(index));
+    }
+    chosen
+}
+
+fn xor_into(target: &mut [u8], source: &[u8]) {
+    for (t, s) in target.iter_mut().zip(source) {
+        *t ^= s;
+    }
+}
+
+fn cbor_head(major: u8, value: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xFF => out.extend_from_slice(&[major | 24, value as u8]),
+        0x100..=0xFFFF => {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xFFFF_FFFF => {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+fn cbor_bytes(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 9);
+    cbor_head(2, data.len() as u64, &mut out);
+    out.extend_from_slice(data);
+    out
+}
+
+/// Reads one CBOR item head of the given major type, returning its value and the rest.
+fn read_cbor_head(major: u8, bytes: &[u8]) -> Result<(u64, &[u8])> {
+    let invalid = || anyhow!("Invalid UR: malformed CBOR");
+    let (&first, rest) = bytes.split_first().ok_or_else(invalid)?;
+    if first >> 5 != major {
+        return Err(invalid());
+    }
+    let len = match first & 0x1F {
+        n @ 0..=23 => return Ok((n as u64, rest)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return Err(invalid()),
+    };
+    let (value, rest) = (rest.get(..len).ok_or_else(invalid)?, &rest[len..]);
+    Ok((value.iter().fold(0, |acc, &b| acc << 8 | b as u64), rest))
+}
+
+fn read_cbor_bytes(bytes: &[u8]) -> Result<(&[u8], &[u8])> {
+    let (len, rest) = read_cbor_head(2, bytes)?;
+    let len = usize::try_from(len).map_err(|_| anyhow!("Invalid UR: malformed CBOR"))?;
+    if rest.len() < len {
+        return Err(anyhow!("Invalid UR: malformed CBOR"));
+    }
+    Ok(rest.split_at(len))
+}
+
+/// Whether a QR payload is a UR, whatever its type.
+pub fn is_ur(payload: &[u8]) -> bool {
+    payload.len() >= 3 && payload[..3].eq_ignore_ascii_case(b"ur:")
+}
+
+/// Splits a file into the parts of a multi-part `ur:bytes` stream.
+pub struct UrEncoder {
+    message_len: usize,
+    checksum: u32,
+    fragments: Vec<Vec<u8>>,
+}
+
+impl UrEncoder {
+    /// Splits `data` into fragments of at most `max_fragment_len` bytes, as evenly as possible.
+    pub fn new(data: &[u8], max_fragment_len: usize) -> Result<Self> {
+        if max_fragment_len < MIN_FRAGMENT_LEN {
+            return Err(anyhow!(
+                "UR fragments must hold at least {} bytes",
+                MIN_FRAGMENT_LEN
+            ));
+        }
+        let message = cbor_bytes(data);
+        let max_count = (message.len() / MIN_FRAGMENT_LEN).max(1);
+        let fragment_len = (1..=max_count)
+            .map(|count| message.len().div_ceil(count))
+            .find(|&len| len <= max_fragment_len)
+            .unwrap_or(MIN_FRAGMENT_LEN);
+
+        let checksum = crc32(&message);
+        let mut padded = message.clone();
+        padded.resize(message.len().div_ceil(fragment_len) * fragment_len, 0);
+        let fragments = padded.chunks(fragment_len).map(<[u8]>::to_vec).collect();
+        Ok(Self {
+            message_len: message.len(),
+            checksum,
+            fragments,
+        })
+    }
+
+    /// Number of fragments; any this many distinct parts usually suffice to decode.
+    pub fn seq_len(&self) -> usize {
+        self.fragments.len()
+    }
+
+    pub fn fragment_len(&self) -> usize {
+        self.fragments.first().map_or(0, Vec::len)
+    }
+
+    /// Part `seq_num` (from 1) in upper case, which QR codes store in alphanumeric mode. Parts
+    /// up to [`seq_len`](Self::seq_len) carry one fragment each and later ones mix several.
+    /// A message that fits one fragment is sent as a single-part UR.
+    pub fn part(&self, seq_num: u32) -> String {
+        let seq_len = self.seq_len();
+        if seq_len == 1 {
+            let message = &self.fragments[0][..self.message_len];
+            return format!("ur:{}/{}", UR_TYPE, bytewords_encode(message)).to_ascii_uppercase();
+        }
+
+        let mut data = alloc::vec![0; self.fragment_len()];
+        for index in choose_fragments(seq_num, seq_len, self.checksum) {
+            xor_into(&mut data, &self.fragments[index]);
+        }
+        let mut cbor = alloc::vec![0x85];
+        cbor_head(0, seq_num as u64, &mut cbor);
+        cbor_head(0, seq_len as u64, &mut cbor);
+        cbor_head(0, self.message_len as u64, &mut cbor);
+        cbor_head(0, self.checksum as u64, &mut cbor);
+        cbor.extend_from_slice(&cbor_bytes(&data));
+        format!(
+            "ur:{}/{}-{}/{}",
+            UR_TYPE,
+            seq_num,
+            seq_len,
+            bytewords_encode(&cbor)
+        )
+        .to_ascii_uppercase()
+    }
+}
+
+/// Sequence length, message length and checksum shared by every part of one message.
+type UrParams = (usize, usize, u32);
+
+/// Collects the parts of a `ur:bytes` stream in any order and peels the mixed ones.
+#[derive(Default)]
+pub struct UrDecoder {
+    params: Option<UrParams>,
+    seen: BTreeSet<u32>,
+    simple: BTreeMap<usize, Vec<u8>>,
+    mixed: Vec<(BTreeSet<usize>, Vec<u8>)>,
+}
+
+impl UrDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of distinct parts received.
+    pub fn parts_received(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Fragments recovered so far and the number in the message, once a multi-part UR was seen.
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        self.params
+            .map(|(seq_len, _, _)| (self.simple.len(), seq_len))
+    }
+
+    /// Adds one scanned UR and returns the file contents once the message is complete.
+    pub fn receive(&mut self, payload: &[u8]) -> Result<Option<Vec<u8>>> {
+        let text = core::str::from_utf8(payload).map_err(|_| anyhow!("Invalid UR: not text"))?;
+        if !is_ur(payload) {
+            return Err(anyhow!("Not a UR"));
+        }
+        let mut path = text[3..].split('/');
+        let ur_type = path.next().unwrap_or_default();
+        if !ur_type.eq_ignore_ascii_case(UR_TYPE) {
+            return Err(anyhow!("Unsupported UR type: {}", ur_type));
+        }
+        let components: Vec<&str> = path.collect();
+        match components[..] {
+            [body] => {
+                let message = bytewords_decode(body)?;
+                let (data, _) = read_cbor_bytes(&message)?;
+                Ok(Some(data.to_vec()))
+            }
+            [sequence, body] => self.receive_part(sequence, body),
+            _ => Err(anyhow!("Invalid UR: unexpected path")),
+        }
+    }
+
+    fn receive_part(&mut self, sequence: &str, body: &str) -> Result<Option<Vec<u8>>> {
+        let cbor = bytewords_decode(body)?;
+        let invalid = || anyhow!("Invalid UR part");
+        if cbor.first() != Some(&0x85) {
+            return Err(invalid());
+        }
+        let (seq_num, rest) = read_cbor_head(0, &cbor[1..])?;
+        let (seq_len, rest) = read_cbor_head(0, rest)?;
+        let (message_len, rest) = read_cbor_head(0, rest)?;
+        let (checksum, rest) = read_cbor_head(0, rest)?;
+        let (data, _) = read_cbor_bytes(rest)?;
+        if format!("{}-{}", seq_num, seq_len) != sequence.to_ascii_lowercase() {
+            return Err(invalid());
+        }
+        let seq_num = u32::try_from(seq_num).map_err(|_| invalid())?;
+        let params: UrParams = (
+            usize::try_from(seq_len).map_err(|_| invalid())?,
+            usize::try_from(message_len).map_err(|_| invalid())?,
+            u32::try_from(checksum).map_err(|_| invalid())?,
+        );
+        if seq_num == 0 || params.0 == 0 || data.len() * params.0 < params.1 {
+            return Err(invalid());
+        }
+        if *self.params.get_or_insert(params) != params {
+            return Err(anyhow!("UR part belongs to a different message"));
+        }
+        if !self.seen.insert(seq_num) {
+            return Ok(None);
+        }
+
+        let (seq_len, message_len, checksum) = params;
+        self.add(choose_fragments(seq_num, seq_len, checksum), data.to_vec());
+        if self.simple.len() < seq_len {
+            return Ok(None);
+        }
+        let mut message: Vec<u8> = self.simple.values().flatten().copied().collect();
+        message.truncate(message_len);
+        if crc32(&message) != checksum {
+            return Err(anyhow!("UR message checksum mismatch"));
+        }
+        Ok(Some(read_cbor_bytes(&message)?.0.to_vec()))
+    }
+
+    /// Reduces a part by the fragments already known, and those known parts by it in turn.
+    fn add(&mut self, indexes: BTreeSet<usize>, data: Vec<u8>) {
+        let mut queue = alloc::vec![(indexes, data)];
+        while let Some((mut indexes, mut data)) = queue.pop() {
+            indexes.retain(|index| match self.simple.get(index) {
+                Some(fragment) => {
+                    xor_into(&mut data, fragment);
+                    false
+                }
+                None => true,
+            });
+            match indexes.len() {
+                0 => {}
+                1 => {
+                    let index = indexes.pop_first().unwrap_or_default();
+                    self.simple.insert(index, data);
+                    let (reducible, rest) = core::mem::take(&mut self.mixed)
+                        .into_iter()
+                        .partition(|(mixed, _)| mixed.contains(&index));
+                    self.mixed = rest;
+                    queue.extend::<Vec<_>>(reducible);
+                }
+                _ => {
+                    if !self.mixed.iter().any(|(mixed, _)| *mixed == indexes) {
+                        self.mixed.push((indexes, data));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_vectors() {
+        assert_eq!(crc32(b"Hello, world!"), 0xebe6_c6e6);
+        let encoded = bytewords_encode(&[0, 1, 2, 128, 255]);
+        assert_eq!(encoded, "aeadaolazmjendeoti");
+        assert_eq!(
+            bytewords_decode(&encoded.to_ascii_uppercase()).unwrap(),
+            [0, 1, 2, 128, 255]
+        );
+        assert!(bytewords_decode("aeadaolazmjendeota").is_err());
+
+        let mut rng = Xoshiro256::from_seed(b"Wolf");
+        let first: Vec<u64> = (0..10).map(|_| rng.next() % 100).collect();
+        assert_eq!(first, [42, 81, 85, 8, 82, 84, 76, 73, 70, 88]);
+
+        let degrees: Vec<usize> = (1..=10)
+            .map(|nonce| {
+                choose_degree(
+                    11,
+                    &mut Xoshiro256::from_seed(format!("Wolf-{}", nonce).as_bytes()),
+                )
+            })
+            .collect();
+        assert_eq!(degrees, [11, 3, 6, 5, 2, 1, 2, 11, 1, 3]);
+    }
+
+    #[test]
+    fn test_ur_roundtrip_with_lost_parts() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 31 % 256) as u8).collect();
+        let encoder = UrEncoder::new(&data, 100).unwrap();
+        assert!(encoder.fragment_len() <= 100);
+        let seq_len = encoder.seq_len() as u32;
+
+        // Every third simple part is lost; the mixed parts that follow fill the gaps
+        let mut decoder = UrDecoder::new();
+        let decoded = (1..=seq_len * 4)
+            .filter(|seq_num| *seq_num > seq_len || seq_num % 3 != 0)
+            .find_map(|seq_num| {
+                let part = encoder.part(seq_num);
+                assert!(part.starts_with("UR:BYTES/"));
+                decoder.receive(part.as_bytes()).unwrap()
+            });
+        assert_eq!(decoded, Some(data));
+
+        let single = UrEncoder::new(b"short", 100).unwrap();
+        assert_eq!(single.seq_len(), 1);
+        let part = single.part(1);
+        assert_eq!(part.matches('/').count(), 1);
+        assert_eq!(
+            UrDecoder::new().receive(part.as_bytes()).unwrap(),
+            Some(b"short".to_vec())
+        );
+        assert!(UrDecoder::new().receive(b"ur:crypto-psbt/aeadao").is_err());
+    }
+}
@@ -1,16 +1,43 @@
-use crate::chunk::{decompress, unpack_data, Chunk};
-use crate::qr::decode_qr_from_gray;
+use crate::chunk::{
+    compress_or_store, pack_data, Chunk, ChunkHeader, CompressionSetting, PayloadEncoding,
+    WireFormat, CHUNK_VERSION, HEADER_SIZE, MAX_PAYLOAD_SIZE,
+};
+use crate::core::describe_unfamiliar;
+use crate::qr::{
+    decode_all_qr_from_gray, generate_qr_image, is_blank_frame, parse_ec_level, qr_version_for,
+    EcLevel, FrameSignature, QrStyle,
+};
+#[cfg(not(feature = "decode"))]
+use crate::raster::GrayImage;
+use crate::receive::{to_ack_payload, PayloadReceiver, ReceiveStatus};
+use anyhow::anyhow;
+#[cfg(feature = "decode")]
 use image::GrayImage;
-use raptorq::{Decoder, EncodingPacket, ObjectTransmissionInformation};
-use std::collections::HashMap;
+use raptorq::Encoder;
+use std::collections::VecDeque;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 pub struct QrStreamDecoder {
-    chunks: HashMap<u32, Chunk>,
-    total_chunks: Option<u32>,
-    decoder_raptorq: Option<Decoder>,
-    raptorq_transfer_length: Option<u64>,
+    /// Routes the payloads scanned to the decoder of their wire format.
+    receiver: PayloadReceiver,
+    /// Frames are downscaled so their longer side fits this many pixels (0 = full size).
+    max_dimension: u32,
+    /// Part of each frame to scan, as (x, y, width, height).
+    roi: Option<(u32, u32, u32, u32)>,
+    /// Grayscale pixels kept between frames to avoid reallocating them every call.
+    gray_buffer: Vec<u8>,
+    skip_duplicate_frames: bool,
+    /// Summary of the last scanned frame, to recognise repeats of it.
+    last_frame: Option<FrameSignature>,
+    /// Message of the last `Error` result, kept until the next one replaces it.
+    last_error: Option<String>,
+    /// Description of the last QR code that matched no wire format.
+    unfamiliar: Option<String>,
+    /// Whether to queue useful payloads in `forwarded`.
+    forward_payloads: bool,
+    /// Payloads waiting for `next_payload`.
+    forwarded: VecDeque<Vec<u8>>,
 }
 
 #[wasm_bindgen]
@@ -19,6 +46,8 @@ pub struct ScanResult {
     pub status: ScanStatus,
     pub progress_current: u32,
     pub progress_total: u32,
+    /// Size of the file being received, or 0 while unknown (streams from older encoders).
+    pub original_size: u32,
     filename: String,
     file_data: Vec<u8>,
 }
@@ -43,35 +72,170 @@ impl ScanResult {
     }
 }
 
+impl Default for QrStreamDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[wasm_bindgen]
 impl QrStreamDecoder {
     #[wasm_bindgen(constructor)]
     pub fn new() -> QrStreamDecoder {
         console_error_panic_hook::set_once();
         QrStreamDecoder {
-            chunks: HashMap::new(),
-            total_chunks: None,
-            decoder_raptorq: None,
-            raptorq_transfer_length: None,
+            receiver: PayloadReceiver::new(),
+            max_dimension: 0,
+            roi: None,
+            gray_buffer: Vec::new(),
+            skip_duplicate_frames: true,
+            last_frame: None,
+            last_error: None,
+            unfamiliar: None,
+            forward_payloads: false,
+            forwarded: VecDeque::new(),
         }
     }
 
-    pub fn scan_frame(&mut self, data: &[u8], width: u32, height: u32) -> ScanResult {
-        if data.len() as u32 != width * height * 4 {
-            return self.make_result(ScanStatus::Error, "Invalid data length".to_string(), vec![]);
+    /// Whether to skip QR detection on frames nearly identical to the previous one,
+    /// reporting `Scanning` for them instead (default: true).
+    pub fn set_skip_duplicate_frames(&mut self, skip: bool) {
+        self.skip_duplicate_frames = skip;
+        self.last_frame = None;
+    }
+
+    /// Downscales frames so their longer side is at most `max_dimension` pixels before
+    /// scanning, which cuts per-frame time on phone cameras. 0 scans at full resolution.
+    pub fn set_max_dimension(&mut self, max_dimension: u32) {
+        self.max_dimension = max_dimension;
+    }
+
+    /// Scans only the `width` x `height` rectangle at (`x`, `y`), e.g. a viewfinder box.
+    /// A zero width or height scans the whole frame again.
+    pub fn set_roi(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        self.roi = (width > 0 && height > 0).then_some((x, y, width, height));
+    }
+
+    /// Frame interval announced by the stream's start marker, so the page can match its
+    /// capture rate to it, or 0 before a marker is seen.
+    pub fn sync_interval_ms(&self) -> u32 {
+        self.receiver.sync().map_or(0, |sync| sync.interval_ms)
+    }
+
+    /// Number of data frames per loop announced by the start marker, or 0 before one is seen.
+    pub fn sync_frames(&self) -> u32 {
+        self.receiver.sync().map_or(0, |sync| sync.frames)
+    }
+
+    /// Sorted indices of the chunks received for the stream being decoded, e.g. to fill in
+    /// a grid of chunks on the page.
+    pub fn received_indices(&self) -> Vec<u32> {
+        self.receiver.received()
+    }
+
+    /// Indices below the highest one received that have not been seen yet. Any chunks will do
+    /// to finish, so these only show which frames the camera missed.
+    pub fn missing_indices(&self) -> Vec<u32> {
+        let received = self.receiver.received();
+        let highest = received.last().copied().unwrap_or(0);
+        (0..highest)
+            .filter(|i| received.binary_search(i).is_err())
+            .collect()
+    }
+
+    /// Chunks received as a feedback document, for the page to send back to a carousel
+    /// started with `fountain encode --feedback`.
+    pub fn feedback(&self) -> String {
+        self.receiver.feedback().to_string()
+    }
+
+    /// The chunks received as a PNG of an ACK code, for the page to show to the sender's
+    /// camera, which then shows only the chunks still missing.
+    pub fn ack_png(&self, pixel_scale: u32) -> Result<Vec<u8>, JsError> {
+        let payload = to_ack_payload(&self.receiver.feedback(), PayloadEncoding::Base45);
+        qr_png(&payload, pixel_scale, EcLevel::M).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Number of chunks still needed before decoding can succeed, or 0 before the first chunk.
+    /// RaptorQ usually finishes with exactly the source packet count, occasionally one or two
+    /// more.
+    pub fn estimated_remaining(&self) -> u32 {
+        match self.receiver.header() {
+            Some(header) => header
+                .source_symbol_count()
+                .saturating_sub(self.receiver.progress().0),
+            None => 0,
         }
+    }
 
-        let mut gray_pixels = Vec::with_capacity((width * height) as usize);
-        for i in 0..(width * height) as usize {
-            let offset = i * 4;
-            let r = data[offset] as u32;
-            let g = data[offset + 1] as u32;
-            let b = data[offset + 2] as u32;
-            let luma = (r * 299 + g * 587 + b * 114) / 1000;
-            gray_pixels.push(luma as u8);
+    /// Message of the last scan that reported `Error`, or `undefined` if none has.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.clone()
+    }
+
+    /// Description of the last QR code seen that is neither a fountain chunk nor a UR part,
+    /// e.g. `text "https://..."`, so the page can tell the user the camera is on the wrong
+    /// code. `undefined` if there has been none.
+    pub fn unfamiliar_payload(&self) -> Option<String> {
+        self.unfamiliar.clone()
+    }
+
+    /// Queues the content of every QR code that adds to the transfer for
+    /// [`next_payload`](Self::next_payload), so the page can pass it on, e.g. to
+    /// `fountain serve`, which assembles the file on the computer (default: false).
+    pub fn set_forward_payloads(&mut self, forward: bool) {
+        self.forward_payloads = forward;
+        self.forwarded.clear();
+    }
+
+    /// Takes the oldest queued payload as a `Uint8Array`, or `undefined` when none is left.
+    pub fn next_payload(&mut self) -> Option<Vec<u8>> {
+        self.forwarded.pop_front()
+    }
+
+    pub fn scan_frame(&mut self, data: &[u8], width: u32, height: u32) -> ScanResult {
+        let result = self.scan_pixels(data, width, height, 4, |rgba| {
+            let (r, g, b) = (rgba[0] as u32, rgba[1] as u32, rgba[2] as u32);
+            ((r * 299 + g * 587 + b * 114) / 1000) as u8
+        });
+        self.note_error(result)
+    }
+
+    /// Like `scan_frame`, for grayscale frames with one luma byte per pixel, such as the
+    /// Y plane a `VideoFrame` hands over, which skips the RGBA conversion.
+    pub fn scan_frame_gray(&mut self, data: &[u8], width: u32, height: u32) -> ScanResult {
+        let result = self.scan_pixels(data, width, height, 1, |luma| luma[0]);
+        self.note_error(result)
+    }
+
+    /// Like `scan_frame`, for NV12 frames: the full-size Y plane followed by interleaved U
+    /// and V samples at half resolution. Only the Y plane is read.
+    pub fn scan_frame_nv12(&mut self, data: &[u8], width: u32, height: u32) -> ScanResult {
+        let luma_len = width as usize * height as usize;
+        let chroma_len = 2 * width.div_ceil(2) as usize * height.div_ceil(2) as usize;
+        let result = if data.len() == luma_len + chroma_len {
+            self.scan_pixels(&data[..luma_len], width, height, 1, |luma| luma[0])
+        } else {
+            self.make_result(ScanStatus::Error, "Invalid data length".to_string(), vec![])
+        };
+        self.note_error(result)
+    }
+
+    /// Scans a frame of `bytes_per_pixel` bytes per pixel, which `luma` turns into brightness.
+    fn scan_pixels(
+        &mut self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        bytes_per_pixel: u32,
+        luma: impl Fn(&[u8]) -> u8,
+    ) -> ScanResult {
+        if data.len() != width as usize * height as usize * bytes_per_pixel as usize {
+            return self.make_result(ScanStatus::Error, "Invalid data length".to_string(), vec![]);
         }
 
-        let mut gray_image = match GrayImage::from_raw(width, height, gray_pixels) {
+        let gray_frame = self.grayscale_frame(data, width, height, bytes_per_pixel, luma);
+        let mut gray_image = match gray_frame {
             Some(img) => img,
             None => {
                 return self.make_result(
@@ -82,86 +246,129 @@ impl QrStreamDecoder {
             }
         };
 
-        // Try normal decode
-        if let Some(result) = self.try_decode(&gray_image) {
-            return result;
+        if self.skip_duplicate_frames {
+            // The camera is still on the same code, which the previous frame already scanned
+            let signature = FrameSignature::of(&gray_image);
+            if self
+                .last_frame
+                .as_ref()
+                .is_some_and(|last| last.matches(&signature))
+            {
+                self.gray_buffer = gray_image.into_raw();
+                return self.current_status(ScanStatus::Scanning);
+            }
+            self.last_frame = Some(signature);
         }
-
-        // Try inverted decode (for dark mode / inverted QR codes)
-        for pixel in gray_image.iter_mut() {
-            *pixel = 255 - *pixel;
+        if is_blank_frame(&gray_image) {
+            // A calibration frame shown before the data, or the camera is still covered
+            self.gray_buffer = gray_image.into_raw();
+            return self.current_status(ScanStatus::Scanning);
         }
 
-        if let Some(result) = self.try_decode(&gray_image) {
-            return result;
+        // Try normal decode, then inverted (for dark mode / inverted QR codes)
+        let mut result = self.try_decode(&gray_image);
+        if result.is_none() {
+            for pixel in gray_image.iter_mut() {
+                *pixel = 255 - *pixel;
+            }
+            result = self.try_decode(&gray_image);
         }
 
-        self.current_status(ScanStatus::Scanning)
+        self.gray_buffer = gray_image.into_raw();
+        result.unwrap_or_else(|| self.current_status(ScanStatus::Scanning))
     }
 
-    fn try_decode(&mut self, img: &GrayImage) -> Option<ScanResult> {
-        if let Ok(qr_bytes) = decode_qr_from_gray(img) {
-            let qr_string = String::from_utf8_lossy(&qr_bytes);
-            if let Ok(chunk_bytes) = base45::decode(qr_string.trim()) {
-                if let Ok(chunk) = Chunk::from_bytes(&chunk_bytes) {
-                    return Some(self.process_chunk(chunk));
-                }
+    /// Converts the region of interest of a frame to grayscale, keeping every `step`-th pixel
+    /// in each direction so the result fits `max_dimension`.
+    fn grayscale_frame(
+        &mut self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        bytes_per_pixel: u32,
+        luma: impl Fn(&[u8]) -> u8,
+    ) -> Option<GrayImage> {
+        let (x0, y0, roi_width, roi_height) = match self.roi {
+            Some((x, y, w, h)) if x < width && y < height => {
+                (x, y, w.min(width - x), h.min(height - y))
             }
-        }
-        None
-    }
+            _ => (0, 0, width, height),
+        };
+        let step = match self.max_dimension {
+            0 => 1,
+            max => roi_width.max(roi_height).div_ceil(max).max(1),
+        };
+        let (out_width, out_height) = (roi_width.div_ceil(step), roi_height.div_ceil(step));
 
-    fn process_chunk(&mut self, chunk: Chunk) -> ScanResult {
-        if self.decoder_raptorq.is_none() {
-            let transfer_len = chunk.header.total as u64;
-            let packet_size = chunk.header.packet_size;
-            self.raptorq_transfer_length = Some(transfer_len);
+        let mut gray_pixels = std::mem::take(&mut self.gray_buffer);
+        gray_pixels.clear();
+        gray_pixels.reserve((out_width * out_height) as usize);
+        for y in (y0..y0 + roi_height).step_by(step as usize) {
+            for x in (x0..x0 + roi_width).step_by(step as usize) {
+                let offset = (y as usize * width as usize + x as usize) * bytes_per_pixel as usize;
+                gray_pixels.push(luma(&data[offset..offset + bytes_per_pixel as usize]));
+            }
+        }
 
-            let config =
-                ObjectTransmissionInformation::with_defaults(transfer_len, packet_size);
-            self.decoder_raptorq = Some(Decoder::new(config));
+        GrayImage::from_raw(out_width, out_height, gray_pixels)
+    }
 
-            // Estimate total packets needed (K) for progress bar
-            // Using ceiling division
-            let source_packets = (transfer_len as f64 / packet_size as f64).ceil() as u32;
-            self.total_chunks = Some(source_packets);
-        }
+    /// Consumes the text of a QR code the page has already decoded itself, e.g. with
+    /// `BarcodeDetector` or jsQR, so only chunk reassembly and RaptorQ decoding run here.
+    /// UR parts are accepted too. Other text is ignored and reported as `Scanning`, with
+    /// [`unfamiliar_payload`](Self::unfamiliar_payload) describing it.
+    pub fn ingest_payload(&mut self, text: &str) -> ScanResult {
+        let result = self.scan_payload(text.as_bytes());
+        self.note_error(result)
+    }
 
-        if !self.chunks.contains_key(&chunk.header.index) {
-            self.chunks.insert(chunk.header.index, chunk.clone());
-
-            if let Some(dec) = &mut self.decoder_raptorq {
-                let packet = EncodingPacket::deserialize(&chunk.data);
-                if let Some(result_data) = dec.decode(packet) {
-                    // Success!
-                    let mut final_data = result_data;
-                    if let Some(len) = self.raptorq_transfer_length {
-                        final_data.truncate(len as usize);
-                    }
-
-                    match self.finalize_raptorq(final_data) {
-                        Ok((filename, data)) => {
-                            return self.make_result(ScanStatus::Complete, filename, data)
-                        }
-                        Err(_) => {
-                            return self.make_result(
-                                ScanStatus::Error,
-                                "Decompress failed".to_string(),
-                                vec![],
-                            )
-                        }
-                    }
+    fn try_decode(&mut self, img: &GrayImage) -> Option<ScanResult> {
+        // A frame may show several QR codes at once; feed all of them
+        let mut result: Option<ScanResult> = None;
+        for qr_bytes in decode_all_qr_from_gray(img) {
+            let scan = self.scan_payload(&qr_bytes);
+            match scan.status {
+                ScanStatus::Complete | ScanStatus::Error => return Some(scan),
+                ScanStatus::ChunkFound => result = Some(scan),
+                ScanStatus::Scanning => {
+                    result.get_or_insert(scan);
                 }
             }
-            return self.current_status(ScanStatus::ChunkFound);
         }
+        result
+    }
 
-        self.current_status(ScanStatus::Scanning)
+    fn scan_payload(&mut self, qr_bytes: &[u8]) -> ScanResult {
+        if WireFormat::detect(qr_bytes).is_none() {
+            self.unfamiliar = Some(describe_unfamiliar(qr_bytes));
+        }
+        let result = match self.receiver.feed(qr_bytes) {
+            Ok(ReceiveStatus::Scanning) => self.current_status(ScanStatus::Scanning),
+            Ok(ReceiveStatus::ChunkFound) => self.current_status(ScanStatus::ChunkFound),
+            Ok(ReceiveStatus::Complete) => match self.receiver.take_result() {
+                Some((filename, data)) => self.make_result(ScanStatus::Complete, filename, data),
+                None => self.make_result(
+                    ScanStatus::Error,
+                    "Decoder reported no file".to_string(),
+                    vec![],
+                ),
+            },
+            Err(e) => self.make_result(ScanStatus::Error, e.to_string(), vec![]),
+        };
+        if self.forward_payloads
+            && matches!(result.status, ScanStatus::ChunkFound | ScanStatus::Complete)
+        {
+            self.forwarded.push_back(qr_bytes.to_vec());
+        }
+        result
     }
 
-    fn finalize_raptorq(&self, data: Vec<u8>) -> anyhow::Result<(String, Vec<u8>)> {
-        let packed = decompress(&data)?;
-        unpack_data(&packed)
+    /// Remembers the message of an `Error` result for [`last_error`](Self::last_error).
+    fn note_error(&mut self, result: ScanResult) -> ScanResult {
+        if result.status == ScanStatus::Error {
+            self.last_error = Some(result.filename.clone());
+        }
+        result
     }
 
     fn current_status(&self, status: ScanStatus) -> ScanResult {
@@ -169,14 +376,165 @@ impl QrStreamDecoder {
     }
 
     fn make_result(&self, status: ScanStatus, filename: String, file_data: Vec<u8>) -> ScanResult {
-        let total = self.total_chunks.unwrap_or(0);
-        let current = self.chunks.len() as u32;
+        // A UR stream reports recovered fragments until native chunks arrive
+        let (progress_current, progress_total) = self.receiver.progress();
         ScanResult {
             status,
-            progress_current: current,
-            progress_total: total,
+            progress_current,
+            progress_total,
+            original_size: self
+                .receiver
+                .header()
+                .and_then(|header| header.original_size)
+                .unwrap_or(0),
             filename,
             file_data,
         }
     }
 }
+
+/// Sending side for the browser: packs, compresses and RaptorQ-encodes a file into
+/// Base45 QR payloads that `QrStreamDecoder` (or `fountain-decode`) can read back.
+#[wasm_bindgen]
+pub struct QrStreamEncoder {
+    chunks: Vec<Chunk>,
+    ec_level: EcLevel,
+}
+
+#[wasm_bindgen]
+impl QrStreamEncoder {
+    /// `compression` is `none` or `zlib`, optionally with a level such as `zlib:6` (zstd and
+    /// brotli are only available in the native encoder)
+    /// and `ec_level` is one of `L`, `M`, `Q`, `H`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        file_data: &[u8],
+        filename: &str,
+        chunk_size: usize,
+        redundancy_factor: f64,
+        compression: &str,
+        ec_level: &str,
+    ) -> Result<QrStreamEncoder, JsError> {
+        console_error_panic_hook::set_once();
+        Self::prepare(
+            file_data,
+            filename,
+            chunk_size,
+            redundancy_factor,
+            compression,
+            ec_level,
+        )
+        .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    pub fn chunk_count(&self) -> u32 {
+        self.chunks.len() as u32
+    }
+
+    /// Base45 text to put in the QR code for chunk `index`, for pages rendering QR codes themselves.
+    pub fn get_payload(&self, index: u32) -> Option<String> {
+        let chunk = self.chunks.get(index as usize)?;
+        let payload = chunk.to_qr_payload(PayloadEncoding::Base45).ok()?;
+        String::from_utf8(payload).ok()
+    }
+
+    /// Renders chunk `index` as a PNG image.
+    pub fn render_png(&self, index: u32, pixel_scale: u32) -> Result<Vec<u8>, JsError> {
+        let chunk = self
+            .chunks
+            .get(index as usize)
+            .ok_or_else(|| JsError::new("Chunk index out of range"))?;
+        self.render_chunk_png(chunk, pixel_scale)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    fn prepare(
+        file_data: &[u8],
+        filename: &str,
+        chunk_size: usize,
+        redundancy_factor: f64,
+        compression: &str,
+        ec_level: &str,
+    ) -> anyhow::Result<QrStreamEncoder> {
+        if redundancy_factor.is_nan() || redundancy_factor < 1.0 {
+            return Err(anyhow!(
+                "Invalid redundancy factor: {}. It must be at least 1.0.",
+                redundancy_factor
+            ));
+        }
+        let compression: CompressionSetting = compression.parse()?;
+        let ec_level = parse_ec_level(ec_level)?;
+
+        // Ensure packet size is even for RaptorQ
+        let chunk_size = chunk_size.min(MAX_PAYLOAD_SIZE);
+        let packet_size = chunk_size.saturating_sub(HEADER_SIZE) as u16;
+        let packet_size = packet_size - (packet_size % 2);
+        if packet_size < 4 {
+            return Err(anyhow!("Chunk size {} is too small", chunk_size));
+        }
+
+        let original_size = u32::try_from(file_data.len())
+            .map_err(|_| anyhow!("File of {} bytes is too large to encode", file_data.len()))?;
+        let compressed = compress_or_store(&pack_data(file_data, filename), compression)?;
+        let transfer_length = compressed.len() as u32;
+        let encoder = Encoder::with_defaults(&compressed, packet_size);
+
+        let source_packets = compressed.len().div_ceil(packet_size as usize) as u32;
+        let total_packets = (source_packets as f64 * redundancy_factor).ceil() as u32;
+        let total_packets = total_packets.max(source_packets + 2);
+
+        // get_encoded_packets() takes the number of repair packets per source block
+        let num_blocks = encoder.get_block_encoders().len() as u32;
+        let repair_per_block = (total_packets - source_packets).div_ceil(num_blocks);
+
+        let chunks: Vec<Chunk> = encoder
+            .get_encoded_packets(repair_per_block)
+            .iter()
+            .enumerate()
+            .map(|(i, packet)| Chunk {
+                header: ChunkHeader {
+                    version: CHUNK_VERSION,
+                    total: transfer_length,
+                    index: i as u32,
+                    packet_size,
+                    source_symbols: Some(source_packets),
+                    original_size: Some(original_size),
+                    volume: None,
+                },
+                data: packet.serialize(),
+            })
+            .collect();
+
+        if let Some(first) = chunks.first() {
+            let payload = first.to_qr_payload(PayloadEncoding::Base45)?;
+            qr_version_for(&payload, ec_level).map_err(|_| {
+                anyhow!(
+                    "Chunk size {} does not fit in a QR code at error correction level {:?}",
+                    chunk_size,
+                    ec_level
+                )
+            })?;
+        }
+
+        Ok(QrStreamEncoder { chunks, ec_level })
+    }
+
+    fn render_chunk_png(&self, chunk: &Chunk, pixel_scale: u32) -> anyhow::Result<Vec<u8>> {
+        let payload = chunk.to_qr_payload(PayloadEncoding::Base45)?;
+        qr_png(&payload, pixel_scale, self.ec_level)
+    }
+}
+
+/// Renders `payload` as a QR code in a PNG image.
+fn qr_png(payload: &[u8], pixel_scale: u32, ec_level: EcLevel) -> anyhow::Result<Vec<u8>> {
+    let (image, _) = generate_qr_image(payload, None, pixel_scale, ec_level, &QrStyle::default())?;
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+    Ok(png)
+}
@@ -17,119 +17,1900 @@ fn test_encode_decode_roundtrip() {
     fs::write(&source_file_path, original_content).expect("Failed to write source file");
 
     println!("Encoding...");
-    let encode_result = fountain::encode_file_to_images(&source_file_path, &qr_output_dir, None, 4)
+    let encode_result = fountain::encode_file_to_images(
+        &source_file_path,
+        &qr_output_dir,
+        None,
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        None,
+    )
+    .expect("Encoding failed");
+
+    assert!(encode_result.num_chunks > 0);
+
+    let entries = fs::read_dir(&qr_output_dir).expect("Failed to read qr output dir");
+    let count = entries.count();
+    assert_eq!(count, encode_result.num_chunks);
+
+    println!("Decoding...");
+    let decode_result = fountain::decode_from_images(&qr_output_dir, Some(&decoded_output_path))
+        .expect("Decoding failed");
+
+    // In RaptorQ, decode_result.num_chunks is the number of chunks used for decoding
+    assert!(decode_result.num_chunks > 0);
+    assert_eq!(decode_result.sha256, encode_result.sha256);
+    assert_eq!(
+        encode_result.sha256,
+        fountain::sha256_hex(original_content.as_bytes())
+    );
+
+    let decoded_content =
+        fs::read_to_string(&decoded_output_path).expect("Failed to read decoded file");
+
+    assert_eq!(original_content, decoded_content);
+
+    // Blocks are spooled next to the output while decoding, and cleaned up afterwards
+    let leftovers = fs::read_dir(temp_dir.path())
+        .expect("Failed to read temp dir")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".part"))
+        .count();
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+#[cfg(all(unix, feature = "encode", feature = "decode"))]
+fn test_decode_restores_file_metadata() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+    let decoded_output_path = temp_dir.path().join("restored.sh");
+    fs::create_dir(&qr_output_dir).expect("Failed to create qr output dir");
+
+    let source_file_path = temp_dir.path().join("script.sh");
+    fs::write(&source_file_path, "#!/bin/sh\necho restored\n").expect("Failed to write source");
+    fs::set_permissions(&source_file_path, fs::Permissions::from_mode(0o750))
+        .expect("Failed to set permissions");
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    fs::File::options()
+        .write(true)
+        .open(&source_file_path)
+        .and_then(|file| file.set_modified(mtime))
+        .expect("Failed to set mtime");
+
+    fountain::encode_file_to_images(
+        &source_file_path,
+        &qr_output_dir,
+        None,
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        None,
+    )
+    .expect("Encoding failed");
+
+    let decode_result = fountain::decode_from_images(&qr_output_dir, Some(&decoded_output_path))
+        .expect("Decoding failed");
+    assert_eq!(decode_result.metadata.mode, Some(0o750));
+    assert_eq!(decode_result.metadata.mtime, Some(1_600_000_000));
+
+    let restored = fs::metadata(&decoded_output_path).expect("Failed to stat decoded file");
+    assert_eq!(restored.permissions().mode() & 0o7777, 0o750);
+    assert_eq!(restored.modified().expect("No mtime"), mtime);
+}
+
+#[test]
+#[cfg(all(unix, feature = "encode", feature = "decode"))]
+fn test_decode_never_restores_setuid() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+    let decoded_output_path = temp_dir.path().join("restored.bin");
+
+    // A crafted stream may carry any mode
+    let metadata = fountain::FileMetadata {
+        mode: Some(0o6755 | 0o1000),
+        ..Default::default()
+    };
+    fountain::encode_bytes_to_images_with_options(
+        b"#!/bin/sh\necho hi\n",
+        "setuid.sh",
+        &metadata,
+        &qr_output_dir,
+        &fountain::EncodeOptions::new(),
+    )
+    .expect("Encoding failed");
+
+    fountain::decode_from_images(&qr_output_dir, Some(&decoded_output_path))
+        .expect("Decoding failed");
+    let restored = fs::metadata(&decoded_output_path).expect("Failed to stat decoded file");
+    assert_eq!(restored.permissions().mode() & 0o7777, 0o755);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_encode_decode_binary_payload_roundtrip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_binary");
+    let decoded_output_path = temp_dir.path().join("decoded.bin");
+
+    let source_file_path = temp_dir.path().join("source.bin");
+    let original: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    fs::write(&source_file_path, &original).expect("Failed to write source file");
+
+    fountain::encode_file_to_images(
+        &source_file_path,
+        &qr_output_dir,
+        Some(400),
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Binary,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        None,
+    )
+    .expect("Encoding failed");
+
+    fountain::decode_from_images(&qr_output_dir, Some(&decoded_output_path))
+        .expect("Decoding failed");
+
+    let decoded = fs::read(&decoded_output_path).expect("Failed to read decoded file");
+    assert_eq!(original, decoded);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_from_memory() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_memory");
+
+    let source_file_path = temp_dir.path().join("memory.txt");
+    let original_content = "In-memory decoding test. ".repeat(40);
+    fs::write(&source_file_path, &original_content).expect("Failed to write source file");
+
+    let encode_result = fountain::encode_file_to_images(
+        &source_file_path,
+        &qr_output_dir,
+        Some(300),
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        None,
+    )
+    .expect("Encoding failed");
+
+    let buffers: Vec<Vec<u8>> = encode_result
+        .output_files
+        .iter()
+        .map(|name| fs::read(qr_output_dir.join(name)).expect("Failed to read QR image"))
+        .collect();
+
+    let (filename, data) =
+        fountain::decode_from_image_buffers(&buffers).expect("Decoding buffers failed");
+    assert_eq!(filename, "memory.txt");
+    assert_eq!(data, original_content.as_bytes());
+
+    let images: Vec<image::DynamicImage> = buffers
+        .iter()
+        .map(|buffer| image::load_from_memory(buffer).expect("Failed to load QR image"))
+        .collect();
+
+    let (filename, data) =
+        fountain::decode_from_dynamic_images(&images).expect("Decoding images failed");
+    assert_eq!(filename, "memory.txt");
+    assert_eq!(data, original_content.as_bytes());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_to_vec_does_not_write_files() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_vec");
+    let gif_path = temp_dir.path().join("vec.gif");
+
+    let source_file_path = temp_dir.path().join("vec.txt");
+    let original_content = "Decode to vec test. ".repeat(40);
+    fs::write(&source_file_path, &original_content).expect("Failed to write source file");
+
+    fountain::encode_file_to_images(
+        &source_file_path,
+        &qr_output_dir,
+        Some(300),
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        None,
+    )
+    .expect("Encoding images failed");
+    fountain::encode_file_to_gif(
+        &source_file_path,
+        &gif_path,
+        Some(300),
+        100,
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+    )
+    .expect("Encoding GIF failed");
+    fs::remove_file(&source_file_path).expect("Failed to remove source file");
+
+    let (filename, data) =
+        fountain::decode_from_images_to_vec(&qr_output_dir).expect("Decoding images failed");
+    assert_eq!(filename, "vec.txt");
+    assert_eq!(data, original_content.as_bytes());
+
+    let (filename, data) =
+        fountain::decode_from_gif_to_vec(&gif_path).expect("Decoding GIF failed");
+    assert_eq!(filename, "vec.txt");
+    assert_eq!(data, original_content.as_bytes());
+
+    assert!(!source_file_path.exists());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_encode_decode_tiled_images_roundtrip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_tiled");
+    let decoded_output_path = temp_dir.path().join("decoded_tiled.bin");
+
+    let source_file_path = temp_dir.path().join("tiled.bin");
+    let original: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    fs::write(&source_file_path, &original).expect("Failed to write source file");
+
+    let layout = fountain::GridLayout {
+        columns: 2,
+        rows: 2,
+    };
+    let encode_result = fountain::encode_file_to_tiled_images(
+        &source_file_path,
+        &qr_output_dir,
+        Some(300),
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        layout,
+        None,
+    )
+    .expect("Encoding failed");
+
+    assert_eq!(
+        encode_result.output_files.len(),
+        encode_result.num_chunks.div_ceil(layout.codes_per_image())
+    );
+
+    let decode_result = fountain::decode_from_images(&qr_output_dir, Some(&decoded_output_path))
+        .expect("Decoding failed");
+
+    // Every page holds up to four codes, so more chunks than pages were read
+    assert!(decode_result.num_chunks > encode_result.output_files.len());
+
+    let decoded = fs::read(&decoded_output_path).expect("Failed to read decoded file");
+    assert_eq!(original, decoded);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_encode_bytes_with_embedded_name() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let gif_path = temp_dir.path().join("piped.gif");
+
+    let original: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+
+    fountain::encode_bytes_to_gif(
+        &original,
+        "from_stdin.bin",
+        &fountain::FileMetadata::default(),
+        &gif_path,
+        Some(400),
+        100,
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+    )
+    .expect("Encoding failed");
+
+    let (filename, data) = fountain::decode_from_gif_to_vec(&gif_path).expect("Decoding failed");
+    assert_eq!(filename, "from_stdin.bin");
+    assert_eq!(data, original);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_encode_with_name_template() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_named");
+
+    let original: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+    let template: fountain::NameTemplate = "{stem}-{index:05}-of-{total}.png".parse().unwrap();
+
+    let result = fountain::encode_bytes_to_images(
+        &original,
+        "named.bin",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        Some(400),
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        Some(&template),
+    )
+    .expect("Encoding failed");
+
+    for (i, name) in result.output_files.iter().enumerate() {
+        assert_eq!(
+            name,
+            &format!("named-{:05}-of-{}.png", i + 1, result.num_chunks)
+        );
+        assert!(qr_output_dir.join(name).exists());
+    }
+
+    let (filename, data) =
+        fountain::decode_from_images_to_vec(&qr_output_dir).expect("Decoding failed");
+    assert_eq!(filename, "named.bin");
+    assert_eq!(data, original);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_encode_keeps_existing_images() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_taken");
+    fs::create_dir_all(&qr_output_dir).unwrap();
+    fs::write(qr_output_dir.join("part-002.png"), "keep me").unwrap();
+
+    let original: Vec<u8> = (0..2000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(400)
+        .name_template("part-{index:03}.png".parse().unwrap());
+    let encode = |options: &fountain::EncodeOptions| {
+        fountain::encode_bytes_to_images_with_options(
+            &original,
+            "taken.bin",
+            &fountain::FileMetadata::default(),
+            &qr_output_dir,
+            options,
+        )
+    };
+
+    let Err(error) = encode(&options) else {
+        panic!("Encoding over an existing file succeeded");
+    };
+    assert!(error.to_string().contains("part-002.png"), "{}", error);
+    // Nothing was written, not even the images whose names were free
+    assert_eq!(fs::read_dir(&qr_output_dir).unwrap().count(), 1);
+    assert_eq!(
+        fs::read(qr_output_dir.join("part-002.png")).unwrap(),
+        b"keep me"
+    );
+
+    let result = encode(&options.clone().overwrite(true)).expect("Encoding failed");
+    assert!(result.output_files.contains(&"part-002.png".to_string()));
+    let (_, data) = fountain::decode_from_images_to_vec(&qr_output_dir).expect("Decoding failed");
+    assert_eq!(data, original);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_verify_does_not_write_files() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_verify");
+
+    let original: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+
+    let result = fountain::encode_bytes_to_images(
+        &original,
+        "verify.bin",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        Some(400),
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        None,
+    )
+    .expect("Encoding failed");
+
+    let report = fountain::verify_images(&qr_output_dir).expect("Verification failed");
+    assert_eq!(report.original_filename, "verify.bin");
+    assert_eq!(report.original_size, original.len());
+    assert!(!temp_dir.path().join("verify.bin").exists());
+
+    // Keep too few images to recover the data
+    for name in &result.output_files[2..] {
+        fs::remove_file(qr_output_dir.join(name)).expect("Failed to remove image");
+    }
+    assert!(fountain::verify_images(&qr_output_dir).is_err());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_regenerate_missing_chunks() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_gaps");
+
+    let original: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+    let encode = |indices: Option<&[u32]>| match indices {
+        Some(indices) => fountain::encode_bytes_to_selected_images(
+            &original,
+            "gaps.bin",
+            &fountain::FileMetadata::default(),
+            &qr_output_dir,
+            Some(400),
+            4,
+            fountain::EcLevel::M,
+            &fountain::QrStyle::default(),
+            fountain::PayloadEncoding::Base45,
+            fountain::CompressionAlgorithm::Zlib,
+            fountain::DEFAULT_REDUNDANCY_FACTOR,
+            indices,
+            None,
+        ),
+        None => fountain::encode_bytes_to_images(
+            &original,
+            "gaps.bin",
+            &fountain::FileMetadata::default(),
+            &qr_output_dir,
+            Some(400),
+            4,
+            fountain::EcLevel::M,
+            &fountain::QrStyle::default(),
+            fountain::PayloadEncoding::Base45,
+            fountain::CompressionAlgorithm::Zlib,
+            fountain::DEFAULT_REDUNDANCY_FACTOR,
+            None,
+        ),
+    };
+
+    let result = encode(None).expect("Encoding failed");
+    let removed = &result.output_files[1..result.num_chunks - 1];
+    for name in removed {
+        fs::remove_file(qr_output_dir.join(name)).expect("Failed to remove image");
+    }
+
+    let err = fountain::decode_from_images_to_vec(&qr_output_dir)
+        .expect_err("Decoding should fail with chunks missing");
+    let expected: Vec<String> = (1..result.num_chunks - 1).map(|i| i.to_string()).collect();
+    assert!(
+        err.to_string()
+            .contains(&format!("Missing chunk indices: {}", expected.join(","))),
+        "unexpected error: {}",
+        err
+    );
+
+    let indices: Vec<u32> = (1..result.num_chunks as u32 - 1).collect();
+    let resent = encode(Some(&indices)).expect("Regenerating failed");
+    assert_eq!(resent.output_files, removed);
+
+    let (filename, data) =
+        fountain::decode_from_images_to_vec(&qr_output_dir).expect("Decoding failed");
+    assert_eq!(filename, "gaps.bin");
+    assert_eq!(data, original);
+
+    assert!(encode(Some(&[result.num_chunks as u32])).is_err());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_survives_misread_first_header() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_misread");
+
+    let original: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+
+    let result = fountain::encode_bytes_to_images(
+        &original,
+        "misread.bin",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        Some(400),
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        None,
+    )
+    .expect("Encoding failed");
+
+    // Re-render the first chunk with a wrong transfer length and show it to the decoder first
+    let first = image::open(qr_output_dir.join(&result.output_files[0])).unwrap();
+    let payload = fountain::qr::decode_qr_from_dynamic_image(&first).unwrap();
+    let mut chunk = fountain::Chunk::from_qr_payload(&payload).unwrap();
+    chunk.header.total += 1000;
+    chunk.header.index = 999;
+    let bad_payload = chunk
+        .to_qr_payload(fountain::PayloadEncoding::Base45)
+        .unwrap();
+    let (bad_image, _) = fountain::qr::generate_qr_image(
+        &bad_payload,
+        None,
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+    )
+    .unwrap();
+    bad_image.save(qr_output_dir.join("0_misread.png")).unwrap();
+
+    let mut images = vec![image::DynamicImage::ImageRgb8(bad_image)];
+    for name in &result.output_files {
+        images.push(image::open(qr_output_dir.join(name)).unwrap());
+    }
+    let (filename, data) = fountain::decode_from_dynamic_images(&images).expect("Decoding failed");
+    assert_eq!(filename, "misread.bin");
+    assert_eq!(data, original);
+
+    let info = fountain::inspect_images(&qr_output_dir).expect("Inspecting failed");
+    assert_eq!(info.foreign_chunks, 1);
+    assert_eq!(info.present_indices.len(), result.num_chunks);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_inspect_reports_missing_chunks() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_info");
+
+    let original: Vec<u8> = (0..3000).map(|_| rand::random::<u8>()).collect();
+
+    let result = fountain::encode_bytes_to_images(
+        &original,
+        "info.bin",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        Some(400),
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        None,
+    )
+    .expect("Encoding failed");
+
+    for name in &result.output_files[1..3] {
+        fs::remove_file(qr_output_dir.join(name)).expect("Failed to remove image");
+    }
+
+    let info = fountain::inspect_images(&qr_output_dir).expect("Inspecting failed");
+    assert_eq!(info.format_version, fountain::CHUNK_VERSION);
+    assert_eq!(info.original_size, Some(original.len() as u32));
+    assert_eq!(info.present_indices.len(), result.num_chunks - 2);
+    assert_eq!(info.missing_indices, vec![1, 2]);
+    assert!(info.present_indices.len() as u32 >= info.source_packets);
+    assert_eq!(
+        info.original,
+        Some(("info.bin".to_string(), original.len()))
+    );
+
+    // Nothing is written while inspecting
+    assert_eq!(
+        fs::read_dir(&qr_output_dir).unwrap().count(),
+        result.num_chunks - 2
+    );
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_encode_decode_with_options() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+    let decoded_output_path = temp_dir.path().join("decoded.bin");
+
+    let original: Vec<u8> = (0..3000).map(|i| (i * 7 % 256) as u8).collect();
+    let metadata = fountain::FileMetadata {
+        mode: Some(0o700),
+        ..Default::default()
+    };
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(500)
+        .ec_level(fountain::EcLevel::Q)
+        .payload_encoding(fountain::PayloadEncoding::Binary)
+        .compression(fountain::CompressionAlgorithm::None)
+        .redundancy(2.0)
+        .name_template("part-{index:03}.png".parse().unwrap());
+
+    let result = fountain::encode_bytes_to_images_with_options(
+        &original,
+        "options.bin",
+        &metadata,
+        &qr_output_dir,
+        &options,
+    )
+    .expect("Encoding failed");
+    assert!(result.effective_size <= 500);
+    assert!(qr_output_dir.join("part-001.png").exists());
+
+    let decode_options = fountain::DecodeOptions::new().restore_metadata(false);
+    let decode_result = fountain::decode_from_images_with_options(
+        &qr_output_dir,
+        Some(&decoded_output_path),
+        &decode_options,
+    )
+    .expect("Decoding failed");
+    assert_eq!(decode_result.metadata.mode, Some(0o700));
+    assert_eq!(fs::read(&decoded_output_path).unwrap(), original);
+
+    // The mode was carried but, as requested, not applied
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&decoded_output_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_ne!(mode & 0o7777, 0o700);
+    }
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_delta_transfer() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+    let base_path = temp_dir.path().join("v1.txt");
+    let decoded_output_path = temp_dir.path().join("v2.txt");
+
+    let base: Vec<u8> = (0..20_000u32)
+        .flat_map(|i| format!("line {}\n", i).into_bytes())
+        .collect();
+    let mut updated = base.clone();
+    updated.splice(50_000..50_010, b"changed".iter().copied());
+    fs::write(&base_path, &base).unwrap();
+
+    let delta = fountain::delta::diff(&base, &updated);
+    assert!(delta.len() < 200);
+    let metadata = fountain::FileMetadata {
+        base_sha256: Some(fountain::chunk::sha256_digest(&base)),
+        ..Default::default()
+    };
+    fountain::encode_bytes_to_images_with_options(
+        &delta,
+        "v2.txt",
+        &metadata,
+        &qr_output_dir,
+        &fountain::EncodeOptions::new(),
+    )
+    .expect("Encoding failed");
+
+    // Without the base the file cannot be rebuilt
+    assert!(fountain::decode_from_images_with_options(
+        &qr_output_dir,
+        Some(&decoded_output_path),
+        &fountain::DecodeOptions::new(),
+    )
+    .is_err());
+    assert!(fountain::decode_from_images_to_vec(&qr_output_dir).is_err());
+
+    let result = fountain::decode_from_images_with_options(
+        &qr_output_dir,
+        Some(&decoded_output_path),
+        &fountain::DecodeOptions::new().base(&base_path),
+    )
+    .expect("Decoding failed");
+    assert_eq!(fs::read(&decoded_output_path).unwrap(), updated);
+    assert_eq!(result.sha256, fountain::sha256_hex(&updated));
+
+    // Nor from another base
+    fs::write(&base_path, &updated).unwrap();
+    assert!(fountain::decode_from_images_with_options(
+        &qr_output_dir,
+        Some(&decoded_output_path),
+        &fountain::DecodeOptions::new().base(&base_path),
+    )
+    .is_err());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_single_code_roundtrip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+    let options = fountain::EncodeOptions::new();
+
+    let result = fountain::encode_bytes_to_single_image_with_options(
+        b"JBSWY3DPEHPK3PXP",
+        "seed.txt",
+        &qr_output_dir,
+        &options,
+    )
+    .expect("Encoding failed");
+    assert_eq!(result.num_chunks, 1);
+
+    let (filename, data) =
+        fountain::decode_from_images_to_vec(&qr_output_dir).expect("Decoding failed");
+    assert_eq!(filename, "seed.txt");
+    assert_eq!(data, b"JBSWY3DPEHPK3PXP");
+
+    // Files needing more than one code are refused rather than truncated
+    let mut state = 12345u32;
+    let large: Vec<u8> = (0..5000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect();
+    assert!(fountain::encode_bytes_to_single_image_with_options(
+        &large,
+        "large.bin",
+        &qr_output_dir,
+        &options,
+    )
+    .is_err());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_raw_text_roundtrip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+
+    let text = "https://example.com/wifi?ssid=home";
+    let result = fountain::encode_text_to_image_with_options(
+        text,
+        "link",
+        &qr_output_dir,
+        &fountain::EncodeOptions::new(),
+    )
+    .expect("Encoding failed");
+    assert_eq!(result.num_chunks, 1);
+
+    // The code holds exactly the text, so it reads back as-is and is not a fountain stream
+    let contents = fountain::read_raw_images(&qr_output_dir).expect("Reading failed");
+    assert_eq!(contents, vec![text.as_bytes().to_vec()]);
+    let error = fountain::decode_from_images_to_vec(&qr_output_dir).unwrap_err();
+    assert!(
+        error.to_string().contains("https://example.com"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_pinned_qr_version() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+
+    let original: Vec<u8> = (0..2000).map(|i| (i * 13 % 256) as u8).collect();
+    let options = fountain::EncodeOptions::new()
+        .pixel_scale(2)
+        .qr_version(fountain::qr::Version::Normal(10));
+    let result = fountain::encode_bytes_to_images_with_options(
+        &original,
+        "pinned.bin",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        &options,
+    )
+    .expect("Encoding failed");
+    assert!(result.effective_size < fountain::MAX_PAYLOAD_SIZE);
+
+    // Version 10 is 57 modules wide, plus the default four-module margin on each side
+    for name in &result.output_files {
+        let img = image::open(qr_output_dir.join(name)).unwrap();
+        assert_eq!((img.width(), img.height()), (65 * 2, 65 * 2));
+    }
+    let qr = result.qr.as_ref().expect("Images report their QR sizing");
+    assert_eq!(qr.version, fountain::qr::Version::Normal(10));
+    assert_eq!(qr.modules(), 57);
+    assert_eq!((qr.pixel_scale, qr.image_px), (2, (65 * 2, 65 * 2)));
+    assert_eq!(qr.chunk_bytes.len(), result.num_chunks);
+    assert!(qr
+        .chunk_bytes
+        .iter()
+        .all(|&bytes| bytes > result.effective_size));
+
+    let (_, data) = fountain::decode_from_images_to_vec(&qr_output_dir).expect("Decoding failed");
+    assert_eq!(data, original);
+
+    assert!(fountain::qr::parse_qr_version("41").is_err());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_captions_keep_codes_readable() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+    let gif_path = temp_dir.path().join("captioned.gif");
+
+    let original: Vec<u8> = (0..2000).map(|i| (i * 29 % 256) as u8).collect();
+    let options = fountain::EncodeOptions::new()
+        .qr_version(fountain::qr::Version::Normal(10))
+        .captions(true)
+        .sync_frames(true);
+    let result = fountain::encode_bytes_to_images_with_options(
+        &original,
+        "captioned.bin",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        &options,
+    )
+    .expect("Encoding failed");
+
+    // Two lines of 2-pixel text, 8 font pixels each, plus a gap above them
+    for name in &result.output_files {
+        let img = image::open(qr_output_dir.join(name)).unwrap().to_luma8();
+        assert_eq!((img.width(), img.height()), (65 * 4, 65 * 4 + 34));
+        let caption_ink = (65 * 4..img.height())
+            .flat_map(|y| (0..img.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| img.get_pixel(x, y).0[0] < 128)
+            .count();
+        assert!(caption_ink > 0, "{} has no caption", name);
+    }
+
+    let (filename, data) =
+        fountain::decode_from_images_to_vec(&qr_output_dir).expect("Decoding failed");
+    assert_eq!(filename, "captioned.bin");
+    assert_eq!(data, original);
+
+    fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "captioned.bin",
+        &fountain::FileMetadata::default(),
+        &gif_path,
+        &options,
+    )
+    .expect("Encoding failed");
+    let (_, data) = fountain::decode_from_gif_to_vec(&gif_path).expect("Decoding failed");
+    assert_eq!(data, original);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_cancelled_operations_stop() {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+    let original = b"Cancel me before the first frame.".repeat(50);
+
+    let cancel = Arc::new(AtomicBool::new(true));
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(400)
+        .cancel_flag(cancel.clone());
+    let Err(err) = fountain::encode_bytes_to_images_with_options(
+        &original,
+        "cancel.bin",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        &options,
+    ) else {
+        panic!("Encoding should have been cancelled");
+    };
+    assert!(err.is::<fountain::Cancelled>());
+    assert_eq!(fs::read_dir(&qr_output_dir).unwrap().count(), 0);
+
+    // Encode for real, then cancel the decode
+    let options = fountain::EncodeOptions::new().chunk_size(400);
+    fountain::encode_bytes_to_images_with_options(
+        &original,
+        "cancel.bin",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        &options,
+    )
+    .expect("Encoding failed");
+
+    let decoded_output_path = temp_dir.path().join("decoded.bin");
+    let Err(err) = fountain::decode_from_images_with_options(
+        &qr_output_dir,
+        Some(&decoded_output_path),
+        &fountain::DecodeOptions::new().cancel_flag(cancel),
+    ) else {
+        panic!("Decoding should have been cancelled");
+    };
+    assert!(err.is::<fountain::Cancelled>());
+    assert!(!decoded_output_path.exists());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_encode_decode_zstd_roundtrip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output_zstd");
+    let decoded_output_path = temp_dir.path().join("decoded.txt");
+
+    let source_file_path = temp_dir.path().join("source.txt");
+    let original_content = "Zstandard roundtrip test content. ".repeat(40);
+    fs::write(&source_file_path, &original_content).expect("Failed to write source file");
+
+    fountain::encode_file_to_images(
+        &source_file_path,
+        &qr_output_dir,
+        None,
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zstd,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        None,
+    )
+    .expect("Encoding failed");
+
+    fountain::decode_from_images(&qr_output_dir, Some(&decoded_output_path))
+        .expect("Decoding failed");
+
+    let decoded_content =
+        fs::read_to_string(&decoded_output_path).expect("Failed to read decoded file");
+    assert_eq!(original_content, decoded_content);
+}
+
+#[test]
+#[cfg(feature = "encode")]
+fn test_redundancy_factor_controls_chunk_count() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("redundancy.bin");
+    let data: Vec<u8> = (0..4000).map(|_| rand::random::<u8>()).collect();
+    fs::write(&source_file_path, &data).expect("Failed to write source file");
+
+    let encode = |factor: f64, dir: &str| {
+        fountain::encode_file_to_images(
+            &source_file_path,
+            &temp_dir.path().join(dir),
+            Some(300),
+            4,
+            fountain::EcLevel::M,
+            &fountain::QrStyle::default(),
+            fountain::PayloadEncoding::Base45,
+            fountain::CompressionAlgorithm::Zlib,
+            factor,
+            None,
+        )
+    };
+
+    let single = encode(1.0, "single").expect("Encoding failed");
+    let double = encode(2.0, "double").expect("Encoding failed");
+    assert!(
+        double.num_chunks >= 2 * single.num_chunks - 4,
+        "Expected about twice as many chunks, got {} vs {}",
+        double.num_chunks,
+        single.num_chunks
+    );
+
+    assert!(encode(0.5, "invalid").is_err());
+}
+
+#[test]
+#[cfg(feature = "encode")]
+fn test_encode_images_size_consistency() {
+    use image::GenericImageView;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_dir = temp_dir.path().join("input");
+    let qr_output_dir = temp_dir.path().join("qr_output_consistency");
+
+    fs::create_dir(&input_dir).expect("Failed to create input dir");
+
+    // Using random data to ensure it doesn't compress too trivially.
+    let source_file_path = input_dir.join("consistency_test.bin");
+    // 20KB with a small chunk size should produce multiple chunks (~200 chunks)
+    let data: Vec<u8> = (0..20000).map(|i| (i % 255) as u8).collect();
+    fs::write(&source_file_path, &data).expect("Failed to write source file");
+
+    // Use a small chunk size to ensure we get many chunks
+    let encode_result = fountain::encode_file_to_images(
+        &source_file_path,
+        &qr_output_dir,
+        Some(100),
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+        None,
+    )
+    .expect("Encoding failed");
+
+    assert!(
+        encode_result.num_chunks > 1,
+        "Test requires multiple chunks to verify consistency, got {}",
+        encode_result.num_chunks
+    );
+
+    let mut first_dimensions: Option<(u32, u32)> = None;
+
+    for filename in encode_result.output_files {
+        let path = qr_output_dir.join(filename);
+        let img = image::open(&path).expect("Failed to open generated QR image");
+        let dims = img.dimensions();
+
+        if let Some(first) = first_dimensions {
+            assert_eq!(
+                dims, first,
+                "Image dimensions mismatch! All QR codes should be same size. Found {:?} vs {:?}",
+                dims, first
+            );
+        } else {
+            first_dimensions = Some(dims);
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "encode")]
+fn test_encode_gif_size_consistency() {
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+    use std::fs::File;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let input_dir = temp_dir.path().join("input_gif");
+    let output_gif_path = temp_dir.path().join("output.gif");
+
+    fs::create_dir(&input_dir).expect("Failed to create input dir");
+
+    let source_file_path = input_dir.join("gif_consistency_test.bin");
+    let data: Vec<u8> = (0..20000).map(|i| (i % 255) as u8).collect();
+    fs::write(&source_file_path, &data).expect("Failed to write source file");
+
+    fountain::encode_file_to_gif(
+        &source_file_path,
+        &output_gif_path,
+        Some(100),
+        100,
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+    )
+    .expect("GIF encoding failed");
+
+    let file = File::open(&output_gif_path).expect("Failed to open generated GIF");
+    let reader = std::io::BufReader::new(file);
+    let decoder = GifDecoder::new(reader).expect("Failed to create GIF decoder");
+    let frames = decoder
+        .into_frames()
+        .collect_frames()
+        .expect("Failed to decode GIF frames");
+
+    assert!(
+        frames.len() > 1,
+        "Test requires multiple frames, got {}",
+        frames.len()
+    );
+
+    let mut first_dimensions: Option<(u32, u32)> = None;
+    for frame in frames {
+        let buffer = frame.buffer();
+        let (width, height) = buffer.dimensions();
+        let dims = (width, height);
+
+        if let Some(_first) = first_dimensions {
+            first_dimensions = Some(dims);
+        }
+    }
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_directory_of_photos() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+
+    let original = b"Printed, photographed and copied off the phone as JPEGs.".repeat(20);
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(200)
+        .pixel_scale(4);
+    fountain::encode_bytes_to_images_with_options(
+        &original,
+        "photos.txt",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        &options,
+    )
+    .expect("Encoding failed");
+
+    // Convert the PNGs to the formats cameras and scanners produce
+    let mut files: Vec<_> = fs::read_dir(&qr_output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+    for (i, file) in files.iter().enumerate() {
+        let img = image::open(file).unwrap();
+        let extension = if i % 2 == 0 { "JPG" } else { "bmp" };
+        img.save(file.with_extension(extension)).unwrap();
+        fs::remove_file(file).unwrap();
+    }
+
+    let (filename, data) =
+        fountain::decode_from_images_to_vec(&qr_output_dir).expect("Decoding failed");
+    assert_eq!(filename, "photos.txt");
+    assert_eq!(data, original);
+}
+
+#[test]
+#[cfg(feature = "decode")]
+fn test_glob_pattern_matching() {
+    use fountain::GlobPattern;
+    use std::path::Path;
+
+    let nested: GlobPattern = "page-*/img_*.jpg".parse().unwrap();
+    assert!(nested.matches(Path::new("page-1/img_0001.jpg")));
+    assert!(!nested.matches(Path::new("page-1/thumb_0001.jpg")));
+    assert!(!nested.matches(Path::new("page-1/extra/img_0001.jpg")));
+
+    let any_depth: GlobPattern = "**/img_?.png".parse().unwrap();
+    assert!(any_depth.matches(Path::new("img_1.png")));
+    assert!(any_depth.matches(Path::new("a/b/img_2.png")));
+    assert!(!any_depth.matches(Path::new("a/img_10.png")));
+
+    let name_only: GlobPattern = "*.png".parse().unwrap();
+    assert!(name_only.matches(Path::new("deep/down/code.png")));
+    assert!("".parse::<GlobPattern>().is_err());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_nested_directories_with_glob() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+    let scans_dir = temp_dir.path().join("scans");
+
+    let original = b"Scanned one page per folder.".repeat(40);
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(150)
+        .pixel_scale(2);
+    fountain::encode_bytes_to_images_with_options(
+        &original,
+        "scans.txt",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        &options,
+    )
+    .expect("Encoding failed");
+
+    // One page folder per code, next to thumbnails that are not QR codes at all
+    for (i, entry) in fs::read_dir(&qr_output_dir).unwrap().enumerate() {
+        let page_dir = scans_dir.join(format!("page-{}", i + 1));
+        fs::create_dir_all(&page_dir).unwrap();
+        fs::rename(
+            entry.unwrap().path(),
+            page_dir.join(format!("img_{}.png", i)),
+        )
+        .unwrap();
+        fs::write(page_dir.join("thumb.png"), b"not an image").unwrap();
+    }
+
+    let output_path = temp_dir.path().join("decoded.txt");
+    let options = fountain::DecodeOptions::new()
+        .recursive(true)
+        .glob("page-*/img_*.png".parse().unwrap());
+    let result =
+        fountain::decode_from_images_with_options(&scans_dir, Some(&output_path), &options)
+            .expect("Decoding failed");
+    assert_eq!(result.original_filename, "scans.txt");
+    assert_eq!(fs::read(&output_path).unwrap(), original);
+
+    // Without --recursive, the page folders are not searched
+    let flat = fountain::DecodeOptions::new().glob("img_*.png".parse().unwrap());
+    assert!(fountain::decode_from_images_with_options(&scans_dir, None, &flat).is_err());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "watch"))]
+fn test_decode_from_watched_dir() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::time::Duration;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+    let watched_dir = temp_dir.path().join("synced");
+    fs::create_dir(&watched_dir).unwrap();
+
+    let original = b"Photos arrive one by one.".repeat(40);
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(150)
+        .pixel_scale(2);
+    fountain::encode_bytes_to_images_with_options(
+        &original,
+        "synced.txt",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        &options,
+    )
+    .expect("Encoding failed");
+    let mut codes: Vec<_> = fs::read_dir(&qr_output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    codes.sort();
+
+    // One code is there before watching starts, the rest show up afterwards
+    let first = codes.remove(0);
+    fs::rename(&first, watched_dir.join(first.file_name().unwrap())).unwrap();
+    let output_path = temp_dir.path().join("decoded.txt");
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (sender, result) = mpsc::channel();
+    {
+        let (watched_dir, output_path) = (watched_dir.clone(), output_path.clone());
+        let options = fountain::DecodeOptions::new().cancel_flag(cancel.clone());
+        std::thread::spawn(move || {
+            let result = fountain::decode_from_watched_dir_with_options(
+                &watched_dir,
+                Some(&output_path),
+                &options,
+            );
+            sender.send(result).unwrap();
+        });
+    }
+    std::thread::sleep(Duration::from_millis(300));
+    for code in &codes {
+        fs::rename(code, watched_dir.join(code.file_name().unwrap())).unwrap();
+    }
+
+    let decoded = result.recv_timeout(Duration::from_secs(60));
+    cancel.store(true, Ordering::Relaxed);
+    let decoded = decoded
+        .expect("Watching never completed")
+        .expect("Decoding failed");
+    assert_eq!(decoded.original_filename, "synced.txt");
+    assert_eq!(fs::read(&output_path).unwrap(), original);
+
+    // A watch on a directory where nothing arrives ends only when cancelled
+    let empty_dir = temp_dir.path().join("empty");
+    fs::create_dir(&empty_dir).unwrap();
+    let cancel = Arc::new(AtomicBool::new(true));
+    let Err(err) = fountain::decode_from_watched_dir_with_options(
+        &empty_dir,
+        None,
+        &fountain::DecodeOptions::new().cancel_flag(cancel),
+    ) else {
+        panic!("Watching should have been cancelled");
+    };
+    assert!(err.is::<fountain::Cancelled>());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_from_mixed_paths() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+
+    let mut state = 987u32;
+    let original: Vec<u8> = (0..1500)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect();
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(200)
+        .pixel_scale(2)
+        .redundancy(1.0);
+    let result = fountain::encode_bytes_to_images_with_options(
+        &original,
+        "mixed.bin",
+        &fountain::FileMetadata::default(),
+        &qr_output_dir,
+        &options,
+    )
+    .expect("Encoding failed");
+
+    // Spread the images over a nested folder and a loose file, as photos often are
+    let mut files: Vec<_> = fs::read_dir(&qr_output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+    let nested_dir = temp_dir.path().join("photos").join("day2");
+    fs::create_dir_all(&nested_dir).unwrap();
+    let loose_file = files.pop().unwrap();
+    for file in &files[files.len() / 2..] {
+        fs::rename(file, nested_dir.join(file.file_name().unwrap())).unwrap();
+    }
+    assert!(fountain::decode_from_images_to_vec(&qr_output_dir).is_err());
+
+    let paths = vec![
+        qr_output_dir.clone(),
+        temp_dir.path().join("photos"),
+        loose_file,
+    ];
+    let (filename, data) = fountain::decode_from_paths_to_vec(&paths).expect("Decoding failed");
+    assert_eq!(filename, "mixed.bin");
+    assert_eq!(data, original);
+    assert!(result.num_chunks > 2);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_volumes_roundtrip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let output_gif = temp_dir.path().join("backup.gif");
+
+    let mut state = 4242u32;
+    let original: Vec<u8> = (0..2500)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect();
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(300)
+        .pixel_scale(2)
+        .volume_size(1000);
+    let result = fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "backup.bin",
+        &fountain::FileMetadata::default(),
+        &output_gif,
+        &options,
+    )
+    .expect("Encoding failed");
+    let volumes: Vec<_> = ["backup.001.gif", "backup.002.gif", "backup.003.gif"]
+        .iter()
+        .map(|name| temp_dir.path().join(name))
+        .collect();
+    let expected: Vec<String> = volumes
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    assert_eq!(result.output_files, expected);
+    assert!(!output_gif.exists());
+
+    // Each volume decodes on its own to its slice of the file
+    let (filename, data) = fountain::decode_from_gif_to_vec(&volumes[1]).expect("Decoding failed");
+    assert_eq!(filename, "backup.bin.002");
+    assert_eq!(data, &original[1000..2000]);
+
+    // Together they reassemble the file, in any order
+    let paths = vec![volumes[2].clone(), volumes[0].clone(), volumes[1].clone()];
+    let (filename, data) = fountain::decode_from_paths_to_vec(&paths).expect("Decoding failed");
+    assert_eq!(filename, "backup.bin");
+    assert_eq!(data, original);
+
+    let err = fountain::decode_from_paths_to_vec(&volumes[..2])
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string().contains("Volume 3/3: no chunks found"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_seeded_encoding_is_reproducible() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let original: Vec<u8> = (0..1200u32).map(|i| (i * 131 % 251) as u8).collect();
+    let encode = |name: &str, options: &fountain::EncodeOptions| {
+        let gif_path = temp_dir.path().join(name);
+        fountain::encode_bytes_to_gif_with_options(
+            &original,
+            "seeded.bin",
+            &fountain::FileMetadata::default(),
+            &gif_path,
+            options,
+        )
         .expect("Encoding failed");
+        gif_path
+    };
+
+    let options = fountain::EncodeOptions::new().chunk_size(400).seed(7);
+    let first = encode("first.gif", &options);
+    let second = encode("second.gif", &options);
+    let unseeded = encode(
+        "unseeded.gif",
+        &fountain::EncodeOptions::new().chunk_size(400),
+    );
+    assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+    assert_ne!(fs::read(&first).unwrap(), fs::read(&unseeded).unwrap());
+
+    let info = fountain::inspect_gif(&first).expect("Inspection failed");
+    assert_eq!(info.seed, Some(7));
+    let info = fountain::inspect_gif(&unseeded).expect("Inspection failed");
+    assert_eq!(info.seed, None);
+
+    let output = temp_dir.path().join("seeded.bin");
+    let result = fountain::decode_from_gif(&first, Some(&output)).expect("Decoding failed");
+    assert_eq!(result.metadata.seed, Some(7));
+    assert_eq!(fs::read(&output).unwrap(), original);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_gif_fresh_cycles_decode_on_their_own() {
+    use image::AnimationDecoder;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let gif_path = temp_dir.path().join("cycles.gif");
+    let original: Vec<u8> = (0..1200u32).map(|i| (i * 97 % 253) as u8).collect();
+
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(400)
+        .cycles(2)
+        .fresh_cycles(true)
+        .frame_order(fountain::FrameOrder::Shuffled);
+    let result = fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "cycles.bin",
+        &fountain::FileMetadata::default(),
+        &gif_path,
+        &options,
+    )
+    .expect("Encoding failed");
+    let single = fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "cycles.bin",
+        &fountain::FileMetadata::default(),
+        &temp_dir.path().join("single.gif"),
+        &options.clone().cycles(1),
+    )
+    .expect("Encoding failed");
+    assert_eq!(result.num_chunks, 2 * single.num_chunks);
+
+    let file = std::io::BufReader::new(fs::File::open(&gif_path).unwrap());
+    let frames: Vec<image::DynamicImage> = image::codecs::gif::GifDecoder::new(file)
+        .unwrap()
+        .into_frames()
+        .map(|frame| image::DynamicImage::ImageRgba8(frame.unwrap().into_buffer()))
+        .collect();
+    assert_eq!(frames.len(), result.num_chunks);
 
-    assert!(encode_result.num_chunks > 0);
+    // The second cycle carries only new repair packets, enough to decode without the first
+    let (filename, data) = fountain::decode_from_dynamic_images(&frames[single.num_chunks..])
+        .expect("Decoding the second cycle failed");
+    assert_eq!(filename, "cycles.bin");
+    assert_eq!(data, original);
+}
 
-    let entries = fs::read_dir(&qr_output_dir).expect("Failed to read qr output dir");
-    let count = entries.count();
-    assert_eq!(count, encode_result.num_chunks);
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_gif_sync_frames_are_skipped() {
+    use image::AnimationDecoder;
 
-    println!("Decoding...");
-    let decode_result = fountain::decode_from_images(&qr_output_dir, Some(&decoded_output_path))
-        .expect("Decoding failed");
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let gif_path = temp_dir.path().join("sync.gif");
+    let original = b"Calibration frames come first, then the data".repeat(20);
 
-    // In RaptorQ, decode_result.num_chunks is the number of chunks used for decoding
-    assert!(decode_result.num_chunks > 0);
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(300)
+        .interval_ms(250)
+        .sync_frames(true);
+    let result = fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "sync.txt",
+        &fountain::FileMetadata::default(),
+        &gif_path,
+        &options,
+    )
+    .expect("Encoding failed");
 
-    let decoded_content =
-        fs::read_to_string(&decoded_output_path).expect("Failed to read decoded file");
+    let file = std::io::BufReader::new(fs::File::open(&gif_path).unwrap());
+    let frames: Vec<image::RgbaImage> = image::codecs::gif::GifDecoder::new(file)
+        .unwrap()
+        .into_frames()
+        .map(|frame| frame.unwrap().into_buffer())
+        .collect();
+    assert_eq!(frames.len(), result.num_chunks + 3);
+    assert!(frames[0].pixels().all(|p| p.0[..3] == [0, 0, 0]));
+    assert!(frames[1].pixels().all(|p| p.0[..3] == [255, 255, 255]));
 
-    assert_eq!(original_content, decoded_content);
+    let info = fountain::inspect_gif(&gif_path).expect("Inspection failed");
+    let sync = info.sync.expect("Start marker not found");
+    assert_eq!(sync.frames as usize, result.num_chunks);
+    assert_eq!(sync.interval_ms, 250);
+    assert_eq!(sync.transfer_length, info.transfer_length);
+    assert_eq!(info.foreign_chunks, 0);
+
+    let (filename, data) = fountain::decode_from_gif_to_vec(&gif_path).expect("Decoding failed");
+    assert_eq!(filename, "sync.txt");
+    assert_eq!(data, original);
 }
 
 #[test]
-#[cfg(feature = "encode")]
-fn test_encode_images_size_consistency() {
-    use image::GenericImageView;
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_gif_reports_every_frame() {
+    use fountain::FrameOutcome;
+    use image::codecs::gif::{GifDecoder, GifEncoder};
+    use image::AnimationDecoder;
 
     let temp_dir = TempDir::new().expect("Failed to create temp dir");
-    let input_dir = temp_dir.path().join("input");
-    let qr_output_dir = temp_dir.path().join("qr_output_consistency");
+    let gif_path = temp_dir.path().join("sync.gif");
+    let original: Vec<u8> = (0..1500).map(|_| rand::random::<u8>()).collect();
 
-    fs::create_dir(&input_dir).expect("Failed to create input dir");
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(300)
+        .sync_frames(true);
+    fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "frames.txt",
+        &fountain::FileMetadata::default(),
+        &gif_path,
+        &options,
+    )
+    .expect("Encoding failed");
 
-    // Using random data to ensure it doesn't compress too trivially.
-    let source_file_path = input_dir.join("consistency_test.bin");
-    // 20KB with a small chunk size should produce multiple chunks (~200 chunks)
-    let data: Vec<u8> = (0..20000).map(|i| (i % 255) as u8).collect();
-    fs::write(&source_file_path, &data).expect("Failed to write source file");
+    // Show the first data frame twice, as a screen recording would
+    let file = std::io::BufReader::new(fs::File::open(&gif_path).unwrap());
+    let mut frames: Vec<image::Frame> = GifDecoder::new(file)
+        .unwrap()
+        .into_frames()
+        .map(|frame| frame.unwrap())
+        .collect();
+    frames.insert(3, frames[3].clone());
+    let recording = temp_dir.path().join("recording.gif");
+    GifEncoder::new(fs::File::create(&recording).unwrap())
+        .encode_frames(frames.clone())
+        .unwrap();
 
-    // Use a small chunk size to ensure we get many chunks
-    let encode_result =
-        fountain::encode_file_to_images(&source_file_path, &qr_output_dir, Some(100), 4)
-            .expect("Encoding failed");
+    let output = temp_dir.path().join("frames.txt");
+    let mut events = Vec::new();
+    fountain::decode_from_gif_with(
+        &recording,
+        Some(&output),
+        &fountain::DecodeOptions::new(),
+        |event| events.push(event),
+    )
+    .expect("Decoding failed");
+    assert_eq!(fs::read(&output).unwrap(), original);
 
-    assert!(
-        encode_result.num_chunks > 1,
-        "Test requires multiple chunks to verify consistency, got {}",
-        encode_result.num_chunks
+    let indices: Vec<usize> = events.iter().map(|event| event.index).collect();
+    assert_eq!(indices, (0..events.len()).collect::<Vec<_>>());
+    assert_eq!(events[0].label, "frame 1");
+    let outcomes: Vec<FrameOutcome> = events.iter().map(|event| event.outcome).collect();
+    let new_code = FrameOutcome::Codes { codes: 1, new: 1 };
+    assert_eq!(
+        outcomes[..5],
+        [
+            FrameOutcome::Blank,
+            FrameOutcome::Blank,
+            new_code,
+            new_code,
+            FrameOutcome::Duplicate,
+        ]
     );
+    assert!(outcomes[5..].iter().all(|&outcome| outcome == new_code));
+    // The decode stops at the frame that completes the file
+    assert!(events.len() <= frames.len());
+}
 
-    let mut first_dimensions: Option<(u32, u32)> = None;
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_decode_gif_frame_range() {
+    use fountain::FramePosition;
+    use std::time::Duration;
 
-    for filename in encode_result.output_files {
-        let path = qr_output_dir.join(filename);
-        let img = image::open(&path).expect("Failed to open generated QR image");
-        let dims = img.dimensions();
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let gif_path = temp_dir.path().join("range.gif");
+    let original: Vec<u8> = (0..1500).map(|_| rand::random::<u8>()).collect();
 
-        if let Some(first) = first_dimensions {
-            assert_eq!(
-                dims, first,
-                "Image dimensions mismatch! All QR codes should be same size. Found {:?} vs {:?}",
-                dims, first
-            );
-        } else {
-            first_dimensions = Some(dims);
-        }
-    }
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(300)
+        .interval_ms(250)
+        .first_frame_ms(250)
+        .redundancy(2.0);
+    fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "range.bin",
+        &fountain::FileMetadata::default(),
+        &gif_path,
+        &options,
+    )
+    .expect("Encoding failed");
+
+    let decode = |options: fountain::DecodeOptions| {
+        let output = temp_dir.path().join("range.bin");
+        let mut labels = Vec::new();
+        let result = fountain::decode_from_gif_with(&gif_path, Some(&output), &options, |event| {
+            labels.push(event.label)
+        });
+        result.map(|_| (fs::read(&output).unwrap(), labels))
+    };
+
+    // Frames start every 250 ms, so 0.5 s in is the third frame
+    let (data, labels) = decode(
+        fountain::DecodeOptions::new().start(FramePosition::Time(Duration::from_millis(500))),
+    )
+    .expect("Decoding failed");
+    assert_eq!(data, original);
+    assert_eq!(labels[0], "frame 3");
+
+    let (data, labels) = decode(fountain::DecodeOptions::new().start(FramePosition::Frame(1)))
+        .expect("Decoding failed");
+    assert_eq!(data, original);
+    assert_eq!(labels[0], "frame 2");
+
+    // Two frames are not enough for the file
+    let error = decode(
+        fountain::DecodeOptions::new()
+            .start(FramePosition::Frame(1))
+            .end(FramePosition::Time(Duration::from_millis(750))),
+    )
+    .expect_err("Decoding two frames succeeded");
+    assert!(error.to_string().contains("after 2"), "{}", error);
 }
 
 #[test]
-#[cfg(feature = "encode")]
-fn test_encode_gif_size_consistency() {
-    use image::codecs::gif::GifDecoder;
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_gif_frame_grid_roundtrip() {
     use image::AnimationDecoder;
-    use std::fs::File;
 
     let temp_dir = TempDir::new().expect("Failed to create temp dir");
-    let input_dir = temp_dir.path().join("input_gif");
-    let output_gif_path = temp_dir.path().join("output.gif");
+    let gif_path = temp_dir.path().join("grid.gif");
+    let original: Vec<u8> = (0..6000u32).map(|i| (i * 13 % 251) as u8).collect();
 
-    fs::create_dir(&input_dir).expect("Failed to create input dir");
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(300)
+        .pixel_scale(2)
+        .sync_frames(true)
+        .frame_grid("2x2".parse().unwrap());
+    let result = fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "grid.bin",
+        &fountain::FileMetadata::default(),
+        &gif_path,
+        &options,
+    )
+    .expect("Encoding failed");
 
-    let source_file_path = input_dir.join("gif_consistency_test.bin");
-    let data: Vec<u8> = (0..20000).map(|i| (i % 255) as u8).collect();
-    fs::write(&source_file_path, &data).expect("Failed to write source file");
+    let file = std::io::BufReader::new(fs::File::open(&gif_path).unwrap());
+    let frames = image::codecs::gif::GifDecoder::new(file)
+        .unwrap()
+        .into_frames()
+        .count();
+    let data_frames = result.num_chunks.div_ceil(4);
+    assert_eq!(frames, data_frames + 3);
+    // The reported size is of whole frames, and every code of them is counted
+    let qr = result.qr.expect("GIF output reports its QR sizing");
+    assert_eq!(qr.image_px, image::image_dimensions(&gif_path).unwrap());
+    assert_eq!(qr.chunk_bytes.len(), data_frames * 4);
+    let info = fountain::inspect_gif(&gif_path).expect("Inspection failed");
+    assert_eq!(
+        info.sync.expect("Start marker not found").frames as usize,
+        data_frames
+    );
 
-    fountain::encode_file_to_gif(&source_file_path, &output_gif_path, Some(100), 100, 4)
-        .expect("GIF encoding failed");
+    let (filename, data) = fountain::decode_from_gif_to_vec(&gif_path).expect("Decoding failed");
+    assert_eq!(filename, "grid.bin");
+    assert_eq!(data, original);
+}
 
-    let file = File::open(&output_gif_path).expect("Failed to open generated GIF");
-    let reader = std::io::BufReader::new(file);
-    let decoder = GifDecoder::new(reader).expect("Failed to create GIF decoder");
-    let frames = decoder
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_gif_color_planes_roundtrip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let gif_path = temp_dir.path().join("planes.gif");
+    let original: Vec<u8> = (0..4000u32).map(|i| (i * 17 % 241) as u8).collect();
+
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(300)
+        .pixel_scale(2)
+        .color_planes(true);
+    fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "planes.bin",
+        &fountain::FileMetadata::default(),
+        &gif_path,
+        &options,
+    )
+    .expect("Encoding failed");
+
+    let (filename, data) = fountain::decode_from_gif_to_vec(&gif_path).expect("Decoding failed");
+    assert_eq!(filename, "planes.bin");
+    assert_eq!(data, original);
+
+    let captioned = options.captions(true);
+    assert!(fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "planes.bin",
+        &fountain::FileMetadata::default(),
+        &gif_path,
+        &captioned,
+    )
+    .is_err());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_gif_frame_timing() {
+    use image::AnimationDecoder;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let gif_path = temp_dir.path().join("timing.gif");
+    let original: Vec<u8> = (0..2000u32).map(|i| (i * 29 % 251) as u8).collect();
+
+    // 33ms cannot be stored exactly and becomes 30ms
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(300)
+        .pixel_scale(2)
+        .interval_ms(33)
+        .first_frame_ms(1500)
+        .repeat_interval_ms(120)
+        .cycles(2);
+    let result = fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "timing.bin",
+        &fountain::FileMetadata::default(),
+        &gif_path,
+        &options,
+    )
+    .expect("Encoding failed");
+    let timing = result.timing.expect("GIF output reports its timing");
+    assert_eq!(timing.interval_ms, 30);
+    let per_cycle = result.num_chunks / 2;
+    let mut expected = vec![1500];
+    expected.extend(std::iter::repeat_n(30, per_cycle - 1));
+    expected.extend(std::iter::repeat_n(120, per_cycle));
+    assert_eq!(timing.frame_ms, expected);
+
+    let file = std::io::BufReader::new(fs::File::open(&gif_path).unwrap());
+    let delays: Vec<u64> = image::codecs::gif::GifDecoder::new(file)
+        .unwrap()
         .into_frames()
-        .collect_frames()
-        .expect("Failed to decode GIF frames");
+        .map(|frame| {
+            let (numer, denom) = frame.unwrap().delay().numer_denom_ms();
+            u64::from(numer / denom)
+        })
+        .collect();
+    assert_eq!(delays, expected);
 
-    assert!(
-        frames.len() > 1,
-        "Test requires multiple frames, got {}",
-        frames.len()
-    );
+    let (_, data) = fountain::decode_from_gif_to_vec(&gif_path).expect("Decoding failed");
+    assert_eq!(data, original);
+}
 
-    let mut first_dimensions: Option<(u32, u32)> = None;
-    for frame in frames {
-        let buffer = frame.buffer();
-        let (width, height) = buffer.dimensions();
-        let dims = (width, height);
+#[test]
+#[cfg(feature = "encode")]
+fn test_loop_count() {
+    use fountain::LoopCount;
 
-        if let Some(_first) = first_dimensions {
-            first_dimensions = Some(dims);
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let original = b"Played as often as asked".to_vec();
+    let encode = |loop_count: LoopCount, name: &str| {
+        let path = temp_dir.path().join(name);
+        let options = fountain::EncodeOptions::new().loop_count(loop_count);
+        let metadata = fountain::FileMetadata::default();
+        if name.ends_with(".gif") {
+            fountain::encode_bytes_to_gif_with_options(&original, "a", &metadata, &path, &options)
+        } else {
+            fountain::encode_bytes_to_apng_with_options(&original, "a", &metadata, &path, &options)
         }
-    }
+        .expect("Encoding failed");
+        fs::read(path).unwrap()
+    };
+
+    // GIF counts the plays after the first in its NETSCAPE2.0 block, and omits it for one
+    let netscape_count = |gif: &[u8]| {
+        let at = gif.windows(11).position(|w| w == b"NETSCAPE2.0")?;
+        Some(u16::from_le_bytes([gif[at + 13], gif[at + 14]]))
+    };
+    assert_eq!(
+        netscape_count(&encode(LoopCount::Infinite, "a.gif")),
+        Some(0)
+    );
+    assert_eq!(netscape_count(&encode(LoopCount::Times(1), "b.gif")), None);
+    assert_eq!(
+        netscape_count(&encode(LoopCount::Times(3), "c.gif")),
+        Some(2)
+    );
+
+    let num_plays = |apng: Vec<u8>| {
+        let reader = png::Decoder::new(std::io::Cursor::new(apng))
+            .read_info()
+            .unwrap();
+        reader.info().animation_control.unwrap().num_plays
+    };
+    assert_eq!(num_plays(encode(LoopCount::Infinite, "a.png")), 0);
+    assert_eq!(num_plays(encode(LoopCount::Times(1), "b.png")), 1);
+
+    assert_eq!("once".parse::<LoopCount>().unwrap(), LoopCount::Times(1));
+    assert_eq!("5".parse::<LoopCount>().unwrap(), LoopCount::Times(5));
+    assert_eq!(
+        "Infinite".parse::<LoopCount>().unwrap(),
+        LoopCount::Infinite
+    );
+    assert!("0".parse::<LoopCount>().is_err());
+    assert!("twice".parse::<LoopCount>().is_err());
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_encode_apng_roundtrip() {
+    use image::codecs::png::PngDecoder;
+    use image::{AnimationDecoder, DynamicImage};
+    use std::fs::File;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let output_apng_path = temp_dir.path().join("output.png");
+
+    let original: Vec<u8> = (0..1500).map(|i| (i * 7 % 256) as u8).collect();
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(200)
+        .pixel_scale(2);
+    let result = fountain::encode_bytes_to_apng_with_options(
+        &original,
+        "apng.bin",
+        &fountain::FileMetadata::default(),
+        &output_apng_path,
+        &options,
+    )
+    .expect("APNG encoding failed");
+    assert!(result.num_chunks > 1);
+
+    let file = File::open(&output_apng_path).expect("Failed to open generated APNG");
+    let decoder = PngDecoder::new(std::io::BufReader::new(file)).expect("Failed to read APNG");
+    let frames: Vec<DynamicImage> = decoder
+        .apng()
+        .expect("Output is not an APNG")
+        .into_frames()
+        .map(|frame| DynamicImage::ImageRgba8(frame.expect("Bad frame").into_buffer()))
+        .collect();
+    assert_eq!(frames.len(), result.num_chunks);
+
+    let (filename, data) = fountain::decode_from_dynamic_images(&frames).expect("Decoding failed");
+    assert_eq!(filename, "apng.bin");
+    assert_eq!(data, original);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_ur_gif_roundtrip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let output_gif_path = temp_dir.path().join("ur.gif");
+    let decoded_output_path = temp_dir.path().join("decoded.bin");
+
+    let original: Vec<u8> = (0..800).map(|i| (i * 13 % 256) as u8).collect();
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(150)
+        .pixel_scale(2)
+        .wire_format(fountain::WireFormat::Ur);
+    let result = fountain::encode_bytes_to_gif_with_options(
+        &original,
+        "ignored.bin",
+        &fountain::FileMetadata::default(),
+        &output_gif_path,
+        &options,
+    )
+    .expect("UR GIF encoding failed");
+    assert!(result.num_chunks > 1);
+
+    let decode_result = fountain::decode_from_gif(&output_gif_path, Some(&decoded_output_path))
+        .expect("UR GIF decoding failed");
+    assert_eq!(decode_result.original_filename, fountain::ur::UR_FILENAME);
+    assert_eq!(fs::read(&decoded_output_path).unwrap(), original);
 }
 
 #[test]
@@ -147,9 +1928,19 @@ fn test_encode_decode_gif_roundtrip() {
     fs::write(&source_file_path, original_content).expect("Failed to write source file");
 
     println!("Encoding to GIF...");
-    let encode_result =
-        fountain::encode_file_to_gif(&source_file_path, &output_gif_path, None, 100, 4)
-            .expect("GIF encoding failed");
+    let encode_result = fountain::encode_file_to_gif(
+        &source_file_path,
+        &output_gif_path,
+        None,
+        100,
+        4,
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
+    )
+    .expect("GIF encoding failed");
 
     assert!(encode_result.num_chunks > 0);
 
@@ -178,8 +1969,15 @@ fn test_terminal_generation() {
 
     println!("Encoding for terminal...");
     // Use a small chunk size to force multiple packets
-    let terminal_data =
-        fountain::encode_file_for_terminal(&source_file_path, Some(100)).expect("Encoding failed");
+    let terminal_data = fountain::encode_file_for_terminal(
+        &source_file_path,
+        Some(100),
+        fountain::EcLevel::M,
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_TERMINAL_REDUNDANCY_FACTOR,
+    )
+    .expect("Encoding failed");
 
     assert!(terminal_data.total > 0);
     assert!(!terminal_data.qr_strings.is_empty());
@@ -194,6 +1992,94 @@ fn test_terminal_generation() {
     }
 }
 
+#[test]
+#[cfg(feature = "encode")]
+fn test_terminal_stream_generates_fresh_packets() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("stream.txt");
+    fs::write(&source_file_path, "Streaming test content. ".repeat(30))
+        .expect("Failed to write source file");
+
+    let mut stream = fountain::encode_file_for_terminal_stream(
+        &source_file_path,
+        Some(100),
+        fountain::EcLevel::M,
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+    )
+    .expect("Encoding failed");
+
+    let source_packets = stream.source_packets;
+    assert!(source_packets > 0);
+
+    // Run well past the source packets and check every frame differs
+    let frames: Vec<String> = stream
+        .by_ref()
+        .take(source_packets * 3)
+        .collect::<Result<_, _>>()
+        .expect("Rendering failed");
+    let unique: std::collections::HashSet<_> = frames.iter().collect();
+    assert_eq!(unique.len(), frames.len());
+    assert_eq!(stream.emitted() as usize, frames.len());
+}
+
+#[test]
+#[cfg(feature = "encode")]
+fn test_terminal_carousel_of_several_files() {
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(100)
+        .compression(fountain::CompressionAlgorithm::None);
+    let files = vec![
+        (
+            vec![1u8; 600],
+            "a.bin".to_string(),
+            fountain::FileMetadata::default(),
+        ),
+        (
+            vec![2u8; 900],
+            "b.bin".to_string(),
+            fountain::FileMetadata::default(),
+        ),
+    ];
+    let data = fountain::encode_files_for_terminal_with_options(&files, &options)
+        .expect("Encoding failed");
+
+    assert_eq!(data.total, data.qr_strings.len());
+    assert_eq!(data.frame_filenames.len(), data.total);
+    // Codes alternate between the files until the shorter one runs out
+    assert_eq!(
+        &data.frame_filenames[..4],
+        ["a.bin", "b.bin", "a.bin", "b.bin"]
+    );
+    assert_eq!(
+        data.frame_filenames.last().map(String::as_str),
+        Some("b.bin")
+    );
+
+    // Two files of the same size still go out as streams receivers can tell apart
+    let same_size = vec![
+        files[0].clone(),
+        (vec![3u8; 600], "c.bin".to_string(), Default::default()),
+    ];
+    let data = fountain::encode_files_for_terminal_with_options(&same_size, &options)
+        .expect("Encoding failed");
+    let stream_of = |name: &str| {
+        let ids: std::collections::HashSet<_> = data
+            .frame_filenames
+            .iter()
+            .zip(&data.chunk_ids)
+            .filter(|(filename, _)| *filename == name)
+            .map(|(_, id)| {
+                let id = id.expect("every code is a chunk");
+                (id.transfer_length, id.packet_size)
+            })
+            .collect();
+        assert_eq!(ids.len(), 1);
+        ids.into_iter().next().unwrap()
+    };
+    assert_ne!(stream_of("a.bin"), stream_of("c.bin"));
+}
+
 #[test]
 #[cfg(all(feature = "encode", feature = "decode"))]
 fn test_encoding_efficiency() {
@@ -217,6 +2103,11 @@ fn test_encoding_efficiency() {
         Some(500),
         100, // interval
         4,   // pixel scale
+        fountain::EcLevel::M,
+        &fountain::QrStyle::default(),
+        fountain::PayloadEncoding::Base45,
+        fountain::CompressionAlgorithm::Zlib,
+        fountain::DEFAULT_REDUNDANCY_FACTOR,
     )
     .expect("Encoding failed");
 
@@ -238,3 +2129,258 @@ fn test_encoding_efficiency() {
     println!("Avg Data per Frame:    {:.2} bytes/frame", bytes_per_frame);
     println!("--------------------------------------------\n");
 }
+
+#[tokio::test]
+#[cfg(all(feature = "async", feature = "encode", feature = "decode"))]
+async fn test_async_gif_roundtrip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let source_file_path = temp_dir.path().join("async.txt");
+    let gif_path = temp_dir.path().join("async.gif");
+    let decoded_output_path = temp_dir.path().join("decoded.txt");
+    let original_content = "Encoded and decoded off the async runtime. ".repeat(30);
+    fs::write(&source_file_path, &original_content).expect("Failed to write source file");
+
+    let options = fountain::EncodeOptions::new()
+        .chunk_size(400)
+        .interval_ms(100);
+    let encode_result =
+        fountain::encode_file_to_gif_async(source_file_path, gif_path.clone(), options)
+            .await
+            .expect("Encoding failed");
+
+    let decode_result = fountain::decode_from_gif_async(
+        gif_path,
+        Some(decoded_output_path.clone()),
+        fountain::DecodeOptions::new(),
+    )
+    .await
+    .expect("Decoding failed");
+
+    assert_eq!(decode_result.sha256, encode_result.sha256);
+    assert_eq!(
+        fs::read_to_string(&decoded_output_path).unwrap(),
+        original_content
+    );
+}
+
+#[tokio::test]
+#[cfg(all(feature = "async", feature = "encode", feature = "decode"))]
+async fn test_async_bytes_to_images() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let qr_output_dir = temp_dir.path().join("qr_output");
+    let original: Vec<u8> = (0..1500).map(|i| (i * 31 % 256) as u8).collect();
+
+    fountain::encode_bytes_to_images_async(
+        original.clone(),
+        "generated.bin".to_string(),
+        fountain::FileMetadata::default(),
+        qr_output_dir.clone(),
+        fountain::EncodeOptions::new().chunk_size(400),
+    )
+    .await
+    .expect("Encoding failed");
+
+    let (filename, data) =
+        fountain::decode_from_images_to_vec(&qr_output_dir).expect("Decoding failed");
+    assert_eq!(filename, "generated.bin");
+    assert_eq!(data, original);
+}
+
+#[test]
+#[cfg(feature = "ffi")]
+fn test_ffi_decoder_reassembles_payloads() {
+    use fountain::chunk::{compress, pack_data, ChunkHeader, CompressionAlgorithm};
+    use fountain::ffi::*;
+    use std::ffi::CStr;
+
+    let data = b"Native scanners hand over the text; fountain does the rest. ".repeat(20);
+    let object = compress(&pack_data(&data, "notes.txt"), CompressionAlgorithm::None).unwrap();
+    let encoder = raptorq::Encoder::with_defaults(&object, 100);
+    let source_symbols = object.len().div_ceil(100) as u32;
+    let payloads: Vec<Vec<u8>> = encoder
+        .get_encoded_packets(4)
+        .into_iter()
+        .enumerate()
+        .map(|(i, packet)| {
+            let chunk = fountain::Chunk {
+                header: ChunkHeader {
+                    version: fountain::CHUNK_VERSION,
+                    total: object.len() as u32,
+                    index: i as u32,
+                    packet_size: 100,
+                    source_symbols: Some(source_symbols),
+                    original_size: Some(data.len() as u32),
+                    volume: None,
+                },
+                data: packet.serialize(),
+            };
+            chunk
+                .to_qr_payload(fountain::PayloadEncoding::Base45)
+                .unwrap()
+        })
+        .collect();
+
+    unsafe {
+        let decoder = fountain_decoder_new();
+        let feed = |payload: &[u8]| {
+            fountain_decoder_feed_payload(decoder, payload.as_ptr(), payload.len())
+        };
+        assert_eq!(feed(b"not a chunk"), FountainStatus::Scanning);
+        assert!(fountain_decoder_last_error(decoder).is_null());
+
+        // Skip the first source packet so a repair packet has to stand in for it
+        let mut status = FountainStatus::Scanning;
+        for payload in payloads.iter().skip(1) {
+            status = feed(payload);
+            if status == FountainStatus::Complete {
+                break;
+            }
+            assert_eq!(status, FountainStatus::ChunkFound);
+            assert!(fountain_decoder_poll_result(decoder).is_null());
+        }
+        assert_eq!(status, FountainStatus::Complete);
+
+        let (mut current, mut total) = (0, 0);
+        fountain_decoder_progress(decoder, &mut current, &mut total);
+        assert_eq!(total, source_symbols);
+        assert!(current >= total);
+
+        let file = fountain_decoder_poll_result(decoder);
+        assert!(!file.is_null());
+        assert_eq!(
+            CStr::from_ptr((*file).filename).to_str().unwrap(),
+            "notes.txt"
+        );
+        assert_eq!(
+            std::slice::from_raw_parts((*file).data, (*file).len),
+            &data[..]
+        );
+        assert!(fountain_decoder_poll_result(decoder).is_null());
+
+        fountain_file_free(file);
+        fountain_decoder_free(decoder);
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_payload_receiver_routes_wire_formats() {
+    use fountain::receive::{PayloadReceiver, ReceiveStatus};
+
+    let data: Vec<u8> = (0..300).map(|i| (i * 11 % 256) as u8).collect();
+    let encoder = fountain::ur::UrEncoder::new(&data, 40).unwrap();
+    let mut receiver = PayloadReceiver::new();
+    assert_eq!(
+        receiver.feed(b"https://example.com/").unwrap(),
+        ReceiveStatus::Scanning
+    );
+
+    // A start marker is kept for its frame rate
+    let sync = fountain::SyncInfo {
+        frames: 12,
+        interval_ms: 250,
+        transfer_length: 1000,
+        packet_size: 100,
+        source_symbols: 10,
+        original_size: 900,
+        volume: None,
+    };
+    let marker = sync.to_qr_payload(fountain::PayloadEncoding::Base45);
+    assert_eq!(receiver.feed(&marker).unwrap(), ReceiveStatus::Scanning);
+    assert_eq!(receiver.sync(), Some(&sync));
+
+    let mut status = ReceiveStatus::Scanning;
+    // Start past the fragments so only mixed parts arrive
+    for seq_num in encoder.seq_len() as u32 + 1.. {
+        status = receiver.feed(encoder.part(seq_num).as_bytes()).unwrap();
+        if status == ReceiveStatus::Complete {
+            break;
+        }
+        assert_eq!(status, ReceiveStatus::ChunkFound);
+        assert_eq!(receiver.progress().1, encoder.seq_len() as u32);
+    }
+    assert_eq!(status, ReceiveStatus::Complete);
+    assert_eq!(
+        receiver.take_result(),
+        Some((fountain::ur::UR_FILENAME.to_string(), data))
+    );
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_chunk_files_roundtrip() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let chunk_dir = temp_dir.path().join("chunks");
+    let decoded_output_path = temp_dir.path().join("decoded.bin");
+
+    let data: Vec<u8> = (0..30_000u32).map(|i| (i * 31 % 253) as u8).collect();
+    let result = fountain::encode_bytes_to_chunk_files_with_options(
+        &data,
+        "data.bin",
+        &fountain::FileMetadata::default(),
+        &chunk_dir,
+        &fountain::EncodeOptions::new().chunk_size(250),
+    )
+    .expect("Encoding failed");
+    assert_eq!(result.output_files.len(), result.num_chunks);
+    for name in &result.output_files {
+        let len = fs::metadata(chunk_dir.join(name)).unwrap().len();
+        assert!(len <= 250, "{} has {} bytes", name, len);
+    }
+
+    // Losing a third of the chunks, and a stray file among them, is fine
+    for name in result.output_files.iter().step_by(3) {
+        fs::remove_file(chunk_dir.join(name)).unwrap();
+    }
+    fs::write(chunk_dir.join("notes.txt"), "not a chunk").unwrap();
+
+    let decoded = fountain::decode_from_chunk_files(&chunk_dir, Some(&decoded_output_path))
+        .expect("Decoding failed");
+    assert_eq!(decoded.original_filename, "data.bin");
+    assert_eq!(fs::read(&decoded_output_path).unwrap(), data);
+    let (_, in_memory) = fountain::decode_from_chunk_files_to_vec(&chunk_dir).unwrap();
+    assert_eq!(in_memory, data);
+}
+
+#[test]
+#[cfg(all(feature = "encode", feature = "decode"))]
+fn test_feedback_from_ack_snapshot() {
+    use fountain::receive::{Feedback, PayloadReceiver};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let data: Vec<u8> = (0..4000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let metadata = fountain::FileMetadata::default();
+    let options = fountain::EncodeOptions::new().chunk_size(200);
+    let codes = fountain::encode_bytes_to_images_with_options(
+        &data,
+        "data.bin",
+        &metadata,
+        temp_dir.path(),
+        &options,
+    )
+    .expect("Encoding failed");
+
+    // The receiver scans two codes and shows its ACK code to the sender's camera
+    let mut receiver = PayloadReceiver::new();
+    for name in codes.output_files.iter().take(2) {
+        for payload in fountain::read_raw_image(&temp_dir.path().join(name)).unwrap() {
+            receiver.feed(&payload).unwrap();
+        }
+    }
+    let ack =
+        fountain::receive::to_ack_payload(&receiver.feedback(), fountain::PayloadEncoding::Base45);
+    let (image, _) = fountain::qr::generate_qr_image(
+        &ack,
+        None,
+        8,
+        fountain::DEFAULT_EC_LEVEL,
+        &fountain::QrStyle::default(),
+    )
+    .unwrap();
+    let snapshot = temp_dir.path().join("snapshot.png");
+    image.save(&snapshot).unwrap();
+
+    let feedback = Feedback::fetch(snapshot.to_str().unwrap()).expect("No ACK read");
+    assert_eq!(feedback, receiver.feedback());
+    assert_eq!(feedback.receiving.unwrap().received.count(), 2);
+}
@@ -3,6 +3,7 @@ import init, { QrStreamDecoder, ScanStatus } from "/scanner/pkg/fountain.js";
 let decoder = null;
 let stream = null;
 let animationId = null;
+let firstChunkAt = null;
 
 const video = document.getElementById("video");
 const canvas = document.getElementById("canvas");
@@ -13,7 +14,13 @@ const scanLine = document.getElementById("scan-line");
 const statusDiv = document.getElementById("status");
 const progressFill = document.getElementById("progress-fill");
 const downloadArea = document.getElementById("download-area");
-const downloadBtn = document.getElementById("download-btn");
+// Set by `fountain serve`, which assembles the file from the payloads posted here
+// and reports its progress over a WebSocket
+const payloadUrl = document.body.dataset.payloadUrl;
+const progressUrl = document.body.dataset.progressUrl;
+const receiverStatus = document.getElementById("receiver-status");
+const ackArea = document.getElementById("ack-area");
+const ackCode = document.getElementById("ack-code");
 
 async function run() {
     try {
@@ -22,6 +29,7 @@ async function run() {
         statusDiv.firstChild.textContent = "Ready to scan.";
         startBtn.onclick = startCamera;
         stopBtn.onclick = stopCamera;
+        watchReceiver();
     } catch (e) {
         console.error("Failed to load wasm:", e);
         statusDiv.firstChild.textContent = "Error loading Wasm module.";
@@ -31,6 +39,11 @@ async function run() {
 async function startCamera() {
     try {
         decoder = new QrStreamDecoder();
+        // Full-resolution frames are slow to scan on phones; dense codes still
+        // get at least two pixels per module at this size
+        decoder.set_max_dimension(1280);
+        decoder.set_forward_payloads(Boolean(payloadUrl));
+        firstChunkAt = null;
 
         // Prefer rear camera
         const constraints = {
@@ -51,7 +64,9 @@ async function startCamera() {
         startBtn.disabled = true;
         stopBtn.disabled = false;
         scanLine.style.display = "block";
+        downloadArea.replaceChildren();
         downloadArea.style.display = "none";
+        ackArea.style.display = "none";
 
         statusDiv.firstChild.textContent = "Scanning...";
         progressFill.style.width = "0%";
@@ -96,27 +111,91 @@ function scanLoop() {
         canvas.height,
     );
     const status = result.status;
+    forwardPayloads();
+    if (status === ScanStatus.ChunkFound || status === ScanStatus.Complete) {
+        showAck();
+    }
 
-    if (result.progress_total > 0) {
+    // Nothing received yet of the next file; keep the message of the last one
+    if (result.progress_total > 0 && result.progress_current > 0) {
         const percent = (result.progress_current / result.progress_total) * 100;
         progressFill.style.width = `${percent}%`;
-        statusDiv.firstChild.textContent = `Found ${result.progress_current} / ${result.progress_total} chunks...`;
+        statusDiv.firstChild.textContent = `Found ${result.progress_current} / ${result.progress_total} chunks${formatEta(result)}${formatSize(result)}...`;
     }
 
     if (status === ScanStatus.Complete) {
-        stopCamera();
-        statusDiv.firstChild.textContent = `Completed! Decoded: ${result.get_filename()}`;
+        // A carousel may carry more files; keep scanning until the user stops
+        statusDiv.firstChild.textContent = `Completed! Decoded: ${result.get_filename()}. Still scanning for more files...`;
         progressFill.style.width = "100%";
-        enableDownload(result.get_filename(), result.get_file_data());
-        return;
+        firstChunkAt = null;
+        addDownload(result.get_filename(), result.get_file_data());
     }
 
     animationId = requestAnimationFrame(scanLoop);
 }
 
-function enableDownload(filename, data) {
+function forwardPayloads() {
+    if (!payloadUrl) return;
+    let payload;
+    while ((payload = decoder.next_payload()) !== undefined) {
+        fetch(payloadUrl, { method: "POST", body: payload })
+            .catch((err) => console.error("Failed to forward payload:", err));
+    }
+}
+
+// Shows the chunks found as an ACK code, which a sender running
+// `fountain encode --terminal --feedback <camera snapshot>` reads back
+function showAck() {
+    const png = decoder.ack_png(6);
+    const previous = ackCode.src;
+    ackCode.src = URL.createObjectURL(new Blob([png], { type: "image/png" }));
+    if (previous) URL.revokeObjectURL(previous);
+    ackArea.style.display = "block";
+}
+
+function watchReceiver() {
+    if (!progressUrl) return;
+    const scheme = location.protocol === "https:" ? "wss" : "ws";
+    const socket = new WebSocket(`${scheme}://${location.host}${progressUrl}`);
+    receiverStatus.style.display = "block";
+    socket.onmessage = (event) => {
+        const progress = JSON.parse(event.data);
+        if (progress.saved && progress.received === 0) {
+            receiverStatus.textContent = `Receiver saved ${progress.saved}`;
+        } else if (progress.needed > 0) {
+            const eta = progress.eta_seconds === null ? "" : `, about ${progress.eta_seconds}s left`;
+            receiverStatus.textContent = `Receiver has ${progress.received} / ${progress.needed} chunks${eta}`;
+        }
+    };
+    socket.onclose = () => {
+        receiverStatus.textContent = "Lost the connection to the receiver";
+    };
+}
+
+function formatEta(result) {
+    if (result.progress_current === 0) return "";
+    if (firstChunkAt === null) firstChunkAt = performance.now();
+    if (result.progress_current < 2) return "";
+    const elapsed = (performance.now() - firstChunkAt) / 1000;
+    const perChunk = elapsed / (result.progress_current - 1);
+    const remaining = Math.max(result.progress_total - result.progress_current, 0);
+    return `, about ${Math.ceil(perChunk * remaining)}s left`;
+}
+
+function formatSize(result) {
+    if (result.original_size === 0) return "";
+    return ` (${(result.original_size / 1024).toFixed(1)} KiB file)`;
+}
+
+function addDownload(filename, data) {
+    // A single-code file completes again every time its code is scanned
+    if (downloadArea.querySelector(`button[data-filename="${CSS.escape(filename)}"]`)) return;
+    const button = document.createElement("button");
+    button.dataset.filename = filename;
+    button.textContent = `Download ${filename}`;
+    downloadArea.appendChild(button);
     downloadArea.style.display = "block";
-    downloadBtn.onclick = () => {
+    button.onclick = () => {
         const blob = new Blob([data], { type: "application/octet-stream" });
         const url = URL.createObjectURL(blob);
         const a = document.createElement("a");